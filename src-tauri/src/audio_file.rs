@@ -0,0 +1,676 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+// FLAC frames always hold this many samples of each channel, except for the last one
+const FLAC_BLOCK_SIZE: usize = 4096;
+// room for the tags before the FLAC frames, since they are only known once the recording is done (in bytes)
+const FLAC_METADATA_SPACE: usize = 8192;
+// the highest order of the fixed predictors of FLAC
+const FLAC_MAX_FIXED_ORDER: usize = 4;
+// the largest rice parameter that doesn't need the escape code
+const FLAC_MAX_RICE_PARAMETER: u32 = 14;
+
+const FLAC_CRC8: crc::Crc<u8> = crc::Crc::<u8>::new(&crc::CRC_8_SMBUS);
+const FLAC_CRC16: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_UMTS);
+
+/// The file format of an audio recording, which are both 16-bit
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AudioFileFormat {
+    Wav,
+    Flac,
+}
+
+impl AudioFileFormat {
+    /// Guess the format from the extension of the path, which defaults to WAV
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("flac") => AudioFileFormat::Flac,
+            _ => AudioFileFormat::Wav,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFileFormat::Wav => "wav",
+            AudioFileFormat::Flac => "flac",
+        }
+    }
+}
+
+/// What is saved about the station in an audio file
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct AudioTags {
+    // such as "101.5 MHz FM"
+    pub frequency: Option<String>,
+    // the RBDS program service name, or the name of an HD Radio station
+    pub station: Option<String>,
+    // the RBDS RadioText, or the title of an HD Radio song
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl AudioTags {
+    // the tags as (RIFF INFO id, Vorbis comment name, value)
+    fn fields(&self) -> Vec<(&'static [u8; 4], &'static str, String)> {
+        let software = format!("RTL-SDR Radio {}", env!("CARGO_PKG_VERSION"));
+        let date = Local::now().format("%Y-%m-%d").to_string();
+
+        [
+            (b"INAM", "TITLE", self.title.clone()),
+            (b"IART", "ARTIST", self.artist.clone()),
+            (b"IPRD", "ALBUM", self.album.clone()),
+            (b"ISRC", "ORGANIZATION", self.station.clone()),
+            (b"ICMT", "COMMENT", self.frequency.clone()),
+            (b"ICRD", "DATE", Some(date)),
+            (b"ISFT", "ENCODER", Some(software)),
+        ]
+        .into_iter()
+        .filter_map(|(info_id, comment_name, value)| {
+            value
+                .filter(|value| !value.is_empty())
+                .map(|value| (info_id, comment_name, value))
+        })
+        .collect()
+    }
+}
+
+/// Writes 16-bit audio to a WAV or FLAC file, where the tags are added once it is finished
+pub enum AudioFileWriter {
+    Wav(WavFileWriter),
+    Flac(FlacFileWriter),
+}
+
+impl AudioFileWriter {
+    pub fn create(
+        path: &Path,
+        format: AudioFileFormat,
+        sample_rate: u32,
+        channels: u16,
+    ) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match format {
+            AudioFileFormat::Wav => {
+                AudioFileWriter::Wav(WavFileWriter::new(file, sample_rate, channels)?)
+            }
+            AudioFileFormat::Flac => {
+                AudioFileWriter::Flac(FlacFileWriter::new(file, sample_rate, channels)?)
+            }
+        })
+    }
+
+    /// Write one sample for each channel
+    pub fn write_frame(&mut self, frame: &[i16]) -> io::Result<()> {
+        match self {
+            AudioFileWriter::Wav(writer) => writer.write_frame(frame),
+            AudioFileWriter::Flac(writer) => writer.write_frame(frame),
+        }
+    }
+
+    /// How many samples of each channel have been written
+    pub fn frames_written(&self) -> u64 {
+        match self {
+            AudioFileWriter::Wav(writer) => writer.frames_written,
+            AudioFileWriter::Flac(writer) => writer.frames_written,
+        }
+    }
+
+    pub fn finish(self, tags: &AudioTags) -> io::Result<()> {
+        match self {
+            AudioFileWriter::Wav(writer) => writer.finish(tags),
+            AudioFileWriter::Flac(writer) => writer.finish(tags),
+        }
+    }
+}
+
+pub struct WavFileWriter {
+    file: BufWriter<File>,
+    channels: u16,
+    frames_written: u64,
+}
+
+impl WavFileWriter {
+    fn new(mut file: BufWriter<File>, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        let block_align = channels * 2;
+
+        // the sizes are filled in once the recording is finished
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // integer PCM
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?;
+
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            file,
+            channels,
+            frames_written: 0,
+        })
+    }
+
+    fn write_frame(&mut self, frame: &[i16]) -> io::Result<()> {
+        for sample in frame.iter() {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    fn finish(mut self, tags: &AudioTags) -> io::Result<()> {
+        let data_size = self.frames_written * self.channels as u64 * 2;
+
+        // the tags are in a LIST chunk after the audio
+        let mut info = b"INFO".to_vec();
+        for (info_id, _, value) in tags.fields() {
+            let mut value = value.into_bytes();
+            value.push(0);
+            info.extend_from_slice(info_id);
+            info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            info.extend_from_slice(&value);
+            // every chunk starts on an even byte
+            if value.len() % 2 == 1 {
+                info.push(0);
+            }
+        }
+        self.file.write_all(b"LIST")?;
+        self.file.write_all(&(info.len() as u32).to_le_bytes())?;
+        self.file.write_all(&info)?;
+
+        let file_size = self.file.stream_position()?;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(file_size.saturating_sub(8).min(u32::MAX as u64) as u32).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file
+            .write_all(&(data_size.min(u32::MAX as u64) as u32).to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// A simple FLAC encoder, which picks the best fixed predictor for each channel of every frame
+pub struct FlacFileWriter {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    channels: u16,
+    frames_written: u64,
+    // the samples of each channel that haven't been encoded yet
+    block: Vec<Vec<i32>>,
+    frame_number: u64,
+    min_frame_size: usize,
+    max_frame_size: usize,
+}
+
+impl FlacFileWriter {
+    fn new(mut file: BufWriter<File>, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        file.write_all(b"fLaC")?;
+
+        // the stream info and tags are filled in once the recording is finished
+        file.write_all(&FlacFileWriter::metadata_block_header(false, 0, 34))?;
+        file.write_all(&[0; 34])?;
+        file.write_all(&FlacFileWriter::metadata_block_header(
+            true,
+            1,
+            FLAC_METADATA_SPACE - 4,
+        ))?;
+        file.write_all(&vec![0; FLAC_METADATA_SPACE - 4])?;
+
+        Ok(Self {
+            file,
+            sample_rate,
+            channels,
+            frames_written: 0,
+            block: vec![Vec::with_capacity(FLAC_BLOCK_SIZE); channels as usize],
+            frame_number: 0,
+            min_frame_size: usize::MAX,
+            max_frame_size: 0,
+        })
+    }
+
+    fn write_frame(&mut self, frame: &[i16]) -> io::Result<()> {
+        for (channel, sample) in self.block.iter_mut().zip(frame.iter()) {
+            channel.push(*sample as i32);
+        }
+        self.frames_written += 1;
+
+        if self.block[0].len() == FLAC_BLOCK_SIZE {
+            self.encode_block()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, tags: &AudioTags) -> io::Result<()> {
+        if !self.block[0].is_empty() {
+            self.encode_block()?;
+        }
+
+        let mut stream_info = BitWriter::new();
+        stream_info.write(FLAC_BLOCK_SIZE as u64, 16);
+        stream_info.write(FLAC_BLOCK_SIZE as u64, 16);
+        if self.max_frame_size > 0 {
+            stream_info.write(self.min_frame_size as u64, 24);
+            stream_info.write(self.max_frame_size as u64, 24);
+        } else {
+            stream_info.write(0, 48);
+        }
+        stream_info.write(self.sample_rate as u64, 20);
+        stream_info.write(self.channels as u64 - 1, 3);
+        stream_info.write(16 - 1, 5);
+        stream_info.write(self.frames_written, 36);
+        // the MD5 of the audio is optional
+        stream_info.write(0, 64);
+        stream_info.write(0, 64);
+
+        let mut vendor = format!("RTL-SDR Radio {}", env!("CARGO_PKG_VERSION")).into_bytes();
+        vendor.truncate(256);
+        let mut comments: Vec<Vec<u8>> = tags
+            .fields()
+            .into_iter()
+            .map(|(_, comment_name, value)| format!("{}={}", comment_name, value).into_bytes())
+            .collect();
+        // tags that don't fit in the space saved for them are left out
+        let comments_size = |comments: &Vec<Vec<u8>>| {
+            4 + vendor.len() + 4 + comments.iter().map(|c| 4 + c.len()).sum::<usize>()
+        };
+        while comments_size(&comments) + 8 > FLAC_METADATA_SPACE {
+            comments.pop();
+        }
+
+        let mut vorbis_comment = vec![];
+        vorbis_comment.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        vorbis_comment.extend_from_slice(&vendor);
+        vorbis_comment.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for comment in comments.iter() {
+            vorbis_comment.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            vorbis_comment.extend_from_slice(comment);
+        }
+        let padding_size = FLAC_METADATA_SPACE - 4 - vorbis_comment.len() - 4;
+
+        self.file.seek(SeekFrom::Start(8))?;
+        self.file.write_all(&stream_info.into_bytes())?;
+        self.file.write_all(&FlacFileWriter::metadata_block_header(
+            false,
+            4,
+            vorbis_comment.len(),
+        ))?;
+        self.file.write_all(&vorbis_comment)?;
+        self.file.write_all(&FlacFileWriter::metadata_block_header(
+            true,
+            1,
+            padding_size,
+        ))?;
+        self.file.write_all(&vec![0; padding_size])?;
+        self.file.flush()
+    }
+
+    fn metadata_block_header(is_last: bool, block_type: u8, length: usize) -> [u8; 4] {
+        let length = length as u32;
+        [
+            ((is_last as u8) << 7) | block_type,
+            (length >> 16) as u8,
+            (length >> 8) as u8,
+            length as u8,
+        ]
+    }
+
+    fn encode_block(&mut self) -> io::Result<()> {
+        let block_size = self.block[0].len();
+        let mut frame = BitWriter::new();
+
+        // sync code, with a fixed block size
+        frame.write(0xFFF8, 16);
+        // the block size is at the end of the header, and the sample rate is in the stream info
+        frame.write(0b0111, 4);
+        frame.write(0b0000, 4);
+        // independent channels of 16-bit samples
+        frame.write(self.channels as u64 - 1, 4);
+        frame.write(0b100, 3);
+        frame.write(0, 1);
+        frame.write_utf8(self.frame_number);
+        frame.write(block_size as u64 - 1, 16);
+        let header_crc = FLAC_CRC8.checksum(frame.bytes());
+        frame.write(header_crc as u64, 8);
+
+        for channel in self.block.iter() {
+            FlacFileWriter::encode_subframe(&mut frame, channel);
+        }
+
+        frame.align();
+        let frame_crc = FLAC_CRC16.checksum(frame.bytes());
+        frame.write(frame_crc as u64, 16);
+
+        let frame = frame.into_bytes();
+        self.file.write_all(&frame)?;
+
+        self.min_frame_size = self.min_frame_size.min(frame.len());
+        self.max_frame_size = self.max_frame_size.max(frame.len());
+        self.frame_number += 1;
+        for channel in self.block.iter_mut() {
+            channel.clear();
+        }
+        Ok(())
+    }
+
+    fn encode_subframe(frame: &mut BitWriter, samples: &[i32]) {
+        // silence (or a single sample) is a constant
+        if samples.iter().all(|sample| *sample == samples[0]) {
+            frame.write(0b0000_0000, 8);
+            frame.write_signed(samples[0] as i64, 16);
+            return;
+        }
+
+        // find the fixed predictor with the smallest residual
+        let mut best: Option<(usize, u32, u64)> = None;
+        for order in 0..=FLAC_MAX_FIXED_ORDER.min(samples.len() - 1) {
+            let residuals = FlacFileWriter::fixed_residuals(samples, order);
+            let (rice_parameter, residual_bits) = FlacFileWriter::best_rice_parameter(&residuals);
+            let bits = order as u64 * 16 + 10 + residual_bits;
+            if best
+                .map(|(_, _, best_bits)| bits < best_bits)
+                .unwrap_or(true)
+            {
+                best = Some((order, rice_parameter, bits));
+            }
+        }
+        let (order, rice_parameter, bits) = best.unwrap();
+
+        if bits >= samples.len() as u64 * 16 {
+            // verbatim
+            frame.write(0b0000_0010, 8);
+            for sample in samples.iter() {
+                frame.write_signed(*sample as i64, 16);
+            }
+            return;
+        }
+
+        frame.write(0b0001_0000 | (order as u64) << 1, 8);
+        for sample in samples[..order].iter() {
+            frame.write_signed(*sample as i64, 16);
+        }
+        // rice coding with 4-bit parameters, in a single partition
+        frame.write(0b00, 2);
+        frame.write(0, 4);
+        frame.write(rice_parameter as u64, 4);
+        for residual in FlacFileWriter::fixed_residuals(samples, order) {
+            let value = FlacFileWriter::zigzag(residual);
+            frame.write_unary(value >> rice_parameter);
+            frame.write(value, rice_parameter);
+        }
+    }
+
+    fn fixed_residuals(samples: &[i32], order: usize) -> Vec<i64> {
+        (order..samples.len())
+            .map(|i| {
+                let x = |offset: usize| samples[i - offset] as i64;
+                match order {
+                    0 => x(0),
+                    1 => x(0) - x(1),
+                    2 => x(0) - 2 * x(1) + x(2),
+                    3 => x(0) - 3 * x(1) + 3 * x(2) - x(3),
+                    _ => x(0) - 4 * x(1) + 6 * x(2) - 4 * x(3) + x(4),
+                }
+            })
+            .collect()
+    }
+
+    // the rice parameter that takes the fewest bits, and how many bits that is
+    fn best_rice_parameter(residuals: &[i64]) -> (u32, u64) {
+        (0..=FLAC_MAX_RICE_PARAMETER)
+            .map(|parameter| {
+                let bits = residuals
+                    .iter()
+                    .map(|residual| {
+                        (FlacFileWriter::zigzag(*residual) >> parameter) + 1 + parameter as u64
+                    })
+                    .sum::<u64>();
+                (parameter, bits)
+            })
+            .min_by_key(|(_, bits)| *bits)
+            .unwrap()
+    }
+
+    // rice codes are unsigned, so negative numbers are folded in between the positive ones
+    fn zigzag(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+}
+
+// packs values into bytes, starting with the most significant bit
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u8,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: vec![],
+            current: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64 & ((1 << bits) - 1), bits);
+    }
+
+    // zeros followed by a one
+    fn write_unary(&mut self, value: u64) {
+        for _ in 0..value {
+            self.write_bit(false);
+        }
+        self.write_bit(true);
+    }
+
+    // the frame number of FLAC is coded like UTF-8, but with up to 36 bits
+    fn write_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            self.write(value, 8);
+            return;
+        }
+
+        let continuation_bytes = match value {
+            0..=0x7FF => 1,
+            0x800..=0xFFFF => 2,
+            0x1_0000..=0x1F_FFFF => 3,
+            0x20_0000..=0x3FF_FFFF => 4,
+            0x400_0000..=0x7FFF_FFFF => 5,
+            _ => 6,
+        };
+        let leading_ones = (0xFF00u64 >> (continuation_bytes + 1)) & 0xFF;
+        self.write(leading_ones | (value >> (6 * continuation_bytes)), 8);
+        for i in (0..continuation_bytes).rev() {
+            self.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+        }
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        self.current = (self.current << 1) | bit as u8;
+        self.bit_count += 1;
+        if self.bit_count == 8 {
+            self.bytes.push(self.current);
+            self.current = 0;
+            self.bit_count = 0;
+        }
+    }
+
+    // pad with zeros to the next byte
+    fn align(&mut self) {
+        while self.bit_count != 0 {
+            self.write_bit(false);
+        }
+    }
+
+    // the bytes that are complete so far
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f64::consts::TAU, fs, path::PathBuf};
+
+    use super::*;
+
+    // the last block isn't full, and there are enough blocks for frame numbers that take more than one byte
+    const TEST_FRAMES: usize = FLAC_BLOCK_SIZE * 129 + 1234;
+
+    fn test_path(name: &str, format: AudioFileFormat) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "rtlsdr_radio_test_{}_{}.{}",
+            name,
+            std::process::id(),
+            format.extension()
+        ))
+    }
+
+    fn test_tags() -> AudioTags {
+        AudioTags {
+            frequency: Some(String::from("101.5 MHz FM")),
+            station: Some(String::from("WTST")),
+            title: Some(String::from("Test Title")),
+            artist: Some(String::from("Test Artist")),
+            album: None,
+        }
+    }
+
+    // a tone, silence, and noise, so every kind of FLAC subframe is used
+    fn test_audio(channels: usize) -> Vec<Vec<i16>> {
+        let mut noise: u32 = 0x1234_5678;
+        (0..TEST_FRAMES)
+            .map(|i| {
+                (0..channels)
+                    .map(|channel| match (i / FLAC_BLOCK_SIZE) % 3 {
+                        0 => {
+                            let tone =
+                                (TAU * 440.0 * (channel + 1) as f64 * i as f64 / 48_000.0).sin();
+                            (tone * 20_000.0) as i16
+                        }
+                        1 => 0,
+                        _ => {
+                            noise ^= noise << 13;
+                            noise ^= noise >> 17;
+                            noise ^= noise << 5;
+                            noise as i16
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn write_audio(path: &Path, format: AudioFileFormat, audio: &[Vec<i16>]) {
+        let mut writer =
+            AudioFileWriter::create(path, format, 48_000, audio[0].len() as u16).unwrap();
+        for frame in audio.iter() {
+            writer.write_frame(frame).unwrap();
+        }
+        assert_eq!(writer.frames_written(), audio.len() as u64);
+        writer.finish(&test_tags()).unwrap();
+    }
+
+    #[test]
+    fn wav_round_trip() {
+        for channels in [1, 2] {
+            let path = test_path(&format!("wav_{}", channels), AudioFileFormat::Wav);
+            let audio = test_audio(channels);
+            write_audio(&path, AudioFileFormat::Wav, &audio);
+
+            let mut reader = hound::WavReader::open(&path).unwrap();
+            let spec = reader.spec();
+            assert_eq!(spec.channels, channels as u16);
+            assert_eq!(spec.sample_rate, 48_000);
+            assert_eq!(spec.bits_per_sample, 16);
+            let samples: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+            assert_eq!(samples, audio.concat());
+
+            // the RIFF size covers everything after it, including the tags after the audio
+            let bytes = fs::read(&path).unwrap();
+            let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+            assert_eq!(riff_size as usize, bytes.len() - 8);
+
+            let data_size = audio.len() * channels * 2;
+            let list = &bytes[44 + data_size..];
+            assert_eq!(&list[0..4], b"LIST");
+            let list_size = u32::from_le_bytes(list[4..8].try_into().unwrap());
+            assert_eq!(list_size as usize, list.len() - 8);
+            assert_eq!(&list[8..12], b"INFO");
+            assert_eq!(&list[12..16], b"INAM");
+            assert_eq!(&list[20..31], b"Test Title\0");
+
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn flac_round_trip() {
+        for channels in [1, 2] {
+            let path = test_path(&format!("flac_{}", channels), AudioFileFormat::Flac);
+            let audio = test_audio(channels);
+            write_audio(&path, AudioFileFormat::Flac, &audio);
+
+            let mut reader = claxon::FlacReader::open(&path).unwrap();
+            let stream_info = reader.streaminfo();
+            assert_eq!(stream_info.channels, channels as u32);
+            assert_eq!(stream_info.sample_rate, 48_000);
+            assert_eq!(stream_info.bits_per_sample, 16);
+            assert_eq!(stream_info.samples, Some(TEST_FRAMES as u64));
+            assert_eq!(reader.get_tag("TITLE").next(), Some("Test Title"));
+            assert_eq!(reader.get_tag("ORGANIZATION").next(), Some("WTST"));
+            assert_eq!(reader.get_tag("ALBUM").next(), None);
+
+            // claxon checks the CRC of every frame header and frame, and that the frame numbers count up
+            let samples: Vec<i16> = reader
+                .samples()
+                .map(|sample| sample.unwrap() as i16)
+                .collect();
+            assert_eq!(samples, audio.concat());
+
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn utf8_frame_numbers() {
+        let encode = |value: u64| {
+            let mut writer = BitWriter::new();
+            writer.write_utf8(value);
+            writer.into_bytes()
+        };
+
+        assert_eq!(encode(0x7F), vec![0x7F]);
+        assert_eq!(encode(0x80), vec![0xC2, 0x80]);
+        assert_eq!(encode(0x7FF), vec![0xDF, 0xBF]);
+        assert_eq!(encode(0x800), vec![0xE0, 0xA0, 0x80]);
+        assert_eq!(encode(0xFFFF), vec![0xEF, 0xBF, 0xBF]);
+        assert_eq!(encode(0x1_0000), vec![0xF0, 0x90, 0x80, 0x80]);
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use blocks::Rechunker;
+use radiorust::prelude::*;
+use soapysdr::Direction;
+
+use crate::{
+    modes::types::{AircraftState, ModeSState},
+    radiorust_blocks::{
+        adsb_decode::AdsbDecode, iq_correction::IqCorrection, stream_monitor::StreamMonitor,
+    },
+    sdr::device::SDRDevice,
+};
+
+use super::{set_gain, wait_for_stream, CliEvent, CliOptions};
+
+/// Decode ADS-B messages until the stream ends, printing each aircraft when a new message is received from it
+pub async fn decode_adsb(sdr_dev: &SDRDevice, options: &CliOptions) -> Result<(), String> {
+    let capabilities = sdr_dev.capabilities(Direction::Rx, 0)?;
+
+    // the clock is 1MHz, so we need at least 2MHz sample rate
+    let sample_rate = 2e6;
+    if capabilities.closest_sample_rate(sample_rate) != sample_rate {
+        return Err(format!(
+            "ADS-B decoding requires a sample rate of {}hz",
+            sample_rate
+        ));
+    }
+    let _ = sdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate);
+
+    sdr_dev.set_frequency(Direction::Rx, 0, 1090.0 * 1_000_000.0, "")?;
+
+    // make sure direct sampling is disabled
+    if capabilities.has_setting("direct_samp") {
+        let _ = sdr_dev.write_setting("direct_samp", "0");
+    }
+
+    set_gain(sdr_dev, &capabilities, options.gain)?;
+
+    let _ = sdr_dev.set_bandwidth(Direction::Rx, 0, sample_rate / 2.0);
+
+    let sdr_rx = sdr_dev.rx_stream(sample_rate)?;
+    sdr_rx.activate().await?;
+
+    let stream_monitor = StreamMonitor::<f32>::new();
+    stream_monitor.feed_from(&sdr_rx);
+
+    let iq_correction = IqCorrection::<f32>::new(options.iq_correction);
+    iq_correction.feed_from(&stream_monitor);
+
+    let rechunker = Rechunker::new((sample_rate).round() as usize);
+    rechunker.feed_from(&iq_correction);
+
+    // discard samples that are not processed quickly enough (to prevent slowdowns)
+    let buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 0.1);
+    buffer.feed_from(&rechunker);
+
+    // the time of the last printed message from each aircraft
+    let last_messages: Mutex<HashMap<u32, SystemTime>> = Mutex::new(HashMap::new());
+    let options_clone = options.clone();
+    let adsb_decode = AdsbDecode::new(
+        Arc::new(move |modes_state: ModeSState| {
+            let mut last_messages = last_messages.lock().unwrap();
+            for aircraft in modes_state.aircraft.iter() {
+                let last_message =
+                    last_messages.insert(aircraft.icao_address, aircraft.last_message_timestamp);
+                if last_message != Some(aircraft.last_message_timestamp) {
+                    print_aircraft(&options_clone, aircraft);
+                }
+            }
+        }),
+        false,
+    );
+    adsb_decode.feed_from(&buffer);
+
+    let result = wait_for_stream(&stream_monitor).await;
+
+    let _ = sdr_rx.deactivate().await;
+
+    result
+}
+
+fn print_aircraft(options: &CliOptions, aircraft: &AircraftState) {
+    if options.json {
+        options.print_event(CliEvent::Aircraft(aircraft));
+        return;
+    }
+
+    let adsb_state = &aircraft.adsb_state;
+    let position = match (adsb_state.latitude, adsb_state.longitude) {
+        (Some(latitude), Some(longitude)) => format!("{:.4}, {:.4}", latitude, longitude),
+        _ => String::from("-"),
+    };
+    options.print_line(&format!(
+        "Aircraft {:06X} | {} | {} ft | {} kt | {}° | {}",
+        aircraft.icao_address,
+        adsb_state.callsign.as_deref().unwrap_or("-").trim(),
+        format_optional(adsb_state.altitude),
+        format_optional(adsb_state.speed),
+        format_optional(adsb_state.heading.map(|heading| heading.round())),
+        position
+    ));
+}
+
+fn format_optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::from("-"), |value| value.to_string())
+}
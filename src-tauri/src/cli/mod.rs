@@ -0,0 +1,431 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use radiorust::numbers::Float;
+use serde::Serialize;
+use soapysdr::Direction;
+use tokio::time;
+
+use crate::{
+    modes::types::AircraftState,
+    radio_services::soapysdr_radio::{
+        AirbandChannelState, AudioSettings, ChannelSettings, Deemphasis, NoiseSettings,
+        SidebandSettings, StreamType,
+    },
+    radiorust_blocks::{
+        fm_stereo_decode::FmStereoState,
+        hd_radio_decode::HdRadioState,
+        rbds_decode::RbdsState,
+        stream_monitor::{StreamHealth, StreamMonitor},
+        subaudible_tone::SubaudibleTone,
+    },
+    sdr::{
+        capabilities::SDRCapabilities,
+        device::SDRDevice,
+        enumeration::{get_available_sdr_args, AvailableSDRArgs},
+        rtl_tcp::{get_rtl_tcp_sdr_args, RTL_TCP_DEFAULT_PORT},
+        simulated::{get_simulated_sdr_args, SimulatedArgs},
+    },
+    store::Store,
+};
+
+mod adsb;
+mod radio;
+
+// how often the stream is checked
+const MONITOR_INTERVAL: Duration = Duration::from_millis(250);
+// how long the SDR can go without sending samples before giving up
+const STREAM_TIMEOUT: Duration = Duration::from_secs(3);
+
+const USAGE: &str = "Usage: rtlsdr_radio_cli <COMMAND> [OPTIONS]
+
+Commands:
+  devices              List the available SDRs
+  fm <MHZ>             Play an FM station
+  am <KHZ>             Play an AM station
+  hd <MHZ>             Play an HD Radio station
+  usb <KHZ>            Listen to the upper sideband (SSB)
+  lsb <KHZ>            Listen to the lower sideband (SSB)
+  cw <KHZ>             Listen to morse code (CW)
+  nbfm <MHZ>           Listen to a narrowband FM channel, such as weather radio or a repeater
+  air <MHZ>            Listen to an airband (AM) channel, such as a tower or approach frequency
+  adsb                 Print the aircraft that are sending ADS-B messages
+
+Options:
+  -d, --device <SERIAL|INDEX>  SDR to use (defaults to the first one)
+      --rtl-tcp <HOST[:PORT]>  Use an rtl_tcp server instead of a local SDR
+      --simulated              Use the simulated SDR, which sends test signals
+  -g, --gain <DB>              Gain of the SDR (defaults to automatic gain)
+      --ppm <PPM>              Frequency correction of the SDR
+      --data-dir <PATH>        Use the gain and frequency correction that the app saved for the SDR
+                               in this directory, unless they are given
+      --iq-correction          Remove the DC offset and IQ imbalance of the SDR
+  -p, --program <NUMBER>       HD Radio program to play (defaults to 0)
+  -v, --volume <VOLUME>        Volume from 0 to 1 (defaults to 1)
+  -r, --sample-rate <HZ>       Sample rate of the audio (defaults to 48000)
+      --deemphasis <REGION>    FM de-emphasis: americas (75 µs), international (50 µs), or off
+                               (defaults to international for FM, and off for AM)
+      --audio-bandwidth <HZ>   Highest audio frequency that is played (defaults to 15000 for FM,
+                               and half the IF bandwidth for AM)
+      --am-bandwidth <HZ>      IF bandwidth of AM stations, such as 2500, 5000, or 10000
+                               (defaults to 10000)
+      --bfo <HZ>               BFO offset of SSB, or the pitch of CW (defaults to 0 for SSB, and 700 for CW)
+      --filter-width <HZ>      Audio filter width of SSB and CW (defaults to 2400 for SSB, and 500 for CW)
+      --channel-spacing <HZ>   Channel spacing of narrowband FM: 12500 or 25000 (defaults to 12500),
+                               or of airband: 8333 or 25000 (defaults to 25000)
+      --squelch <LEVEL>        Squelch of narrowband FM and airband from 0 (always open) to 1 (defaults to 0)
+      --monitor <MHZ,...>      Other airband channels to play at the same time, which need to be
+                               within a few hundred KHz of the tuned channel
+      --noise-blanker <LEVEL>  Blank impulse noise of AM, SSB, CW, and airband from 0 (off) to 1
+                               (defaults to 0)
+      --noise-reduction <LEVEL> Remove background noise from the audio from 0 (off) to 1
+                               (defaults to 0)
+  -o, --output <OUTPUT>        Where to play audio: audio, stdout, or none (defaults to audio)
+                               stdout is 16-bit little-endian stereo PCM at the audio sample rate
+                               (44100hz for HD Radio), and metadata is printed to stderr instead
+      --json                   Print devices and metadata as JSON lines
+  -h, --help                   Print this message";
+
+enum Command {
+    Devices,
+    Radio {
+        stream_type: StreamType,
+        // in MHz for FM, HD Radio, narrowband FM, and airband, and KHz for AM, SSB, and CW
+        frequency: f64,
+    },
+    Adsb,
+    Help,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AudioOutput {
+    Device,
+    Stdout,
+    None,
+}
+
+#[derive(Clone)]
+pub struct CliOptions {
+    device: Option<String>,
+    rtl_tcp: Option<String>,
+    simulated: bool,
+    pub gain: Option<f64>,
+    ppm: Option<f64>,
+    // where the app saves its data, such as the preferences of each SDR
+    data_dir: Option<PathBuf>,
+    pub iq_correction: bool,
+    pub program: u32,
+    pub volume: f64,
+    pub sample_rate: f64,
+    pub audio: AudioSettings,
+    pub sideband: SidebandSettings,
+    pub channel: ChannelSettings,
+    pub noise: NoiseSettings,
+    pub output: AudioOutput,
+    pub json: bool,
+}
+
+/// Metadata that is printed as a JSON line
+#[derive(Serialize)]
+pub enum CliEvent<'a> {
+    Device(&'a AvailableSDRArgs),
+    Rbds(&'a RbdsState),
+    FmStereo(&'a FmStereoState),
+    Squelch(bool),
+    SubaudibleTone(&'a Option<SubaudibleTone>),
+    AirbandChannel(&'a AirbandChannelState),
+    HdRadio(&'a HdRadioState),
+    Aircraft(&'a AircraftState),
+}
+
+impl CliOptions {
+    /// Print metadata to stdout, unless stdout is being used for audio
+    pub fn print_line(&self, line: &str) {
+        if self.output == AudioOutput::Stdout {
+            eprintln!("{}", line);
+        } else {
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(stdout, "{}", line);
+            let _ = stdout.flush();
+        }
+    }
+
+    pub fn print_event(&self, event: CliEvent) {
+        match serde_json::to_string(&event) {
+            Ok(line) => self.print_line(&line),
+            Err(err) => eprintln!("Could not serialize metadata: {}", err),
+        }
+    }
+}
+
+/// Run the headless receiver with the command-line arguments (not including the program name)
+pub fn run(args: Vec<String>) -> ExitCode {
+    let (command, options) = match parse_args(args) {
+        Ok(parsed_args) => parsed_args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async move {
+            let mut options = options;
+
+            match command {
+                Command::Help => {
+                    println!("{}", USAGE);
+                    Ok(())
+                }
+                Command::Devices => list_devices(&options),
+                Command::Radio {
+                    stream_type,
+                    frequency,
+                } => {
+                    let sdr_dev = open_sdr(&mut options)?;
+                    radio::play_radio(&sdr_dev, stream_type, frequency, &options).await
+                }
+                Command::Adsb => {
+                    let sdr_dev = open_sdr(&mut options)?;
+                    adsb::decode_adsb(&sdr_dev, &options).await
+                }
+            }
+        });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<(Command, CliOptions), String> {
+    let mut command = None;
+    let mut options = CliOptions {
+        device: None,
+        rtl_tcp: None,
+        simulated: false,
+        gain: None,
+        ppm: None,
+        data_dir: None,
+        iq_correction: false,
+        program: 0,
+        volume: 1.0,
+        sample_rate: 48000.0,
+        audio: AudioSettings::default(),
+        sideband: SidebandSettings::default(),
+        channel: ChannelSettings::default(),
+        noise: NoiseSettings::default(),
+        output: AudioOutput::Device,
+        json: false,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} requires a value", name));
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok((Command::Help, options)),
+            "-d" | "--device" => options.device = Some(value(&arg)?),
+            "--rtl-tcp" => options.rtl_tcp = Some(value(&arg)?),
+            "--simulated" => options.simulated = true,
+            "-g" | "--gain" => options.gain = Some(parse_number(&arg, value(&arg)?)?),
+            "--ppm" => options.ppm = Some(parse_number(&arg, value(&arg)?)?),
+            "--data-dir" => options.data_dir = Some(PathBuf::from(value(&arg)?)),
+            "--iq-correction" => options.iq_correction = true,
+            "-p" | "--program" => options.program = parse_number(&arg, value(&arg)?)?,
+            "-v" | "--volume" => options.volume = parse_number(&arg, value(&arg)?)?,
+            "-r" | "--sample-rate" => options.sample_rate = parse_number(&arg, value(&arg)?)?,
+            "--deemphasis" => {
+                options.audio.deemphasis = Some(match value(&arg)?.as_str() {
+                    "americas" => Deemphasis::Americas,
+                    "international" => Deemphasis::International,
+                    "off" => Deemphasis::Disabled,
+                    region => return Err(format!("Unknown de-emphasis: {}", region)),
+                })
+            }
+            "--audio-bandwidth" => {
+                options.audio.audio_bandwidth = Some(parse_number(&arg, value(&arg)?)?)
+            }
+            "--am-bandwidth" => {
+                options.audio.am_bandwidth = Some(parse_number(&arg, value(&arg)?)?)
+            }
+            "--bfo" => options.sideband.bfo_offset = Some(parse_number(&arg, value(&arg)?)?),
+            "--filter-width" => {
+                options.sideband.filter_width = Some(parse_number(&arg, value(&arg)?)?)
+            }
+            "--channel-spacing" => {
+                options.channel.channel_spacing = Some(parse_number(&arg, value(&arg)?)?)
+            }
+            "--squelch" => options.channel.squelch = Some(parse_number(&arg, value(&arg)?)?),
+            "--monitor" => {
+                for channel in value(&arg)?.split(',') {
+                    let channel = parse_number(&arg, channel.trim().to_string())?;
+                    options.channel.monitored_channels.push(channel);
+                }
+            }
+            "--noise-blanker" => {
+                options.noise.noise_blanker = Some(parse_number(&arg, value(&arg)?)?)
+            }
+            "--noise-reduction" => {
+                options.noise.noise_reduction = Some(parse_number(&arg, value(&arg)?)?)
+            }
+            "-o" | "--output" => {
+                options.output = match value(&arg)?.as_str() {
+                    "audio" => AudioOutput::Device,
+                    "stdout" => AudioOutput::Stdout,
+                    "none" => AudioOutput::None,
+                    output => return Err(format!("Unknown output: {}", output)),
+                }
+            }
+            "--json" => options.json = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ if command.is_some() => return Err(format!("Unexpected argument: {}", arg)),
+            "devices" => command = Some(Command::Devices),
+            "adsb" => command = Some(Command::Adsb),
+            "fm" | "am" | "hd" | "usb" | "lsb" | "cw" | "nbfm" | "air" => {
+                let stream_type = match arg.as_str() {
+                    "fm" => StreamType::FM,
+                    "am" => StreamType::AM,
+                    "usb" => StreamType::USB,
+                    "lsb" => StreamType::LSB,
+                    "cw" => StreamType::CW,
+                    "nbfm" => StreamType::NBFM,
+                    "air" => StreamType::AIR,
+                    _ => StreamType::HD,
+                };
+                let frequency = parse_number("frequency", value("The frequency")?)?;
+                command = Some(Command::Radio {
+                    stream_type,
+                    frequency,
+                });
+            }
+            _ => return Err(format!("Unknown command: {}", arg)),
+        }
+    }
+
+    let command = command.ok_or(String::from("A command is required"))?;
+
+    Ok((command, options))
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
+fn list_devices(options: &CliOptions) -> Result<(), String> {
+    let sdrs = get_available_sdr_args()
+        .map_err(|_| String::from("Could not enumerate SDRs with SoapySDR"))?;
+
+    if sdrs.is_empty() && !options.json {
+        options.print_line("No SDRs were found");
+    }
+
+    for (index, sdr) in sdrs.iter().enumerate() {
+        if options.json {
+            options.print_event(CliEvent::Device(sdr));
+        } else {
+            options.print_line(&format!(
+                "{}: {} (driver: {}, serial: {})",
+                index, sdr.label, sdr.driver, sdr.serial
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// get the SDR picked by the options, and apply its frequency correction
+fn open_sdr(options: &mut CliOptions) -> Result<SDRDevice, String> {
+    let sdr_args = if options.simulated {
+        get_simulated_sdr_args(SimulatedArgs::default())
+    } else if let Some(address) = &options.rtl_tcp {
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (
+                host.to_string(),
+                parse_number("--rtl-tcp", port.to_string())?,
+            ),
+            None => (address.clone(), RTL_TCP_DEFAULT_PORT),
+        };
+        get_rtl_tcp_sdr_args(host, port)
+    } else {
+        let sdrs = get_available_sdr_args()
+            .map_err(|_| String::from("Could not enumerate SDRs with SoapySDR"))?;
+
+        match &options.device {
+            Some(device) => sdrs
+                .iter()
+                .enumerate()
+                .find(|(index, sdr)| sdr.serial == *device || index.to_string() == *device)
+                .map(|(_, sdr)| sdr.clone())
+                .ok_or(format!("Could not find an SDR matching {}", device))?,
+            None => sdrs
+                .first()
+                .cloned()
+                .ok_or(String::from("No SDRs were found"))?,
+        }
+    };
+
+    // fill in the settings that weren't given with the ones saved by the app
+    if let Some(data_dir) = &options.data_dir {
+        let preferences =
+            Store::new(Some(data_dir.as_path())).get_device_preferences(&sdr_args);
+        options.gain = options.gain.or(preferences.gain);
+        options.ppm = options.ppm.or(preferences.ppm);
+    }
+
+    eprintln!("Using {}", sdr_args.label);
+    let sdr_dev = SDRDevice::new(sdr_args)?;
+
+    if let Some(ppm) = options.ppm {
+        sdr_dev.set_frequency_correction(Direction::Rx, 0, ppm)?;
+    }
+
+    Ok(sdr_dev)
+}
+
+// use the requested gain, or automatic gain if none was requested
+fn set_gain(
+    sdr_dev: &SDRDevice,
+    capabilities: &SDRCapabilities,
+    gain: Option<f64>,
+) -> Result<(), String> {
+    match gain {
+        Some(gain) => {
+            if capabilities.has_automatic_gain {
+                sdr_dev.set_gain_mode(Direction::Rx, 0, false)?;
+            }
+            sdr_dev.set_gain(Direction::Rx, 0, capabilities.gain_range.clamp(gain))
+        }
+        None if capabilities.has_automatic_gain => sdr_dev.set_gain_mode(Direction::Rx, 0, true),
+        None if capabilities.has_manual_gain() => {
+            sdr_dev.set_gain(Direction::Rx, 0, capabilities.gain_range.maximum)
+        }
+        None => Ok(()),
+    }
+}
+
+// run until the stream ends (such as at the end of an IQ file), or fails
+async fn wait_for_stream<Flt>(stream_monitor: &StreamMonitor<Flt>) -> Result<(), String>
+where
+    Flt: Float,
+{
+    loop {
+        match stream_monitor.health(STREAM_TIMEOUT) {
+            StreamHealth::Healthy => {}
+            StreamHealth::Stalled => return Err(String::from("The SDR stopped sending samples")),
+            StreamHealth::Ended => return Ok(()),
+        }
+
+        time::sleep(MONITOR_INTERVAL).await;
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use radiorust::prelude::*;
+use soapysdr::Direction;
+
+use crate::{
+    radio_services::{
+        radio_pipeline::{RadioPipeline, RadioPipelineCallbacks, RadioPipelineSettings},
+        soapysdr_radio::{AirbandChannelState, StreamType, TUNING_OFFSET},
+    },
+    radiorust_blocks::{
+        audio_recorder::RecordingChannels,
+        better_cpal::AudioPlayer,
+        fm_stereo_decode::{FmStereoState, StereoInterleave},
+        hd_radio_decode::HdRadioState,
+        pcm_writer::PcmWriter,
+        rbds_decode::RbdsState,
+        stream_monitor::StreamMonitor,
+        subaudible_tone::SubaudibleTone,
+    },
+    sdr::device::SDRDevice,
+};
+
+use super::{set_gain, wait_for_stream, AudioOutput, CliEvent, CliOptions};
+
+/// Play an FM, AM, HD Radio, SSB, CW, narrowband FM, or airband station until the stream ends, printing its metadata as it changes
+pub async fn play_radio(
+    sdr_dev: &SDRDevice,
+    stream_type: StreamType,
+    frequency: f64,
+    options: &CliOptions,
+) -> Result<(), String> {
+    let capabilities = sdr_dev.capabilities(Direction::Rx, 0)?;
+
+    // use the supported sample rate closest to 1.024 MHz
+    let sample_rate = capabilities.closest_sample_rate(1.024e6);
+    let _ = sdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate);
+
+    // tune above the station, so the DC spike of the SDR is not on top of it
+    let sdr_freq = stream_type.station_frequency(frequency) + TUNING_OFFSET;
+    if !capabilities.supports_frequency(sdr_freq) {
+        eprintln!("Warning: the SDR does not support {}hz", sdr_freq);
+    }
+    sdr_dev.set_frequency(Direction::Rx, 0, sdr_freq, "")?;
+
+    let _ = sdr_dev.set_bandwidth(Direction::Rx, 0, 1.000e6);
+
+    let sdr_rx = sdr_dev.rx_stream(sample_rate)?;
+    sdr_rx.activate().await?;
+
+    // turn on direct sampling mode if in low frequencies (only RTL-SDRs have this setting)
+    if capabilities.has_setting("direct_samp") {
+        let _ = sdr_dev.write_setting("direct_samp", stream_type.direct_sampling_mode());
+    }
+
+    set_gain(sdr_dev, &capabilities, options.gain)?;
+
+    let stream_monitor = StreamMonitor::<f32>::new();
+    stream_monitor.feed_from(&sdr_rx);
+
+    // the HD Radio decoder stops when the pipeline is dropped, so it is kept until the stream ends
+    let settings = RadioPipelineSettings {
+        stream_type: stream_type.clone(),
+        freq: frequency,
+        sample_rate: options.sample_rate,
+        hd_radio_program: options.program,
+        iq_correction: options.iq_correction,
+        audio: options.audio,
+        sideband: options.sideband,
+        channel: options.channel.clone(),
+        noise: options.noise,
+    };
+    let pipeline = RadioPipeline::new(
+        &stream_monitor,
+        sample_rate,
+        &settings,
+        pipeline_callbacks(options),
+    )?;
+
+    let volume = blocks::GainControl::<f32>::new(options.volume);
+    pipeline.feed_audio_to(&volume);
+
+    let _playback = output_audio(
+        &volume,
+        settings.audio_sample_rate(),
+        stream_type.recording_channels(),
+        options,
+    )?;
+
+    let result = wait_for_stream(&stream_monitor).await;
+
+    let _ = sdr_rx.deactivate().await;
+
+    result
+}
+
+// print what the station is playing, but only when something people care about changes
+fn pipeline_callbacks(options: &CliOptions) -> RadioPipelineCallbacks {
+    let options_clone = options.clone();
+    let last_metadata = Mutex::new(None);
+    let rbds_callback = Arc::new(move |state: RbdsState| {
+        let metadata = (
+            state.pi,
+            state.service_name.clone(),
+            state.radio_text.clone(),
+            state.program_type.clone(),
+        );
+        let mut last_metadata = last_metadata.lock().unwrap();
+        if last_metadata.as_ref() == Some(&metadata) {
+            return;
+        }
+        *last_metadata = Some(metadata);
+
+        if options_clone.json {
+            options_clone.print_event(CliEvent::Rbds(&state));
+        } else {
+            options_clone.print_line(&format!(
+                "RBDS: PI {:04X} | {} | {} | {}",
+                state.pi,
+                state.service_name.trim(),
+                state.program_type.as_deref().unwrap_or("-"),
+                state.radio_text.trim()
+            ));
+        }
+    });
+
+    // printed whenever the station switches between mono and stereo
+    let options_clone = options.clone();
+    let last_is_stereo = Mutex::new(None);
+    let fm_stereo_callback = Arc::new(move |state: FmStereoState| {
+        let mut last_is_stereo = last_is_stereo.lock().unwrap();
+        if *last_is_stereo == Some(state.is_stereo) {
+            return;
+        }
+        *last_is_stereo = Some(state.is_stereo);
+
+        if options_clone.json {
+            options_clone.print_event(CliEvent::FmStereo(&state));
+        } else {
+            options_clone.print_line(if state.is_stereo { "Stereo" } else { "Mono" });
+        }
+    });
+
+    let options_clone = options.clone();
+    let squelch_callback = Arc::new(move |is_open: bool| {
+        if options_clone.json {
+            options_clone.print_event(CliEvent::Squelch(is_open));
+        } else {
+            options_clone.print_line(if is_open {
+                "Squelch: open"
+            } else {
+                "Squelch: closed"
+            });
+        }
+    });
+
+    let options_clone = options.clone();
+    let subaudible_tone_callback = Arc::new(move |tone: Option<SubaudibleTone>| {
+        if options_clone.json {
+            options_clone.print_event(CliEvent::SubaudibleTone(&tone));
+            return;
+        }
+
+        options_clone.print_line(&match tone {
+            Some(SubaudibleTone::Ctcss(freq)) => format!("Tone: CTCSS {:.1} Hz", freq),
+            Some(SubaudibleTone::Dcs { code, inverted }) => {
+                format!("Tone: DCS {:03o}{}", code, if inverted { "I" } else { "N" })
+            }
+            None => String::from("Tone: none"),
+        });
+    });
+
+    let options_clone = options.clone();
+    let airband_channel_callback = Arc::new(move |state: AirbandChannelState| {
+        if options_clone.json {
+            options_clone.print_event(CliEvent::AirbandChannel(&state));
+        } else {
+            options_clone.print_line(&format!(
+                "Squelch: {:.3} MHz {}",
+                state.frequency,
+                if state.is_open { "open" } else { "closed" }
+            ));
+        }
+    });
+
+    let options_clone = options.clone();
+    let last_metadata = Mutex::new(None);
+    let hd_radio_callback = Arc::new(move |state: HdRadioState| {
+        let metadata = (
+            state.program,
+            state.title.clone(),
+            state.artist.clone(),
+            state.album.clone(),
+            state.station_info.as_ref().map(|info| info.name.clone()),
+        );
+        let mut last_metadata = last_metadata.lock().unwrap();
+        if last_metadata.as_ref() == Some(&metadata) {
+            return;
+        }
+        *last_metadata = Some(metadata);
+
+        print_hd_radio_state(&options_clone, state);
+    });
+
+    RadioPipelineCallbacks {
+        rbds: rbds_callback,
+        // the RadioText is already printed with the rest of the RBDS data
+        radio_text: Arc::new(|_| {}),
+        fm_stereo: fm_stereo_callback,
+        squelch: squelch_callback,
+        subaudible_tone: subaudible_tone_callback,
+        airband_channel: airband_channel_callback,
+        hd_radio: hd_radio_callback,
+    }
+}
+
+fn print_hd_radio_state(options: &CliOptions, mut state: HdRadioState) {
+    if options.json {
+        // the album art is too big to print
+        state.thumbnail_data = None;
+        options.print_event(CliEvent::HdRadio(&state));
+    } else {
+        let station_name = state
+            .station_info
+            .as_ref()
+            .map(|info| info.name.as_str())
+            .unwrap_or("-");
+        options.print_line(&format!(
+            "HD Radio: {} HD{} | {} - {} ({})",
+            station_name,
+            state.program + 1,
+            state.title,
+            state.artist,
+            state.album
+        ));
+    }
+}
+
+// play the audio with the chosen output, which stops playing once the returned player is dropped
+fn output_audio<P>(
+    source: &P,
+    sample_rate: f64,
+    channels: RecordingChannels,
+    options: &CliOptions,
+) -> Result<Option<AudioPlayer>, String>
+where
+    P: Producer<Signal<Complex<f32>>>,
+{
+    match options.output {
+        AudioOutput::Device => {
+            let buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 1.0);
+            buffer.feed_from(source);
+
+            let playback = AudioPlayer::new(
+                sample_rate,
+                None,
+                2,
+                Some(channels == RecordingChannels::Mono),
+            )
+            .map_err(|err| format!("Could not open the audio device: {}", err))?;
+            if channels == RecordingChannels::Stereo {
+                // the player needs stereo audio as alternating samples
+                let stereo_interleave = StereoInterleave::<f32>::new();
+                stereo_interleave.feed_from(&buffer);
+                playback.feed_from(&stereo_interleave);
+            } else {
+                playback.feed_from(&buffer);
+            }
+
+            Ok(Some(playback))
+        }
+        AudioOutput::Stdout => {
+            let pcm_writer =
+                PcmWriter::<f32>::new(io::stdout(), channels == RecordingChannels::Mono);
+            pcm_writer.feed_from(source);
+
+            Ok(None)
+        }
+        AudioOutput::None => Ok(None),
+    }
+}
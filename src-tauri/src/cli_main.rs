@@ -0,0 +1,5 @@
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    rtlsdr_radio_lib::run_cli()
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+use log::error;
+use serde::Serialize;
+
+use crate::events::EventSink;
+
+/// What went wrong in a radio service, so the frontend can show what actually broke
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum RadioError {
+    // the SDR could not be opened or configured (such as setting the frequency or gain)
+    Device(String),
+    // the stream of samples could not be started, or it stopped working
+    Stream(String),
+    // the audio output could not be opened
+    Audio(String),
+    // a decoder (such as HD Radio) could not be started
+    Decoder(String),
+    // an SDR (or anything else that was asked for) does not exist
+    NotFound(String),
+    // the settings that were asked for can't be used (such as stations that are too far apart)
+    Settings(String),
+    // the service is already running, or isn't running when it needs to be
+    State(String),
+    // an IQ or audio recording could not be started or saved
+    Recording(String),
+    // the saved stations or preferences could not be read or written
+    Storage(String),
+}
+
+pub type RadioResult<T> = Result<T, RadioError>;
+
+impl RadioError {
+    pub fn message(&self) -> &str {
+        match self {
+            RadioError::Device(message)
+            | RadioError::Stream(message)
+            | RadioError::Audio(message)
+            | RadioError::Decoder(message)
+            | RadioError::NotFound(message)
+            | RadioError::Settings(message)
+            | RadioError::State(message)
+            | RadioError::Recording(message)
+            | RadioError::Storage(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for RadioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for RadioError {}
+
+// most helpers still return `String` errors, which can use `?` on a `RadioError`
+impl From<RadioError> for String {
+    fn from(error: RadioError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct RadioErrorEvent {
+    service: String,
+    error: RadioError,
+}
+
+/// Report an error that stopped a service as the `radio_error` event
+pub fn emit_error(events: &dyn EventSink, service: &str, error: RadioError) {
+    error!("{} stopped because of an error: {:?}", service, error);
+
+    events.emit(
+        "radio_error",
+        RadioErrorEvent {
+            service: service.to_string(),
+            error,
+        },
+    );
+}
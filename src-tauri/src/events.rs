@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use log::error;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Listener};
+
+/// Identifies a listener, so it can be removed with [`EventSink::unlisten`]
+pub type EventListenerId = u32;
+
+/// Called with the JSON payload of each event that is listened for
+pub type EventHandler = Box<dyn Fn(&str) + Send>;
+
+/// Where the radio services publish their events (such as status changes and errors), and where they listen for
+/// events (such as new settings). The Tauri app is one implementation, but programs that embed the decoding core
+/// (or tests) can provide their own.
+pub trait EventSink: Send + Sync {
+    fn emit_json(&self, event: &str, payload: Value);
+
+    fn listen(&self, event: &str, handler: EventHandler) -> EventListenerId;
+
+    fn unlisten(&self, id: EventListenerId);
+}
+
+pub type SharedEventSink = Arc<dyn EventSink>;
+
+impl dyn EventSink {
+    /// Publish an event with any payload that can be serialized to JSON
+    pub fn emit<S: Serialize>(&self, event: &str, payload: S) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => self.emit_json(event, payload),
+            Err(err) => error!("Failed to serialize the payload of {}: {}", event, err),
+        }
+    }
+}
+
+/// Events are sent to (and received from) the frontend
+impl EventSink for AppHandle {
+    fn emit_json(&self, event: &str, payload: Value) {
+        if let Err(err) = Emitter::emit(self, event, payload) {
+            error!("Failed to emit {}: {}", event, err);
+        }
+    }
+
+    fn listen(&self, event: &str, handler: EventHandler) -> EventListenerId {
+        Listener::listen(self, event, move |event| handler(event.payload()))
+    }
+
+    fn unlisten(&self, id: EventListenerId) {
+        Listener::unlisten(self, id);
+    }
+}
+
+/// Keeps every event that is emitted, so tests can check what a service published. Listeners are called with the
+/// events emitted to the sink, so tests can also send settings to a service.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingEventSink {
+    events: std::sync::Mutex<Vec<(String, Value)>>,
+    emitted: std::sync::Condvar,
+    #[allow(clippy::type_complexity)]
+    listeners:
+        std::sync::Mutex<Vec<(EventListenerId, String, Arc<std::sync::Mutex<EventHandler>>)>>,
+    next_listener_id: std::sync::atomic::AtomicU32,
+}
+
+#[cfg(test)]
+impl RecordingEventSink {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Every event emitted so far, in order
+    pub fn events(&self) -> Vec<(String, Value)> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Wait until an event with the payload has been emitted, returning false if it wasn't before the timeout
+    pub fn wait_for(&self, event: &str, payload: Value, timeout: std::time::Duration) -> bool {
+        let events = self.events.lock().unwrap();
+        let (_events, result) = self
+            .emitted
+            .wait_timeout_while(events, timeout, |events| {
+                !events
+                    .iter()
+                    .any(|(name, value)| name == event && *value == payload)
+            })
+            .unwrap();
+        !result.timed_out()
+    }
+}
+
+#[cfg(test)]
+impl EventSink for RecordingEventSink {
+    fn emit_json(&self, event: &str, payload: Value) {
+        self.events
+            .lock()
+            .unwrap()
+            .push((event.to_string(), payload.clone()));
+        self.emitted.notify_all();
+
+        // the handlers are called without the list locked, so they can emit (or listen) themselves
+        let handlers: Vec<_> = self
+            .listeners
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, name, _)| name == event)
+            .map(|(_, _, handler)| handler.clone())
+            .collect();
+        let payload = payload.to_string();
+        for handler in handlers {
+            (handler.lock().unwrap())(&payload);
+        }
+    }
+
+    fn listen(&self, event: &str, handler: EventHandler) -> EventListenerId {
+        let id = self
+            .next_listener_id
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.listeners.lock().unwrap().push((
+            id,
+            event.to_string(),
+            Arc::new(std::sync::Mutex::new(handler)),
+        ));
+        id
+    }
+
+    fn unlisten(&self, id: EventListenerId) {
+        self.listeners
+            .lock()
+            .unwrap()
+            .retain(|(listener_id, _, _)| *listener_id != id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn recording_sink_calls_listeners() {
+        let events = RecordingEventSink::new();
+        let sink: SharedEventSink = events.clone();
+        let (sender, receiver) = mpsc::channel();
+        let id = sink.listen(
+            "radio_update_settings",
+            Box::new(move |payload: &str| {
+                let _ = sender.send(payload.to_string());
+            }),
+        );
+
+        sink.emit("radio_update_settings", json!({ "volume": 0.5 }));
+        sink.emit("radio_status", "running");
+        assert_eq!(receiver.try_recv().unwrap(), r#"{"volume":0.5}"#);
+        assert!(receiver.try_recv().is_err());
+
+        sink.unlisten(id);
+        sink.emit("radio_update_settings", json!({ "volume": 1.0 }));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(events.events().len(), 3);
+    }
+}
@@ -1,13 +1,20 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod audio_file;
+mod cli;
+mod error;
+mod events;
 mod modes;
 mod nrsc5;
 mod radio_services;
 mod radiorust_blocks;
 mod sdr;
+mod store;
 mod utils;
 
+use audio_file::AudioFileFormat;
+use error::{RadioError, RadioResult};
 use log::info;
 use modes::types::ModeSState;
 use nrsc5::{
@@ -16,40 +23,81 @@ use nrsc5::{
 };
 use radio_services::{
     nrsc5::Nrsc5State,
+    rtl_tcp_server::{RtlTcpServerState, RTL_TCP_DEFAULT_BIND_ADDRESS},
     soapysdr_adsb::{self, AdsbDecoderState},
-    soapysdr_radio::{self, RtlSdrState},
+    soapysdr_multi_fm::{MultiFmSettings, MultiFmState, StationRbdsState},
+    soapysdr_radio::{self, MediaControlsConfig, RtlSdrState},
+    soapysdr_scanner::{ScanSettings, ScanUpdate, ScannerState},
+};
+use radiorust_blocks::{
+    hd_radio_decode::HdRadioState, rbds_decode::RbdsState, sigmf_writer::SigMfRecorder,
+    spectrum_analyzer::SpectrumFrame,
+};
+use sdr::{
+    calibration::{CalibrationResult, CalibrationSource},
+    capabilities::SDRCapabilities,
+    enumeration::{AvailableSDRArgs, AvailableSDRsRefresher},
+    frequency_correction::get_frequency_correction,
+    iq_file::{get_iq_file_sdr_args, FilePacing, IqSampleFormat},
+    rtl_tcp::{get_rtl_tcp_sdr_args, RTL_TCP_DEFAULT_PORT},
+    simulated::{get_simulated_sdr_args, SimulatedArgs},
+    SDRRegistry,
 };
-use radiorust_blocks::{hd_radio_decode::HdRadioState, rbds_decode::RbdsState};
-use sdr::{enumeration::AvailableSDRArgs, SDRState};
 use serde::Serialize;
 use std::{
     env,
     ffi::{c_char, c_void, CStr},
+    net::IpAddr,
+    path::PathBuf,
+    process::ExitCode,
     sync::{Arc, Mutex},
     thread::sleep,
     time::Duration,
 };
-use tauri::{async_runtime::block_on, ipc::Channel, AppHandle, State};
+use store::{DevicePreferences, Station, StationDetails, StationType, Store};
+use tauri::{
+    async_runtime::block_on,
+    ipc::{Channel, IpcResponse},
+    AppHandle, Emitter, Manager, State,
+};
+use tauri_plugin_shell::ShellExt;
 use utils::{setup_callbacks, setup_dependencies};
 
 struct AppState {
     nrsc5_state: Nrsc5State,
     rtl_sdr_state: Arc<Mutex<RtlSdrState>>,
     adsb_state: Arc<Mutex<AdsbDecoderState>>,
-    sdrs: Arc<Mutex<Vec<SDRState>>>,
+    multi_fm_state: Arc<Mutex<MultiFmState>>,
+    scanner_state: Arc<Mutex<ScannerState>>,
+    rtl_tcp_server_state: Arc<Mutex<RtlTcpServerState>>,
+    sdr_registry: SDRRegistry,
+    store: Store,
 }
 
 impl AppState {
-    pub fn new() -> Self {
+    pub fn new(app: &AppHandle) -> Self {
+        let data_dir = app.path().app_data_dir().ok();
+
         Self {
             nrsc5_state: Nrsc5State::new(),
             rtl_sdr_state: Arc::new(Mutex::new(RtlSdrState::new())),
             adsb_state: Arc::new(Mutex::new(AdsbDecoderState::new())),
-            sdrs: Arc::new(Mutex::new(vec![])),
+            multi_fm_state: Arc::new(Mutex::new(MultiFmState::new())),
+            scanner_state: Arc::new(Mutex::new(ScannerState::new())),
+            rtl_tcp_server_state: Arc::new(Mutex::new(RtlTcpServerState::new())),
+            store: Store::new(data_dir.as_deref()),
+            sdr_registry: SDRRegistry::new(Arc::new(app.clone()), data_dir),
         }
     }
 }
 
+// the radio services publish their updates through callbacks, which are forwarded to the frontend
+fn channel_callback<T: IpcResponse + 'static>(channel: Channel<T>) -> Arc<dyn Fn(T) + Send + Sync> {
+    Arc::new(move |data| {
+        let _ = channel.send(data);
+    })
+}
+
 #[tokio::main]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub async fn run() {
@@ -57,8 +105,9 @@ pub async fn run() {
         .plugin(tauri_plugin_log::Builder::new().build())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_shell::init())
-        .manage(AppState::new())
         .setup(move |app| {
+            app.manage(AppState::new(app.handle()));
+
             setup_dependencies(app);
             setup_callbacks(app);
 
@@ -71,58 +120,130 @@ pub async fn run() {
             stop_stream,
             start_adsb_decoding,
             stop_adsb_decoding,
+            start_multi_fm,
+            stop_multi_fm,
+            start_scan,
+            stop_scan,
             get_sdr_states,
+            refresh_sdrs,
             connect_to_sdr,
-            disconnect_sdr
+            disconnect_sdr,
+            get_sdr_capabilities,
+            get_sdr_frequency_correction,
+            set_sdr_frequency_correction,
+            get_device_preferences,
+            set_device_preferences,
+            get_saved_stations,
+            is_station_saved,
+            save_station,
+            update_station,
+            remove_station,
+            get_seconds_listened,
+            increase_listening_duration,
+            calibrate_sdr,
+            add_iq_file_sdr,
+            add_rtl_tcp_sdr,
+            add_simulated_sdr,
+            remove_virtual_sdr,
+            start_iq_recording,
+            stop_iq_recording,
+            start_audio_recording,
+            stop_audio_recording,
+            set_time_shift_paused,
+            seek_time_shift,
+            start_rtl_tcp_server,
+            stop_rtl_tcp_server
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
+/// Entry point of the headless command-line receiver, which uses the same decoding blocks as the app
+pub fn run_cli() -> ExitCode {
+    cli::run(env::args().skip(1).collect())
+}
+
 #[tauri::command]
-fn start_nrsc5(app: AppHandle, state: State<AppState>, fm_freq: String, channel: String) {
+fn start_nrsc5(
+    app: AppHandle,
+    state: State<AppState>,
+    fm_freq: String,
+    channel: String,
+) -> RadioResult<()> {
     if state.nrsc5_state.is_playing() {
-        return;
+        return Err(RadioError::State(String::from("nrsc5 is already running")));
     };
-    state.nrsc5_state.start_thread(app, fm_freq, channel);
+    let nrsc5_command = app
+        .shell()
+        .sidecar("nrsc5")
+        .expect("failed to create `nrsc5` binary command");
+    state
+        .nrsc5_state
+        .start_thread(Arc::new(app), nrsc5_command, fm_freq, channel);
+
+    Ok(())
 }
 
 #[tauri::command]
-fn stop_nrsc5(app: AppHandle, state: State<AppState>) {
-    state.nrsc5_state.stop_thread(app);
+fn stop_nrsc5(app: AppHandle, state: State<AppState>) -> RadioResult<()> {
+    state.nrsc5_state.stop_thread(&app);
+
+    Ok(())
 }
 
 #[tauri::command]
-fn start_stream(
+async fn start_stream(
     app: AppHandle,
-    state: State<AppState>,
+    state: State<'_, AppState>,
     stream_settings: soapysdr_radio::StreamSettings,
     sdr_args: AvailableSDRArgs,
     rbds_channel: Channel<RbdsState>,
     hd_radio_channel: Channel<HdRadioState>,
-) {
+    spectrum_channel: Option<Channel<SpectrumFrame>>,
+) -> RadioResult<()> {
     if state.rtl_sdr_state.lock().unwrap().is_playing() {
-        return;
+        return Err(RadioError::State(String::from(
+            "The radio is already playing",
+        )));
     };
+
+    // the app icon is shown by the media controls, and Windows also needs the window they belong to
+    let resource_dir = app.path().resource_dir().unwrap();
+    #[cfg(not(target_os = "windows"))]
+    let hwnd = None;
+    #[cfg(target_os = "windows")]
+    let hwnd = Some(app.get_webview_window("main").unwrap().hwnd().unwrap().0 as usize);
+    let media_controls_config = MediaControlsConfig {
+        icon_url: format!(
+            "file://{}/resources/AppIcon.png",
+            resource_dir.as_os_str().to_str().unwrap()
+        ),
+        hwnd,
+    };
+
     state.rtl_sdr_state.lock().unwrap().start_stream(
-        app,
+        Arc::new(app),
+        state.sdr_registry.clone(),
         stream_settings,
         sdr_args,
-        rbds_channel,
-        hd_radio_channel,
-    );
+        channel_callback(rbds_channel),
+        channel_callback(hd_radio_channel),
+        spectrum_channel.map(channel_callback),
+        Some(media_controls_config),
+    )
 }
 
 #[tauri::command]
-async fn stop_stream(app: AppHandle, state: State<'_, AppState>) -> Result<String, ()> {
+async fn stop_stream(app: AppHandle, state: State<'_, AppState>) -> RadioResult<()> {
     let rtlsdr_state_clone = state.rtl_sdr_state.clone();
 
     tokio::task::spawn_blocking(move || {
-        block_on(rtlsdr_state_clone.lock().unwrap().stop_stream(app));
+        block_on(rtlsdr_state_clone.lock().unwrap().stop_stream(&app));
     })
     .await
     .unwrap();
-    Ok("".to_string())
+
+    Ok(())
 }
 
 #[tauri::command]
@@ -132,32 +253,109 @@ fn start_adsb_decoding(
     stream_settings: soapysdr_adsb::StreamSettings,
     sdr_args: AvailableSDRArgs,
     modes_channel: Channel<ModeSState>,
-) {
+    spectrum_channel: Option<Channel<SpectrumFrame>>,
+) -> RadioResult<()> {
     if state.adsb_state.lock().unwrap().is_running() {
-        return;
+        return Err(RadioError::State(String::from(
+            "ADS-B is already being decoded",
+        )));
     };
-    state
-        .adsb_state
-        .lock()
-        .unwrap()
-        .start_decoding(app, stream_settings, sdr_args, modes_channel);
+    state.adsb_state.lock().unwrap().start_decoding(
+        Arc::new(app),
+        state.sdr_registry.clone(),
+        stream_settings,
+        sdr_args,
+        channel_callback(modes_channel),
+        spectrum_channel.map(channel_callback),
+    );
+
+    Ok(())
 }
 
 #[tauri::command]
-async fn stop_adsb_decoding(app: AppHandle, state: State<'_, AppState>) -> Result<String, ()> {
+async fn stop_adsb_decoding(app: AppHandle, state: State<'_, AppState>) -> RadioResult<()> {
     let adsb_state_clone = state.adsb_state.clone();
 
     tokio::task::spawn_blocking(move || {
-        block_on(adsb_state_clone.lock().unwrap().stop_decoding(app));
+        block_on(adsb_state_clone.lock().unwrap().stop_decoding(&app));
+    })
+    .await
+    .unwrap();
+
+    Ok(())
+}
+
+#[tauri::command]
+fn start_multi_fm(
+    app: AppHandle,
+    state: State<AppState>,
+    settings: MultiFmSettings,
+    sdr_args: AvailableSDRArgs,
+    rbds_channel: Channel<StationRbdsState>,
+) -> RadioResult<()> {
+    if state.multi_fm_state.lock().unwrap().is_running() {
+        return Err(RadioError::State(String::from(
+            "FM stations are already being monitored",
+        )));
+    };
+    state.multi_fm_state.lock().unwrap().start_monitoring(
+        Arc::new(app),
+        state.sdr_registry.clone(),
+        settings,
+        sdr_args,
+        channel_callback(rbds_channel),
+    )
+}
+
+#[tauri::command]
+async fn stop_multi_fm(app: AppHandle, state: State<'_, AppState>) -> RadioResult<()> {
+    let multi_fm_state_clone = state.multi_fm_state.clone();
+
+    tokio::task::spawn_blocking(move || {
+        block_on(multi_fm_state_clone.lock().unwrap().stop_monitoring(&app));
+    })
+    .await
+    .unwrap();
+
+    Ok(())
+}
+
+#[tauri::command]
+fn start_scan(
+    app: AppHandle,
+    state: State<AppState>,
+    settings: ScanSettings,
+    sdr_args: AvailableSDRArgs,
+    scan_channel: Channel<ScanUpdate>,
+) -> RadioResult<()> {
+    if state.scanner_state.lock().unwrap().is_running() {
+        return Err(RadioError::State(String::from("A scan is already running")));
+    };
+    state.scanner_state.lock().unwrap().start_scan(
+        Arc::new(app),
+        state.sdr_registry.clone(),
+        settings,
+        sdr_args,
+        channel_callback(scan_channel),
+    )
+}
+
+#[tauri::command]
+async fn stop_scan(app: AppHandle, state: State<'_, AppState>) -> RadioResult<()> {
+    let scanner_state_clone = state.scanner_state.clone();
+
+    tokio::task::spawn_blocking(move || {
+        block_on(scanner_state_clone.lock().unwrap().stop_scan(&app));
     })
     .await
     .unwrap();
-    Ok("".to_string())
+
+    Ok(())
 }
 
 #[tauri::command]
-async fn get_sdr_states(state: State<'_, AppState>) -> Result<serde_json::Value, ()> {
-    let sdrs = state.sdrs.lock().unwrap();
+async fn get_sdr_states(state: State<'_, AppState>) -> RadioResult<serde_json::Value> {
+    let sdrs = state.sdr_registry.lock();
 
     Ok(sdrs
         .clone()
@@ -166,27 +364,358 @@ async fn get_sdr_states(state: State<'_, AppState>) -> Result<serde_json::Value,
 }
 
 #[tauri::command]
-async fn connect_to_sdr(app: AppHandle, args: AvailableSDRArgs) -> Result<(), ()> {
+fn refresh_sdrs(refresher: State<AvailableSDRsRefresher>) -> RadioResult<()> {
+    info!("Refreshing available SDRs");
+
+    refresher.refresh();
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn connect_to_sdr(state: State<'_, AppState>, args: AvailableSDRArgs) -> RadioResult<()> {
     info!("Connecting to {}", args.label);
 
-    let result = sdr::connect_to_sdr(args, app);
+    sdr::connect_to_sdr(&state.sdr_registry, args)
+}
+
+#[tauri::command]
+async fn disconnect_sdr(state: State<'_, AppState>, args: AvailableSDRArgs) -> RadioResult<()> {
+    info!("Disconnecting from {}", args.label);
+
+    let result = sdr::disconnect_sdr(&state.sdr_registry, args);
+
+    return result;
+}
+
+#[tauri::command]
+async fn get_sdr_capabilities(
+    state: State<'_, AppState>,
+    args: AvailableSDRArgs,
+) -> RadioResult<SDRCapabilities> {
+    sdr::get_sdr_capabilities(&state.sdr_registry, args)
+}
+
+#[tauri::command]
+fn get_sdr_frequency_correction(
+    state: State<AppState>,
+    args: AvailableSDRArgs,
+) -> RadioResult<f64> {
+    Ok(get_frequency_correction(state.sdr_registry.data_dir(), &args))
+}
+
+#[tauri::command]
+async fn set_sdr_frequency_correction(
+    state: State<'_, AppState>,
+    args: AvailableSDRArgs,
+    ppm: f64,
+) -> RadioResult<()> {
+    info!(
+        "Setting frequency correction of {} to {} PPM",
+        args.label, ppm
+    );
+
+    sdr::set_sdr_frequency_correction(&state.sdr_registry, args, ppm)
+}
+
+#[tauri::command]
+fn get_device_preferences(
+    state: State<AppState>,
+    args: AvailableSDRArgs,
+) -> RadioResult<DevicePreferences> {
+    Ok(state.store.get_device_preferences(&args))
+}
+
+#[tauri::command]
+async fn set_device_preferences(
+    state: State<'_, AppState>,
+    args: AvailableSDRArgs,
+    preferences: DevicePreferences,
+) -> RadioResult<()> {
+    let ppm = preferences.ppm;
+    state
+        .store
+        .update_device_preferences(&args, |saved_preferences| {
+            // keep the saved frequency correction if a new one wasn't sent
+            *saved_preferences = DevicePreferences {
+                ppm: preferences.ppm.or(saved_preferences.ppm),
+                ..preferences
+            };
+        })
+        .map_err(RadioError::Storage)?;
 
-    if result.is_err() {
-        return Err(());
+    // apply the frequency correction, if the SDR is connected
+    match ppm {
+        Some(ppm) => sdr::set_sdr_frequency_correction(&state.sdr_registry, args, ppm),
+        None => Ok(()),
     }
+}
 
-    Ok(())
+#[tauri::command]
+fn get_saved_stations(
+    state: State<AppState>,
+    station_type: Option<StationType>,
+) -> RadioResult<Vec<StationDetails>> {
+    Ok(state.store.get_stations(station_type))
 }
 
 #[tauri::command]
-async fn disconnect_sdr(
-    app: AppHandle,
+fn is_station_saved(state: State<AppState>, station: Station) -> RadioResult<bool> {
+    Ok(state.store.is_station_saved(&station))
+}
+
+#[tauri::command]
+fn save_station(state: State<AppState>, station: StationDetails) -> RadioResult<()> {
+    state
+        .store
+        .save_station(station)
+        .map_err(RadioError::Storage)
+}
+
+#[tauri::command]
+fn update_station(
+    state: State<AppState>,
+    old_station: Station,
+    new_station: StationDetails,
+) -> RadioResult<()> {
+    state
+        .store
+        .update_station(&old_station, new_station)
+        .map_err(RadioError::Storage)
+}
+
+#[tauri::command]
+fn remove_station(state: State<AppState>, station: Station) -> RadioResult<()> {
+    state
+        .store
+        .remove_station(&station)
+        .map_err(RadioError::Storage)
+}
+
+#[tauri::command]
+fn get_seconds_listened(state: State<AppState>) -> RadioResult<f64> {
+    Ok(state.store.get_seconds_listened())
+}
+
+#[tauri::command]
+fn increase_listening_duration(state: State<AppState>, seconds: f64) -> RadioResult<f64> {
+    state
+        .store
+        .increase_listening_duration(seconds)
+        .map_err(RadioError::Storage)
+}
+
+#[tauri::command]
+async fn calibrate_sdr(
     state: State<'_, AppState>,
     args: AvailableSDRArgs,
-) -> Result<(), &str> {
-    info!("Disconnecting from {}", args.label);
+    source: CalibrationSource,
+) -> RadioResult<CalibrationResult> {
+    let sdr_registry = state.sdr_registry.clone();
 
-    let result = sdr::disconnect_sdr(args, app, state);
+    tokio::task::spawn_blocking(move || {
+        block_on(sdr::calibration::calibrate_sdr(sdr_registry, args, source))
+    })
+    .await
+    .unwrap()
+}
 
-    return result;
+#[tauri::command]
+async fn add_iq_file_sdr(
+    state: State<'_, AppState>,
+    path: String,
+    format: Option<IqSampleFormat>,
+    sample_rate: Option<f64>,
+    center_freq: Option<f64>,
+    looping: bool,
+    pacing: FilePacing,
+) -> RadioResult<AvailableSDRArgs> {
+    let args = get_iq_file_sdr_args(path, format, sample_rate, center_freq, looping, pacing)
+        .map_err(RadioError::Device)?;
+
+    sdr::add_virtual_sdr(&state.sdr_registry, args.clone())?;
+
+    Ok(args)
+}
+
+#[tauri::command]
+async fn add_rtl_tcp_sdr(
+    state: State<'_, AppState>,
+    host: String,
+    port: u16,
+) -> RadioResult<AvailableSDRArgs> {
+    let args = get_rtl_tcp_sdr_args(host, port);
+
+    sdr::add_virtual_sdr(&state.sdr_registry, args.clone())?;
+
+    Ok(args)
+}
+
+#[tauri::command]
+async fn add_simulated_sdr(
+    state: State<'_, AppState>,
+    args: Option<SimulatedArgs>,
+) -> RadioResult<AvailableSDRArgs> {
+    let args = get_simulated_sdr_args(args.unwrap_or_default());
+
+    sdr::add_virtual_sdr(&state.sdr_registry, args.clone())?;
+
+    Ok(args)
+}
+
+#[tauri::command]
+async fn remove_virtual_sdr(state: State<'_, AppState>, args: AvailableSDRArgs) -> RadioResult<()> {
+    info!("Removing {}", args.label);
+
+    sdr::remove_virtual_sdr(&state.sdr_registry, args)
+}
+
+// get the IQ recorder of whichever pipeline is currently running
+fn get_active_iq_recorder(state: &AppState) -> Option<SigMfRecorder> {
+    state
+        .rtl_sdr_state
+        .lock()
+        .unwrap()
+        .get_iq_recorder()
+        .or(state.adsb_state.lock().unwrap().get_iq_recorder())
+}
+
+#[tauri::command]
+fn start_iq_recording(
+    app: AppHandle,
+    state: State<AppState>,
+    path: String,
+    format: Option<IqSampleFormat>,
+) -> RadioResult<()> {
+    let iq_recorder = get_active_iq_recorder(&state).ok_or(RadioError::State(String::from(
+        "There is no active stream to record",
+    )))?;
+
+    iq_recorder
+        .start(PathBuf::from(path), format.unwrap_or(IqSampleFormat::Cf32))
+        .map_err(RadioError::Recording)?;
+
+    let _ = app.emit("iq_recording_status", "recording");
+
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_iq_recording(app: AppHandle, state: State<AppState>) -> RadioResult<()> {
+    let iq_recorder = get_active_iq_recorder(&state).ok_or(RadioError::State(String::from(
+        "There is no active stream to record",
+    )))?;
+
+    iq_recorder.stop().map_err(RadioError::Recording)?;
+
+    let _ = app.emit("iq_recording_status", "stopped");
+
+    Ok(())
+}
+
+#[tauri::command]
+fn start_audio_recording(
+    app: AppHandle,
+    state: State<AppState>,
+    path: String,
+    format: Option<AudioFileFormat>,
+    split_tracks: Option<bool>,
+) -> RadioResult<()> {
+    let audio_recorder = state
+        .rtl_sdr_state
+        .lock()
+        .unwrap()
+        .get_audio_recorder()
+        .ok_or(RadioError::State(String::from(
+            "There is no active stream to record",
+        )))?;
+
+    audio_recorder
+        .start(PathBuf::from(path), format, split_tracks.unwrap_or(false))
+        .map_err(RadioError::Recording)?;
+
+    let _ = app.emit("audio_recording_status", "recording");
+
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_audio_recording(app: AppHandle, state: State<AppState>) -> RadioResult<()> {
+    let audio_recorder = state
+        .rtl_sdr_state
+        .lock()
+        .unwrap()
+        .get_audio_recorder()
+        .ok_or(RadioError::State(String::from(
+            "There is no active stream to record",
+        )))?;
+
+    audio_recorder.stop().map_err(RadioError::Recording)?;
+
+    let _ = app.emit("audio_recording_status", "stopped");
+
+    Ok(())
+}
+
+#[tauri::command]
+fn set_time_shift_paused(state: State<AppState>, paused: bool) -> RadioResult<()> {
+    state
+        .rtl_sdr_state
+        .lock()
+        .unwrap()
+        .set_time_shift_paused(paused)
+}
+
+// play the stream from `offset` seconds behind live, where 0 catches up to live
+#[tauri::command]
+fn seek_time_shift(state: State<AppState>, offset: f64) -> RadioResult<()> {
+    let time_shift = state
+        .rtl_sdr_state
+        .lock()
+        .unwrap()
+        .get_time_shift()
+        .ok_or(RadioError::State(String::from(
+            "There is no active stream to seek",
+        )))?;
+
+    time_shift.seek(offset);
+
+    Ok(())
+}
+
+#[tauri::command]
+fn start_rtl_tcp_server(
+    app: AppHandle,
+    state: State<AppState>,
+    sdr_args: AvailableSDRArgs,
+    bind_address: Option<IpAddr>,
+    port: Option<u16>,
+) -> RadioResult<()> {
+    // checked under the same lock as the start, so two servers can't be started at once
+    let rtl_tcp_server_state = state.rtl_tcp_server_state.lock().unwrap();
+    if rtl_tcp_server_state.is_running() {
+        return Err(RadioError::State(String::from(
+            "The rtl_tcp server is already running",
+        )));
+    };
+    rtl_tcp_server_state.start_server(
+        Arc::new(app),
+        state.sdr_registry.clone(),
+        sdr_args,
+        bind_address.unwrap_or(RTL_TCP_DEFAULT_BIND_ADDRESS),
+        port.unwrap_or(RTL_TCP_DEFAULT_PORT),
+    )
+}
+
+#[tauri::command]
+async fn stop_rtl_tcp_server(app: AppHandle, state: State<'_, AppState>) -> RadioResult<()> {
+    let rtl_tcp_server_state_clone = state.rtl_tcp_server_state.clone();
+
+    tokio::task::spawn_blocking(move || {
+        block_on(rtl_tcp_server_state_clone.lock().unwrap().stop_server(&app));
+    })
+    .await
+    .unwrap();
+
+    Ok(())
 }
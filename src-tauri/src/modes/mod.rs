@@ -13,6 +13,26 @@ use crc::perform_modes_crc;
 use log::debug;
 use types::*;
 
+/// Real Mode S messages (as bits) that are used to test decoding without an SDR
+pub const MODES_TEST_MESSAGES: [&str; 16] = [
+    "1000110110101100010000101101111101011000101001010010001110110101001001001111110111111011100000000100111111011101",
+    "1000110110101100010000101101111110011001000100001111011000110011101110000100000001001100010011011000101010110011",
+    "1000110110101100010000101101111101011000101001010100011101001111000101010110110111000010011010111111100101100111",
+    "0101110110101100010000101101111100001001001110101011100010110010110100101010101101000101111011111110111000001111",
+    "1000110110101100010000101101111110011001000100001111011000110011101110000011110001001101010110000011000010100001",
+    "0101110110101100001011001001110011100011110111001101000100001110101001011010111001000011000101010010010000011011",
+    "1000110110100000010111110010000110011011000001101011011010101111000110001001010000000000110010111100001100111111", // Airborne velocity (air speed)
+    "1000110110101100000011111111100111100001000010100011001000000000000000000000000000000000000101111101011101101000", // Aircraft Status
+    "0101110110101100000011111111100100110010000000001011011101001001101101110010010111010001110100010111000010001101",
+    "1000110110100000000001001110100011101010001000010100100001010101111011110101110000001000100010100010010001001001", // Target and Status Information
+    "1000110101001000010000001101011000100000001011001100001101110001110000110010110011100000010101110110000010011000", // Aircraft Identification
+    "1000110110101100000011111111100101011000101111110000011101100001100111010110001111001101110110111101001100001101", // Airborne position
+    "1000110101000000011000100001110101011000110000111000011001000011010111001100010000010010011010010010101011010110", // Airborne position (CPR Odd)
+    "1000110101000000011000100001110101011000110000111000001011010110100100001100100010101100001010000110001110100111", // Airborne position (CPR Even)
+    "1000110101000000011000100001110101011000110000111000001011010110100100001100100010101100001010000110001110100111", // Airborne position (locally unambiguous)
+    "1000110110100100001000111100010010011001000010011011111100011101000100000000100001011010100111000011110000101011", // Vertical Velocity
+];
+
 pub async fn detect_modes_signal(m: Vec<u16>, modes_state: &mut ModeSState) {
     /* Go through each sample, and see if it and the following 9 samples match the start of the Mode S preamble.
      *
@@ -23,17 +23,15 @@ pub struct Nrsc5 {
 unsafe impl Send for Nrsc5 {}
 
 impl Nrsc5 {
-    pub fn new(callback: nrsc5_callback_t, opaque: *mut c_void) -> Self {
-        let result = Self::init(callback, opaque);
-        if result.is_ok() {
-            Self {
-                nrsc5_state: result.unwrap(),
-                callback: callback,
-                opaque: opaque,
-            }
-        } else {
-            panic!("Error while initiating nrsc5: {}", result.unwrap_err());
-        }
+    pub fn new(callback: nrsc5_callback_t, opaque: *mut c_void) -> Result<Self, String> {
+        let nrsc5_state = Self::init(callback, opaque)
+            .map_err(|err| format!("Error while initiating nrsc5: {}", err))?;
+
+        Ok(Self {
+            nrsc5_state,
+            callback: callback,
+            opaque: opaque,
+        })
     }
 
     pub fn pipe_samples(&self, samples: &[i16]) -> i32 {
@@ -68,9 +66,6 @@ impl Nrsc5 {
                 // If the function succeeds, start and assign `nrsc5_state` to the struct
                 nrsc5_start(nrsc5_state);
 
-                let cstr = unsafe { CStr::from_ptr(nrsc5_state as *const _) }.to_string_lossy();
-                println!("{}", cstr);
-
                 return Ok(nrsc5_state);
             } else {
                 return Err("Failed to open pipe".to_string());
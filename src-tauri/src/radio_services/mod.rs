@@ -1,3 +1,8 @@
 pub mod nrsc5;
+pub mod radio_pipeline;
+pub mod rtl_tcp_server;
 pub mod soapysdr_adsb;
+pub mod soapysdr_multi_fm;
 pub mod soapysdr_radio;
+pub mod soapysdr_scanner;
+pub mod stream_recovery;
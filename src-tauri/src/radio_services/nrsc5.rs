@@ -8,8 +8,9 @@ use std::{
 };
 
 use log::{debug, error, info};
-use tauri::{AppHandle, Emitter};
-use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+use tauri_plugin_shell::process::{Command, CommandEvent};
+
+use crate::events::{EventSink, SharedEventSink};
 
 pub struct Nrsc5State(Arc<Mutex<Nrsc5>>);
 pub struct Nrsc5 {
@@ -25,17 +26,21 @@ impl Nrsc5State {
         })))
     }
 
-    pub fn start_thread(&self, app: AppHandle, fm_freq: String, channel: String) {
+    /// Run the nrsc5 binary (`nrsc5_command`), publishing the metadata that it prints as events
+    pub fn start_thread(
+        &self,
+        events: SharedEventSink,
+        nrsc5_command: Command,
+        fm_freq: String,
+        channel: String,
+    ) {
         // we can make a clone because a clone of Arc is just making another reference to the original
         let nrsc5_state = self.0.clone();
 
         let shutdown_flag = nrsc5_state.lock().unwrap().shutdown_flag.clone();
 
         let nrsc5_thread = thread::spawn(move || {
-            let (mut rx, child) = app
-                .shell()
-                .sidecar("nrsc5")
-                .expect("failed to create `nrsc5` binary command")
+            let (mut rx, child) = nrsc5_command
                 .args([fm_freq, channel])
                 .spawn()
                 .expect("Failed to spawn sidecar");
@@ -53,7 +58,7 @@ impl Nrsc5State {
                         CommandEvent::Stderr(line) => {
                             let string_output = String::from_utf8(line).unwrap();
                             debug!("{}", string_output);
-                            Nrsc5State::handle_nrsc5_output(app.clone(), string_output);
+                            Nrsc5State::handle_nrsc5_output(&*events, string_output);
                         }
                         CommandEvent::Error(line) => {
                             error!("Sidecar Error: {}", line);
@@ -84,7 +89,7 @@ impl Nrsc5State {
         nrsc5.nrsc5_thread = Some(nrsc5_thread);
     }
 
-    pub fn stop_thread(&self, app: AppHandle) {
+    pub fn stop_thread(&self, events: &dyn EventSink) {
         if let Ok(mut nrsc5) = self.0.try_lock() {
             nrsc5.shutdown_flag.store(true, Ordering::SeqCst);
 
@@ -94,8 +99,7 @@ impl Nrsc5State {
 
             nrsc5.shutdown_flag.store(false, Ordering::SeqCst);
 
-            app.emit("nrsc5_status", Some("stopped"))
-                .expect("failed to emit event");
+            events.emit("nrsc5_status", Some("stopped"));
         } else {
             error!("Could not acquire lock immediately");
         }
@@ -122,10 +126,9 @@ impl Nrsc5State {
         return true;
     }
 
-    fn handle_nrsc5_output(app: AppHandle, line: String) {
+    fn handle_nrsc5_output(events: &dyn EventSink, line: String) {
         if line.starts_with("Found") {
-            app.emit("nrsc5_status", Some("sdr-found"))
-                .expect("failed to emit event");
+            events.emit("nrsc5_status", Some("sdr-found"));
         } else if Nrsc5State::is_timestamp(
             line.split(" ")
                 .nth(0)
@@ -136,33 +139,25 @@ impl Nrsc5State {
 
             // continuously send synchronized message to keep frontend updated (a timestamp always means synced)
             if !message.starts_with("Lost synchronization") {
-                app.emit("nrsc5_status", Some("synchronized"))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_status", Some("synchronized"));
             } else {
-                app.emit("nrsc5_status", Some("synchronization_lost"))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_status", Some("synchronization_lost"));
             }
 
             if message.starts_with("Title: ") {
-                app.emit("nrsc5_title", message.strip_prefix("Title: "))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_title", message.strip_prefix("Title: "));
             } else if message.starts_with("Artist: ") {
-                app.emit("nrsc5_artist", message.strip_prefix("Artist: "))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_artist", message.strip_prefix("Artist: "));
             } else if message.starts_with("Audio bit rate: ") {
-                app.emit("nrsc5_br", message.strip_prefix("Audio bit rate: "))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_br", message.strip_prefix("Audio bit rate: "));
             } else if message.starts_with("Station name: ") {
-                app.emit("nrsc5_station", message.strip_prefix("Station name: "))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_station", message.strip_prefix("Station name: "));
             } else if message.starts_with("Slogan: ") {
-                app.emit("nrsc5_slogan", message.strip_prefix("Slogan: "))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_slogan", message.strip_prefix("Slogan: "));
             } else if message.starts_with("Message: ") {
-                app.emit("nrsc5_message", message.strip_prefix("Message: "))
-                    .expect("failed to emit event");
+                events.emit("nrsc5_message", message.strip_prefix("Message: "));
             } else if message.starts_with("BER: ") {
-                app.emit(
+                events.emit(
                     "nrsc5_ber",
                     message
                         .strip_prefix("BER: ")
@@ -170,8 +165,7 @@ impl Nrsc5State {
                         .split(",")
                         .nth(0)
                         .unwrap(),
-                )
-                .expect("failed to emit event");
+                );
             }
         }
     }
@@ -0,0 +1,357 @@
+use std::sync::Arc;
+
+use radiorust::prelude::*;
+
+use crate::{
+    error::{RadioError, RadioResult},
+    nrsc5::bindings::{NRSC5_SAMPLE_RATE_AUDIO, NRSC5_SAMPLE_RATE_CS16_FM},
+    radio_services::soapysdr_radio::{
+        AirbandChannelState, AudioSettings, ChannelSettings, NoiseSettings, SidebandSettings,
+        StreamType, TUNING_OFFSET,
+    },
+    radiorust_blocks::{
+        am_demod::AmDemod,
+        audio_mixer::AudioMixer,
+        biquad_filter::{BiquadFilter, BiquadFilterSettings},
+        channelizer::Channelizer,
+        fm_stereo_decode::{FmStereoDecode, FmStereoStateCallback},
+        hd_radio_decode::{HdRadioDecode, HdRadioStateCallback},
+        iq_correction::IqCorrection,
+        noise_blanker::NoiseBlanker,
+        noise_reduction::NoiseReduction,
+        rbds_decode::{new_rbds_receiver, RbdsStateCallback},
+        squelch::{Squelch, SquelchCallback},
+        ssb_demod::SsbDemod,
+        subaudible_tone::{SubaudibleToneCallback, SubaudibleToneDetect},
+    },
+};
+
+pub type RadioTextCallback = Arc<dyn Fn(String) + Send + Sync>;
+pub type AirbandChannelCallback = Arc<dyn Fn(AirbandChannelState) + Send + Sync>;
+
+/// The settings a [`RadioPipeline`] is built with, where everything but the stream type and sample rate can be
+/// changed while it plays
+#[derive(Clone)]
+pub struct RadioPipelineSettings {
+    pub stream_type: StreamType,
+    // the frequency of the station (in MHz or KHz, depending on the stream type)
+    pub freq: f64,
+    // the sample rate of the audio (in hz), which HD Radio doesn't use
+    pub sample_rate: f64,
+    pub hd_radio_program: u32,
+    // remove the DC offset and IQ imbalance of the SDR
+    pub iq_correction: bool,
+    pub audio: AudioSettings,
+    pub sideband: SidebandSettings,
+    pub channel: ChannelSettings,
+    pub noise: NoiseSettings,
+}
+
+impl RadioPipelineSettings {
+    /// The filter applied to the IQ samples of a station before it is demodulated
+    pub fn if_filter(&self, stream_type: &StreamType) -> BiquadFilterSettings {
+        if matches!(stream_type, StreamType::NBFM | StreamType::AIR) {
+            self.channel.if_filter(stream_type)
+        } else {
+            self.audio.if_filter(stream_type)
+        }
+    }
+
+    /// The sample rate of the audio that comes out of the pipeline, where nrsc5 always uses its own
+    pub fn audio_sample_rate(&self) -> f64 {
+        if self.stream_type == StreamType::HD {
+            NRSC5_SAMPLE_RATE_AUDIO as f64
+        } else {
+            self.sample_rate
+        }
+    }
+}
+
+/// What a [`RadioPipeline`] reports while it plays, which the radio service sends to the frontend and the CLI prints
+#[derive(Clone)]
+pub struct RadioPipelineCallbacks {
+    pub rbds: RbdsStateCallback,
+    // called with each complete RadioText message of RBDS
+    pub radio_text: RadioTextCallback,
+    pub fm_stereo: FmStereoStateCallback,
+    // the squelch of narrowband FM
+    pub squelch: SquelchCallback,
+    pub subaudible_tone: SubaudibleToneCallback,
+    pub airband_channel: AirbandChannelCallback,
+    pub hd_radio: HdRadioStateCallback,
+}
+
+/// The blocks that turn the IQ samples of an SDR into the audio of a station, which are shared by the radio service and
+/// the CLI, so they only differ in where the audio goes
+pub struct RadioPipeline {
+    stream_type: StreamType,
+    iq_correction: IqCorrection<f32>,
+    noise_blanker: NoiseBlanker<f32>,
+    if_filter: BiquadFilter<f32>,
+    sideband_demodulator: SsbDemod<f32>,
+    squelch: Squelch<f32>,
+    airband_filters: Vec<BiquadFilter<f32>>,
+    airband_demodulators: Vec<AmDemod<f32>>,
+    noise_reduction: NoiseReduction<f32>,
+    audio_filter: BiquadFilter<f32>,
+    // the decoder stops when it is dropped, so it is kept as long as the pipeline
+    hd_radio_decoder: Option<HdRadioDecode<f32>>,
+}
+
+impl RadioPipeline {
+    /// Build the blocks for `settings.stream_type`, which are fed the IQ samples of an SDR that is tuned
+    /// [`TUNING_OFFSET`] above the station
+    pub fn new<P>(
+        source: &P,
+        sample_rate: f64,
+        settings: &RadioPipelineSettings,
+        callbacks: RadioPipelineCallbacks,
+    ) -> RadioResult<Self>
+    where
+        P: Producer<Signal<Complex<f32>>>,
+    {
+        let stream_type = settings.stream_type.clone();
+
+        // remove the DC offset and IQ imbalance, if enabled
+        let iq_correction = IqCorrection::<f32>::new(settings.iq_correction);
+        iq_correction.feed_from(source);
+
+        // shift the station from the tuning offset back to the center
+        let freq_shifter = blocks::FreqShifter::<f32>::with_shift(TUNING_OFFSET);
+        freq_shifter.feed_from(&iq_correction);
+
+        // if HD Radio, focus in on the lower sideband
+        let downsampled_rate = if stream_type == StreamType::HD {
+            NRSC5_SAMPLE_RATE_CS16_FM
+        } else {
+            336000.0
+        };
+        let downsample1 = blocks::Downsampler::<f32>::new(
+            16384,
+            downsampled_rate,
+            stream_type.required_bandwidth(),
+        );
+        downsample1.feed_from(&freq_shifter);
+
+        // add lowpass filter
+        let filter1 = blocks::Filter::new(|_, freq| {
+            if freq.abs() <= 100000.0 {
+                Complex::from(1.0)
+            } else {
+                Complex::from(0.0)
+            }
+        });
+        if stream_type != StreamType::HD {
+            filter1.feed_from(&downsample1);
+        }
+
+        // blank impulse noise before any narrow filters spread it out (only used for AM, SSB, CW, and airband)
+        let noise_blanker = NoiseBlanker::<f32>::new(settings.noise.noise_blanker_level());
+        if stream_type == StreamType::AIR {
+            noise_blanker.feed_from(&freq_shifter);
+        } else if stream_type == StreamType::AM || stream_type.is_sideband() {
+            noise_blanker.feed_from(&filter1);
+        }
+
+        // narrow the filter to the chosen IF bandwidth or channel (only used for AM and narrowband FM)
+        let if_filter = BiquadFilter::<f32>::new(settings.if_filter(&stream_type));
+        if stream_type == StreamType::AM {
+            if_filter.feed_from(&noise_blanker);
+        } else if stream_type == StreamType::NBFM {
+            if_filter.feed_from(&filter1);
+        }
+
+        // only used for SSB and CW
+        let sideband_demodulator =
+            SsbDemod::<f32>::new(settings.sideband.demod_settings(&stream_type));
+
+        // only used for narrowband FM
+        let squelch = Squelch::<f32>::new(settings.channel.squelch_level(), callbacks.squelch);
+
+        // remove everything above the audio (such as the stereo pilot and RBDS) before downsampling, where the audio
+        // filter takes care of the chosen audio bandwidth
+        let max_audio_frequency = stream_type.max_audio_frequency(settings.sample_rate);
+        let filter2 = blocks::filters::Filter::new_rectangular(move |bin, freq| {
+            if bin.abs() >= 1 && freq.abs() <= max_audio_frequency {
+                Complex::from(1.0)
+            } else {
+                Complex::from(0.0)
+            }
+        });
+
+        // the channel filter and squelch of every airband channel can be changed while playing
+        let mut airband_filters = vec![];
+        let mut airband_demodulators = vec![];
+        let mut hd_radio_decoder = None;
+
+        match stream_type {
+            StreamType::FM => {
+                let demodulator = blocks::modulation::FmDemod::<f32>::new(150000.0);
+                demodulator.feed_from(&filter1);
+
+                // split the audio into left and right channels
+                let stereo_decoder = FmStereoDecode::<f32>::new(callbacks.fm_stereo);
+                stereo_decoder.feed_from(&demodulator);
+                filter2.feed_from(&stereo_decoder);
+
+                let radio_text_callback = callbacks.radio_text;
+                let rbds_receiver = new_rbds_receiver(callbacks.rbds, move |radio_text: String| {
+                    radio_text_callback(radio_text)
+                });
+                rbds_receiver.feed_from(&demodulator);
+            }
+            StreamType::AM => {
+                let demodulator = AmDemod::<f32>::new();
+                demodulator.feed_from(&if_filter);
+                filter2.feed_from(&demodulator);
+            }
+            StreamType::USB | StreamType::LSB | StreamType::CW => {
+                sideband_demodulator.feed_from(&noise_blanker);
+                filter2.feed_from(&sideband_demodulator);
+            }
+            StreamType::NBFM => {
+                let demodulator =
+                    blocks::modulation::FmDemod::<f32>::new(settings.channel.deviation());
+                demodulator.feed_from(&if_filter);
+
+                // find the CTCSS tone or DCS code, even while the squelch is closed
+                let tone_detector = SubaudibleToneDetect::<f32>::new(callbacks.subaudible_tone);
+                tone_detector.feed_from(&demodulator);
+
+                squelch.feed_from(&demodulator);
+                filter2.feed_from(&squelch);
+            }
+            StreamType::AIR => {
+                let channels = settings.channel.airband_channels(settings.freq);
+                let if_bandwidth = settings.channel.if_bandwidth(&stream_type);
+
+                // every channel has to fit inside of the capture, where the tuned channel is at 0 hz
+                let station_freq = stream_type.station_frequency(settings.freq);
+                let offsets: Vec<f64> = channels
+                    .iter()
+                    .map(|channel| stream_type.station_frequency(*channel) - station_freq)
+                    .collect();
+                let max_offset = (sample_rate - if_bandwidth) / 2.0;
+                if let Some((channel, _)) = channels
+                    .iter()
+                    .zip(offsets.iter())
+                    .find(|(_, offset)| (*offset - TUNING_OFFSET).abs() > max_offset)
+                {
+                    return Err(RadioError::Stream(format!(
+                        "{} MHz is too far from {} MHz to be monitored at the same time",
+                        channel, settings.freq
+                    )));
+                }
+
+                // the channels are decimated to at least the audio sample rate, so they only need to be downsampled
+                let channelizer =
+                    Channelizer::<f32>::new(offsets, if_bandwidth, settings.sample_rate);
+                channelizer.feed_from(&noise_blanker);
+
+                // every channel is heard at once, but the squelch keeps the quiet ones silent
+                let mixer = AudioMixer::<f32>::new(channels.len());
+                for ((channel_output, mixer_input), channel) in channelizer
+                    .channels()
+                    .iter()
+                    .zip(mixer.inputs().iter())
+                    .zip(channels.iter().copied())
+                {
+                    // the channelizer only keeps the channels from aliasing, so the neighbors (as close as 8.33 kHz)
+                    // are removed at the decimated rate, where a sharp filter is cheap
+                    let channel_filter = BiquadFilter::<f32>::new(settings.if_filter(&stream_type));
+                    channel_filter.feed_from(channel_output);
+
+                    let airband_channel_callback = callbacks.airband_channel.clone();
+                    let demodulator = AmDemod::<f32>::with_settings(
+                        settings.channel.am_demod_settings(),
+                        Some(Arc::new(move |is_open: bool| {
+                            airband_channel_callback(AirbandChannelState {
+                                frequency: channel,
+                                is_open,
+                            });
+                        })),
+                    );
+                    demodulator.feed_from(&channel_filter);
+                    mixer_input.feed_from(&demodulator);
+                    airband_filters.push(channel_filter);
+                    airband_demodulators.push(demodulator);
+                }
+                filter2.feed_from(&mixer);
+            }
+            StreamType::HD => {
+                let hd_radio_callback = callbacks.hd_radio;
+                let decoder =
+                    HdRadioDecode::<f32>::new(settings.hd_radio_program, true, move |state| {
+                        hd_radio_callback(state)
+                    })
+                    .map_err(RadioError::Decoder)?;
+                decoder.feed_from(&downsample1);
+                hd_radio_decoder = Some(decoder);
+            }
+        }
+
+        // downsample so the output device can play the audio
+        let downsample2 =
+            blocks::Downsampler::<f32>::new(4096, settings.sample_rate, settings.sample_rate / 2.0);
+        downsample2.feed_from(&filter2);
+
+        // remove background noise, which is passed through unless it is turned on
+        let noise_reduction = NoiseReduction::<f32>::new(settings.noise.noise_reduction_level());
+        noise_reduction.feed_from(&downsample2);
+
+        // apply the de-emphasis and audio bandwidth, which can be changed while playing
+        let audio_filter = BiquadFilter::<f32>::new(settings.audio.audio_filter(&stream_type));
+        audio_filter.feed_from(&noise_reduction);
+
+        Ok(Self {
+            stream_type,
+            iq_correction,
+            noise_blanker,
+            if_filter,
+            sideband_demodulator,
+            squelch,
+            airband_filters,
+            airband_demodulators,
+            noise_reduction,
+            audio_filter,
+            hd_radio_decoder,
+        })
+    }
+
+    /// Send the audio to `consumer`, where HD Radio comes straight from the decoder (which is already filtered)
+    pub fn feed_audio_to<C>(&self, consumer: &C)
+    where
+        C: Consumer<Signal<Complex<f32>>>,
+    {
+        match &self.hd_radio_decoder {
+            Some(hd_radio_decoder) => consumer.feed_from(hd_radio_decoder),
+            None => consumer.feed_from(&self.audio_filter),
+        }
+    }
+
+    pub fn hd_radio_decoder(&self) -> Option<&HdRadioDecode<f32>> {
+        self.hd_radio_decoder.as_ref()
+    }
+
+    /// Apply the settings that can be changed while playing, where the stream type it was built with is kept
+    pub fn update(&self, settings: &RadioPipelineSettings) {
+        let stream_type = &self.stream_type;
+        self.iq_correction.set_enabled(settings.iq_correction);
+        self.if_filter.set_settings(settings.if_filter(stream_type));
+        self.audio_filter
+            .set_settings(settings.audio.audio_filter(stream_type));
+        self.sideband_demodulator
+            .set_settings(settings.sideband.demod_settings(stream_type));
+        self.noise_blanker
+            .set_level(settings.noise.noise_blanker_level());
+        self.noise_reduction
+            .set_level(settings.noise.noise_reduction_level());
+        self.squelch.set_level(settings.channel.squelch_level());
+        for filter in self.airband_filters.iter() {
+            filter.set_settings(settings.if_filter(stream_type));
+        }
+        for demodulator in self.airband_demodulators.iter() {
+            demodulator.set_settings(settings.channel.am_demod_settings());
+        }
+    }
+}
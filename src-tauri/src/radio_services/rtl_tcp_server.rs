@@ -0,0 +1,305 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{debug, error, info, warn};
+use radiorust::prelude::*;
+use soapysdr::Direction;
+use tauri::async_runtime;
+
+use crate::{
+    error::{RadioError, RadioResult},
+    events::{EventSink, SharedEventSink},
+    radiorust_blocks::rtl_tcp_sink::RtlTcpSink,
+    sdr::{
+        device::SDRDevice,
+        enumeration::AvailableSDRArgs,
+        get_sdr_dev, release_sdr_dev,
+        rtl_tcp::{
+            get_rtl_tcp_tuner_gains, RTL_TCP_DEFAULT_SAMPLE_RATE, RTL_TCP_SET_AGC_MODE,
+            RTL_TCP_SET_BIAS_TEE, RTL_TCP_SET_DIRECT_SAMPLING, RTL_TCP_SET_FREQ,
+            RTL_TCP_SET_FREQ_CORRECTION, RTL_TCP_SET_GAIN, RTL_TCP_SET_GAIN_BY_INDEX,
+            RTL_TCP_SET_GAIN_MODE, RTL_TCP_SET_OFFSET_TUNING, RTL_TCP_SET_SAMPLE_RATE,
+            RTL_TCP_SET_TEST_MODE, RTL_TCP_TUNER_NAMES,
+        },
+        SDRDeviceState, SDRRegistry,
+    },
+};
+
+// how often to check for new clients and the shutdown flag
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// only share the SDR with this computer, unless another address is picked
+pub const RTL_TCP_DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+pub struct RtlTcpServerState(Arc<Mutex<RtlTcpServerData>>);
+pub struct RtlTcpServerData {
+    pub server_thread: Option<async_runtime::JoinHandle<()>>,
+    pub shutdown_flag: Arc<AtomicBool>,
+}
+
+impl RtlTcpServerState {
+    pub fn new() -> Self {
+        RtlTcpServerState(Arc::new(Mutex::new(RtlTcpServerData {
+            server_thread: None,
+            shutdown_flag: Arc::new(AtomicBool::new(false)),
+        })))
+    }
+
+    /// Share a connected SDR over the network. Only one client can use the SDR
+    /// at a time, and the SDR is marked as in use while a client is attached.
+    pub fn start_server(
+        &self,
+        events: SharedEventSink,
+        sdr_registry: SDRRegistry,
+        sdr_args: AvailableSDRArgs,
+        bind_address: IpAddr,
+        port: u16,
+    ) -> RadioResult<()> {
+        let is_connected = sdr_registry.lock().iter().any(|sdr| {
+            sdr.args == sdr_args && matches!(sdr.dev, SDRDeviceState::Connected { dev: _ })
+        });
+        if !is_connected {
+            return Err(RadioError::State(String::from(
+                "SDR must be connected before it can be shared",
+            )));
+        }
+
+        // the address or port can't be used, such as when another server is already listening on it
+        let listener = TcpListener::bind((bind_address, port)).map_err(|err| {
+            RadioError::Settings(format!(
+                "Could not listen on {}:{}: {}",
+                bind_address, port, err
+            ))
+        })?;
+        // don't block so the shutdown flag can be checked
+        listener.set_nonblocking(true).unwrap();
+
+        info!(
+            "Sharing {} with rtl_tcp on {}:{}",
+            sdr_args.label, bind_address, port
+        );
+
+        let mut server_data = self.0.lock().unwrap();
+
+        // every server gets its own flag, so stopping an old one can't stop a new one
+        let shutdown_flag = Arc::new(AtomicBool::new(false));
+        server_data.shutdown_flag = shutdown_flag.clone();
+
+        let server_state = self.0.clone();
+        server_data.server_thread = Some(async_runtime::spawn_blocking(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+
+            events.emit("rtl_tcp_server_status", "listening");
+
+            while !shutdown_flag.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, address)) => {
+                        handle_client(
+                            &*events,
+                            &sdr_registry,
+                            &rt,
+                            sdr_args.clone(),
+                            stream,
+                            address,
+                            shutdown_flag.clone(),
+                        );
+
+                        events.emit("rtl_tcp_server_status", "listening");
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                    Err(err) => {
+                        error!("rtl_tcp server stopped accepting clients: {}", err);
+                        break;
+                    }
+                }
+            }
+
+            // the server isn't running anymore, unless it was already stopped and another one started
+            let mut server_data = server_state.lock().unwrap();
+            if Arc::ptr_eq(&server_data.shutdown_flag, &shutdown_flag) {
+                server_data.server_thread = None;
+            }
+        }));
+
+        Ok(())
+    }
+
+    pub async fn stop_server(&self, events: &dyn EventSink) {
+        // the thread locks the state when it ends, so the lock isn't held while waiting for it
+        let server_thread = if let Ok(mut server_data) = self.0.lock() {
+            server_data.shutdown_flag.store(true, Ordering::SeqCst);
+            server_data.server_thread.take()
+        } else {
+            error!("Could not acquire lock immediately");
+            return;
+        };
+
+        if let Some(thread) = server_thread {
+            thread.await.expect("Failed to join thread");
+        }
+
+        events.emit("rtl_tcp_server_status", "stopped");
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.0.lock().unwrap().server_thread.is_some()
+    }
+}
+
+fn handle_client(
+    events: &dyn EventSink,
+    sdr_registry: &SDRRegistry,
+    rt: &tokio::runtime::Runtime,
+    sdr_args: AvailableSDRArgs,
+    mut stream: TcpStream,
+    address: SocketAddr,
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_nodelay(true);
+
+    // take the SDR so nothing else can use it while the client is attached
+    let (sdr_dev, sdr_args) = match get_sdr_dev(sdr_registry, sdr_args) {
+        Ok(sdr_dev) => sdr_dev,
+        Err(err) => {
+            warn!("Rejected rtl_tcp client {}: {}", address, err);
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    };
+
+    info!("rtl_tcp client {} connected", address);
+    events.emit("rtl_tcp_server_status", "client_connected");
+
+    // start with the same defaults as rtl_tcp
+    let _ = sdr_dev.set_sample_rate(Direction::Rx, 0, RTL_TCP_DEFAULT_SAMPLE_RATE);
+    let _ = sdr_dev.set_gain_mode(Direction::Rx, 0, true);
+
+    // send the header with the tuner type and number of gain values, where SoapySDR names the tuner with its maker
+    // (such as "Rafael Micro R820T")
+    let tuner = sdr_args.tuner.to_lowercase();
+    let tuner_type = RTL_TCP_TUNER_NAMES
+        .iter()
+        .position(|name| tuner.contains(&name.to_lowercase()))
+        .unwrap_or(0);
+    let tuner_gains = get_rtl_tcp_tuner_gains(tuner_type);
+    let mut header = [0u8; 12];
+    header[0..4].copy_from_slice(b"RTL0");
+    header[4..8].copy_from_slice(&(tuner_type as u32).to_be_bytes());
+    header[8..12].copy_from_slice(&(tuner_gains.len() as u32).to_be_bytes());
+
+    if stream.write_all(&header).is_ok() {
+        stream_to_client(rt, &sdr_dev, &mut stream, tuner_gains, shutdown_flag);
+    }
+
+    let _ = stream.shutdown(Shutdown::Both);
+    info!("rtl_tcp client {} disconnected", address);
+
+    // release the SDR
+    if let Err(err) = release_sdr_dev(sdr_registry, sdr_dev, sdr_args) {
+        warn!("Could not release SDR: {}", err);
+    }
+}
+
+fn stream_to_client(
+    rt: &tokio::runtime::Runtime,
+    sdr_dev: &SDRDevice,
+    stream: &mut TcpStream,
+    tuner_gains: &[i32],
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    let sample_stream = stream.try_clone();
+    if sample_stream.is_err() {
+        error!("Could not send samples to rtl_tcp client");
+        return;
+    }
+
+    // the radiorust blocks need to be created inside of the runtime
+    let _guard = rt.enter();
+
+    let sdr_rx = sdr_dev.rx_stream(RTL_TCP_DEFAULT_SAMPLE_RATE).unwrap();
+    let rtl_tcp_sink = RtlTcpSink::<f32>::new(sample_stream.unwrap());
+    rtl_tcp_sink.feed_from(&sdr_rx);
+
+    if let Err(err) = rt.block_on(sdr_rx.activate()) {
+        error!("Could not start SDR stream for rtl_tcp client: {}", err);
+        return;
+    }
+
+    // time out reads so the shutdown flag can be checked
+    let _ = stream.set_read_timeout(Some(POLL_INTERVAL));
+
+    // each command is a 1 byte id followed by a big-endian u32
+    let mut command = [0u8; 5];
+    let mut command_len = 0;
+
+    while !shutdown_flag.load(Ordering::SeqCst) && !rtl_tcp_sink.is_closed() {
+        match stream.read(&mut command[command_len..]) {
+            Ok(0) => break,
+            Ok(read_len) => {
+                command_len += read_len;
+                if command_len == command.len() {
+                    let param =
+                        u32::from_be_bytes([command[1], command[2], command[3], command[4]]);
+                    if let Err(err) = handle_command(sdr_dev, tuner_gains, command[0], param) {
+                        warn!(
+                            "Failed to apply rtl_tcp command {:#04x}: {}",
+                            command[0], err
+                        );
+                    }
+                    command_len = 0;
+                }
+            }
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                continue
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+fn handle_command(
+    sdr_dev: &SDRDevice,
+    tuner_gains: &[i32],
+    command: u8,
+    param: u32,
+) -> Result<(), String> {
+    let is_enabled = if param != 0 { "true" } else { "false" };
+
+    match command {
+        RTL_TCP_SET_FREQ => sdr_dev.set_frequency(Direction::Rx, 0, param as f64, ""),
+        RTL_TCP_SET_SAMPLE_RATE => sdr_dev.set_sample_rate(Direction::Rx, 0, param as f64),
+        // 0 -> automatic, 1 -> manual
+        RTL_TCP_SET_GAIN_MODE => sdr_dev.set_gain_mode(Direction::Rx, 0, param == 0),
+        // the gain is sent in tenths of a dB
+        RTL_TCP_SET_GAIN => sdr_dev.set_gain(Direction::Rx, 0, (param as i32) as f64 / 10.0),
+        RTL_TCP_SET_GAIN_BY_INDEX => {
+            let gain = tuner_gains
+                .get(param as usize)
+                .ok_or(format!("Invalid gain index {}", param))?;
+            sdr_dev.set_gain(Direction::Rx, 0, *gain as f64 / 10.0)
+        }
+        RTL_TCP_SET_FREQ_CORRECTION => {
+            sdr_dev.set_frequency_correction(Direction::Rx, 0, (param as i32) as f64)
+        }
+        RTL_TCP_SET_DIRECT_SAMPLING => sdr_dev.write_setting("direct_samp", &param.to_string()),
+        RTL_TCP_SET_AGC_MODE => sdr_dev.write_setting("digital_agc", is_enabled),
+        RTL_TCP_SET_OFFSET_TUNING => sdr_dev.write_setting("offset_tune", is_enabled),
+        RTL_TCP_SET_TEST_MODE => sdr_dev.write_setting("testmode", is_enabled),
+        RTL_TCP_SET_BIAS_TEE => sdr_dev.write_setting("biastee", is_enabled),
+        _ => {
+            // the IF gain and crystal frequencies can't be set through SoapySDR
+            debug!("Ignoring unsupported rtl_tcp command {:#04x}", command);
+            Ok(())
+        }
+    }
+}
@@ -1,48 +1,64 @@
-use std::{
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
-    },
-    time::Duration,
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
 };
 
 use blocks::Rechunker;
-use log::error;
-use radiorust::{blocks::io::rf, prelude::*};
+use log::{error, warn};
+use radiorust::prelude::*;
 use soapysdr::Direction;
-use tauri::{async_runtime, ipc::Channel, AppHandle, Emitter};
-use tokio::{self, time};
+use tauri::async_runtime;
 
 use crate::{
-    modes::types::ModeSState,
-    radiorust_blocks::adsb_decode::AdsbDecode,
-    sdr::{enumeration::AvailableSDRArgs, get_sdr_dev, release_sdr_dev},
+    error::{emit_error, RadioError},
+    events::{EventSink, SharedEventSink},
+    radio_services::stream_recovery::{
+        emit_recovery_status, monitor_stream, recover_sdr_dev, SessionEnd, StreamRecoveryStatus,
+    },
+    radiorust_blocks::{
+        adsb_decode::{AdsbDecode, ModeSStateCallback},
+        iq_correction::IqCorrection,
+        sigmf_writer::{SigMfRecorder, SigMfWriterBlock},
+        spectrum_analyzer::{SpectrumAnalyzer, SpectrumCallback, SpectrumSettings},
+        stream_monitor::StreamMonitor,
+    },
+    sdr::{enumeration::AvailableSDRArgs, get_sdr_dev, release_sdr_dev, SDRRegistry},
 };
 
 pub struct AdsbDecoderState(Arc<Mutex<AdsbDecoderData>>);
 pub struct AdsbDecoderData {
     pub decode_thread: Option<async_runtime::JoinHandle<()>>,
     pub shutdown_flag: Arc<AtomicBool>,
+    pub iq_recorder: Option<SigMfRecorder>,
 }
 
 #[derive(serde::Deserialize)]
-pub struct StreamSettings {}
+pub struct StreamSettings {
+    #[serde(default)]
+    spectrum: Option<SpectrumSettings>,
+    // remove the DC offset and IQ imbalance of the SDR
+    #[serde(default)]
+    iq_correction: bool,
+}
 
 impl AdsbDecoderState {
     pub fn new() -> Self {
         AdsbDecoderState(Arc::new(Mutex::new(AdsbDecoderData {
             decode_thread: None,
             shutdown_flag: Arc::new(AtomicBool::new(false)),
+            iq_recorder: None,
         })))
     }
 
     // TODO: use these stream settings and allow users to modify ADS-B decode settings
     pub fn start_decoding(
         &self,
-        app: AppHandle,
-        _stream_settings: StreamSettings,
+        events: SharedEventSink,
+        sdr_registry: SDRRegistry,
+        stream_settings: StreamSettings,
         sdr_args: AvailableSDRArgs,
-        modes_channel: Channel<ModeSState>,
+        modes_callback: ModeSStateCallback,
+        spectrum_callback: Option<SpectrumCallback>,
     ) {
         let adbs_decoder_state = self.0.clone();
         let adbs_decoder_state_clone = adbs_decoder_state.clone();
@@ -55,103 +71,432 @@ impl AdsbDecoderState {
                     .unwrap()
                     .block_on(async move {
                         // get SDR
-                        let rtlsdr_dev_result = get_sdr_dev(app.clone(), sdr_args);
+                        let rtlsdr_dev_result = get_sdr_dev(&sdr_registry, sdr_args);
 
-                        if rtlsdr_dev_result.is_err() {
+                        if let Err(err) = &rtlsdr_dev_result {
                             // notify frontend of error
-                            app.emit("rtlsdr_err", unsafe {
-                                rtlsdr_dev_result.unwrap_err_unchecked()
-                            })
-                            .expect("failed to emit event");
-                            app.emit("rtlsdr_status", "stopped")
-                                .expect("failed to emit event");
+                            emit_error(&*events, "adsb", err.clone());
+                            events.emit("adsb_status", "stopped");
 
                             // remove the reference to the thread
                             drop(adbs_decoder_state.lock().unwrap().decode_thread.take());
                             return;
                         }
 
-                        let (rtlsdr_dev, sdr_args) = rtlsdr_dev_result.unwrap();
+                        let (mut rtlsdr_dev, mut sdr_args) = rtlsdr_dev_result.unwrap();
+                        let mut is_recovering = false;
 
-                        // set sample rate (the clock is 1MHz, so we need at least 2MHz sample rate, which the RTL-SDR can barely do)
-                        let sample_rate = 2e6;
-                        let _ = rtlsdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate);
+                        loop {
+                            let session_end = 'session: {
+                                // get what the SDR supports, so valid settings are used
+                                let capabilities = match rtlsdr_dev.capabilities(Direction::Rx, 0) {
+                                    Ok(capabilities) => capabilities,
+                                    Err(err) => {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err))
+                                    }
+                                };
 
-                        // set center frequency
-                        rtlsdr_dev
-                            .set_frequency(Direction::Rx, 0, 1090.0 * 1_000_000.0, "")
-                            .expect("Failed to set frequency");
+                                // set sample rate (the clock is 1MHz, so we need at least 2MHz sample rate, which the RTL-SDR can barely do)
+                                let sample_rate = 2e6;
+                                if capabilities.closest_sample_rate(sample_rate) != sample_rate {
+                                    // this happens with IQ recordings, which have a fixed sample rate
+                                    break 'session SessionEnd::Aborted(RadioError::Device(
+                                        format!(
+                                            "ADS-B decoding requires a sample rate of {}hz",
+                                            sample_rate
+                                        ),
+                                    ));
+                                }
+                                let _ = rtlsdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate);
 
-                        // make sure direct sampling is disabled
-                        let _ = rtlsdr_dev.write_setting("direct_samp", "0");
+                                // set center frequency
+                                if let Err(err) = rtlsdr_dev.set_frequency(
+                                    Direction::Rx,
+                                    0,
+                                    1090.0 * 1_000_000.0,
+                                    "",
+                                ) {
+                                    break 'session SessionEnd::Failed(RadioError::Device(err));
+                                }
 
-                        // enable automatic gain mode
-                        rtlsdr_dev
-                            .set_gain_mode(Direction::Rx, 0, true)
-                            .expect("Failed to set automatic gain");
+                                // make sure direct sampling is disabled
+                                if capabilities.has_setting("direct_samp") {
+                                    let _ = rtlsdr_dev.write_setting("direct_samp", "0");
+                                }
 
-                        // set the bandwidth
-                        let _ = rtlsdr_dev.set_bandwidth(Direction::Rx, 0, sample_rate / 2.0);
+                                // enable automatic gain mode, otherwise use the maximum gain
+                                if capabilities.has_automatic_gain {
+                                    if let Err(err) =
+                                        rtlsdr_dev.set_gain_mode(Direction::Rx, 0, true)
+                                    {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err));
+                                    }
+                                } else if capabilities.has_manual_gain() {
+                                    let _ = rtlsdr_dev.set_gain(
+                                        Direction::Rx,
+                                        0,
+                                        capabilities.gain_range.maximum,
+                                    );
+                                }
 
-                        // start sdr rx stream
-                        let rx_stream = rtlsdr_dev.rx_stream::<Complex<f32>>(&[0]).unwrap();
-                        let sdr_rx = rf::soapysdr::SoapySdrRx::new(rx_stream, sample_rate);
-                        sdr_rx.activate().await.unwrap();
+                                // set the bandwidth
+                                let _ =
+                                    rtlsdr_dev.set_bandwidth(Direction::Rx, 0, sample_rate / 2.0);
 
-                        let rechunker = Rechunker::new((sample_rate).round() as usize);
-                        rechunker.feed_from(&sdr_rx);
+                                // start sdr rx stream
+                                let sdr_rx = match rtlsdr_dev.rx_stream(sample_rate) {
+                                    Ok(sdr_rx) => sdr_rx,
+                                    Err(err) => {
+                                        break 'session SessionEnd::Failed(RadioError::Stream(err))
+                                    }
+                                };
+                                if let Err(err) = sdr_rx.activate().await {
+                                    break 'session SessionEnd::Failed(RadioError::Stream(err));
+                                }
 
-                        // add buffer to discard samples that take long than 1 second to be processed by ADS-B decode (to prevent slowdowns)
-                        let buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 0.1);
-                        buffer.feed_from(&rechunker);
+                                // watch for read failures and timeouts
+                                let stream_monitor = StreamMonitor::<f32>::new();
+                                stream_monitor.feed_from(&sdr_rx);
 
-                        let adsb_decode = AdsbDecode::new(modes_channel, false);
-                        adsb_decode.feed_from(&buffer);
+                                // add IQ recorder (only writes samples while a recording is started)
+                                let events_clone = events.clone();
+                                let iq_writer = SigMfWriterBlock::<f32>::new(
+                                    sdr_args.clone(),
+                                    sample_rate,
+                                    1090.0 * 1_000_000.0,
+                                    rtlsdr_dev.gain(Direction::Rx, 0).unwrap_or(0.0),
+                                    Arc::new(move |err: String| {
+                                        events_clone.emit("iq_recording_error", err);
+                                        events_clone.emit("iq_recording_status", "stopped");
+                                    }),
+                                );
+                                iq_writer.feed_from(&stream_monitor);
+                                let iq_recorder = iq_writer.recorder();
+                                adbs_decoder_state.lock().unwrap().iq_recorder =
+                                    Some(iq_recorder.clone());
 
-                        // let wavwriter = WavWriterBlock::new(
-                        //     String::from("../adsb_output.wav"),
-                        //     false,
-                        //     Some(10.0),
-                        // );
-                        // wavwriter.feed_from(&adsb_decode);
+                                // show what the SDR sees, if the frontend wants it
+                                if let Some(spectrum_callback) = spectrum_callback.clone() {
+                                    let spectrum_analyzer = SpectrumAnalyzer::<f32>::new(
+                                        stream_settings.spectrum.unwrap_or_default(),
+                                        1090.0 * 1_000_000.0,
+                                        spectrum_callback,
+                                    );
+                                    spectrum_analyzer.feed_from(&stream_monitor);
+                                }
 
-                        while !shutdown_flag.load(Ordering::SeqCst) {
-                            // notify frontend that decoding is happening
-                            app.emit("adsb_status", "running")
-                                .expect("failed to emit event");
+                                // remove the DC offset and IQ imbalance, if enabled
+                                let iq_correction =
+                                    IqCorrection::<f32>::new(stream_settings.iq_correction);
+                                iq_correction.feed_from(&iq_writer);
 
-                            time::sleep(Duration::from_millis(250)).await;
-                        }
+                                let rechunker = Rechunker::new((sample_rate).round() as usize);
+                                rechunker.feed_from(&iq_correction);
+
+                                // add buffer to discard samples that take long than 1 second to be processed by ADS-B decode (to prevent slowdowns)
+                                let buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 0.1);
+                                buffer.feed_from(&rechunker);
+
+                                let adsb_decode = AdsbDecode::new(modes_callback.clone(), false);
+                                adsb_decode.feed_from(&buffer);
+
+                                // let wavwriter = WavWriterBlock::new(
+                                //     String::from("../adsb_output.wav"),
+                                //     false,
+                                //     Some(10.0),
+                                // );
+                                // wavwriter.feed_from(&adsb_decode);
+
+                                if is_recovering {
+                                    emit_recovery_status(
+                                        &*events,
+                                        "adsb",
+                                        StreamRecoveryStatus::Recovered,
+                                    );
+                                    is_recovering = false;
+                                }
+
+                                let session_end = monitor_stream(
+                                    &stream_monitor,
+                                    &sdr_rx,
+                                    &shutdown_flag,
+                                    || {
+                                        // notify frontend that decoding is happening
+                                        events.emit("adsb_status", "running");
+                                    },
+                                )
+                                .await;
+
+                                // save the recording, since it can't continue with a new stream
+                                if let SessionEnd::Failed(_) = session_end {
+                                    if iq_recorder.is_recording() {
+                                        let _ = iq_recorder.stop();
+                                    }
+                                }
+
+                                let _ = sdr_rx.deactivate().await;
+
+                                session_end
+                            };
+
+                            match session_end {
+                                SessionEnd::Stopped => {
+                                    // release the SDR
+                                    if let Err(err) =
+                                        release_sdr_dev(&sdr_registry, rtlsdr_dev, sdr_args)
+                                    {
+                                        warn!("Could not release SDR: {}", err);
+                                    }
 
-                        // release the SDR
-                        release_sdr_dev(app, rtlsdr_dev, sdr_args).unwrap();
+                                    // the recording ended, so nothing else will say that it stopped
+                                    if !shutdown_flag.load(Ordering::SeqCst) {
+                                        drop(
+                                            adbs_decoder_state.lock().unwrap().decode_thread.take(),
+                                        );
+                                        events.emit("adsb_status", "stopped");
+                                    }
+                                    break;
+                                }
+                                SessionEnd::Aborted(error) => {
+                                    if let Err(err) =
+                                        release_sdr_dev(&sdr_registry, rtlsdr_dev, sdr_args)
+                                    {
+                                        warn!("Could not release SDR: {}", err);
+                                    }
+                                    emit_error(&*events, "adsb", error);
+                                    events.emit("adsb_status", "stopped");
+
+                                    // remove the reference to the thread, so it can be started again
+                                    drop(adbs_decoder_state.lock().unwrap().decode_thread.take());
+                                    break;
+                                }
+                                SessionEnd::Failed(error) => {
+                                    // restart with the same settings once the SDR is available again
+                                    let Some(recovered_sdr) = recover_sdr_dev(
+                                        &*events,
+                                        &sdr_registry,
+                                        "adsb",
+                                        &sdr_args,
+                                        error,
+                                        &shutdown_flag,
+                                    )
+                                    .await
+                                    else {
+                                        break;
+                                    };
+                                    (rtlsdr_dev, sdr_args) = recovered_sdr;
+                                    is_recovering = true;
+                                }
+                            }
+                        }
                     })
             }));
     }
 
-    pub async fn stop_decoding(&self, app: AppHandle) {
-        if let Ok(mut adsb_decoder_data) = self.0.clone().lock() {
-            adsb_decoder_data
-                .shutdown_flag
-                .store(true, Ordering::SeqCst);
-
-            if let Some(thread) = adsb_decoder_data.decode_thread.take() {
-                thread.await.expect("Failed to join thread");
+    pub async fn stop_decoding(&self, events: &dyn EventSink) {
+        // the decode thread needs the lock too, so it isn't held while waiting for the thread
+        let (shutdown_flag, thread) = match self.0.lock() {
+            Ok(mut adsb_decoder_data) => {
+                adsb_decoder_data
+                    .shutdown_flag
+                    .store(true, Ordering::SeqCst);
+                (
+                    adsb_decoder_data.shutdown_flag.clone(),
+                    adsb_decoder_data.decode_thread.take(),
+                )
+            }
+            Err(_) => {
+                error!("Could not acquire lock immediately");
+                return;
             }
+        };
 
-            adsb_decoder_data
-                .shutdown_flag
-                .store(false, Ordering::SeqCst);
+        if let Some(thread) = thread {
+            thread.await.expect("Failed to join thread");
+        }
 
-            app.emit("adsb_status", Some("stopped"))
-                .expect("failed to emit event");
-        } else {
-            error!("Could not acquire lock immediately");
-            return;
+        // make sure an IQ recording in progress is saved
+        if let Ok(mut adsb_decoder_data) = self.0.lock() {
+            if let Some(iq_recorder) = adsb_decoder_data.iq_recorder.take() {
+                if iq_recorder.is_recording() {
+                    let _ = iq_recorder.stop();
+                }
+            }
         }
+
+        shutdown_flag.store(false, Ordering::SeqCst);
+
+        events.emit("adsb_status", Some("stopped"));
+    }
+
+    pub fn get_iq_recorder(&self) -> Option<SigMfRecorder> {
+        return self.0.clone().lock().unwrap().iq_recorder.clone();
     }
 
     pub fn is_running(&self) -> bool {
         return self.0.clone().lock().unwrap().decode_thread.is_some();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        events::RecordingEventSink,
+        modes::{types::ModeSState, MODES_TEST_MESSAGES},
+        sdr::{
+            add_virtual_sdr,
+            iq_file::{get_iq_file_sdr_args, FilePacing, IqSampleFormat},
+            simulated::{get_simulated_sdr_args, SimulatedArgs, SimulatedSignal},
+        },
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    // start decoding with the SDR, passing along every state the decoder sends
+    fn start_decoder(
+        events: &Arc<RecordingEventSink>,
+        sdr_args: AvailableSDRArgs,
+    ) -> (AdsbDecoderState, mpsc::Receiver<ModeSState>) {
+        let sdr_registry = SDRRegistry::new(events.clone(), None);
+        add_virtual_sdr(&sdr_registry, sdr_args.clone()).unwrap();
+
+        let (modes_sender, modes_receiver) = mpsc::channel::<ModeSState>();
+        let modes_sender = Mutex::new(modes_sender);
+        let modes_callback: ModeSStateCallback = Arc::new(move |state| {
+            let _ = modes_sender.lock().unwrap().send(state);
+        });
+
+        let decoder = AdsbDecoderState::new();
+        decoder.start_decoding(
+            events.clone(),
+            sdr_registry,
+            serde_json::from_value(json!({})).unwrap(),
+            sdr_args,
+            modes_callback,
+            None,
+        );
+
+        (decoder, modes_receiver)
+    }
+
+    #[test]
+    fn decodes_simulated_sdr() {
+        let events = RecordingEventSink::new();
+        let (decoder, modes_receiver) = start_decoder(
+            &events,
+            get_simulated_sdr_args(SimulatedArgs {
+                name: String::from("ADS-B"),
+                signals: vec![SimulatedSignal::ModeS {
+                    frequency: 1090e6,
+                    messages: Some(vec![MODES_TEST_MESSAGES[0].to_string()]),
+                }],
+                noise_level: 0.01,
+                pacing: FilePacing::RealTime,
+            }),
+        );
+
+        assert!(events.wait_for("adsb_status", json!("running"), TIMEOUT));
+
+        // the aircraft in the simulated message is reported
+        let deadline = Instant::now() + TIMEOUT;
+        let mut found_aircraft = false;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match modes_receiver.recv_timeout(remaining) {
+                Ok(state) => {
+                    if state
+                        .aircraft
+                        .iter()
+                        .any(|aircraft| aircraft.icao_address == 0xAC42DF)
+                    {
+                        found_aircraft = true;
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        assert!(found_aircraft);
+
+        async_runtime::block_on(decoder.stop_decoding(&*events));
+        assert!(events.wait_for("adsb_status", json!("stopped"), TIMEOUT));
+        assert!(!decoder.is_running());
+
+        // nothing went wrong along the way
+        assert!(events
+            .events()
+            .iter()
+            .all(|(event, _)| event != "radio_error"));
+    }
+
+    #[test]
+    fn stops_at_end_of_recording() {
+        let path = std::env::temp_dir().join(format!(
+            "rtlsdr_radio_test_adsb_end_{}.cu8",
+            std::process::id()
+        ));
+        std::fs::write(&path, [127u8; 200_000]).unwrap();
+        let sdr_args = get_iq_file_sdr_args(
+            path.to_string_lossy().to_string(),
+            Some(IqSampleFormat::Cu8),
+            Some(2e6),
+            Some(1090e6),
+            false,
+            FilePacing::AsFastAsPossible,
+        )
+        .unwrap();
+
+        let events = RecordingEventSink::new();
+        let (decoder, _modes_receiver) = start_decoder(&events, sdr_args);
+
+        // the session ends on its own once the whole file is played, which isn't a failure
+        assert!(events.wait_for("adsb_status", json!("stopped"), TIMEOUT));
+        assert!(!decoder.is_running());
+        assert!(events
+            .events()
+            .iter()
+            .all(|(event, _)| { event != "radio_error" && event != "stream_recovery_status" }));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn stops_at_unsupported_sample_rate() {
+        // a recording has a fixed sample rate, which can't be changed to the 2MHz the decoder needs
+        let path =
+            std::env::temp_dir().join(format!("rtlsdr_radio_test_adsb_{}.cu8", std::process::id()));
+        std::fs::write(&path, [127u8; 2_000]).unwrap();
+        let sdr_args = get_iq_file_sdr_args(
+            path.to_string_lossy().to_string(),
+            Some(IqSampleFormat::Cu8),
+            Some(1e6),
+            Some(1090e6),
+            false,
+            FilePacing::AsFastAsPossible,
+        )
+        .unwrap();
+
+        let events = RecordingEventSink::new();
+        let (_decoder, _modes_receiver) = start_decoder(&events, sdr_args);
+
+        // the ADS-B view only listens to its own status, so it has to be told that decoding stopped
+        assert!(events.wait_for("adsb_status", json!("stopped"), TIMEOUT));
+        assert!(events
+            .events()
+            .iter()
+            .any(|(event, payload)| event == "radio_error" && payload["service"] == "adsb"));
+        assert!(events
+            .events()
+            .iter()
+            .all(|(event, payload)| event != "adsb_status" || payload != "running"));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
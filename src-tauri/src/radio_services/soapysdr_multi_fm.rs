@@ -0,0 +1,489 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use log::{error, info, warn};
+use radiorust::prelude::*;
+use serde::Serialize;
+use soapysdr::Direction;
+use tauri::async_runtime;
+
+use crate::{
+    error::{emit_error, RadioError, RadioResult},
+    events::{EventSink, SharedEventSink},
+    radio_services::stream_recovery::{
+        emit_recovery_status, monitor_stream, recover_sdr_dev, SessionEnd, StreamRecoveryStatus,
+    },
+    radiorust_blocks::{
+        better_cpal,
+        channelizer::Channelizer,
+        iq_correction::IqCorrection,
+        rbds_decode::{new_rbds_receiver, RbdsState},
+        stream_monitor::StreamMonitor,
+    },
+    sdr::{enumeration::AvailableSDRArgs, get_sdr_dev, release_sdr_dev, SDRRegistry},
+};
+
+// a wide capture covers many FM stations at once
+const CAPTURE_SAMPLE_RATE: f64 = 2.4e6;
+// FM stations are 200 kHz wide
+const STATION_BANDWIDTH: f64 = 200_000.0;
+// high enough for the 57 kHz RBDS subcarrier
+const STATION_SAMPLE_RATE: f64 = 240_000.0;
+
+pub struct MultiFmState(Arc<Mutex<MultiFmData>>);
+pub struct MultiFmData {
+    pub monitor_thread: Option<async_runtime::JoinHandle<()>>,
+    pub shutdown_flag: Arc<AtomicBool>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct MultiFmSettings {
+    // frequencies of the stations to decode (in MHz)
+    stations: Vec<f64>,
+    // center frequency of the capture (in MHz), which defaults to the middle of the stations
+    center_freq: Option<f64>,
+    // index of the station that is played
+    audio_station: usize,
+    volume: f64,
+    gain: f64,
+    // sample rate of the audio output
+    sample_rate: f64,
+    // remove the DC offset and IQ imbalance of the SDR
+    #[serde(default)]
+    iq_correction: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StationRbdsState {
+    // frequency of the station (in MHz)
+    pub frequency: f64,
+    pub rbds_state: RbdsState,
+}
+
+pub type StationRbdsCallback = Arc<dyn Fn(StationRbdsState) + Send + Sync>;
+
+impl MultiFmState {
+    pub fn new() -> Self {
+        MultiFmState(Arc::new(Mutex::new(MultiFmData {
+            monitor_thread: None,
+            shutdown_flag: Arc::new(AtomicBool::new(false)),
+        })))
+    }
+
+    /// Demodulate several FM stations from one wideband capture, decoding RBDS from all of
+    /// them while playing the audio of one.
+    pub fn start_monitoring(
+        &self,
+        events: SharedEventSink,
+        sdr_registry: SDRRegistry,
+        settings: MultiFmSettings,
+        sdr_args: AvailableSDRArgs,
+        rbds_callback: StationRbdsCallback,
+    ) -> RadioResult<()> {
+        if settings.stations.is_empty() {
+            return Err(RadioError::Settings(String::from(
+                "At least one station is required",
+            )));
+        }
+        if settings.audio_station >= settings.stations.len() {
+            return Err(RadioError::Settings(String::from(
+                "The station to play does not exist",
+            )));
+        }
+
+        let center_freq = settings.center_freq.unwrap_or_else(|| {
+            let lowest = settings.stations.iter().copied().fold(f64::MAX, f64::min);
+            let highest = settings.stations.iter().copied().fold(f64::MIN, f64::max);
+            let middle = (lowest + highest) / 2.0;
+
+            // move half a channel over if a station would be on the DC spike of the SDR
+            if settings
+                .stations
+                .iter()
+                .any(|station| (station - middle).abs() < STATION_BANDWIDTH / 4.0 / 1_000_000.0)
+            {
+                middle + STATION_BANDWIDTH / 2.0 / 1_000_000.0
+            } else {
+                middle
+            }
+        }) * 1_000_000.0;
+
+        // every station has to fit inside of the capture
+        let max_offset = (CAPTURE_SAMPLE_RATE - STATION_BANDWIDTH) / 2.0;
+        for station in settings.stations.iter() {
+            if (station * 1_000_000.0 - center_freq).abs() > max_offset {
+                return Err(RadioError::Settings(format!(
+                    "{} MHz is too far from the center frequency of {} MHz",
+                    station,
+                    center_freq / 1_000_000.0
+                )));
+            }
+        }
+
+        let num_stations = settings.stations.len();
+
+        let multi_fm_state = self.0.clone();
+        let multi_fm_state_clone = multi_fm_state.clone();
+
+        let shutdown_flag = multi_fm_state.lock().unwrap().shutdown_flag.clone();
+
+        multi_fm_state.lock().unwrap().monitor_thread =
+            Some(async_runtime::spawn_blocking(move || {
+                tokio::runtime::Runtime::new()
+                    .unwrap()
+                    .block_on(async move {
+                        // get SDR
+                        let rtlsdr_dev_result = get_sdr_dev(&sdr_registry, sdr_args);
+
+                        if let Err(err) = &rtlsdr_dev_result {
+                            // notify frontend of error
+                            emit_error(&*events, "multi_fm", err.clone());
+                            events.emit("multi_fm_status", "stopped");
+
+                            // remove the reference to the thread
+                            drop(multi_fm_state_clone.lock().unwrap().monitor_thread.take());
+                            return;
+                        }
+
+                        let (mut rtlsdr_dev, mut sdr_args) = rtlsdr_dev_result.unwrap();
+                        let mut is_recovering = false;
+
+                        // keep track of the latest settings, so they are used if the stream is restarted
+                        let latest_settings = Arc::new(Mutex::new(settings));
+
+                        loop {
+                            let settings = latest_settings.lock().unwrap().clone();
+
+                            let session_end = 'session: {
+                                // get what the SDR supports, so valid settings are used
+                                let capabilities = match rtlsdr_dev.capabilities(Direction::Rx, 0) {
+                                    Ok(capabilities) => capabilities,
+                                    Err(err) => {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err))
+                                    }
+                                };
+
+                                let sample_rate =
+                                    capabilities.closest_sample_rate(CAPTURE_SAMPLE_RATE);
+                                if sample_rate < CAPTURE_SAMPLE_RATE {
+                                    warn!(
+                                        "{} can only capture {}hz, so some stations may be cut off",
+                                        sdr_args.label, sample_rate
+                                    );
+                                }
+                                let _ = rtlsdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate);
+
+                                // set center frequency
+                                if let Err(err) =
+                                    rtlsdr_dev.set_frequency(Direction::Rx, 0, center_freq, "")
+                                {
+                                    break 'session SessionEnd::Failed(RadioError::Device(err));
+                                }
+
+                                // set the bandwidth
+                                let _ = rtlsdr_dev.set_bandwidth(Direction::Rx, 0, sample_rate);
+
+                                // make sure direct sampling is disabled
+                                if capabilities.has_setting("direct_samp") {
+                                    let _ = rtlsdr_dev.write_setting("direct_samp", "0");
+                                }
+
+                                // use manual gain when possible (automatic gain does not work that well)
+                                if capabilities.has_automatic_gain {
+                                    if let Err(err) = rtlsdr_dev.set_gain_mode(
+                                        Direction::Rx,
+                                        0,
+                                        !capabilities.has_manual_gain(),
+                                    ) {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err));
+                                    }
+                                }
+                                if capabilities.has_manual_gain() {
+                                    let gain = capabilities.gain_range.clamp(settings.gain);
+                                    if let Err(err) = rtlsdr_dev.set_gain(Direction::Rx, 0, gain) {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err));
+                                    }
+                                }
+
+                                // start sdr rx stream
+                                let sdr_rx = match rtlsdr_dev.rx_stream(sample_rate) {
+                                    Ok(sdr_rx) => sdr_rx,
+                                    Err(err) => {
+                                        break 'session SessionEnd::Failed(RadioError::Stream(err))
+                                    }
+                                };
+                                if let Err(err) = sdr_rx.activate().await {
+                                    break 'session SessionEnd::Failed(RadioError::Stream(err));
+                                }
+
+                                // watch for read failures and timeouts
+                                let stream_monitor = StreamMonitor::<f32>::new();
+                                stream_monitor.feed_from(&sdr_rx);
+
+                                // remove the DC offset and IQ imbalance, if enabled
+                                let iq_correction =
+                                    IqCorrection::<f32>::new(settings.iq_correction);
+                                iq_correction.feed_from(&stream_monitor);
+
+                                // split the capture into a channel for each station
+                                let channelizer = Channelizer::<f32>::new(
+                                    settings
+                                        .stations
+                                        .iter()
+                                        .map(|station| station * 1_000_000.0 - center_freq)
+                                        .collect(),
+                                    STATION_BANDWIDTH,
+                                    STATION_SAMPLE_RATE,
+                                );
+                                channelizer.feed_from(&iq_correction);
+
+                                let mut demodulators = vec![];
+                                for (channel, station) in
+                                    channelizer.channels().iter().zip(settings.stations.iter())
+                                {
+                                    // demodulate fm signal
+                                    let demodulator =
+                                        blocks::modulation::FmDemod::<f32>::new(150000.0);
+                                    demodulator.feed_from(channel);
+
+                                    // tag the RBDS data with the station it came from
+                                    let station_rbds_callback = rbds_callback.clone();
+                                    let frequency = *station;
+                                    let rbds_receiver = new_rbds_receiver(
+                                        Arc::new(move |rbds_state| {
+                                            station_rbds_callback(StationRbdsState {
+                                                frequency,
+                                                rbds_state,
+                                            });
+                                        }),
+                                        |_| {},
+                                    );
+                                    rbds_receiver.feed_from(&demodulator);
+
+                                    demodulators.push(demodulator);
+                                }
+
+                                // filter frequencies beyond normal human hearing range (20hz to 16 kHz)
+                                let audio_filter =
+                                    blocks::filters::Filter::new_rectangular(|bin, freq| {
+                                        if bin.abs() >= 1
+                                            && freq.abs() >= 20.0
+                                            && freq.abs() <= 16000.0
+                                        {
+                                            blocks::filters::deemphasis_factor(50e-6, freq)
+                                        } else {
+                                            Complex::from(0.0)
+                                        }
+                                    });
+                                let audio_station =
+                                    settings.audio_station.min(demodulators.len() - 1);
+                                audio_filter.feed_from(&demodulators[audio_station]);
+
+                                // downsample so the output device can play the audio
+                                let downsample = blocks::Downsampler::<f32>::new(
+                                    4096,
+                                    settings.sample_rate,
+                                    settings.sample_rate / 2.0,
+                                );
+                                downsample.feed_from(&audio_filter);
+
+                                // add a volume block
+                                let volume = blocks::GainControl::<f32>::new(settings.volume);
+                                volume.feed_from(&downsample);
+
+                                // add a buffer
+                                let buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 1.0);
+                                buffer.feed_from(&volume);
+
+                                // output the stream
+                                let playback = match better_cpal::AudioPlayer::new(
+                                    settings.sample_rate,
+                                    None,
+                                    2,
+                                    Some(true),
+                                ) {
+                                    Ok(playback) => playback,
+                                    Err(err) => {
+                                        let _ = sdr_rx.deactivate().await;
+                                        break 'session SessionEnd::Aborted(RadioError::Audio(
+                                            format!("Could not open the audio device: {}", err),
+                                        ));
+                                    }
+                                };
+                                playback.feed_from(&buffer);
+
+                                let sdr_clone = rtlsdr_dev.clone();
+                                let latest_settings_clone = latest_settings.clone();
+                                let current_audio_station = Mutex::new(audio_station);
+                                let settings_listener = events.listen(
+                                    "multi_fm_update_settings",
+                                    Box::new(move |payload: &str| {
+                                        let Ok(new_settings) =
+                                            serde_json::from_str::<MultiFmSettings>(payload)
+                                        else {
+                                            return;
+                                        };
+
+                                        if volume.get() != new_settings.volume {
+                                            volume.set(new_settings.volume);
+                                        }
+                                        iq_correction.set_enabled(new_settings.iq_correction);
+
+                                        // switch which station is played
+                                        let mut current_audio_station =
+                                            current_audio_station.lock().unwrap();
+                                        if new_settings.audio_station != *current_audio_station
+                                            && new_settings.audio_station < demodulators.len()
+                                        {
+                                            audio_filter.feed_from(
+                                                &demodulators[new_settings.audio_station],
+                                            );
+                                            *current_audio_station = new_settings.audio_station;
+                                        }
+
+                                        let gain = capabilities.gain_range.clamp(new_settings.gain);
+                                        if capabilities.has_manual_gain()
+                                            && sdr_clone.gain(Direction::Rx, 0).ok() != Some(gain)
+                                        {
+                                            // set new gain
+                                            if let Err(err) =
+                                                sdr_clone.set_gain(Direction::Rx, 0, gain)
+                                            {
+                                                error!("Failed to set new gain: {}", err);
+                                            }
+                                        }
+
+                                        // the stations can't change without restarting
+                                        let mut latest_settings =
+                                            latest_settings_clone.lock().unwrap();
+                                        latest_settings.audio_station = *current_audio_station;
+                                        latest_settings.volume = new_settings.volume;
+                                        latest_settings.gain = new_settings.gain;
+                                        latest_settings.iq_correction = new_settings.iq_correction;
+                                    }),
+                                );
+
+                                if is_recovering {
+                                    emit_recovery_status(
+                                        &*events,
+                                        "multi_fm",
+                                        StreamRecoveryStatus::Recovered,
+                                    );
+                                    is_recovering = false;
+                                }
+
+                                let session_end = monitor_stream(
+                                    &stream_monitor,
+                                    &sdr_rx,
+                                    &shutdown_flag,
+                                    || {
+                                        // notify frontend that the stations are being monitored
+                                        events.emit("multi_fm_status", "running");
+                                    },
+                                )
+                                .await;
+
+                                // stop the blocks of this session from changing settings
+                                events.unlisten(settings_listener);
+
+                                let _ = sdr_rx.deactivate().await;
+
+                                session_end
+                            };
+
+                            match session_end {
+                                SessionEnd::Stopped => {
+                                    // release the SDR
+                                    if let Err(err) =
+                                        release_sdr_dev(&sdr_registry, rtlsdr_dev, sdr_args)
+                                    {
+                                        warn!("Could not release SDR: {}", err);
+                                    }
+
+                                    // the recording ended, so nothing else will say that it stopped
+                                    if !shutdown_flag.load(Ordering::SeqCst) {
+                                        drop(
+                                            multi_fm_state_clone
+                                                .lock()
+                                                .unwrap()
+                                                .monitor_thread
+                                                .take(),
+                                        );
+                                        events.emit("multi_fm_status", "stopped");
+                                    }
+                                    break;
+                                }
+                                SessionEnd::Aborted(error) => {
+                                    if let Err(err) =
+                                        release_sdr_dev(&sdr_registry, rtlsdr_dev, sdr_args)
+                                    {
+                                        warn!("Could not release SDR: {}", err);
+                                    }
+                                    emit_error(&*events, "multi_fm", error);
+                                    events.emit("multi_fm_status", "stopped");
+
+                                    // remove the reference to the thread, so it can be started again
+                                    drop(
+                                        multi_fm_state_clone.lock().unwrap().monitor_thread.take(),
+                                    );
+                                    break;
+                                }
+                                SessionEnd::Failed(error) => {
+                                    // restart with the same settings once the SDR is available again
+                                    let Some(recovered_sdr) = recover_sdr_dev(
+                                        &*events,
+                                        &sdr_registry,
+                                        "multi_fm",
+                                        &sdr_args,
+                                        error,
+                                        &shutdown_flag,
+                                    )
+                                    .await
+                                    else {
+                                        break;
+                                    };
+                                    (rtlsdr_dev, sdr_args) = recovered_sdr;
+                                    is_recovering = true;
+                                }
+                            }
+                        }
+                    })
+            }));
+
+        info!("Started monitoring {} FM stations", num_stations);
+
+        Ok(())
+    }
+
+    pub async fn stop_monitoring(&self, events: &dyn EventSink) {
+        // the monitor thread needs the lock too, so it isn't held while waiting for the thread
+        let (shutdown_flag, thread) = match self.0.lock() {
+            Ok(mut multi_fm_data) => {
+                multi_fm_data.shutdown_flag.store(true, Ordering::SeqCst);
+                (
+                    multi_fm_data.shutdown_flag.clone(),
+                    multi_fm_data.monitor_thread.take(),
+                )
+            }
+            Err(_) => {
+                error!("Could not acquire lock immediately");
+                return;
+            }
+        };
+
+        if let Some(thread) = thread {
+            thread.await.expect("Failed to join thread");
+        }
+
+        shutdown_flag.store(false, Ordering::SeqCst);
+
+        events.emit("multi_fm_status", Some("stopped"));
+    }
+
+    pub fn is_running(&self) -> bool {
+        return self.0.clone().lock().unwrap().monitor_thread.is_some();
+    }
+}
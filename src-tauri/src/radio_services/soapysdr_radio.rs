@@ -1,45 +1,463 @@
 use std::{
+    ffi::c_void,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
     },
-    time::Duration,
 };
 
-use log::{debug, error};
-use radiorust::{blocks::io::rf, prelude::*};
+use log::{debug, error, warn};
+use radiorust::prelude::*;
 use soapysdr::Direction;
-use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
-use tauri::{async_runtime, ipc::Channel, AppHandle, Emitter, Listener, Manager};
-use tokio::{self, time};
+use souvlaki::{
+    MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig, SeekDirection,
+};
+use tauri::async_runtime;
 
 use crate::{
-    nrsc5::bindings::{NRSC5_SAMPLE_RATE_CS16_FM, NRSC5_SAMPLE_RATE_CU8},
+    audio_file::AudioTags,
+    error::{emit_error, RadioError, RadioResult},
+    events::{EventSink, SharedEventSink},
+    radio_services::{
+        radio_pipeline::{RadioPipeline, RadioPipelineCallbacks, RadioPipelineSettings},
+        stream_recovery::{
+            emit_recovery_status, monitor_stream, recover_sdr_dev, SessionEnd, StreamRecoveryStatus,
+        },
+    },
     radiorust_blocks::{
-        am_demod::AmDemod,
+        am_demod::AmDemodSettings,
+        audio_recorder::{AudioRecorder, AudioRecorderBlock, RecordingChannels},
         better_cpal,
-        hd_radio_decode::{HdRadioDecode, HdRadioState},
-        pauseable::Pauseable,
-        rbds_decode::{DownMixer, RbdsDecode, RbdsState},
-        wav_writer::WavWriterBlock,
+        biquad_filter::BiquadFilterSettings,
+        fm_stereo_decode::{FmStereoState, StereoInterleave},
+        hd_radio_decode::{HdRadioState, HdRadioStateCallback},
+        rbds_decode::{RbdsState, RbdsStateCallback},
+        sigmf_writer::{SigMfRecorder, SigMfWriterBlock},
+        spectrum_analyzer::{SpectrumAnalyzer, SpectrumCallback, SpectrumSettings},
+        ssb_demod::{Sideband, SsbDemodSettings},
+        stream_monitor::StreamMonitor,
+        subaudible_tone::SubaudibleTone,
+        time_shift::{TimeShift, TimeShiftBlock, TimeShiftPosition},
     },
-    sdr::{enumeration::AvailableSDRArgs, get_sdr_dev, release_sdr_dev},
+    sdr::{enumeration::AvailableSDRArgs, get_sdr_dev, release_sdr_dev, SDRRegistry},
+    store::Store,
 };
 
-#[derive(serde::Deserialize, PartialEq)]
+// the SDR is tuned above the station, so the DC spike of the SDR is not on top of it (in hz)
+pub const TUNING_OFFSET: f64 = 250_000.0;
+// broadcast FM audio goes up to 15 kHz
+const FM_AUDIO_BANDWIDTH: f64 = 15_000.0;
+// everything from just below the 19 kHz stereo pilot is removed from FM audio (in hz)
+const FM_PILOT_CUTOFF: f64 = 18_000.0;
+// frequencies below normal human hearing (in hz)
+const AUDIO_HIGHPASS: f64 = 20.0;
+// the narrowest IF bandwidth that can be picked for AM (in hz)
+const MIN_AM_BANDWIDTH: f64 = 1_000.0;
+// SSB and CW audio never goes above this, even with the widest filter and the BFO offset (in hz)
+const SIDEBAND_AUDIO_BANDWIDTH: f64 = 5_000.0;
+// SSB voice starts at 300 hz, and the filter width is added to that (in hz)
+const SSB_LOW_CUT: f64 = 300.0;
+const SSB_FILTER_WIDTH: f64 = 2_400.0;
+const CW_FILTER_WIDTH: f64 = 500.0;
+// the pitch that CW is heard at (in hz)
+const CW_PITCH: f64 = 700.0;
+// narrowband FM and airband voice is between the CTCSS/DCS tones and 3 kHz (in hz)
+const VOICE_AUDIO_HIGHPASS: f64 = 300.0;
+const VOICE_AUDIO_BANDWIDTH: f64 = 3_000.0;
+// the channel spacing of narrowband FM and airband (in hz), where 25 kHz narrowband FM channels use twice the deviation
+const NBFM_CHANNEL_SPACING: f64 = 12_500.0;
+const AIRBAND_CHANNEL_SPACING: f64 = 25_000.0 / 3.0;
+const WIDE_CHANNEL_SPACING: f64 = 25_000.0;
+// the IF bandwidth of airband channels, which is wider for 25 kHz channels since their transmitters drift more (in hz)
+const AIRBAND_IF_BANDWIDTH: f64 = 7_000.0;
+const AIRBAND_WIDE_IF_BANDWIDTH: f64 = 10_000.0;
+// the squelch of airband goes up to this far above the noise floor (in dB)
+const AIRBAND_SQUELCH_RANGE: f64 = 30.0;
+// how much audio is kept to rewind, unless it is set (in seconds)
+const DEFAULT_TIME_SHIFT_LENGTH: f64 = 300.0;
+// the buffer is kept in memory, where 15 minutes of 48 kHz stereo audio is about 170 MB
+const MAX_TIME_SHIFT_LENGTH: f64 = 900.0;
+// how far the seek buttons of the media controls skip (in seconds)
+const MEDIA_SEEK_TIME: f64 = 10.0;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
 pub enum StreamType {
     FM = 0,
     AM = 1,
     HD = 2,
+    // upper and lower sideband, and morse code (CW), which are used by HF amateur and utility stations
+    USB = 3,
+    LSB = 4,
+    CW = 5,
+    // narrowband FM, which is used by weather radio, public service, and amateur repeaters
+    NBFM = 6,
+    // AM voice between 118 and 137 MHz, which is used by aircraft and air traffic control
+    AIR = 7,
+}
+
+impl StreamType {
+    /// FM, HD Radio, narrowband FM, and airband frequencies are in MHz, while AM, SSB, and CW frequencies are in KHz
+    pub fn frequency_multiplier(&self) -> f64 {
+        match self {
+            StreamType::AM | StreamType::USB | StreamType::LSB | StreamType::CW => 1_000.0,
+            StreamType::FM | StreamType::HD | StreamType::NBFM | StreamType::AIR => 1_000_000.0,
+        }
+    }
+
+    /// The frequency (in hz) that a station or channel is on, since airband channels with 8.33 kHz spacing are
+    /// named by the nearest 5 kHz instead of their actual frequency (such as 118.010 for 118.00833 MHz)
+    pub fn station_frequency(&self, freq: f64) -> f64 {
+        if *self != StreamType::AIR {
+            return freq * self.frequency_multiplier();
+        }
+
+        let channel = (freq * 1_000.0).round() as i64;
+        let block = channel - channel.rem_euclid(25);
+        let khz = match channel - block {
+            5 => block as f64,
+            10 => block as f64 + AIRBAND_CHANNEL_SPACING / 1_000.0,
+            15 => block as f64 + 2.0 * AIRBAND_CHANNEL_SPACING / 1_000.0,
+            _ => channel as f64,
+        };
+        khz * 1_000.0
+    }
+
+    /// The bandwidth (in hz) needed to receive one station
+    pub fn required_bandwidth(&self) -> f64 {
+        match self {
+            StreamType::FM => 200_000.0,
+            // SSB and CW are narrower, but the filter is picked by the demodulator
+            StreamType::AM | StreamType::USB | StreamType::LSB | StreamType::CW => 10_000.0,
+            StreamType::NBFM | StreamType::AIR => WIDE_CHANNEL_SPACING,
+            // TODO: properly use both sidebands of HD Radio signal
+            StreamType::HD => 400_000.0,
+        }
+    }
+
+    /// The direct sampling mode of RTL-SDRs, which is needed for the AM band and HF
+    /// (0 -> disabled, 1 -> I-branch direct sampling, 2 -> Q-branch direct sampling)
+    pub fn direct_sampling_mode(&self) -> &'static str {
+        match self {
+            StreamType::AM | StreamType::USB | StreamType::LSB | StreamType::CW => "2",
+            StreamType::FM | StreamType::HD | StreamType::NBFM | StreamType::AIR => "0",
+        }
+    }
+
+    /// SSB and CW are demodulated by the [`SsbDemod`](crate::radiorust_blocks::ssb_demod::SsbDemod) block
+    pub fn is_sideband(&self) -> bool {
+        matches!(self, StreamType::USB | StreamType::LSB | StreamType::CW)
+    }
+
+    /// Only FM and HD Radio have stereo audio
+    pub fn is_stereo(&self) -> bool {
+        matches!(self, StreamType::FM | StreamType::HD)
+    }
+
+    /// How the channels of the demodulated audio are laid out, where HD Radio audio comes straight from the decoder
+    pub fn recording_channels(&self) -> RecordingChannels {
+        match self {
+            StreamType::FM => RecordingChannels::Stereo,
+            StreamType::HD => RecordingChannels::Interleaved,
+            _ => RecordingChannels::Mono,
+        }
+    }
+
+    /// The highest frequency the demodulated audio can have before it is downsampled to `sample_rate` (in hz), where
+    /// the stereo pilot and RBDS of FM are always removed
+    pub fn max_audio_frequency(&self, sample_rate: f64) -> f64 {
+        let nyquist = sample_rate / 2.0;
+        match self {
+            StreamType::FM => nyquist.min(FM_PILOT_CUTOFF),
+            _ => nyquist,
+        }
+    }
+
+    /// Describes a station for the tags of an audio recording (such as "101.5 MHz FM Radio")
+    pub fn frequency_label(&self, freq: f64) -> String {
+        let unit = if self.frequency_multiplier() == 1_000.0 {
+            "KHz"
+        } else {
+            "MHz"
+        };
+        format!("{} {} {}", freq, unit, self.display_name())
+    }
+
+    /// Shown in the media controls of the OS
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            StreamType::FM => "FM Radio",
+            StreamType::AM => "AM Radio",
+            StreamType::HD => "HD Radio",
+            StreamType::USB => "Upper Sideband",
+            StreamType::LSB => "Lower Sideband",
+            StreamType::CW => "CW",
+            StreamType::NBFM => "Narrowband FM",
+            StreamType::AIR => "Airband",
+        }
+    }
+
+    // the frontend tells streams apart with this prefix on their status
+    fn status_prefix(&self) -> &'static str {
+        match self {
+            StreamType::FM => "fm",
+            StreamType::AM => "am",
+            StreamType::HD => "hd",
+            StreamType::USB => "usb",
+            StreamType::LSB => "lsb",
+            StreamType::CW => "cw",
+            StreamType::NBFM => "nbfm",
+            StreamType::AIR => "air",
+        }
+    }
+}
+
+/// The FM de-emphasis, which depends on the region the station is in
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Deemphasis {
+    // 75 µs, used in the Americas and South Korea
+    Americas,
+    // 50 µs, used everywhere else
+    International,
+    Disabled,
+}
+
+impl Deemphasis {
+    /// The time constant of the de-emphasis (in seconds)
+    pub fn time_constant(&self) -> Option<f64> {
+        match self {
+            Deemphasis::Americas => Some(75e-6),
+            Deemphasis::International => Some(50e-6),
+            Deemphasis::Disabled => None,
+        }
+    }
+}
+
+/// How the audio is filtered, where anything that isn't set uses the default of the stream type
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct AudioSettings {
+    #[serde(default)]
+    pub deemphasis: Option<Deemphasis>,
+    // the highest frequency that is played (in hz)
+    #[serde(default)]
+    pub audio_bandwidth: Option<f64>,
+    // the IF bandwidth of AM stations (in hz), which is limited to the bandwidth of an AM channel
+    #[serde(default)]
+    pub am_bandwidth: Option<f64>,
+}
+
+impl AudioSettings {
+    fn am_bandwidth(&self) -> f64 {
+        let max_bandwidth = StreamType::AM.required_bandwidth();
+        self.am_bandwidth
+            .unwrap_or(max_bandwidth)
+            .clamp(MIN_AM_BANDWIDTH, max_bandwidth)
+    }
+
+    /// The filter applied to the IQ samples of a station before it is demodulated
+    pub fn if_filter(&self, stream_type: &StreamType) -> BiquadFilterSettings {
+        BiquadFilterSettings {
+            lowpass: match stream_type {
+                StreamType::AM => Some(self.am_bandwidth() / 2.0),
+                // the SSB demodulator has its own filter
+                _ => None,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// The filter applied to the demodulated audio (AM stations don't use pre-emphasis, so they don't need de-emphasis)
+    pub fn audio_filter(&self, stream_type: &StreamType) -> BiquadFilterSettings {
+        let (default_deemphasis, default_bandwidth) = match stream_type {
+            StreamType::AM => (Deemphasis::Disabled, self.am_bandwidth() / 2.0),
+            StreamType::FM | StreamType::HD => (Deemphasis::International, FM_AUDIO_BANDWIDTH),
+            StreamType::USB | StreamType::LSB | StreamType::CW => {
+                (Deemphasis::Disabled, SIDEBAND_AUDIO_BANDWIDTH)
+            }
+            StreamType::NBFM | StreamType::AIR => (Deemphasis::Disabled, VOICE_AUDIO_BANDWIDTH),
+        };
+
+        BiquadFilterSettings {
+            deemphasis: self
+                .deemphasis
+                .unwrap_or(default_deemphasis)
+                .time_constant(),
+            // the CTCSS and DCS tones of narrowband FM shouldn't be heard
+            highpass: Some(
+                if matches!(stream_type, StreamType::NBFM | StreamType::AIR) {
+                    VOICE_AUDIO_HIGHPASS
+                } else {
+                    AUDIO_HIGHPASS
+                },
+            ),
+            lowpass: Some(self.audio_bandwidth.unwrap_or(default_bandwidth)),
+        }
+    }
+}
+
+/// How noise is removed, where both are off unless they are set
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct NoiseSettings {
+    // blanks impulse noise (such as from power lines) in the IQ samples, from 0 (off) to 1
+    #[serde(default)]
+    pub noise_blanker: Option<f64>,
+    // removes steady background noise (such as hiss) from the audio, from 0 (off) to 1
+    #[serde(default)]
+    pub noise_reduction: Option<f64>,
+}
+
+impl NoiseSettings {
+    pub fn noise_blanker_level(&self) -> f64 {
+        self.noise_blanker.unwrap_or(0.0).clamp(0.0, 1.0)
+    }
+
+    pub fn noise_reduction_level(&self) -> f64 {
+        self.noise_reduction.unwrap_or(0.0).clamp(0.0, 1.0)
+    }
+}
+
+/// How SSB and CW are demodulated, where anything that isn't set uses the default of the stream type
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct SidebandSettings {
+    // fine tunes SSB, or sets the pitch of CW (in hz)
+    #[serde(default)]
+    pub bfo_offset: Option<f64>,
+    // the width of the audio that is kept (in hz)
+    #[serde(default)]
+    pub filter_width: Option<f64>,
+}
+
+impl SidebandSettings {
+    pub fn demod_settings(&self, stream_type: &StreamType) -> SsbDemodSettings {
+        let sideband = if *stream_type == StreamType::LSB {
+            Sideband::Lower
+        } else {
+            Sideband::Upper
+        };
+
+        // CW is a single tone at the pitch, so the filter is centered on it
+        if *stream_type == StreamType::CW {
+            let pitch = self.bfo_offset.unwrap_or(CW_PITCH);
+            let filter_width = self.filter_width.unwrap_or(CW_FILTER_WIDTH);
+            SsbDemodSettings {
+                sideband,
+                bfo_offset: pitch,
+                low_cut: (pitch - filter_width / 2.0).max(0.0),
+                high_cut: pitch + filter_width / 2.0,
+            }
+        } else {
+            SsbDemodSettings {
+                sideband,
+                bfo_offset: self.bfo_offset.unwrap_or(0.0),
+                low_cut: SSB_LOW_CUT,
+                high_cut: SSB_LOW_CUT + self.filter_width.unwrap_or(SSB_FILTER_WIDTH),
+            }
+        }
+    }
+}
+
+/// How narrowband FM and airband channels are received, where anything that isn't set uses the default of the stream type
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+pub struct ChannelSettings {
+    // 12.5 kHz or 25 kHz for narrowband FM, and 8.33 kHz or 25 kHz for airband (in hz)
+    #[serde(default)]
+    pub channel_spacing: Option<f64>,
+    // from 0 (always open) to 1 (only open for very strong signals)
+    #[serde(default)]
+    pub squelch: Option<f64>,
+    // other airband channels that are played at the same time (in MHz), which need to fit in the capture of the SDR
+    #[serde(default)]
+    pub monitored_channels: Vec<f64>,
+}
+
+impl ChannelSettings {
+    // narrowband FM defaults to 12.5 kHz channels, and airband to 25 kHz channels
+    fn is_wide(&self, stream_type: &StreamType) -> bool {
+        self.channel_spacing
+            .map(|spacing| spacing >= WIDE_CHANNEL_SPACING)
+            .unwrap_or(*stream_type == StreamType::AIR)
+    }
+
+    /// The peak deviation of a narrowband FM channel (in hz)
+    pub fn deviation(&self) -> f64 {
+        if self.is_wide(&StreamType::NBFM) {
+            5_000.0
+        } else {
+            2_500.0
+        }
+    }
+
+    /// The bandwidth of the channel filter, which is a little narrower than the channel so the neighbors are removed
+    pub fn if_bandwidth(&self, stream_type: &StreamType) -> f64 {
+        let is_wide = self.is_wide(stream_type);
+        match stream_type {
+            StreamType::AIR if is_wide => AIRBAND_WIDE_IF_BANDWIDTH,
+            StreamType::AIR => AIRBAND_IF_BANDWIDTH,
+            _ if is_wide => WIDE_CHANNEL_SPACING * 0.8,
+            _ => NBFM_CHANNEL_SPACING * 0.8,
+        }
+    }
+
+    pub fn if_filter(&self, stream_type: &StreamType) -> BiquadFilterSettings {
+        BiquadFilterSettings {
+            lowpass: Some(self.if_bandwidth(stream_type) / 2.0),
+            ..Default::default()
+        }
+    }
+
+    pub fn squelch_level(&self) -> f64 {
+        self.squelch.unwrap_or(0.0).clamp(0.0, 1.0)
+    }
+
+    /// How airband channels are demodulated
+    pub fn am_demod_settings(&self) -> AmDemodSettings {
+        let squelch_level = self.squelch_level();
+        AmDemodSettings {
+            agc: true,
+            squelch: (squelch_level > 0.0).then(|| squelch_level * AIRBAND_SQUELCH_RANGE),
+        }
+    }
+
+    /// The airband channels that are played (in MHz), starting with the tuned channel
+    pub fn airband_channels(&self, freq: f64) -> Vec<f64> {
+        let mut channels = vec![freq];
+        for channel in self.monitored_channels.iter() {
+            if !channels.contains(channel) {
+                channels.push(*channel);
+            }
+        }
+        channels
+    }
+}
+
+/// Whether the squelch of an airband channel is open
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AirbandChannelState {
+    // in MHz
+    pub frequency: f64,
+    pub is_open: bool,
+}
+
+/// What the media controls of the OS need from whatever is running the stream
+#[derive(Clone)]
+pub struct MediaControlsConfig {
+    // shown when the station doesn't have its own artwork
+    pub icon_url: String,
+    // the window that the controls belong to, which is only needed on Windows
+    pub hwnd: Option<usize>,
 }
 
 pub struct RtlSdrState(Arc<Mutex<RtlSdrData>>);
 pub struct RtlSdrData {
     pub radio_stream_thread: Option<async_runtime::JoinHandle<()>>,
     pub shutdown_flag: Arc<AtomicBool>,
+    pub iq_recorder: Option<SigMfRecorder>,
+    pub audio_recorder: Option<AudioRecorder>,
+    pub time_shift: Option<TimeShift>,
+    pub media_controls: Option<Arc<Mutex<MediaControls>>>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 pub struct StreamSettings {
     freq: f64,
     volume: f64,
@@ -47,6 +465,44 @@ pub struct StreamSettings {
     sample_rate: f64,
     stream_type: StreamType,
     hd_radio_program: Option<u32>,
+    #[serde(default)]
+    spectrum: Option<SpectrumSettings>,
+    // remove the DC offset and IQ imbalance of the SDR
+    #[serde(default)]
+    iq_correction: bool,
+    #[serde(flatten)]
+    audio: AudioSettings,
+    #[serde(flatten)]
+    sideband: SidebandSettings,
+    #[serde(flatten)]
+    channel: ChannelSettings,
+    #[serde(flatten)]
+    noise: NoiseSettings,
+    // how much audio is kept to pause and rewind (in seconds)
+    #[serde(default)]
+    time_shift_length: Option<f64>,
+}
+
+impl StreamSettings {
+    fn time_shift_length(&self) -> f64 {
+        self.time_shift_length
+            .unwrap_or(DEFAULT_TIME_SHIFT_LENGTH)
+            .clamp(0.0, MAX_TIME_SHIFT_LENGTH)
+    }
+
+    fn pipeline_settings(&self) -> RadioPipelineSettings {
+        RadioPipelineSettings {
+            stream_type: self.stream_type.clone(),
+            freq: self.freq,
+            sample_rate: self.sample_rate,
+            hd_radio_program: self.hd_radio_program.unwrap_or_default(),
+            iq_correction: self.iq_correction,
+            audio: self.audio,
+            sideband: self.sideband,
+            channel: self.channel.clone(),
+            noise: self.noise,
+        }
+    }
 }
 
 impl RtlSdrState {
@@ -54,479 +510,802 @@ impl RtlSdrState {
         RtlSdrState(Arc::new(Mutex::new(RtlSdrData {
             radio_stream_thread: None,
             shutdown_flag: Arc::new(AtomicBool::new(false)),
+            iq_recorder: None,
+            audio_recorder: None,
+            time_shift: None,
+            media_controls: None,
         })))
     }
 
     pub fn start_stream(
         &self,
-        app: AppHandle,
+        events: SharedEventSink,
+        sdr_registry: SDRRegistry,
         stream_settings: StreamSettings,
         default_sdr_args: AvailableSDRArgs,
-        rbds_channel: Channel<RbdsState>,
-        hd_radio_channel: Channel<HdRadioState>,
-    ) {
+        rbds_callback: RbdsStateCallback,
+        hd_radio_callback: HdRadioStateCallback,
+        spectrum_callback: Option<SpectrumCallback>,
+        media_controls_config: Option<MediaControlsConfig>,
+    ) -> RadioResult<()> {
         let rtlsdr_state = self.0.clone();
         let rtlsdr_state_clone = rtlsdr_state.clone();
 
-        let shutdown_flag = rtlsdr_state.lock().unwrap().shutdown_flag.clone();
-
-        // set defaults for FM Radio
-        let mut freq_mul: f64 = 1_000_000.0;
-        let mut freq_offset: f64 = 0.0;
-        let mut required_bandwidth: f64 = 200_000.0;
-        let mut downsampled_rate = 336000.0;
+        // get the SDR first, so the caller finds out if it can't be used
+        let (mut rtlsdr_dev, mut sdr_args) = get_sdr_dev(&sdr_registry, default_sdr_args)?;
 
-        // if AM Radio, use KHz instead
-        if stream_settings.stream_type == StreamType::AM {
-            freq_mul = 1_000.0;
-            required_bandwidth = 10_000.0;
-        }
+        let shutdown_flag = rtlsdr_state.lock().unwrap().shutdown_flag.clone();
 
-        // TODO: properly use both sidebands of HD Radio signal
-        // if HD Radio, focus in on the lower sideband
-        if stream_settings.stream_type == StreamType::HD {
-            required_bandwidth = 400_000.0;
-            downsampled_rate = NRSC5_SAMPLE_RATE_CS16_FM;
-        }
+        let freq_offset = TUNING_OFFSET;
+
+        // kept between sessions, so the buffered audio isn't lost when the SDR is recovered
+        let events_clone = events.clone();
+        let time_shift = TimeShift::new(
+            stream_settings.time_shift_length(),
+            stream_settings.stream_type.recording_channels(),
+            Arc::new(move |position: TimeShiftPosition| {
+                events_clone.emit("time_shift_position", position);
+            }),
+        );
+        rtlsdr_state.lock().unwrap().time_shift = Some(time_shift.clone());
+
+        // kept between sessions, so a recording continues after the SDR is recovered
+        let events_clone = events.clone();
+        let audio_recorder = AudioRecorder::new(
+            stream_settings.pipeline_settings().audio_sample_rate(),
+            stream_settings.stream_type.recording_channels(),
+            Arc::new(move |err: String| {
+                events_clone.emit("audio_recording_error", err);
+                events_clone.emit("audio_recording_status", "stopped");
+            }),
+        );
+        audio_recorder.update_tags(|tags| {
+            tags.frequency = Some(
+                stream_settings
+                    .stream_type
+                    .frequency_label(stream_settings.freq),
+            );
+        });
+        rtlsdr_state.lock().unwrap().audio_recorder = Some(audio_recorder.clone());
 
         rtlsdr_state.lock().unwrap().radio_stream_thread =
             Some(async_runtime::spawn_blocking(move || {
                 tokio::runtime::Runtime::new()
                     .unwrap()
                     .block_on(async move {
-                        // get SDR
-                        let rtlsdr_dev_result = get_sdr_dev(app.clone(), default_sdr_args);
-
-                        if rtlsdr_dev_result.is_err() {
-                            // notify frontend of error
-                            app.emit("rtlsdr_err", unsafe {
-                                rtlsdr_dev_result.unwrap_err_unchecked()
-                            })
-                            .expect("failed to emit event");
-                            app.emit("rtlsdr_status", "stopped")
-                                .expect("failed to emit event");
-
-                            // remove the reference to the thread
-                            drop(
-                                rtlsdr_state_clone
-                                    .lock()
-                                    .unwrap()
-                                    .radio_stream_thread
-                                    .take(),
-                            );
-                            return;
-                        }
-
-                        // setup media controls
-                        #[cfg(not(target_os = "windows"))]
-                        let hwnd = None;
-
-                        #[cfg(target_os = "windows")]
-                        let hwnd = {
-                            let webview_window = app.get_webview_window("main").unwrap();
-                            let native_window = webview_window.hwnd().unwrap();
-                            let hwnd = native_window.0;
-
-                            Some(hwnd)
-                        };
-
-                        let config = PlatformConfig {
-                            dbus_name: "dev.njf.RTL_SDR_Radio",
-                            display_name: "RTL-SDR Radio",
-                            hwnd,
-                        };
-
-                        let mut controls = MediaControls::new(config).unwrap();
-                        let _ = controls.set_playback(MediaPlayback::Playing { progress: None });
-
-                        let resource_dir = app.path().resource_dir().unwrap();
-                        let icon_url = format!(
-                            "file://{}/resources/AppIcon.png",
-                            resource_dir.as_os_str().to_str().unwrap()
-                        );
-                        let radio_type_name =
-                            Some(if stream_settings.stream_type == StreamType::FM {
-                                "FM Radio"
-                            } else if stream_settings.stream_type == StreamType::HD {
-                                "HD Radio"
-                            } else {
-                                "AM Radio"
+                        let radio_type_name = Some(stream_settings.stream_type.display_name());
+                        let icon_url = media_controls_config
+                            .as_ref()
+                            .map(|config| config.icon_url.clone())
+                            .unwrap_or_default();
+
+                        // tag recordings with what the station is playing
+                        let audio_recorder_clone = audio_recorder.clone();
+                        let rbds_callback: RbdsStateCallback = Arc::new(move |state: RbdsState| {
+                            audio_recorder_clone.update_tags(|tags| {
+                                let station = state.service_name.trim();
+                                let title = state.radio_text.trim();
+                                tags.station = (!station.is_empty()).then(|| station.to_string());
+                                tags.title = (!title.is_empty()).then(|| title.to_string());
                             });
-                        let _ = controls.set_metadata(MediaMetadata {
-                            title: radio_type_name,
-                            cover_url: Some(icon_url.as_str()),
-                            ..Default::default()
+                            rbds_callback(state);
                         });
+                        let audio_recorder_clone = audio_recorder.clone();
+                        let hd_radio_callback: HdRadioStateCallback =
+                            Arc::new(move |state: HdRadioState| {
+                                audio_recorder_clone.update_tags(|tags| {
+                                    let non_empty = |value: &String| {
+                                        (!value.trim().is_empty()).then(|| value.trim().to_string())
+                                    };
+                                    tags.station = state
+                                        .station_info
+                                        .as_ref()
+                                        .and_then(|info| non_empty(&info.name));
+                                    tags.title = non_empty(&state.title);
+                                    tags.artist = non_empty(&state.artist);
+                                    tags.album = non_empty(&state.album);
+                                });
+                                hd_radio_callback(state);
+                            });
 
-                        let is_paused = Arc::new(Mutex::new(false));
-                        let is_paused_clone = is_paused.clone();
-
-                        let controls_arc = Arc::new(Mutex::new(controls));
-                        let controls_clone = controls_arc.clone();
-
-                        // The closure must be Send and have a static lifetime.
-                        {
-                            controls_arc
-                                .lock()
-                                .unwrap()
-                                .attach(move |event: MediaControlEvent| {
-                                    let mut is_paused_locked = is_paused_clone.lock().unwrap();
-                                    match event {
-                                        MediaControlEvent::Pause => {
-                                            *is_paused_locked = true;
-                                        }
-                                        MediaControlEvent::Play => {
-                                            *is_paused_locked = false;
-                                        }
-                                        MediaControlEvent::Toggle => {
-                                            *is_paused_locked =
-                                                if *is_paused_locked { false } else { true };
-                                        }
-                                        _ => {
-                                            debug!("Unhandled Media Control: {:?}", event);
-                                        }
-                                    }
-                                    let mut locked_controls = controls_clone.lock().unwrap();
-                                    if *is_paused_locked {
-                                        let _ = locked_controls
-                                            .set_playback(MediaPlayback::Paused { progress: None });
-                                    } else {
-                                        let _ =
-                                            locked_controls.set_playback(MediaPlayback::Playing {
-                                                progress: None,
-                                            });
-                                    }
-                                })
-                                .unwrap();
-                        }
-
-                        let (rtlsdr_dev, sdr_args) = rtlsdr_dev_result.unwrap();
-
-                        // set corresponding sample rate
-                        let mut sample_rate = if sdr_args.driver == "sdrplay" {
-                            1e6
-                        } else {
-                            1.024e6
-                        };
-
-                        // set sample rate
-                        let _ = rtlsdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate);
-
-                        // set center frequency
-                        let sdr_freq = stream_settings.freq * freq_mul + freq_offset;
-                        debug!("{}hz", sdr_freq);
-                        rtlsdr_dev
-                            .set_frequency(Direction::Rx, 0, sdr_freq, "")
-                            .expect("Failed to set frequency");
-
-                        // set the bandwidth
-                        let _ = rtlsdr_dev.set_bandwidth(Direction::Rx, 0, 1.000e6);
-
-                        // start sdr rx stream
-                        let rx_stream = rtlsdr_dev.rx_stream::<Complex<f32>>(&[0]).unwrap();
-                        let sdr_rx = rf::soapysdr::SoapySdrRx::new(rx_stream, sample_rate);
-                        sdr_rx.activate().await.unwrap();
-
-                        // turn on direct sampling mode if in low frequencies
-                        if stream_settings.stream_type == StreamType::AM {
-                            // 0 -> disabled, 1 -> I-branch direct sampling, 2 -> Q-branch direct sampling
-                            let _ = rtlsdr_dev.write_setting("direct_samp", "2");
-                        } else {
-                            let _ = rtlsdr_dev.write_setting("direct_samp", "0");
-                        }
+                        // setup media controls, if whatever is running the stream has them
+                        let controls_arc = media_controls_config.and_then(|config| {
+                            let platform_config = PlatformConfig {
+                                dbus_name: "dev.njf.RTL_SDR_Radio",
+                                display_name: "RTL-SDR Radio",
+                                hwnd: config.hwnd.map(|hwnd| hwnd as *mut c_void),
+                            };
+
+                            // the radio still works without media controls, so they are skipped if they fail
+                            let mut controls = match MediaControls::new(platform_config) {
+                                Ok(controls) => controls,
+                                Err(err) => {
+                                    warn!("Could not set up media controls: {:?}", err);
+                                    return None;
+                                }
+                            };
+                            let _ =
+                                controls.set_playback(MediaPlayback::Playing { progress: None });
+                            let _ = controls.set_metadata(MediaMetadata {
+                                title: radio_type_name,
+                                cover_url: Some(icon_url.as_str()),
+                                ..Default::default()
+                            });
 
-                        // disable automatic gain mode on RTL-SDR (does not work that well)
-                        if sdr_args.driver == "rtlsdr" {
-                            rtlsdr_dev
-                                .set_gain_mode(Direction::Rx, 0, false)
-                                .expect("Failed to set automatic gain");
-                        } else {
-                            rtlsdr_dev
-                                .set_gain_mode(Direction::Rx, 0, true)
-                                .expect("Failed to set automatic gain");
-                        }
+                            let time_shift_clone = time_shift.clone();
 
-                        // set a predetermined gain value
-                        // TODO: figure out a better automatic way to set this
-                        // if SDRPlay, use better special RF Gain control
-                        if sdr_args.driver == "sdrplay" {
-                            let _ = rtlsdr_dev.write_setting(
-                                "rfgain_sel",
-                                stream_settings.gain.round().to_string().as_str(),
-                            );
-                        } else {
-                            rtlsdr_dev
-                                .set_gain(Direction::Rx, 0, stream_settings.gain)
-                                .expect("Failed to set a gain value");
-                        }
+                            let controls_arc = Arc::new(Mutex::new(controls));
+                            let controls_clone = controls_arc.clone();
 
-                        // add frequency shifter
-                        let freq_shifter = blocks::FreqShifter::<f32>::with_shift(0.0e6);
-                        freq_shifter.feed_from(&sdr_rx);
-
-                        // add downsampler
-                        let downsample1 = blocks::Downsampler::<f32>::new(
-                            16384,
-                            downsampled_rate,
-                            required_bandwidth,
-                        );
-                        downsample1.feed_from(&freq_shifter);
-
-                        // add lowpass filter
-                        let filter1 = blocks::Filter::new(|_, freq| {
-                            if freq.abs() <= 100000.0 {
-                                Complex::from(1.0)
-                            } else {
-                                Complex::from(0.0)
+                            // The closure must be Send and have a static lifetime.
+                            {
+                                let attach_result = controls_arc.lock().unwrap().attach(
+                                    move |event: MediaControlEvent| {
+                                        match event {
+                                            MediaControlEvent::Pause => {
+                                                time_shift_clone.set_paused(true);
+                                            }
+                                            MediaControlEvent::Play => {
+                                                time_shift_clone.set_paused(false);
+                                            }
+                                            MediaControlEvent::Toggle => {
+                                                time_shift_clone
+                                                    .set_paused(!time_shift_clone.is_paused());
+                                            }
+                                            // rewind, or catch up to live
+                                            MediaControlEvent::Seek(direction) => {
+                                                time_shift_clone.skip(match direction {
+                                                    SeekDirection::Forward => MEDIA_SEEK_TIME,
+                                                    SeekDirection::Backward => -MEDIA_SEEK_TIME,
+                                                });
+                                            }
+                                            MediaControlEvent::SeekBy(direction, duration) => {
+                                                let seconds = duration.as_secs_f64();
+                                                time_shift_clone.skip(match direction {
+                                                    SeekDirection::Forward => seconds,
+                                                    SeekDirection::Backward => -seconds,
+                                                });
+                                            }
+                                            _ => {
+                                                debug!("Unhandled Media Control: {:?}", event);
+                                            }
+                                        }
+                                        set_media_playback(
+                                            &mut controls_clone.lock().unwrap(),
+                                            time_shift_clone.is_paused(),
+                                        );
+                                    },
+                                );
+                                if let Err(err) = attach_result {
+                                    warn!("Could not listen to media controls: {:?}", err);
+                                }
                             }
-                        });
-                        if !(stream_settings.stream_type == StreamType::HD) {
-                            filter1.feed_from(&downsample1);
-                        }
 
-                        let pauser = Pauseable::new(is_paused);
+                            Some(controls_arc)
+                        });
+                        rtlsdr_state_clone.lock().unwrap().media_controls = controls_arc.clone();
 
-                        if stream_settings.stream_type == StreamType::FM {
-                            // demodulate fm signal
-                            let demodulator = blocks::modulation::FmDemod::<f32>::new(150000.0);
-                            demodulator.feed_from(&filter1);
-                            pauser.feed_from(&demodulator);
+                        let mut is_recovering = false;
+                        // the sample rate of the SDR (not of the audio), which is saved with its preferences
+                        let mut sdr_sample_rate = None;
 
-                            // add a buffer
-                            let rbds_buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 5.0);
-                            rbds_buffer.feed_from(&demodulator);
+                        // keep track of the latest settings, so they are used if the stream is restarted
+                        let latest_settings = Arc::new(Mutex::new(stream_settings));
 
-                            // upper sideband
-                            const RBDS_FREQ: f64 = 57_000.0;
-                            const RBDS_BANDWIDTH: f64 = 2_000.0;
+                        loop {
+                            let stream_settings = latest_settings.lock().unwrap().clone();
 
-                            // Step 1. apply bandpass signal to 57Khz with bandwidth of 4KHz for RBDS decoding
-                            let rbds_bandpass_filter = blocks::Filter::new(|_, freq| {
-                                if freq.abs() >= (RBDS_FREQ + 1000.0 - (RBDS_BANDWIDTH / 2.0))
-                                    && freq.abs() <= (RBDS_FREQ + 1000.0 + (RBDS_BANDWIDTH / 2.0))
+                            let session_end = 'session: {
+                                // get what the SDR supports, so valid settings are used
+                                let capabilities = match rtlsdr_dev.capabilities(Direction::Rx, 0) {
+                                    Ok(capabilities) => capabilities,
+                                    Err(err) => {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err))
+                                    }
+                                };
+
+                                // use the supported sample rate closest to 1.024 MHz
+                                let sample_rate = capabilities.closest_sample_rate(1.024e6);
+
+                                // set sample rate
+                                let _ = rtlsdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate);
+                                sdr_sample_rate = Some(sample_rate);
+
+                                // set center frequency
+                                let sdr_freq = stream_settings
+                                    .stream_type
+                                    .station_frequency(stream_settings.freq)
+                                    + freq_offset;
+                                debug!("{}hz", sdr_freq);
+                                if !capabilities.supports_frequency(sdr_freq) {
+                                    warn!("{} does not support {}hz", sdr_args.label, sdr_freq);
+                                }
+                                if let Err(err) =
+                                    rtlsdr_dev.set_frequency(Direction::Rx, 0, sdr_freq, "")
                                 {
-                                    Complex::from(1.0)
-                                } else {
-                                    Complex::from(0.0)
+                                    break 'session SessionEnd::Failed(RadioError::Device(err));
                                 }
-                            });
-                            rbds_bandpass_filter.feed_from(&rbds_buffer);
 
-                            // Step 2. downmix the signal
-                            let rbds_downmixer = DownMixer::<f32>::new(RBDS_FREQ as f32);
-                            rbds_downmixer.feed_from(&rbds_bandpass_filter);
+                                // set the bandwidth
+                                let _ = rtlsdr_dev.set_bandwidth(Direction::Rx, 0, 1.000e6);
 
-                            // Step 3. remove high-frequency data and very-low frequency data
-                            let rbds_lowpass_filter = blocks::Filter::new(|_, freq| {
-                                if freq.abs() >= 10.0 && freq.abs() <= (RBDS_FREQ) {
-                                    Complex::from(1.0)
-                                } else {
-                                    Complex::from(0.0)
+                                // start sdr rx stream
+                                let sdr_rx = match rtlsdr_dev.rx_stream(sample_rate) {
+                                    Ok(sdr_rx) => sdr_rx,
+                                    Err(err) => {
+                                        break 'session SessionEnd::Failed(RadioError::Stream(err))
+                                    }
+                                };
+                                if let Err(err) = sdr_rx.activate().await {
+                                    break 'session SessionEnd::Failed(RadioError::Stream(err));
                                 }
-                            });
-                            rbds_lowpass_filter.feed_from(&rbds_downmixer);
-
-                            let controls_clone2 = controls_arc.clone();
-                            // add rbds decoder to output FM stream
-                            let rdbs_decoder =
-                                RbdsDecode::<f32>::new(rbds_channel, move |radiotext: String| {
-                                    let _ = controls_clone2.lock().unwrap().set_metadata(
-                                        MediaMetadata {
-                                            title: Some(&radiotext),
-                                            artist: radio_type_name,
-                                            cover_url: Some(icon_url.as_str()),
-                                            ..Default::default()
-                                        },
-                                    );
-                                });
-                            rdbs_decoder.feed_from(&rbds_lowpass_filter);
-                        } else if stream_settings.stream_type == StreamType::AM {
-                            let demodulator = AmDemod::<f32>::new();
-                            demodulator.feed_from(&filter1);
-                            pauser.feed_from(&demodulator);
-                        } else if stream_settings.stream_type == StreamType::HD {
-                            let controls_clone2 = controls_arc.clone();
-
-                            let hd_radio_decoder = HdRadioDecode::<f32>::new(
-                                stream_settings.hd_radio_program.unwrap(),
-                                true,
-                                move |state: HdRadioState| {
-                                    let thumbnail_base64 = state.thumbnail_data.clone();
-                                    let cover_url = if state.clone().thumbnail_data.is_some() {
-                                        thumbnail_base64.unwrap()
-                                    } else {
-                                        icon_url.clone()
-                                    };
-                                    let _ = controls_clone2.lock().unwrap().set_metadata(
-                                        MediaMetadata {
-                                            title: Some(&state.title),
-                                            artist: Some(&state.artist),
-                                            album: Some(&state.album),
-                                            cover_url: Some(cover_url.as_str()),
-                                            ..Default::default()
-                                        },
+
+                                // turn on direct sampling mode if in low frequencies (only RTL-SDRs have this setting)
+                                if capabilities.has_setting("direct_samp") {
+                                    let _ = rtlsdr_dev.write_setting(
+                                        "direct_samp",
+                                        stream_settings.stream_type.direct_sampling_mode(),
                                     );
+                                }
 
-                                    //println!("HD Radio State: {:#?}", state);
+                                // use manual gain when possible (automatic gain does not work that well)
+                                if capabilities.has_automatic_gain {
+                                    if let Err(err) = rtlsdr_dev.set_gain_mode(
+                                        Direction::Rx,
+                                        0,
+                                        !capabilities.has_manual_gain(),
+                                    ) {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err));
+                                    }
+                                }
 
-                                    hd_radio_channel.send(state);
-                                },
-                            );
-                            hd_radio_decoder.feed_from(&downsample1);
+                                // set the gain to the closest value the SDR supports
+                                let gain = capabilities.gain_range.clamp(stream_settings.gain);
+                                if capabilities.has_manual_gain() {
+                                    if let Err(err) = rtlsdr_dev.set_gain(Direction::Rx, 0, gain) {
+                                        break 'session SessionEnd::Failed(RadioError::Device(err));
+                                    }
+                                }
 
-                            // let test_recorder = WavWriterBlock::<f32>::new(
-                            //     "nrsc5_test_direct_output.wav".to_string(),
-                            //     false,
-                            //     None,
-                            // );
-                            // test_recorder.feed_from(&hd_radio_decoder);
+                                // watch for read failures and timeouts
+                                let stream_monitor = StreamMonitor::<f32>::new();
+                                stream_monitor.feed_from(&sdr_rx);
+
+                                // add IQ recorder (only writes samples while a recording is started)
+                                let events_clone = events.clone();
+                                let iq_writer = SigMfWriterBlock::<f32>::new(
+                                    sdr_args.clone(),
+                                    sample_rate,
+                                    sdr_freq,
+                                    gain,
+                                    Arc::new(move |err: String| {
+                                        events_clone.emit("iq_recording_error", err);
+                                        events_clone.emit("iq_recording_status", "stopped");
+                                    }),
+                                );
+                                iq_writer.feed_from(&stream_monitor);
+                                let iq_recorder = iq_writer.recorder();
+                                rtlsdr_state_clone.lock().unwrap().iq_recorder =
+                                    Some(iq_recorder.clone());
+
+                                // show what the SDR sees, if the frontend wants it
+                                let spectrum_analyzer =
+                                    spectrum_callback.clone().map(|spectrum_callback| {
+                                        let spectrum_analyzer = SpectrumAnalyzer::<f32>::new(
+                                            stream_settings.spectrum.unwrap_or_default(),
+                                            sdr_freq,
+                                            spectrum_callback,
+                                        );
+                                        spectrum_analyzer.feed_from(&stream_monitor);
+                                        spectrum_analyzer
+                                    });
+
+                                // demodulate the station
+                                let pipeline = match RadioPipeline::new(
+                                    &iq_writer,
+                                    sample_rate,
+                                    &stream_settings.pipeline_settings(),
+                                    pipeline_callbacks(
+                                        &events,
+                                        &rbds_callback,
+                                        &hd_radio_callback,
+                                        &controls_arc,
+                                        &icon_url,
+                                    ),
+                                ) {
+                                    Ok(pipeline) => Arc::new(pipeline),
+                                    Err(err) => {
+                                        let _ = sdr_rx.deactivate().await;
+                                        break 'session SessionEnd::Aborted(err);
+                                    }
+                                };
+
+                                // only the HD Radio decoder listens for settings on its own
+                                let mut hd_radio_listener = None;
+                                if pipeline.hd_radio_decoder().is_some() {
+                                    let pipeline_clone = pipeline.clone();
+                                    let old_station_freq_orig = Arc::new(Mutex::new(0.0));
+                                    let old_station_freq = old_station_freq_orig.clone();
+                                    hd_radio_listener = Some(events.listen(
+                                        "radio_update_settings",
+                                        Box::new(move |payload: &str| {
+                                            let Some(hd_radio_decoder) =
+                                                pipeline_clone.hd_radio_decoder()
+                                            else {
+                                                return;
+                                            };
+                                            if let Ok(new_settings) =
+                                                serde_json::from_str::<StreamSettings>(payload)
+                                            {
+                                                // keep the current program if none was sent
+                                                let program = new_settings
+                                                    .hd_radio_program
+                                                    .unwrap_or(hd_radio_decoder.get());
+                                                if hd_radio_decoder.get() != program {
+                                                    hd_radio_decoder.set(program);
+                                                } else if new_settings.freq
+                                                    != *old_station_freq.lock().unwrap()
+                                                {
+                                                    hd_radio_decoder.reset_state();
+                                                    (*old_station_freq
+                                                        .lock()
+                                                        .as_deref_mut()
+                                                        .unwrap()) = new_settings.freq;
+                                                }
+                                            }
+                                        }),
+                                    ));
+                                }
 
-                            pauser.feed_from(&hd_radio_decoder);
+                                // add audio recorder (only writes samples while a recording is started)
+                                let audio_recorder_block =
+                                    AudioRecorderBlock::<f32>::new(audio_recorder.clone());
+                                pipeline.feed_audio_to(&audio_recorder_block);
+
+                                // play the audio through the time-shift buffer, after the recorder so recordings stay live
+                                let time_shift_block =
+                                    TimeShiftBlock::<f32>::new(time_shift.clone());
+                                time_shift_block.feed_from(&audio_recorder_block);
+
+                                // add a volume block
+                                let volume =
+                                    blocks::GainControl::<f32>::new(stream_settings.volume);
+                                volume.feed_from(&time_shift_block);
+
+                                // add a buffer
+                                let buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 1.0);
+                                buffer.feed_from(&volume);
+
+                                // FM audio has a channel in each part of the samples, which the player needs one after the other
+                                let stereo_interleave = StereoInterleave::<f32>::new();
+                                if stream_settings.stream_type == StreamType::FM {
+                                    stereo_interleave.feed_from(&buffer);
+                                }
 
-                            let old_station_freq_orig = Arc::new(Mutex::new(0.0));
-                            let old_station_freq = old_station_freq_orig.clone();
-                            app.clone().listen("radio_update_settings", move |event| {
-                                if let Ok(new_settings) =
-                                    serde_json::from_str::<StreamSettings>(&event.payload())
-                                {
-                                    if hd_radio_decoder.get()
-                                        != new_settings.hd_radio_program.unwrap()
-                                    {
-                                        hd_radio_decoder
-                                            .set(new_settings.hd_radio_program.unwrap());
-                                    } else if new_settings.freq != *old_station_freq.lock().unwrap()
-                                    {
-                                        hd_radio_decoder.reset_state();
-                                        (*old_station_freq.lock().as_deref_mut().unwrap()) =
-                                            new_settings.freq;
+                                // output the stream (only FM and HD Radio are stereo)
+                                let playback = match better_cpal::AudioPlayer::new(
+                                    stream_settings.pipeline_settings().audio_sample_rate(),
+                                    None,
+                                    2,
+                                    Some(!stream_settings.stream_type.is_stereo()),
+                                ) {
+                                    Ok(playback) => playback,
+                                    Err(err) => {
+                                        if let Some(hd_radio_listener) = hd_radio_listener {
+                                            events.unlisten(hd_radio_listener);
+                                        }
+                                        let _ = sdr_rx.deactivate().await;
+                                        break 'session SessionEnd::Aborted(RadioError::Audio(
+                                            format!("Could not open the audio device: {}", err),
+                                        ));
                                     }
+                                };
+                                if stream_settings.stream_type == StreamType::FM {
+                                    playback.feed_from(&stereo_interleave);
+                                } else {
+                                    playback.feed_from(&buffer);
                                 }
-                            });
-                        }
 
-                        // filter frequencies beyond normal human hearing range (20hz to 16 kHz)
-                        let filter2 = blocks::filters::Filter::new_rectangular(|bin, freq| {
-                            if bin.abs() >= 1 && freq.abs() >= 20.0 && freq.abs() <= 16000.0 {
-                                blocks::filters::deemphasis_factor(50e-6, freq)
-                            } else {
-                                Complex::from(0.0)
-                            }
-                        });
-                        if stream_settings.stream_type != StreamType::HD {
-                            filter2.feed_from(&pauser);
-                        }
+                                let sdr_clone = rtlsdr_dev.clone();
+                                let pipeline_clone = pipeline.clone();
+                                let iq_recorder_clone = iq_recorder.clone();
+                                let audio_recorder_clone = audio_recorder.clone();
+                                let time_shift_clone = time_shift.clone();
+                                let latest_settings_clone = latest_settings.clone();
+                                let stream_type = stream_settings.stream_type.clone();
+                                let settings_listener = events.listen(
+                                    "radio_update_settings",
+                                    Box::new(move |payload: &str| {
+                                        if let Ok(new_settings) =
+                                            serde_json::from_str::<StreamSettings>(payload)
+                                        {
+                                            if volume.get() != new_settings.volume {
+                                                volume.set(new_settings.volume);
+                                            }
+                                            // errors are logged, since a failing SDR is restarted by the stream monitor
+                                            let sdr_freq = stream_type
+                                                .station_frequency(new_settings.freq)
+                                                + freq_offset;
+                                            if sdr_clone.frequency(Direction::Rx, 0).ok()
+                                                != Some(sdr_freq)
+                                            {
+                                                // set center frequency
+                                                if let Err(err) = sdr_clone.set_frequency(
+                                                    Direction::Rx,
+                                                    0,
+                                                    sdr_freq,
+                                                    "",
+                                                ) {
+                                                    error!("Failed to set new frequency: {}", err);
+                                                }
+                                            }
+                                            iq_recorder_clone.set_frequency(sdr_freq);
+                                            if latest_settings_clone.lock().unwrap().freq
+                                                != new_settings.freq
+                                            {
+                                                // the metadata was for the last station
+                                                audio_recorder_clone.update_tags(|tags| {
+                                                    *tags = AudioTags {
+                                                        frequency: Some(
+                                                            stream_type
+                                                                .frequency_label(new_settings.freq),
+                                                        ),
+                                                        ..Default::default()
+                                                    };
+                                                });
+                                            }
+                                            pipeline_clone
+                                                .update(&new_settings.pipeline_settings());
+                                            time_shift_clone
+                                                .set_length(new_settings.time_shift_length());
+                                            if let Some(spectrum_analyzer) = &spectrum_analyzer {
+                                                spectrum_analyzer.set_center_frequency(sdr_freq);
+                                                if let Some(spectrum_settings) =
+                                                    new_settings.spectrum
+                                                {
+                                                    spectrum_analyzer
+                                                        .set_settings(spectrum_settings);
+                                                }
+                                            }
+                                            let gain =
+                                                capabilities.gain_range.clamp(new_settings.gain);
+                                            iq_recorder_clone.set_gain(gain);
+                                            if capabilities.has_manual_gain()
+                                                && sdr_clone.gain(Direction::Rx, 0).ok()
+                                                    != Some(gain)
+                                            {
+                                                // set new gain
+                                                if let Err(err) =
+                                                    sdr_clone.set_gain(Direction::Rx, 0, gain)
+                                                {
+                                                    error!("Failed to set new gain: {}", err);
+                                                }
+                                            }
+
+                                            let mut latest_settings =
+                                                latest_settings_clone.lock().unwrap();
+                                            latest_settings.freq = new_settings.freq;
+                                            latest_settings.volume = new_settings.volume;
+                                            latest_settings.gain = new_settings.gain;
+                                            latest_settings.hd_radio_program =
+                                                new_settings.hd_radio_program;
+                                            if new_settings.spectrum.is_some() {
+                                                latest_settings.spectrum = new_settings.spectrum;
+                                            }
+                                            latest_settings.iq_correction =
+                                                new_settings.iq_correction;
+                                            latest_settings.audio = new_settings.audio;
+                                            latest_settings.sideband = new_settings.sideband;
+                                            latest_settings.channel = new_settings.channel;
+                                            latest_settings.noise = new_settings.noise;
+                                            latest_settings.time_shift_length =
+                                                new_settings.time_shift_length;
+                                        }
+                                    }),
+                                );
 
-                        // downsample so the output device can play the audio
-                        let downsample2 = blocks::Downsampler::<f32>::new(
-                            4096,
-                            stream_settings.sample_rate,
-                            stream_settings.sample_rate / 2.0,
-                        );
-                        downsample2.feed_from(&filter2);
-
-                        // add a volume block
-                        let volume = blocks::GainControl::<f32>::new(stream_settings.volume);
-                        if stream_settings.stream_type == StreamType::HD {
-                            volume.feed_from(&pauser);
-                        } else {
-                            volume.feed_from(&downsample2);
-                        }
+                                let prefix = stream_settings.stream_type.status_prefix();
 
-                        // add a buffer
-                        let buffer = blocks::Buffer::new(0.0, 0.0, 0.0, 1.0);
-                        buffer.feed_from(&volume);
-
-                        // output the stream
-                        let playback = better_cpal::AudioPlayer::new(
-                            stream_settings.sample_rate,
-                            None,
-                            2,
-                            Some(if stream_settings.stream_type == StreamType::HD {
-                                false
-                            } else {
-                                true
-                            }),
-                        )
-                        .unwrap();
-                        playback.feed_from(&buffer);
-
-                        let sdr_clone = rtlsdr_dev.clone();
-                        let args_clone = sdr_args.clone();
-                        app.listen("radio_update_settings", move |event| {
-                            if let Ok(new_settings) =
-                                serde_json::from_str::<StreamSettings>(&event.payload())
-                            {
-                                if volume.get() != new_settings.volume {
-                                    volume.set(new_settings.volume);
+                                if is_recovering {
+                                    emit_recovery_status(
+                                        &*events,
+                                        "radio",
+                                        StreamRecoveryStatus::Recovered,
+                                    );
+                                    is_recovering = false;
                                 }
-                                let sdr_freq = new_settings.freq * freq_mul;
-                                if sdr_clone.frequency(Direction::Rx, 0).unwrap() != sdr_freq {
-                                    // set center frequency
-                                    sdr_clone
-                                        .set_frequency(Direction::Rx, 0, sdr_freq, "")
-                                        .expect("Failed to set new frequency");
+
+                                let session_end = monitor_stream(
+                                    &stream_monitor,
+                                    &sdr_rx,
+                                    &shutdown_flag,
+                                    || {
+                                        // notify frontend that audio is playing
+                                        events.emit(
+                                            "rtlsdr_status",
+                                            format!("{}_{}", prefix, "running"),
+                                        );
+                                    },
+                                )
+                                .await;
+
+                                // stop the blocks of this session from changing settings
+                                events.unlisten(settings_listener);
+                                if let Some(hd_radio_listener) = hd_radio_listener {
+                                    events.unlisten(hd_radio_listener);
                                 }
-                                if args_clone.driver == "sdrplay" {
-                                    let _ = sdr_clone.write_setting(
-                                        "rfgain_sel",
-                                        new_settings.gain.round().to_string().as_str(),
-                                    );
-                                } else {
-                                    if sdr_clone.gain(Direction::Rx, 0).unwrap()
-                                        != new_settings.gain
-                                    {
-                                        // set center frequency
-                                        sdr_clone
-                                            .set_gain(Direction::Rx, 0, new_settings.gain)
-                                            .expect("Failed to set new gain");
+
+                                // save the recording, since it can't continue with a new stream
+                                if let SessionEnd::Failed(_) = session_end {
+                                    if iq_recorder.is_recording() {
+                                        let _ = iq_recorder.stop();
                                     }
                                 }
-                            }
-                        });
 
-                        let prefix: &str;
+                                let _ = sdr_rx.deactivate().await;
 
-                        if stream_settings.stream_type == StreamType::FM {
-                            prefix = "fm";
-                        } else if stream_settings.stream_type == StreamType::AM {
-                            prefix = "am";
-                        } else {
-                            prefix = "hd";
-                        }
+                                session_end
+                            };
 
-                        while !shutdown_flag.load(Ordering::SeqCst) {
-                            // notify frontend that audio is playing
-                            app.emit("rtlsdr_status", format!("{}_{}", prefix, "running"))
-                                .expect("failed to emit event");
+                            match session_end {
+                                SessionEnd::Stopped => {
+                                    save_device_preferences(
+                                        &sdr_registry,
+                                        &sdr_args,
+                                        &latest_settings.lock().unwrap(),
+                                        sdr_sample_rate,
+                                    );
+
+                                    // release the SDR
+                                    if let Err(err) =
+                                        release_sdr_dev(&sdr_registry, rtlsdr_dev, sdr_args)
+                                    {
+                                        warn!("Could not release SDR: {}", err);
+                                    }
 
-                            time::sleep(Duration::from_millis(250)).await;
+                                    // the recording ended, so nothing else will say that it stopped
+                                    if !shutdown_flag.load(Ordering::SeqCst) {
+                                        drop(
+                                            rtlsdr_state_clone
+                                                .lock()
+                                                .unwrap()
+                                                .radio_stream_thread
+                                                .take(),
+                                        );
+                                        events.emit("rtlsdr_status", "stopped");
+                                    }
+                                    break;
+                                }
+                                SessionEnd::Aborted(error) => {
+                                    if let Err(err) =
+                                        release_sdr_dev(&sdr_registry, rtlsdr_dev, sdr_args)
+                                    {
+                                        warn!("Could not release SDR: {}", err);
+                                    }
+                                    emit_error(&*events, "radio", error);
+                                    events.emit("rtlsdr_status", "stopped");
+
+                                    // remove the reference to the thread, so the stream can be started again
+                                    drop(
+                                        rtlsdr_state_clone
+                                            .lock()
+                                            .unwrap()
+                                            .radio_stream_thread
+                                            .take(),
+                                    );
+                                    break;
+                                }
+                                SessionEnd::Failed(error) => {
+                                    // restart with the same settings once the SDR is available again
+                                    let Some(recovered_sdr) = recover_sdr_dev(
+                                        &*events,
+                                        &sdr_registry,
+                                        "radio",
+                                        &sdr_args,
+                                        error,
+                                        &shutdown_flag,
+                                    )
+                                    .await
+                                    else {
+                                        break;
+                                    };
+                                    (rtlsdr_dev, sdr_args) = recovered_sdr;
+                                    is_recovering = true;
+                                }
+                            }
                         }
 
-                        // release the SDR
-                        release_sdr_dev(app, rtlsdr_dev, sdr_args).unwrap();
+                        // save the audio recording, since the stream has ended
+                        if audio_recorder.is_recording() {
+                            let _ = audio_recorder.stop();
+                        }
                     })
             }));
+
+        Ok(())
     }
 
-    pub async fn stop_stream(&self, app: AppHandle) {
-        if let Ok(mut rtl_sdr_data) = self.0.clone().lock() {
-            rtl_sdr_data.shutdown_flag.store(true, Ordering::SeqCst);
+    pub async fn stop_stream(&self, events: &dyn EventSink) {
+        // the stream thread needs the lock too, so it isn't held while waiting for the thread
+        let (shutdown_flag, thread) = match self.0.lock() {
+            Ok(mut rtl_sdr_data) => {
+                rtl_sdr_data.shutdown_flag.store(true, Ordering::SeqCst);
+                (
+                    rtl_sdr_data.shutdown_flag.clone(),
+                    rtl_sdr_data.radio_stream_thread.take(),
+                )
+            }
+            Err(_) => {
+                error!("Could not acquire lock immediately");
+                return;
+            }
+        };
+
+        if let Some(thread) = thread {
+            thread.await.expect("Failed to join thread");
+        }
 
-            if let Some(thread) = rtl_sdr_data.radio_stream_thread.take() {
-                thread.await.expect("Failed to join thread");
+        if let Ok(mut rtl_sdr_data) = self.0.lock() {
+            // make sure an IQ recording in progress is saved
+            if let Some(iq_recorder) = rtl_sdr_data.iq_recorder.take() {
+                if iq_recorder.is_recording() {
+                    let _ = iq_recorder.stop();
+                }
             }
 
-            rtl_sdr_data.shutdown_flag.store(false, Ordering::SeqCst);
+            // and the audio recording, in case the stream ended before it could
+            if let Some(audio_recorder) = rtl_sdr_data.audio_recorder.take() {
+                if audio_recorder.is_recording() {
+                    let _ = audio_recorder.stop();
+                }
+            }
 
-            app.emit("rtlsdr_status", Some("stopped"))
-                .expect("failed to emit event");
-        } else {
-            error!("Could not acquire lock immediately");
-            return;
+            // the buffered audio can't be played once the stream has stopped
+            rtl_sdr_data.time_shift = None;
+            rtl_sdr_data.media_controls = None;
         }
+
+        shutdown_flag.store(false, Ordering::SeqCst);
+
+        events.emit("rtlsdr_status", Some("stopped"));
+    }
+
+    pub fn get_iq_recorder(&self) -> Option<SigMfRecorder> {
+        return self.0.clone().lock().unwrap().iq_recorder.clone();
+    }
+
+    pub fn get_audio_recorder(&self) -> Option<AudioRecorder> {
+        return self.0.clone().lock().unwrap().audio_recorder.clone();
+    }
+
+    pub fn get_time_shift(&self) -> Option<TimeShift> {
+        return self.0.clone().lock().unwrap().time_shift.clone();
+    }
+
+    /// Pause or resume the time-shifted audio, and show it in the media controls
+    pub fn set_time_shift_paused(&self, paused: bool) -> RadioResult<()> {
+        let (time_shift, media_controls) = {
+            let rtl_sdr_data = self.0.lock().unwrap();
+            (
+                rtl_sdr_data.time_shift.clone(),
+                rtl_sdr_data.media_controls.clone(),
+            )
+        };
+        let time_shift = time_shift.ok_or(RadioError::State(String::from(
+            "There is no active stream to pause",
+        )))?;
+
+        time_shift.set_paused(paused);
+        if let Some(media_controls) = media_controls {
+            set_media_playback(&mut media_controls.lock().unwrap(), paused);
+        }
+
+        Ok(())
     }
 
     pub fn is_playing(&self) -> bool {
         return self.0.clone().lock().unwrap().radio_stream_thread.is_some();
     }
 }
+
+// send what the station is playing to the frontend and the media controls
+fn pipeline_callbacks(
+    events: &SharedEventSink,
+    rbds_callback: &RbdsStateCallback,
+    hd_radio_callback: &HdRadioStateCallback,
+    media_controls: &Option<Arc<Mutex<MediaControls>>>,
+    icon_url: &str,
+) -> RadioPipelineCallbacks {
+    let events_clone = events.clone();
+    let fm_stereo_callback = Arc::new(move |state: FmStereoState| {
+        events_clone.emit("fm_stereo_state", state);
+    });
+    let events_clone = events.clone();
+    let squelch_callback = Arc::new(move |is_open: bool| {
+        events_clone.emit("squelch_state", is_open);
+    });
+    let events_clone = events.clone();
+    let subaudible_tone_callback = Arc::new(move |tone: Option<SubaudibleTone>| {
+        events_clone.emit("subaudible_tone", tone);
+    });
+    let events_clone = events.clone();
+    let airband_channel_callback = Arc::new(move |state: AirbandChannelState| {
+        events_clone.emit("airband_channel_state", state);
+    });
+
+    let controls_clone = media_controls.clone();
+    let icon_url_clone = icon_url.to_string();
+    let radio_text_callback = Arc::new(move |radio_text: String| {
+        if let Some(controls) = &controls_clone {
+            let _ = controls.lock().unwrap().set_metadata(MediaMetadata {
+                title: Some(&radio_text),
+                artist: Some(StreamType::FM.display_name()),
+                cover_url: Some(icon_url_clone.as_str()),
+                ..Default::default()
+            });
+        }
+    });
+
+    let controls_clone = media_controls.clone();
+    let icon_url_clone = icon_url.to_string();
+    let hd_radio_callback_clone = hd_radio_callback.clone();
+    let hd_radio_callback = Arc::new(move |state: HdRadioState| {
+        if let Some(controls) = &controls_clone {
+            let cover_url = state
+                .thumbnail_data
+                .clone()
+                .unwrap_or(icon_url_clone.clone());
+            let _ = controls.lock().unwrap().set_metadata(MediaMetadata {
+                title: Some(&state.title),
+                artist: Some(&state.artist),
+                album: Some(&state.album),
+                cover_url: Some(cover_url.as_str()),
+                ..Default::default()
+            });
+        }
+        hd_radio_callback_clone(state);
+    });
+
+    RadioPipelineCallbacks {
+        rbds: rbds_callback.clone(),
+        radio_text: radio_text_callback,
+        fm_stereo: fm_stereo_callback,
+        squelch: squelch_callback,
+        subaudible_tone: subaudible_tone_callback,
+        airband_channel: airband_channel_callback,
+        hd_radio: hd_radio_callback,
+    }
+}
+
+fn set_media_playback(controls: &mut MediaControls, is_paused: bool) {
+    let playback = if is_paused {
+        MediaPlayback::Paused { progress: None }
+    } else {
+        MediaPlayback::Playing { progress: None }
+    };
+    let _ = controls.set_playback(playback);
+}
+
+// remember the settings that were last used with the SDR, so they can be used again
+fn save_device_preferences(
+    sdr_registry: &SDRRegistry,
+    sdr_args: &AvailableSDRArgs,
+    settings: &StreamSettings,
+    sample_rate: Option<f64>,
+) {
+    let result =
+        Store::new(sdr_registry.data_dir()).update_device_preferences(sdr_args, |preferences| {
+            preferences.gain = Some(settings.gain);
+            if sample_rate.is_some() {
+                preferences.sample_rate = sample_rate;
+            }
+            preferences.preferred_mode = Some(settings.stream_type.clone());
+        });
+
+    if let Err(err) = result {
+        warn!(
+            "Could not save the preferences of {}: {}",
+            sdr_args.label, err
+        );
+    }
+}
@@ -0,0 +1,616 @@
+use std::{
+    f64::consts::TAU,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use radiorust::prelude::*;
+use rustfft::{num_complex::Complex as FftComplex, FftPlanner};
+use serde::{Deserialize, Serialize};
+use soapysdr::Direction;
+use tauri::async_runtime;
+use tokio::time;
+
+use crate::{
+    error::{emit_error, RadioError, RadioResult},
+    events::{EventSink, SharedEventSink},
+    radio_services::soapysdr_radio::StreamType,
+    radiorust_blocks::{
+        rbds_decode::{new_rbds_receiver, RbdsState},
+        sample_capture::SampleCapture,
+    },
+    sdr::{
+        device::SDRDevice, enumeration::AvailableSDRArgs, get_sdr_dev, release_sdr_dev, SDRRegistry,
+    },
+};
+
+// a wide capture measures many channels at each step of the sweep
+const SWEEP_SAMPLE_RATE: f64 = 2.048e6;
+// the edges of the capture are attenuated by the SDR's filters
+const USABLE_BANDWIDTH_RATIO: f64 = 0.8;
+// samples at the start of the stream are discarded while the tuner settles (in seconds)
+const SETTLE_TIME: f64 = 0.05;
+const MEASUREMENT_TIME: f64 = 0.1;
+const SWEEP_FFT_SIZE: usize = 2048;
+
+// how far above the noise floor a channel must be to be a station (in dB)
+const DEFAULT_THRESHOLD: f64 = 10.0;
+// how long to wait for the RBDS data of a station (in seconds)
+const DEFAULT_DWELL_TIME: f64 = 5.0;
+// the program service name is sent in pieces, so wait until it stops changing
+const SERVICE_NAME_STABLE_TIME: Duration = Duration::from_secs(1);
+const RBDS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const FM_SAMPLE_RATE: f64 = 1.024e6;
+const FM_DEMOD_SAMPLE_RATE: f64 = 336000.0;
+const FM_PILOT_FREQ: f64 = 19_000.0;
+// how long to listen for the stereo pilot (in seconds)
+const PILOT_CAPTURE_TIME: f64 = 0.5;
+// how much stronger than the rest of the guard band the pilot must be (in dB)
+const MIN_PILOT_TO_NOISE: f64 = 10.0;
+
+/// The channels of a broadcast band (in hz)
+struct Band {
+    start: f64,
+    end: f64,
+    step: f64,
+    // the part of each channel that is measured
+    channel_bandwidth: f64,
+}
+
+impl Band {
+    fn for_stream_type(stream_type: &StreamType) -> RadioResult<Self> {
+        match stream_type {
+            // every channel of every region, since only the Americas use odd tenths with 200 kHz between them
+            StreamType::FM => Ok(Band {
+                start: 87.5e6,
+                end: 108.0e6,
+                step: 100e3,
+                channel_bandwidth: 100e3,
+            }),
+            StreamType::AM => Ok(Band {
+                start: 530e3,
+                end: 1700e3,
+                step: 10e3,
+                channel_bandwidth: 8e3,
+            }),
+            StreamType::HD => Err(RadioError::Settings(String::from(
+                "HD Radio stations are found by scanning the FM band",
+            ))),
+            StreamType::USB | StreamType::LSB | StreamType::CW => {
+                Err(RadioError::Settings(String::from(
+                    "SSB and CW signals can't be scanned for, since they are not on a channel plan",
+                )))
+            }
+            StreamType::NBFM => Err(RadioError::Settings(String::from(
+                "Narrowband FM channels can't be scanned for, since they are only used while talking",
+            ))),
+            StreamType::AIR => Err(RadioError::Settings(String::from(
+                "Airband channels can't be scanned for, since they are only used while talking",
+            ))),
+        }
+    }
+
+    fn channels(&self) -> Vec<f64> {
+        let num_channels = ((self.end - self.start) / self.step).round() as usize + 1;
+        (0..num_channels)
+            .map(|i| self.start + i as f64 * self.step)
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ScanSettings {
+    stream_type: StreamType,
+    gain: f64,
+    // how far above the noise floor a channel must be to be a station (in dB)
+    threshold: Option<f64>,
+    // how long to wait for the RBDS data of each FM station (in seconds)
+    dwell_time: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScannedStation {
+    // in MHz for FM, and KHz for AM (the same as stream settings)
+    pub frequency: f64,
+    // how far above the noise floor the station is (in dB)
+    pub signal_strength: f64,
+    pub is_stereo: Option<bool>,
+    pub pi: Option<u16>,
+    pub service_name: Option<String>,
+    pub program_type: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub enum ScanUpdate {
+    // measuring the power of every channel in the band (from 0 to 1)
+    Sweeping { progress: f64 },
+    // waiting for the RBDS data of a station (in MHz)
+    Identifying { frequency: f64 },
+    StationFound(ScannedStation),
+    Finished(Vec<ScannedStation>),
+}
+
+pub type ScanUpdateCallback = Arc<dyn Fn(ScanUpdate) + Send + Sync>;
+
+pub struct ScannerState(Arc<Mutex<ScannerData>>);
+pub struct ScannerData {
+    pub scan_thread: Option<async_runtime::JoinHandle<()>>,
+    pub shutdown_flag: Arc<AtomicBool>,
+}
+
+impl ScannerState {
+    pub fn new() -> Self {
+        ScannerState(Arc::new(Mutex::new(ScannerData {
+            scan_thread: None,
+            shutdown_flag: Arc::new(AtomicBool::new(false)),
+        })))
+    }
+
+    /// Sweep a broadcast band for stations, and identify FM stations with their RBDS data
+    pub fn start_scan(
+        &self,
+        events: SharedEventSink,
+        sdr_registry: SDRRegistry,
+        settings: ScanSettings,
+        sdr_args: AvailableSDRArgs,
+        scan_callback: ScanUpdateCallback,
+    ) -> RadioResult<()> {
+        let band = Band::for_stream_type(&settings.stream_type)?;
+
+        let scanner_state = self.0.clone();
+
+        let shutdown_flag = scanner_state.lock().unwrap().shutdown_flag.clone();
+
+        scanner_state.lock().unwrap().scan_thread =
+            Some(async_runtime::spawn_blocking(move || {
+                tokio::runtime::Runtime::new()
+                    .unwrap()
+                    .block_on(async move {
+                        events.emit("scanner_status", "running");
+
+                        match get_sdr_dev(&sdr_registry, sdr_args) {
+                            Ok((sdr_dev, sdr_args)) => {
+                                info!("Scanning with {}", sdr_args.label);
+
+                                let scan_result = scan_band(
+                                    &sdr_dev,
+                                    &settings,
+                                    &band,
+                                    &scan_callback,
+                                    &shutdown_flag,
+                                )
+                                .await;
+
+                                match scan_result {
+                                    Ok(stations) => {
+                                        scan_callback(ScanUpdate::Finished(stations));
+                                    }
+                                    // stopping the scan is not an error
+                                    Err(_) if shutdown_flag.load(Ordering::SeqCst) => {}
+                                    Err(err) => {
+                                        emit_error(&*events, "scanner", err);
+                                    }
+                                }
+
+                                if let Err(err) = release_sdr_dev(&sdr_registry, sdr_dev, sdr_args)
+                                {
+                                    warn!("Could not release SDR: {}", err);
+                                }
+                            }
+                            Err(err) => {
+                                // notify frontend of error
+                                emit_error(&*events, "scanner", err);
+                            }
+                        }
+
+                        events.emit("scanner_status", "stopped");
+                    })
+            }));
+
+        Ok(())
+    }
+
+    pub async fn stop_scan(&self, events: &dyn EventSink) {
+        if let Ok(mut scanner_data) = self.0.clone().lock() {
+            scanner_data.shutdown_flag.store(true, Ordering::SeqCst);
+
+            if let Some(thread) = scanner_data.scan_thread.take() {
+                thread.await.expect("Failed to join thread");
+            }
+
+            scanner_data.shutdown_flag.store(false, Ordering::SeqCst);
+
+            events.emit("scanner_status", Some("stopped"));
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        // the scan ends on its own once every station is found
+        return self
+            .0
+            .clone()
+            .lock()
+            .unwrap()
+            .scan_thread
+            .as_ref()
+            .is_some_and(|thread| !thread.inner().is_finished());
+    }
+}
+
+async fn scan_band(
+    sdr_dev: &SDRDevice,
+    settings: &ScanSettings,
+    band: &Band,
+    scan_callback: &ScanUpdateCallback,
+    shutdown_flag: &AtomicBool,
+) -> RadioResult<Vec<ScannedStation>> {
+    let capabilities = sdr_dev
+        .capabilities(Direction::Rx, 0)
+        .map_err(RadioError::Device)?;
+
+    // turn on direct sampling mode for the AM band (only RTL-SDRs have this setting)
+    if capabilities.has_setting("direct_samp") {
+        let _ = sdr_dev.write_setting("direct_samp", settings.stream_type.direct_sampling_mode());
+    }
+
+    // the noise floor is only meaningful with a fixed gain
+    if capabilities.has_automatic_gain {
+        sdr_dev
+            .set_gain_mode(Direction::Rx, 0, !capabilities.has_manual_gain())
+            .map_err(RadioError::Device)?;
+    }
+    if capabilities.has_manual_gain() {
+        sdr_dev
+            .set_gain(
+                Direction::Rx,
+                0,
+                capabilities.gain_range.clamp(settings.gain),
+            )
+            .map_err(RadioError::Device)?;
+    }
+
+    let channels = band.channels();
+    let channel_powers = sweep_band(sdr_dev, band, &channels, scan_callback, shutdown_flag).await?;
+
+    // most channels are empty, so a low percentile of the channel powers is the noise floor
+    let mut sorted_powers = channel_powers.clone();
+    sorted_powers.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor = sorted_powers[sorted_powers.len() / 4];
+    let threshold = settings.threshold.unwrap_or(DEFAULT_THRESHOLD);
+
+    // strong stations spill into the channels next to them, so only keep the peaks
+    let candidates: Vec<usize> = (0..channels.len())
+        .filter(|&i| {
+            channel_powers[i] - noise_floor >= threshold
+                && (i == 0 || channel_powers[i] >= channel_powers[i - 1])
+                && (i == channels.len() - 1 || channel_powers[i] >= channel_powers[i + 1])
+        })
+        .collect();
+
+    info!(
+        "Found {} possible stations above a noise floor of {:.1} dB",
+        candidates.len(),
+        noise_floor
+    );
+
+    let freq_mul = settings.stream_type.frequency_multiplier();
+    let dwell_time = settings.dwell_time.unwrap_or(DEFAULT_DWELL_TIME);
+
+    let mut stations = vec![];
+    for i in candidates {
+        if shutdown_flag.load(Ordering::SeqCst) {
+            return Err(RadioError::Stream(String::from("The scan was stopped")));
+        }
+
+        let mut station = ScannedStation {
+            frequency: (channels[i] / freq_mul * 1000.0).round() / 1000.0,
+            signal_strength: channel_powers[i] - noise_floor,
+            is_stereo: None,
+            pi: None,
+            service_name: None,
+            program_type: None,
+        };
+
+        // only FM stations have RBDS data
+        if settings.stream_type == StreamType::FM {
+            scan_callback(ScanUpdate::Identifying {
+                frequency: station.frequency,
+            });
+
+            match identify_fm_station(sdr_dev, channels[i], dwell_time, shutdown_flag).await {
+                Ok((is_stereo, rbds_state)) => {
+                    station.is_stereo = Some(is_stereo);
+                    if let Some(rbds_state) = rbds_state {
+                        station.pi = Some(rbds_state.pi);
+                        station.service_name = Some(rbds_state.service_name.trim().to_string())
+                            .filter(|service_name| !service_name.is_empty());
+                        station.program_type = rbds_state.program_type;
+                    }
+                }
+                Err(err) => warn!("Could not identify {} MHz: {}", station.frequency, err),
+            }
+        }
+
+        scan_callback(ScanUpdate::StationFound(station.clone()));
+        stations.push(station);
+    }
+
+    Ok(stations)
+}
+
+// measure the power of every channel (in dB), capturing as many channels as possible at once
+async fn sweep_band(
+    sdr_dev: &SDRDevice,
+    band: &Band,
+    channels: &[f64],
+    scan_callback: &ScanUpdateCallback,
+    shutdown_flag: &AtomicBool,
+) -> RadioResult<Vec<f64>> {
+    let capabilities = sdr_dev
+        .capabilities(Direction::Rx, 0)
+        .map_err(RadioError::Device)?;
+    let sample_rate = capabilities.closest_sample_rate(SWEEP_SAMPLE_RATE);
+    sdr_dev
+        .set_sample_rate(Direction::Rx, 0, sample_rate)
+        .map_err(RadioError::Device)?;
+
+    let usable_bandwidth = sample_rate * USABLE_BANDWIDTH_RATIO;
+    // center each capture between two channels, so none of them are on the DC spike
+    let channels_below_center =
+        ((usable_bandwidth / 2.0 - band.channel_bandwidth / 2.0 - band.step / 2.0) / band.step)
+            .floor()
+            .max(0.0);
+
+    let mut channel_powers = vec![f64::NEG_INFINITY; channels.len()];
+    let mut next_channel = 0;
+    while next_channel < channels.len() {
+        if shutdown_flag.load(Ordering::SeqCst) {
+            return Err(RadioError::Stream(String::from("The scan was stopped")));
+        }
+
+        let center_freq =
+            channels[next_channel] + channels_below_center * band.step + band.step / 2.0;
+        let (power_spectrum, sample_rate) =
+            measure_power_spectrum(sdr_dev, center_freq, sample_rate).await?;
+
+        let fft_size = power_spectrum.len();
+        let freq_to_bin = |freq: f64| {
+            (((freq - center_freq) / sample_rate + 0.5) * fft_size as f64)
+                .round()
+                .clamp(0.0, (fft_size - 1) as f64) as usize
+        };
+
+        let first_channel = next_channel;
+        while next_channel < channels.len()
+            && (channels[next_channel] - center_freq).abs() + band.channel_bandwidth / 2.0
+                <= usable_bandwidth / 2.0
+        {
+            let channel_freq = channels[next_channel];
+            let power: f64 =
+                power_spectrum[freq_to_bin(channel_freq - band.channel_bandwidth / 2.0)
+                    ..=freq_to_bin(channel_freq + band.channel_bandwidth / 2.0)]
+                    .iter()
+                    .sum();
+            channel_powers[next_channel] = 10.0 * power.max(1e-20).log10();
+            next_channel += 1;
+        }
+
+        // the sample rate is too low for even one channel
+        if next_channel == first_channel {
+            return Err(RadioError::Device(format!(
+                "A sample rate of {}hz is too low to scan this band",
+                sample_rate
+            )));
+        }
+
+        scan_callback(ScanUpdate::Sweeping {
+            progress: next_channel as f64 / channels.len() as f64,
+        });
+    }
+
+    Ok(channel_powers)
+}
+
+// get the averaged power of each frequency around the center frequency, from lowest to highest
+async fn measure_power_spectrum(
+    sdr_dev: &SDRDevice,
+    center_freq: f64,
+    requested_sample_rate: f64,
+) -> RadioResult<(Vec<f64>, f64)> {
+    sdr_dev
+        .set_frequency(Direction::Rx, 0, center_freq, "")
+        .map_err(RadioError::Device)?;
+
+    let sdr_rx = sdr_dev
+        .rx_stream(requested_sample_rate)
+        .map_err(RadioError::Stream)?;
+    let sample_capture = SampleCapture::<f32>::new(
+        ((SETTLE_TIME + MEASUREMENT_TIME) * requested_sample_rate) as usize,
+    );
+    sample_capture.feed_from(&sdr_rx);
+
+    sdr_rx.activate().await.map_err(RadioError::Stream)?;
+    let (raw_samples, sample_rate) = sample_capture.samples().await.map_err(RadioError::Stream)?;
+    let _ = sdr_rx.deactivate().await;
+    drop(sdr_rx);
+
+    let settle_samples = ((SETTLE_TIME * sample_rate) as usize).min(raw_samples.len());
+    let samples: Vec<FftComplex<f64>> = raw_samples[settle_samples..]
+        .iter()
+        .map(|sample| FftComplex::new(sample.re as f64, sample.im as f64))
+        .collect();
+
+    Ok((
+        average_power_spectrum(&samples, SWEEP_FFT_SIZE),
+        sample_rate,
+    ))
+}
+
+// average the power of hann-windowed FFTs, with 0hz moved to the middle
+fn average_power_spectrum(samples: &[FftComplex<f64>], fft_size: usize) -> Vec<f64> {
+    let fft = FftPlanner::<f64>::new().plan_fft_forward(fft_size);
+    let window: Vec<f64> = (0..fft_size)
+        .map(|i| 0.5 - 0.5 * (TAU * i as f64 / fft_size as f64).cos())
+        .collect();
+
+    let mut power_sums = vec![0.0; fft_size];
+    let mut num_averages = 0;
+    for block in samples.chunks_exact(fft_size) {
+        let mut buffer: Vec<FftComplex<f64>> = block
+            .iter()
+            .zip(window.iter())
+            .map(|(sample, window)| *sample * *window)
+            .collect();
+        fft.process(&mut buffer);
+
+        for (power_sum, bin) in power_sums.iter_mut().zip(buffer.iter()) {
+            *power_sum += bin.norm_sqr();
+        }
+        num_averages += 1;
+    }
+
+    (0..fft_size)
+        .map(|i| power_sums[(i + fft_size / 2) % fft_size] / num_averages.max(1) as f64)
+        .collect()
+}
+
+// tune to an FM station, then check for a stereo pilot and wait for its RBDS data
+async fn identify_fm_station(
+    sdr_dev: &SDRDevice,
+    frequency: f64,
+    dwell_time: f64,
+    shutdown_flag: &AtomicBool,
+) -> Result<(bool, Option<RbdsState>), String> {
+    let capabilities = sdr_dev.capabilities(Direction::Rx, 0)?;
+    let sample_rate = capabilities.closest_sample_rate(FM_SAMPLE_RATE);
+    sdr_dev.set_sample_rate(Direction::Rx, 0, sample_rate)?;
+    sdr_dev.set_frequency(Direction::Rx, 0, frequency, "")?;
+
+    let sdr_rx = sdr_dev.rx_stream(sample_rate)?;
+
+    // add downsampler
+    let downsample = blocks::Downsampler::<f32>::new(
+        16384,
+        FM_DEMOD_SAMPLE_RATE,
+        StreamType::FM.required_bandwidth(),
+    );
+    downsample.feed_from(&sdr_rx);
+
+    // add lowpass filter
+    let filter = blocks::Filter::new(|_, freq| {
+        if freq.abs() <= 100000.0 {
+            Complex::from(1.0)
+        } else {
+            Complex::from(0.0)
+        }
+    });
+    filter.feed_from(&downsample);
+
+    // demodulate fm signal
+    let demodulator = blocks::modulation::FmDemod::<f32>::new(150000.0);
+    demodulator.feed_from(&filter);
+
+    let pilot_capture = SampleCapture::<f32>::new(
+        ((SETTLE_TIME + PILOT_CAPTURE_TIME) * FM_DEMOD_SAMPLE_RATE) as usize,
+    );
+    pilot_capture.feed_from(&demodulator);
+
+    // keep the latest RBDS data, and when the service name last changed
+    let latest_rbds_state: Arc<Mutex<Option<(RbdsState, Instant)>>> = Arc::new(Mutex::new(None));
+    let latest_rbds_state_clone = latest_rbds_state.clone();
+    let rbds_receiver = new_rbds_receiver(
+        Arc::new(move |rbds_state: RbdsState| {
+            let mut latest_rbds_state = latest_rbds_state_clone.lock().unwrap();
+            let last_changed = match latest_rbds_state.as_ref() {
+                Some((old_state, last_changed))
+                    if old_state.service_name == rbds_state.service_name =>
+                {
+                    *last_changed
+                }
+                _ => Instant::now(),
+            };
+            *latest_rbds_state = Some((rbds_state, last_changed));
+        }),
+        |_| {},
+    );
+    rbds_receiver.feed_from(&demodulator);
+
+    let dwell_start = Instant::now();
+    sdr_rx.activate().await?;
+
+    let is_stereo = match pilot_capture.samples().await {
+        Ok((samples, sample_rate)) => {
+            let settle_samples = ((SETTLE_TIME * sample_rate) as usize).min(samples.len());
+            has_stereo_pilot(&samples[settle_samples..], sample_rate)
+        }
+        Err(err) => {
+            let _ = sdr_rx.deactivate().await;
+            return Err(err);
+        }
+    };
+
+    // wait until the station is identified, or the dwell time is over
+    while dwell_start.elapsed().as_secs_f64() < dwell_time && !shutdown_flag.load(Ordering::SeqCst)
+    {
+        let is_identified =
+            latest_rbds_state
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|(rbds_state, last_changed)| {
+                    rbds_state.pi != 0
+                        && !rbds_state.service_name.trim().is_empty()
+                        && last_changed.elapsed() >= SERVICE_NAME_STABLE_TIME
+                });
+        if is_identified {
+            break;
+        }
+
+        time::sleep(RBDS_POLL_INTERVAL).await;
+    }
+
+    let _ = sdr_rx.deactivate().await;
+
+    let rbds_state = latest_rbds_state
+        .lock()
+        .unwrap()
+        .take()
+        .map(|(rbds_state, _)| rbds_state)
+        .filter(|rbds_state| rbds_state.pi != 0);
+
+    Ok((is_stereo, rbds_state))
+}
+
+// stereo stations have a 19 kHz pilot tone in the otherwise empty space between mono and stereo audio
+fn has_stereo_pilot(samples: &[Complex<f32>], sample_rate: f64) -> bool {
+    const FFT_SIZE: usize = 8192;
+
+    let samples: Vec<FftComplex<f64>> = samples
+        .iter()
+        .map(|sample| FftComplex::new(sample.re as f64, 0.0))
+        .collect();
+    let power_spectrum = average_power_spectrum(&samples, FFT_SIZE);
+
+    let freq_to_bin = |freq: f64| ((freq / sample_rate + 0.5) * FFT_SIZE as f64).round() as usize;
+    let pilot_bin = freq_to_bin(FM_PILOT_FREQ);
+    let pilot_power = power_spectrum[pilot_bin - 1..=pilot_bin + 1]
+        .iter()
+        .copied()
+        .fold(0.0, f64::max);
+
+    // the guard band around the pilot (16 to 22 kHz), without the pilot itself
+    let mut guard_band: Vec<f64> = (freq_to_bin(16_000.0)..=freq_to_bin(22_000.0))
+        .filter(|bin| bin.abs_diff(pilot_bin) > 3)
+        .map(|bin| power_spectrum[bin])
+        .collect();
+    guard_band.sort_by(|a, b| a.total_cmp(b));
+    let noise_power = guard_band[guard_band.len() / 2];
+
+    10.0 * (pilot_power / noise_power.max(1e-20)).log10() >= MIN_PILOT_TO_NOISE
+}
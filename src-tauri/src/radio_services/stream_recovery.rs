@@ -0,0 +1,160 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use log::{info, warn};
+use radiorust::numbers::Float;
+use serde::Serialize;
+use tokio::time;
+
+use crate::{
+    error::RadioError,
+    events::EventSink,
+    radiorust_blocks::stream_monitor::{StreamHealth, StreamMonitor},
+    sdr::{
+        device::{SDRDevice, SDRRx},
+        enumeration::AvailableSDRArgs,
+        get_sdr_dev, is_sdr_available, reset_sdr_dev, SDRRegistry,
+    },
+};
+
+// how often the stream health and shutdown flag are checked
+const MONITOR_INTERVAL: Duration = Duration::from_millis(250);
+// how long the SDR can go without sending samples before the stream is restarted
+const STREAM_TIMEOUT: Duration = Duration::from_secs(3);
+// how often to check if the SDR has reappeared
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// how long to wait before reconnecting after a failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Why a streaming session ended
+pub enum SessionEnd {
+    // the service was stopped by the user, or the recording it was playing ended
+    Stopped,
+    // the stream failed and should be restarted
+    Failed(RadioError),
+    // the session can't continue, and restarting it wouldn't help (such as when there is no audio output)
+    Aborted(RadioError),
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub enum StreamRecoveryStatus {
+    // the stream stopped because of an error (such as a USB error or the SDR being unplugged)
+    StreamFailed { error: String },
+    // the SDR was released, and the service is waiting for it to be available again
+    WaitingForDevice,
+    Reconnecting { attempt: u32 },
+    // the session was restarted with the same settings
+    Recovered,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct StreamRecoveryEvent {
+    service: String,
+    status: StreamRecoveryStatus,
+}
+
+pub fn emit_recovery_status(events: &dyn EventSink, service: &str, status: StreamRecoveryStatus) {
+    info!("{} stream recovery: {:?}", service, status);
+
+    events.emit(
+        "stream_recovery_status",
+        StreamRecoveryEvent {
+            service: service.to_string(),
+            status,
+        },
+    );
+}
+
+/// Wait until the service is stopped or its stream fails, calling `on_running` while the stream is healthy.
+/// A recording that was played to the end stops the session, as if the service had been stopped.
+pub async fn monitor_stream<Flt>(
+    stream_monitor: &StreamMonitor<Flt>,
+    sdr_rx: &SDRRx,
+    shutdown_flag: &AtomicBool,
+    mut on_running: impl FnMut(),
+) -> SessionEnd
+where
+    Flt: Float,
+{
+    while !shutdown_flag.load(Ordering::SeqCst) {
+        match stream_monitor.health(STREAM_TIMEOUT) {
+            StreamHealth::Healthy => {}
+            StreamHealth::Ended if sdr_rx.has_reached_end() => {
+                info!("The recording was played to the end");
+                return SessionEnd::Stopped;
+            }
+            StreamHealth::Stalled => {
+                return SessionEnd::Failed(RadioError::Stream(String::from(
+                    "The SDR stopped sending samples",
+                )));
+            }
+            StreamHealth::Ended => {
+                return SessionEnd::Failed(RadioError::Stream(String::from(
+                    "The SDR stream ended unexpectedly",
+                )));
+            }
+        }
+
+        on_running();
+
+        time::sleep(MONITOR_INTERVAL).await;
+    }
+
+    SessionEnd::Stopped
+}
+
+/// Release an SDR whose stream failed, then wait for it to reappear and get it again.
+/// Returns `None` if the service is stopped before the SDR could be recovered.
+pub async fn recover_sdr_dev(
+    events: &dyn EventSink,
+    sdr_registry: &SDRRegistry,
+    service: &str,
+    sdr_args: &AvailableSDRArgs,
+    error: RadioError,
+    shutdown_flag: &AtomicBool,
+) -> Option<(SDRDevice, AvailableSDRArgs)> {
+    warn!(
+        "{} stream from {} failed: {}",
+        service, sdr_args.label, error
+    );
+    emit_recovery_status(
+        events,
+        service,
+        StreamRecoveryStatus::StreamFailed {
+            error: error.to_string(),
+        },
+    );
+
+    // drop the broken device, so a new one is opened when reconnecting
+    reset_sdr_dev(sdr_registry, sdr_args.clone());
+
+    emit_recovery_status(events, service, StreamRecoveryStatus::WaitingForDevice);
+
+    // the list of SDRs is kept up to date by the available SDRs callback
+    let mut attempt = 0;
+    while !shutdown_flag.load(Ordering::SeqCst) {
+        if !is_sdr_available(sdr_registry, sdr_args) {
+            time::sleep(DEVICE_POLL_INTERVAL).await;
+            continue;
+        }
+
+        attempt += 1;
+        emit_recovery_status(
+            events,
+            service,
+            StreamRecoveryStatus::Reconnecting { attempt },
+        );
+
+        match get_sdr_dev(sdr_registry, sdr_args.clone()) {
+            Ok(sdr_dev) => return Some(sdr_dev),
+            Err(err) => {
+                warn!("Could not reconnect to {}: {}", sdr_args.label, err);
+                time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+
+    None
+}
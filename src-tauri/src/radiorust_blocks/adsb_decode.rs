@@ -9,10 +9,11 @@ use radiorust::{
     prelude::{ChunkBufPool, Complex},
     signal::Signal,
 };
-use tauri::ipc::Channel;
 use tokio::{spawn, sync::Mutex, time::Instant};
 use types::ModeSState;
 
+pub type ModeSStateCallback = Arc<dyn Fn(ModeSState) + Send + Sync>;
+
 /// A custom radiorust block that saves the input stream to a wav file at the specified path. You can enabled the pass_along argument to pass along samples, so it can be between blocks.
 pub struct AdsbDecode<Flt> {
     receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
@@ -26,14 +27,14 @@ impl<Flt> AdsbDecode<Flt>
 where
     Flt: Float + Into<f64>,
 {
-    pub fn new(modes_channel: Channel<ModeSState>, pass_along: bool) -> Self {
+    /// Decode ADS-B, passing each update of the aircraft to `modes_callback`
+    pub fn new(modes_callback: ModeSStateCallback, pass_along: bool) -> Self {
         let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
         let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
 
         let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
 
         let modes_state = Arc::new(Mutex::new(ModeSState::new()));
-        let modes_channel_arc = Arc::new(Mutex::new(modes_channel));
 
         // used just for testing
         #[cfg(debug_assertions)]
@@ -44,8 +45,7 @@ where
                 #[cfg(debug_assertions)]
                 if is_first_run {
                     let mut modes_state_mut = modes_state.lock().await;
-                    let mut modes_channel_mut = modes_channel_arc.lock().await;
-                    decode_test_modes(&mut modes_channel_mut, &mut modes_state_mut).await;
+                    decode_test_modes(&modes_callback, &mut modes_state_mut).await;
                     is_first_run = false;
                 }
 
@@ -59,7 +59,7 @@ where
                     } => {
                         let input_chunk_clone = input_chunk.clone();
                         let modes_state_clone = modes_state.clone();
-                        let modes_channel_clone = modes_channel_arc.clone();
+                        let modes_callback_clone = modes_callback.clone();
                         spawn(async move {
                             let start = Instant::now();
 
@@ -104,8 +104,7 @@ where
                                 .collect();
 
                             // send update of data (whether new or not)
-                            let modes_channel_mut = modes_channel_clone.lock().await;
-                            modes_channel_mut.send(modes_state_mut.clone()).unwrap();
+                            modes_callback_clone(modes_state_mut.clone());
 
                             let duration = start.elapsed();
 
@@ -153,27 +152,8 @@ where
 }
 
 #[cfg(debug_assertions)]
-async fn decode_test_modes(modes_channel: &mut Channel<ModeSState>, modes_state: &mut ModeSState) {
-    let example_messages = [
-                  "1000110110101100010000101101111101011000101001010010001110110101001001001111110111111011100000000100111111011101",
-                  "1000110110101100010000101101111110011001000100001111011000110011101110000100000001001100010011011000101010110011",
-                  "1000110110101100010000101101111101011000101001010100011101001111000101010110110111000010011010111111100101100111",
-                  "0101110110101100010000101101111100001001001110101011100010110010110100101010101101000101111011111110111000001111",
-                  "1000110110101100010000101101111110011001000100001111011000110011101110000011110001001101010110000011000010100001",
-                  "0101110110101100001011001001110011100011110111001101000100001110101001011010111001000011000101010010010000011011",
-                  "1000110110100000010111110010000110011011000001101011011010101111000110001001010000000000110010111100001100111111", // Airborne velocity (air speed)
-                  "1000110110101100000011111111100111100001000010100011001000000000000000000000000000000000000101111101011101101000", // Aircraft Status
-                  "0101110110101100000011111111100100110010000000001011011101001001101101110010010111010001110100010111000010001101",
-                  "1000110110100000000001001110100011101010001000010100100001010101111011110101110000001000100010100010010001001001", // Target and Status Information
-                  "1000110101001000010000001101011000100000001011001100001101110001110000110010110011100000010101110110000010011000", // Aircraft Identification
-                  "1000110110101100000011111111100101011000101111110000011101100001100111010110001111001101110110111101001100001101", // Airborne position
-                  "1000110101000000011000100001110101011000110000111000011001000011010111001100010000010010011010010010101011010110", // Airborne position (CPR Odd)
-                  "1000110101000000011000100001110101011000110000111000001011010110100100001100100010101100001010000110001110100111", // Airborne position (CPR Even)
-                  "1000110101000000011000100001110101011000110000111000001011010110100100001100100010101100001010000110001110100111", // Airborne position (locally unambiguous)
-                  "1000110110100100001000111100010010011001000010011011111100011101000100000000100001011010100111000011110000101011", // Vertical Velocity
-                ];
-
-    for message in example_messages {
+async fn decode_test_modes(modes_callback: &ModeSStateCallback, modes_state: &mut ModeSState) {
+    for message in MODES_TEST_MESSAGES {
         let mut message_vec: Vec<u8> = Vec::new();
         let mut current_byte = 0u8;
         let mut bit_index = 0;
@@ -198,6 +178,6 @@ async fn decode_test_modes(modes_channel: &mut Channel<ModeSState>, modes_state:
             .collect();
         debug!("{}", vec_to_string_vec.join(""));
         decode_modes_msg(message_vec, modes_state).await;
-        modes_channel.send(modes_state.clone()).unwrap();
+        modes_callback(modes_state.clone());
     }
 }
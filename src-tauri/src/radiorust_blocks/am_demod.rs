@@ -1,3 +1,8 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
 use biquad::{self, Biquad, Coefficients, DirectForm1, ToHertz, Type, Q_BUTTERWORTH_F64};
 
 use radiorust::{
@@ -9,9 +14,36 @@ use radiorust::{
 };
 use tokio::spawn;
 
+use super::squelch::SquelchCallback;
+
+// the carrier is averaged over this, which is longer than a period of the lowest audio (in seconds)
+const CARRIER_TIME_CONSTANT: f64 = 0.05;
+// stops the AGC from endlessly amplifying silence
+const MIN_CARRIER_LEVEL: f64 = 1e-6;
+// the phase of noise is unrelated to itself after this long, while the phase of a carrier stays steady (in seconds)
+const PHASE_LAG_TIME: f64 = 0.0005;
+// keeps a carrier without any noise from having an infinite signal to noise ratio
+const MAX_PHASE_STEADINESS: f64 = 0.999_999;
+// the signal to noise ratio needs to drop this much below the threshold to close the squelch (in dB)
+const HYSTERESIS: f64 = 3.0;
+// how long the squelch stays open after the carrier goes away, so short fades aren't cut off (in seconds)
+const HANG_TIME: f64 = 0.25;
+// fade the audio in and out instead of clicking (in seconds)
+const FADE_TIME: f64 = 0.005;
+
+/// How an [`AmDemod`] demodulates the signal, which can be changed while running
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AmDemodSettings {
+    // divide the audio by the carrier level, so weak and strong stations are as loud
+    pub agc: bool,
+    // how far above the noise the carrier needs to be for audio to be played (in dB), where None is always open
+    pub squelch: Option<f64>,
+}
+
 pub struct AmDemod<Flt> {
     receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
     sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    settings: Arc<Mutex<AmDemodSettings>>,
 }
 
 impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for AmDemod<Flt> }
@@ -22,15 +54,41 @@ where
     Flt: Float + Into<f64>,
 {
     pub fn new() -> Self {
+        Self::with_settings(AmDemodSettings::default(), None)
+    }
+
+    /// `squelch_callback` is called whenever the squelch opens or closes. The squelch starts closed, and the noise is
+    /// measured from how much the phase of the carrier wanders instead of its level, so a carrier that is always on
+    /// (such as ATIS) opens it as well.
+    pub fn with_settings(
+        settings: AmDemodSettings,
+        squelch_callback: Option<SquelchCallback>,
+    ) -> Self {
         let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
         let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
 
-        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+        let settings = Arc::new(Mutex::new(settings));
 
-        let mut coeffs: Option<Coefficients<f64>> = None;
-        let mut filter: Option<DirectForm1<f64>> = None;
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
 
+        let settings_clone = settings.clone();
         spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut filter: Option<DirectForm1<f64>> = None;
+            let mut carrier_alpha: f64 = 0.0;
+            let mut phase_lag: usize = 1;
+            let mut fade_alpha: f64 = 0.0;
+            let mut hang_samples: usize = 0;
+
+            let mut carrier: f64 = MIN_CARRIER_LEVEL;
+            // the latest phases (as unit vectors), and how steady the phase is over the lag
+            let mut phase_history: VecDeque<Complex<f64>> = VecDeque::new();
+            let mut phase_steadiness: Complex<f64> = Complex::new(0.0, 0.0);
+            let mut is_open = false;
+            let mut samples_since_carrier: usize = 0;
+            let mut gain: f64 = 1.0;
+            let mut last_reported: Option<bool> = None;
+
             loop {
                 let Ok(signal) = receiver.recv().await else {
                     return;
@@ -40,37 +98,98 @@ where
                         sample_rate,
                         chunk: input_chunk,
                     } => {
-                        if coeffs.is_none() || filter.is_none() {
-                            coeffs = Some(
-                                Coefficients::<f64>::from_params(
-                                    Type::LowPass,
-                                    ToHertz::hz(sample_rate),
-                                    ToHertz::khz(15),
-                                    Q_BUTTERWORTH_F64,
-                                )
-                                .unwrap(),
-                            );
-
-                            filter = Some(DirectForm1::<f64>::new(coeffs.unwrap()));
+                        if sample_rate != current_sample_rate {
+                            current_sample_rate = sample_rate;
+
+                            // the sample rate needs to be above 30 kHz for the lowpass filter
+                            filter = Coefficients::<f64>::from_params(
+                                Type::LowPass,
+                                ToHertz::hz(sample_rate),
+                                ToHertz::khz(15),
+                                Q_BUTTERWORTH_F64,
+                            )
+                            .ok()
+                            .map(DirectForm1::<f64>::new);
+
+                            carrier_alpha =
+                                1.0 - (-1.0 / (CARRIER_TIME_CONSTANT * sample_rate)).exp();
+                            phase_lag = ((PHASE_LAG_TIME * sample_rate).round() as usize).max(1);
+                            phase_history.clear();
+                            phase_steadiness = Complex::new(0.0, 0.0);
+                            fade_alpha = 1.0 - (-1.0 / (FADE_TIME * sample_rate)).exp();
+                            hang_samples = (HANG_TIME * sample_rate) as usize;
                         }
+
+                        let settings = *settings_clone.lock().unwrap();
+
                         let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
 
                         // get the magnitude for each sample
                         for &sample in input_chunk.iter() {
                             let magnitude = AmDemod::calc_magnitude(sample);
+                            carrier += (magnitude - carrier) * carrier_alpha;
+                            carrier = carrier.max(MIN_CARRIER_LEVEL);
+
+                            // a carrier keeps turning at the same rate (even when it is slightly off frequency),
+                            // while noise turns randomly, so the average turn over the lag is shorter with more noise
+                            let phase = if magnitude > 0.0 {
+                                Complex::new(sample.re.into(), sample.im.into()) / magnitude
+                            } else {
+                                Complex::new(0.0, 0.0)
+                            };
+                            phase_history.push_back(phase);
+                            if phase_history.len() > phase_lag {
+                                let lagged_phase = phase_history.pop_front().unwrap();
+                                phase_steadiness += (phase * lagged_phase.conj()
+                                    - phase_steadiness)
+                                    * carrier_alpha;
+                            }
 
                             // center signal on 0
-                            let centered_mag = magnitude - 1.0;
+                            let centered_mag = if settings.agc {
+                                (magnitude - carrier) / carrier
+                            } else {
+                                magnitude - 1.0
+                            };
 
                             // run the lowpass filter
-                            let filtered_magnitude = filter.unwrap().run(centered_mag);
+                            let filtered_magnitude = match filter.as_mut() {
+                                Some(filter) => filter.run(centered_mag),
+                                None => centered_mag,
+                            };
+
+                            if let Some(squelch) = settings.squelch {
+                                // the steadiness is the share of the signal that is carrier instead of noise
+                                let steadiness = phase_steadiness.norm().min(MAX_PHASE_STEADINESS);
+                                let snr_db = 10.0 * (steadiness / (1.0 - steadiness)).log10();
+
+                                if snr_db > squelch {
+                                    is_open = true;
+                                    samples_since_carrier = 0;
+                                } else if snr_db < squelch - HYSTERESIS {
+                                    samples_since_carrier += 1;
+                                    if samples_since_carrier > hang_samples {
+                                        is_open = false;
+                                    }
+                                }
+                            } else {
+                                is_open = true;
+                            }
+                            gain += ((is_open as u8 as f64) - gain) * fade_alpha;
 
                             output_chunk.push(Complex {
-                                re: Flt::from(filtered_magnitude).unwrap(),
+                                re: Flt::from(filtered_magnitude * gain).unwrap(),
                                 im: Flt::from(0.0).unwrap(),
                             });
                         }
 
+                        if let Some(squelch_callback) = &squelch_callback {
+                            if last_reported != Some(is_open) {
+                                squelch_callback(is_open);
+                                last_reported = Some(is_open);
+                            }
+                        }
+
                         let Ok(()) = sender
                             .send(Signal::Samples {
                                 sample_rate,
@@ -92,9 +211,14 @@ where
         Self {
             receiver_connector,
             sender_connector,
+            settings,
         }
     }
 
+    pub fn set_settings(&self, settings: AmDemodSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
     fn calc_magnitude(c: Complex<Flt>) -> f64 {
         (c.re.powi(2) + c.im.powi(2)).sqrt().into()
     }
@@ -0,0 +1,107 @@
+use radiorust::{
+    flow::{new_receiver, new_sender, Receiver, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use tokio::spawn;
+
+/// One input of an [`AudioMixer`]
+pub struct AudioMixerInput<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for AudioMixerInput<Flt> }
+
+/// A custom radiorust block that adds together the audio of several streams, such as the channels of a
+/// [`Channelizer`](super::channelizer::Channelizer). Every input needs to have the same sample rate and chunk sizes,
+/// since a chunk is read from each of them in turn.
+pub struct AudioMixer<Flt> {
+    inputs: Vec<AudioMixerInput<Flt>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for AudioMixer<Flt> }
+
+impl<Flt> AudioMixer<Flt>
+where
+    Flt: Float,
+{
+    pub fn new(num_inputs: usize) -> Self {
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let mut inputs = vec![];
+        let mut receivers: Vec<Receiver<Signal<Complex<Flt>>>> = vec![];
+        for _ in 0..num_inputs {
+            let (receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+            inputs.push(AudioMixerInput { receiver_connector });
+            receivers.push(receiver);
+        }
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        spawn(async move {
+            let mut mixed: Vec<Complex<Flt>> = vec![];
+
+            loop {
+                let mut mixed_sample_rate = None;
+                mixed.clear();
+
+                for receiver in receivers.iter_mut() {
+                    // skip events, so one chunk of samples is read from every input
+                    let (sample_rate, chunk) = loop {
+                        let Ok(signal) = receiver.recv().await else {
+                            return;
+                        };
+                        match signal {
+                            Signal::Samples { sample_rate, chunk } => break (sample_rate, chunk),
+                            Signal::Event(event) => {
+                                let Ok(()) = sender.send(Signal::Event(event)).await else {
+                                    return;
+                                };
+                            }
+                        }
+                    };
+
+                    if mixed_sample_rate.is_none() {
+                        mixed_sample_rate = Some(sample_rate);
+                        mixed.extend(chunk.iter());
+                    } else {
+                        // the chunks should be the same size, but anything extra is dropped just in case
+                        mixed.truncate(chunk.len());
+                        for (mixed_sample, sample) in mixed.iter_mut().zip(chunk.iter()) {
+                            *mixed_sample = *mixed_sample + *sample;
+                        }
+                    }
+                }
+
+                let Some(sample_rate) = mixed_sample_rate else {
+                    return;
+                };
+
+                let mut output_chunk = buf_pool.get_with_capacity(mixed.len());
+                output_chunk.extend(mixed.iter());
+
+                let Ok(()) = sender
+                    .send(Signal::Samples {
+                        sample_rate,
+                        chunk: output_chunk.finalize(),
+                    })
+                    .await
+                else {
+                    return;
+                };
+            }
+        });
+
+        Self {
+            inputs,
+            sender_connector,
+        }
+    }
+
+    pub fn inputs(&self) -> &[AudioMixerInput<Flt>] {
+        &self.inputs
+    }
+}
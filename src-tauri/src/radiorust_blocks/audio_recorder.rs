@@ -0,0 +1,328 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use log::info;
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use tokio::spawn;
+
+use super::recording::{stop_failed_recording, to_i16, RecordingErrorCallback};
+use crate::audio_file::{AudioFileFormat, AudioFileWriter, AudioTags};
+
+// a new title only starts a new track once the current one is at least this long, so short RadioText
+// messages (such as ads) don't each get their own file (in seconds)
+const MIN_TRACK_LENGTH: f64 = 30.0;
+
+/// How the channels of the audio are laid out in the samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordingChannels {
+    // in the real part of each sample
+    Mono,
+    // left in the real part and right in the imaginary part of each sample
+    Stereo,
+    // alternating samples for the left and right channels, such as from the HD Radio decoder
+    Interleaved,
+}
+
+struct AudioRecording {
+    path: PathBuf,
+    format: AudioFileFormat,
+    split_tracks: bool,
+    track_number: u32,
+    writer: AudioFileWriter,
+    // the tags of the track being written
+    tags: AudioTags,
+}
+
+/// A handle to start and stop recording the audio of an [`AudioRecorderBlock`], and to tag it with what the station
+/// is playing. It can be kept between blocks, so a recording continues after the SDR is reconnected.
+#[derive(Clone)]
+pub struct AudioRecorder {
+    recording: Arc<Mutex<Option<AudioRecording>>>,
+    // the latest tags, which a new recording starts with
+    tags: Arc<Mutex<AudioTags>>,
+    sample_rate: u32,
+    channels: RecordingChannels,
+    error_callback: RecordingErrorCallback,
+}
+
+impl AudioRecorder {
+    pub fn new(
+        sample_rate: f64,
+        channels: RecordingChannels,
+        error_callback: RecordingErrorCallback,
+    ) -> Self {
+        Self {
+            recording: Arc::new(Mutex::new(None)),
+            tags: Arc::new(Mutex::new(AudioTags::default())),
+            sample_rate: sample_rate as u32,
+            channels,
+            error_callback,
+        }
+    }
+
+    /// Start recording to `path`, where the format is picked from its extension if it isn't given. If `split_tracks`
+    /// is enabled, a new file (ending with the track number) is started whenever the title changes.
+    pub fn start(
+        &self,
+        mut path: PathBuf,
+        format: Option<AudioFileFormat>,
+        split_tracks: bool,
+    ) -> Result<(), String> {
+        let mut recording = self.recording.lock().unwrap();
+        if recording.is_some() {
+            return Err(String::from("Already recording"));
+        }
+
+        let format = format.unwrap_or(AudioFileFormat::from_path(&path));
+        if path.extension().is_none() {
+            path.set_extension(format.extension());
+        }
+        let writer = self.create_writer(&path, format)?;
+
+        info!("Started audio recording to {}", path.display());
+
+        *recording = Some(AudioRecording {
+            path,
+            format,
+            split_tracks,
+            track_number: 1,
+            writer,
+            tags: self.tags.lock().unwrap().clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Stop recording and save the tags of the last track
+    pub fn stop(&self) -> Result<(), String> {
+        let Some(recording) = self.recording.lock().unwrap().take() else {
+            return Err(String::from("Not recording"));
+        };
+
+        AudioRecorder::finish(recording)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.lock().unwrap().is_some()
+    }
+
+    // save the tags of a recording that was taken out of the recorder
+    fn finish(recording: AudioRecording) -> Result<(), String> {
+        let frames_written = recording.writer.frames_written();
+        recording
+            .writer
+            .finish(&recording.tags)
+            .map_err(|err| format!("Could not save audio recording: {}", err))?;
+
+        info!(
+            "Saved audio recording with {} samples to {}",
+            frames_written,
+            recording.path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Change the tags of the recording, such as when RBDS or HD Radio metadata arrives
+    pub fn update_tags(&self, update: impl FnOnce(&mut AudioTags)) {
+        let tags = {
+            let mut tags = self.tags.lock().unwrap();
+            let old_tags = tags.clone();
+            update(&mut tags);
+            if *tags == old_tags {
+                return;
+            }
+            tags.clone()
+        };
+
+        let mut recording_locked = self.recording.lock().unwrap();
+        let Some(recording) = recording_locked.as_mut() else {
+            return;
+        };
+
+        let track_length = recording.writer.frames_written() as f64 / self.sample_rate as f64;
+        if recording.split_tracks
+            && tags.title != recording.tags.title
+            && track_length >= MIN_TRACK_LENGTH
+        {
+            if let Err(err) = self.next_track(recording, &tags) {
+                let recording = recording_locked.take().unwrap();
+                drop(recording_locked);
+                stop_failed_recording(
+                    recording,
+                    format!(
+                        "Failed to start the next track of the audio recording: {}",
+                        err
+                    ),
+                    AudioRecorder::finish,
+                    &self.error_callback,
+                );
+                return;
+            }
+        }
+        recording.tags = tags;
+    }
+
+    // save the current track, and continue the recording in a new file
+    fn next_track(&self, recording: &mut AudioRecording, tags: &AudioTags) -> Result<(), String> {
+        recording.track_number += 1;
+        let path = AudioRecorder::track_path(&recording.path, recording.track_number);
+        let writer = self.create_writer(&path, recording.format)?;
+
+        let finished_writer = std::mem::replace(&mut recording.writer, writer);
+        finished_writer
+            .finish(&recording.tags)
+            .map_err(|err| format!("Could not save audio recording: {}", err))?;
+
+        info!(
+            "Started track {} of the audio recording ({})",
+            recording.track_number,
+            tags.title.as_deref().unwrap_or("no title")
+        );
+
+        Ok(())
+    }
+
+    fn create_writer(
+        &self,
+        path: &Path,
+        format: AudioFileFormat,
+    ) -> Result<AudioFileWriter, String> {
+        let channels = match self.channels {
+            RecordingChannels::Mono => 1,
+            RecordingChannels::Stereo | RecordingChannels::Interleaved => 2,
+        };
+        AudioFileWriter::create(path, format, self.sample_rate, channels)
+            .map_err(|err| format!("Could not create {}: {}", path.display(), err))
+    }
+
+    // the first track uses the path that was given, and the ones after it have their number added
+    fn track_path(path: &Path, track_number: u32) -> PathBuf {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut track_path = path.with_file_name(format!("{} ({})", stem, track_number));
+        if let Some(extension) = path.extension() {
+            track_path.set_extension(extension);
+        }
+        track_path
+    }
+}
+
+/// A custom radiorust block that passes along demodulated audio and records it while a recording is started through
+/// its [`AudioRecorder`].
+pub struct AudioRecorderBlock<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for AudioRecorderBlock<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for AudioRecorderBlock<Flt> }
+
+impl<Flt> AudioRecorderBlock<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(recorder: AudioRecorder) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        spawn(async move {
+            // the left sample of interleaved audio, while waiting for the right one
+            let mut left_sample: Option<i16> = None;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        let failed_recording = {
+                            let mut recording_locked = recorder.recording.lock().unwrap();
+                            if let Some(recording) = recording_locked.as_mut() {
+                                let mut write_result = Ok(());
+                                for sample in input_chunk.iter() {
+                                    let left = to_i16(sample.re);
+                                    write_result = match recorder.channels {
+                                        RecordingChannels::Mono => {
+                                            recording.writer.write_frame(&[left])
+                                        }
+                                        RecordingChannels::Stereo => {
+                                            recording.writer.write_frame(&[left, to_i16(sample.im)])
+                                        }
+                                        RecordingChannels::Interleaved => {
+                                            match left_sample.take() {
+                                                Some(previous) => {
+                                                    recording.writer.write_frame(&[previous, left])
+                                                }
+                                                None => {
+                                                    left_sample = Some(left);
+                                                    Ok(())
+                                                }
+                                            }
+                                        }
+                                    };
+                                    if write_result.is_err() {
+                                        break;
+                                    }
+                                }
+
+                                write_result.err().and_then(|err| {
+                                    recording_locked.take().map(|recording| {
+                                        (
+                                            recording,
+                                            format!("Failed to write audio recording: {}", err),
+                                        )
+                                    })
+                                })
+                            } else {
+                                left_sample = None;
+                                None
+                            }
+                        };
+                        if let Some((recording, err)) = failed_recording {
+                            stop_failed_recording(
+                                recording,
+                                err,
+                                AudioRecorder::finish,
+                                &recorder.error_callback,
+                            );
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: input_chunk,
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+        }
+    }
+}
@@ -22,6 +22,7 @@ use std::fmt;
 
 #[derive(Debug)]
 enum ErrorVariant {
+    NoDevice,
     BuildStreamInvalidArgument(&'static str),
     BuildStreamDriverError(cpal::BuildStreamError),
     PlayStreamDriverError(cpal::PlayStreamError),
@@ -36,6 +37,7 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use ErrorVariant::*;
         match &self.0 {
+            NoDevice => write!(f, "no audio output device available"),
             BuildStreamInvalidArgument(s) => {
                 write!(f, "invalid argument when opening audio device: {s}")
             }
@@ -50,6 +52,7 @@ impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         use ErrorVariant::*;
         match &self.0 {
+            NoDevice => None,
             BuildStreamInvalidArgument(_) => None,
             BuildStreamDriverError(inner) => Some(inner),
             PlayStreamDriverError(inner) => Some(inner),
@@ -126,8 +129,12 @@ impl AudioPlayer {
         channels: u16,
         virtual_channels: Option<bool>,
     ) -> Result<Self, Error> {
+        // return an error instead of panicking, so the stream can report it
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or(Error(ErrorVariant::NoDevice))?;
         Self::with_device(
-            &default_output_device(),
+            &device,
             sample_rate,
             buffer_size,
             channels,
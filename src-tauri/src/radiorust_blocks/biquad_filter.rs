@@ -0,0 +1,177 @@
+use std::sync::{Arc, Mutex};
+
+use biquad::{Biquad, Coefficients, DirectForm1, ToHertz, Type, Q_BUTTERWORTH_F64};
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use tokio::spawn;
+
+/// The Q of each biquad in a 4th order Butterworth filter
+pub const BUTTERWORTH_4_Q: [f64; 2] = [0.541_196_1, 1.306_563];
+
+/// What a [`BiquadFilter`] does, where each stage is skipped if it isn't set
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BiquadFilterSettings {
+    // time constant of the de-emphasis (in seconds)
+    pub deemphasis: Option<f64>,
+    // frequencies below this are removed (in hz)
+    pub highpass: Option<f64>,
+    // frequencies above this are removed (in hz)
+    pub lowpass: Option<f64>,
+}
+
+// the filters for one part of the samples
+struct ChannelFilter {
+    deemphasis_alpha: Option<f64>,
+    deemphasis_value: f64,
+    highpass: Option<DirectForm1<f64>>,
+    lowpass: Vec<DirectForm1<f64>>,
+}
+
+impl ChannelFilter {
+    fn new(settings: &BiquadFilterSettings, sample_rate: f64) -> Self {
+        let nyquist = sample_rate / 2.0;
+        let biquad = |filter_type: Type<f64>, freq: f64, q: f64| {
+            Coefficients::<f64>::from_params(
+                filter_type,
+                ToHertz::hz(sample_rate),
+                ToHertz::hz(freq),
+                q,
+            )
+            .ok()
+            .map(DirectForm1::<f64>::new)
+        };
+
+        Self {
+            deemphasis_alpha: settings
+                .deemphasis
+                .map(|time_constant| 1.0 - (-1.0 / (time_constant * sample_rate)).exp()),
+            deemphasis_value: 0.0,
+            highpass: settings
+                .highpass
+                .filter(|&freq| freq > 0.0 && freq < nyquist)
+                .and_then(|freq| biquad(Type::HighPass, freq, Q_BUTTERWORTH_F64)),
+            // a lowpass at or above the Nyquist frequency wouldn't do anything
+            lowpass: settings
+                .lowpass
+                .filter(|&freq| freq > 0.0 && freq < nyquist)
+                .map(|freq| {
+                    BUTTERWORTH_4_Q
+                        .iter()
+                        .filter_map(|&q| biquad(Type::LowPass, freq, q))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn run(&mut self, mut value: f64) -> f64 {
+        if let Some(alpha) = self.deemphasis_alpha {
+            self.deemphasis_value += (value - self.deemphasis_value) * alpha;
+            value = self.deemphasis_value;
+        }
+        if let Some(highpass) = self.highpass.as_mut() {
+            value = highpass.run(value);
+        }
+        for lowpass in self.lowpass.iter_mut() {
+            value = lowpass.run(value);
+        }
+        value
+    }
+}
+
+/// A custom radiorust block that applies de-emphasis, a highpass, and a lowpass filter, which can all be changed while
+/// running. The real and imaginary parts are filtered separately, so it works for both stereo audio (from
+/// [`FmStereoDecode`](super::fm_stereo_decode::FmStereoDecode)) and IQ samples, where the lowpass is half the bandwidth.
+pub struct BiquadFilter<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    settings: Arc<Mutex<BiquadFilterSettings>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for BiquadFilter<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for BiquadFilter<Flt> }
+
+impl<Flt> BiquadFilter<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(settings: BiquadFilterSettings) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let settings = Arc::new(Mutex::new(settings));
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        let settings_clone = settings.clone();
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut current_settings: Option<BiquadFilterSettings> = None;
+            let mut filters: Option<(ChannelFilter, ChannelFilter)> = None;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        // the coefficients depend on both the settings and the sample rate
+                        let settings = *settings_clone.lock().unwrap();
+                        if sample_rate != current_sample_rate || current_settings != Some(settings)
+                        {
+                            current_sample_rate = sample_rate;
+                            current_settings = Some(settings);
+                            filters = Some((
+                                ChannelFilter::new(&settings, sample_rate),
+                                ChannelFilter::new(&settings, sample_rate),
+                            ));
+                        }
+                        let (re_filter, im_filter) = filters.as_mut().unwrap();
+
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+
+                        for sample in input_chunk.iter() {
+                            output_chunk.push(Complex::new(
+                                Flt::from_f64(re_filter.run(sample.re.into())).unwrap(),
+                                Flt::from_f64(im_filter.run(sample.im.into())).unwrap(),
+                            ));
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+            settings,
+        }
+    }
+
+    pub fn set_settings(&self, settings: BiquadFilterSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+}
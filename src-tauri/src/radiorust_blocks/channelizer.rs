@@ -0,0 +1,230 @@
+use std::f64::consts::{PI, TAU};
+
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, Sender, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use tokio::spawn;
+
+// how many filter taps to use per ratio of the input sample rate to the channel bandwidth
+const TAPS_PER_BANDWIDTH_RATIO: f64 = 4.0;
+const MIN_FILTER_TAPS: usize = 16;
+const MAX_FILTER_TAPS: usize = 256;
+
+/// One narrowband output of a [`Channelizer`]
+pub struct ChannelizerOutput<Flt> {
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    offset: f64,
+}
+
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for ChannelizerOutput<Flt> }
+
+impl<Flt> ChannelizerOutput<Flt> {
+    /// The offset of the channel from the center frequency (in hz)
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+}
+
+/// A custom radiorust block that splits a wideband stream into narrowband channels, each at an offset from the center frequency.
+/// Every channel is mixed down to 0hz, low-pass filtered to its bandwidth, and decimated, so it can be demodulated on its own.
+pub struct Channelizer<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    outputs: Vec<ChannelizerOutput<Flt>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for Channelizer<Flt> }
+
+struct ChannelState<Flt> {
+    sender: Sender<Signal<Complex<Flt>>>,
+    offset: f64,
+    // oscillator that mixes the channel down to 0hz
+    oscillator: Complex<f64>,
+    oscillator_step: Complex<f64>,
+    // the most recent mixed samples, used by the filter
+    history: Vec<Complex<f64>>,
+    history_index: usize,
+    samples_until_output: usize,
+    is_closed: bool,
+}
+
+impl<Flt> Channelizer<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    /// Create a channel for each offset (in hz). Channels are decimated to the lowest
+    /// sample rate that is at least `min_sample_rate`.
+    pub fn new(offsets: Vec<f64>, channel_bandwidth: f64, min_sample_rate: f64) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+
+        let mut outputs = vec![];
+        let mut channels = vec![];
+        for offset in offsets {
+            let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+            outputs.push(ChannelizerOutput {
+                sender_connector,
+                offset,
+            });
+            channels.push(ChannelState {
+                sender,
+                offset,
+                oscillator: Complex::new(1.0, 0.0),
+                oscillator_step: Complex::new(1.0, 0.0),
+                history: vec![],
+                history_index: 0,
+                samples_until_output: 0,
+                is_closed: false,
+            });
+        }
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut filter_taps: Vec<f64> = vec![];
+            let mut decimation = 1;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        // the filter and oscillators depend on the sample rate
+                        if sample_rate != current_sample_rate {
+                            current_sample_rate = sample_rate;
+                            decimation = ((sample_rate / min_sample_rate).floor() as usize).max(1);
+                            filter_taps =
+                                Channelizer::<Flt>::lowpass_taps(sample_rate, channel_bandwidth);
+
+                            for channel in channels.iter_mut() {
+                                channel.oscillator = Complex::new(1.0, 0.0);
+                                channel.oscillator_step =
+                                    Complex::from_polar(1.0, -TAU * channel.offset / sample_rate);
+                                channel.history = vec![Complex::new(0.0, 0.0); filter_taps.len()];
+                                channel.history_index = 0;
+                                channel.samples_until_output = decimation;
+                            }
+                        }
+
+                        let input: Vec<Complex<f64>> = input_chunk
+                            .iter()
+                            .map(|sample| Complex::new(sample.re.into(), sample.im.into()))
+                            .collect();
+
+                        for channel in channels.iter_mut() {
+                            if channel.is_closed {
+                                continue;
+                            }
+
+                            let mut output_chunk =
+                                buf_pool.get_with_capacity(input.len() / decimation + 1);
+
+                            for sample in input.iter() {
+                                channel.history[channel.history_index] =
+                                    *sample * channel.oscillator;
+                                channel.oscillator *= channel.oscillator_step;
+                                channel.history_index =
+                                    (channel.history_index + 1) % channel.history.len();
+
+                                // only calculate the filtered samples that are kept
+                                channel.samples_until_output -= 1;
+                                if channel.samples_until_output > 0 {
+                                    continue;
+                                }
+                                channel.samples_until_output = decimation;
+
+                                // the oldest sample is at the current index
+                                let history_len = channel.history.len();
+                                let filtered: Complex<f64> = filter_taps
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, tap)| {
+                                        channel.history[(channel.history_index + i) % history_len]
+                                            * *tap
+                                    })
+                                    .sum();
+
+                                output_chunk.push(Complex::new(
+                                    Flt::from_f64(filtered.re).unwrap(),
+                                    Flt::from_f64(filtered.im).unwrap(),
+                                ));
+                            }
+
+                            // keep rounding errors from changing the oscillator's amplitude
+                            channel.oscillator /= channel.oscillator.norm();
+
+                            let result = channel
+                                .sender
+                                .send(Signal::Samples {
+                                    sample_rate: sample_rate / decimation as f64,
+                                    chunk: output_chunk.finalize(),
+                                })
+                                .await;
+                            if result.is_err() {
+                                channel.is_closed = true;
+                            }
+                        }
+
+                        if channels.iter().all(|channel| channel.is_closed) {
+                            return;
+                        }
+                    }
+                    Signal::Event(event) => {
+                        for channel in channels.iter_mut().filter(|channel| !channel.is_closed) {
+                            if channel
+                                .sender
+                                .send(Signal::Event(event.clone()))
+                                .await
+                                .is_err()
+                            {
+                                channel.is_closed = true;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            outputs,
+        }
+    }
+
+    pub fn channels(&self) -> &[ChannelizerOutput<Flt>] {
+        &self.outputs
+    }
+
+    // windowed-sinc low-pass filter that keeps half of the bandwidth on each side of 0hz
+    fn lowpass_taps(sample_rate: f64, bandwidth: f64) -> Vec<f64> {
+        let num_taps = ((TAPS_PER_BANDWIDTH_RATIO * sample_rate / bandwidth).round() as usize)
+            .clamp(MIN_FILTER_TAPS, MAX_FILTER_TAPS);
+        let cutoff = (bandwidth / 2.0) / sample_rate;
+        let center = (num_taps - 1) as f64 / 2.0;
+
+        let taps: Vec<f64> = (0..num_taps)
+            .map(|i| {
+                let n = i as f64 - center;
+                let sinc = if n == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (TAU * cutoff * n).sin() / (PI * n)
+                };
+                // hamming window
+                let window = 0.54 - 0.46 * (TAU * i as f64 / (num_taps - 1) as f64).cos();
+                sinc * window
+            })
+            .collect();
+
+        // don't change the level of the signal
+        let gain: f64 = taps.iter().sum();
+        taps.into_iter().map(|tap| tap / gain).collect()
+    }
+}
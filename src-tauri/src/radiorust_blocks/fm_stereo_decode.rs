@@ -0,0 +1,388 @@
+use std::{f64::consts::TAU, sync::Arc};
+
+use biquad::{Biquad, Coefficients, DirectForm1, ToHertz, Type};
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use serde::Serialize;
+use tokio::spawn;
+
+const PILOT_FREQ: f64 = 19_000.0;
+// the pilot must be within 20 hz of 19 kHz, so the PLL doesn't need to search much further (in hz)
+const PILOT_MAX_ERROR: f64 = 20.0;
+// quality of the bandpass filters that separate the pilot from the audio around it, where two are used so the L-R
+// sidebands around 38 kHz aren't mistaken for noise
+const PILOT_FILTER_Q: f64 = 10.0;
+// bandwidth of the PLL that locks onto the pilot (in hz)
+const PLL_BANDWIDTH: f64 = 10.0;
+// how long the pilot level and noise are averaged over (in seconds)
+const LEVEL_TIME_CONSTANT: f64 = 0.1;
+// how quickly the audio moves between mono and stereo (in seconds)
+const BLEND_TIME_CONSTANT: f64 = 0.5;
+// pilot to noise ratios (in dB) below which the audio is mono, and above which it is fully stereo
+const MONO_PILOT_SNR: f64 = 20.0;
+const STEREO_PILOT_SNR: f64 = 35.0;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FmStereoState {
+    // the station is stereo, and its pilot is strong enough to decode it
+    pub is_stereo: bool,
+    // how much of the stereo audio is used, from 0 (mono) to 1 (full stereo)
+    pub blend: f32,
+}
+
+pub type FmStereoStateCallback = Arc<dyn Fn(FmStereoState) + Send + Sync>;
+
+/// A custom radiorust block that decodes stereo audio from a demodulated FM signal (the multiplex signal).
+/// A PLL locks onto the 19 kHz pilot, which is doubled to demodulate the L-R signal at 38 kHz.
+///
+/// The left channel is output in the real part of each sample and the right channel in the imaginary part,
+/// so filters and downsamplers treat them independently. Everything above 15 kHz (such as the pilot and RBDS)
+/// is left in both channels, so the audio must be lowpass filtered afterwards.
+pub struct FmStereoDecode<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for FmStereoDecode<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for FmStereoDecode<Flt> }
+
+impl<Flt> FmStereoDecode<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    /// `stereo_callback` is called whenever the station switches between mono and stereo, or the blend changes
+    pub fn new(stereo_callback: FmStereoStateCallback) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut pilot_filters: Option<Vec<DirectForm1<f64>>> = None;
+            let mut level_alpha: f64 = 0.0;
+            let mut blend_alpha: f64 = 0.0;
+
+            // PLL state, in radians and radians per sample
+            let mut pll_phase: f64 = 0.0;
+            let mut pll_freq: f64 = 0.0;
+            let mut pll_min_freq: f64 = 0.0;
+            let mut pll_max_freq: f64 = 0.0;
+            let mut pll_alpha: f64 = 0.0;
+            let mut pll_beta: f64 = 0.0;
+
+            // the pilot amplitude measured in phase with the PLL, and the power of everything around the pilot
+            let mut pilot_amplitude: f64 = 0.0;
+            let mut pilot_band_power: f64 = 0.0;
+
+            let mut blend: f64 = 0.0;
+            let mut last_state: Option<FmStereoState> = None;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        // the filter and loop depend on the sample rate
+                        if sample_rate != current_sample_rate {
+                            current_sample_rate = sample_rate;
+
+                            // the subcarrier needs to be below the Nyquist frequency
+                            pilot_filters = if sample_rate > 4.0 * PILOT_FREQ {
+                                Coefficients::<f64>::from_params(
+                                    Type::BandPass,
+                                    ToHertz::hz(sample_rate),
+                                    ToHertz::hz(PILOT_FREQ),
+                                    PILOT_FILTER_Q,
+                                )
+                                .ok()
+                                .map(|coefficients| vec![DirectForm1::<f64>::new(coefficients); 2])
+                            } else {
+                                None
+                            };
+
+                            level_alpha = 1.0 - (-1.0 / (LEVEL_TIME_CONSTANT * sample_rate)).exp();
+                            blend_alpha = 1.0 - (-1.0 / (BLEND_TIME_CONSTANT * sample_rate)).exp();
+
+                            // a second-order loop with a damping of 0.707, where the phase detector has a gain of 0.5
+                            let natural_freq = TAU * PLL_BANDWIDTH / sample_rate;
+                            pll_alpha = 2.0 * 0.707 * natural_freq / 0.5;
+                            pll_beta = natural_freq * natural_freq / 0.5;
+                            pll_freq = TAU * PILOT_FREQ / sample_rate;
+                            pll_min_freq = TAU * (PILOT_FREQ - PILOT_MAX_ERROR) / sample_rate;
+                            pll_max_freq = TAU * (PILOT_FREQ + PILOT_MAX_ERROR) / sample_rate;
+                            pll_phase = 0.0;
+
+                            pilot_amplitude = 0.0;
+                            pilot_band_power = 0.0;
+                            blend = 0.0;
+                        }
+
+                        // the sample rate is too low for the subcarrier, so there is no stereo to decode
+                        let Some(filters) = pilot_filters.as_mut() else {
+                            let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+                            for sample in input_chunk.iter() {
+                                output_chunk.push(Complex::new(sample.re, sample.re));
+                            }
+
+                            let Ok(()) = sender
+                                .send(Signal::Samples {
+                                    sample_rate,
+                                    chunk: output_chunk.finalize(),
+                                })
+                                .await
+                            else {
+                                return;
+                            };
+                            continue;
+                        };
+
+                        // only use stereo if the pilot is strong enough compared to the noise around it
+                        let pilot_power = pilot_amplitude.max(0.0).powi(2) / 2.0;
+                        let noise_power = (pilot_band_power - pilot_power).max(f64::EPSILON);
+                        let pilot_snr = 10.0 * (pilot_power / noise_power).log10();
+                        let target_blend = ((pilot_snr - MONO_PILOT_SNR)
+                            / (STEREO_PILOT_SNR - MONO_PILOT_SNR))
+                            .clamp(0.0, 1.0);
+
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+
+                        for sample in input_chunk.iter() {
+                            let mpx: f64 = sample.re.into();
+
+                            let pilot = filters
+                                .iter_mut()
+                                .fold(mpx, |value, filter| filter.run(value));
+                            let (pll_sin, pll_cos) = pll_phase.sin_cos();
+
+                            // the error is normalized, so the loop works the same for any pilot level
+                            pilot_band_power += (pilot * pilot - pilot_band_power) * level_alpha;
+                            let pilot_level = (2.0 * pilot_band_power).sqrt().max(f64::EPSILON);
+                            let phase_error = pilot * pll_cos / pilot_level;
+
+                            pilot_amplitude +=
+                                (2.0 * pilot * pll_sin - pilot_amplitude) * level_alpha;
+
+                            pll_freq = (pll_freq + pll_beta * phase_error)
+                                .clamp(pll_min_freq, pll_max_freq);
+                            pll_phase = (pll_phase + pll_freq + pll_alpha * phase_error) % TAU;
+
+                            // mix L-R down from the subcarrier, which is locked to twice the pilot
+                            blend += (target_blend - blend) * blend_alpha;
+                            let difference = 2.0 * mpx * (2.0 * pll_sin * pll_cos) * blend;
+
+                            // mpx contains (L+R)/2 and difference contains (L-R)/2
+                            output_chunk.push(Complex::new(
+                                Flt::from_f64(mpx + difference).unwrap(),
+                                Flt::from_f64(mpx - difference).unwrap(),
+                            ));
+                        }
+
+                        // only report changes that can be seen, instead of after every chunk
+                        let state = FmStereoState {
+                            is_stereo: target_blend > 0.0,
+                            blend: ((blend * 10.0).round() / 10.0) as f32,
+                        };
+                        if last_state.as_ref() != Some(&state) {
+                            stereo_callback(state.clone());
+                            last_state = Some(state);
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+        }
+    }
+}
+
+/// A custom radiorust block that turns the two channels from [`FmStereoDecode`] (left in the real part and
+/// right in the imaginary part) into alternating samples for each channel, which is what the
+/// `AudioPlayer` expects when `virtual_channels` is disabled.
+pub struct StereoInterleave<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for StereoInterleave<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for StereoInterleave<Flt> }
+
+impl<Flt> StereoInterleave<Flt>
+where
+    Flt: Float,
+{
+    pub fn new() -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        spawn(async move {
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len() * 2);
+
+                        for sample in input_chunk.iter() {
+                            output_chunk.push(Complex::new(sample.re, Flt::zero()));
+                            output_chunk.push(Complex::new(sample.im, Flt::zero()));
+                        }
+
+                        // the sample rate is still per channel
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Mutex};
+
+    use radiorust::prelude::*;
+
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 256_000.0;
+    const TONE_FREQ: f64 = 1_000.0;
+
+    // the amplitude of the tone in one channel, over a whole number of periods
+    fn tone_amplitude(channel: &[f64]) -> f64 {
+        let (sum_sin, sum_cos) =
+            channel
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(sum_sin, sum_cos), (index, value)| {
+                    let (sin, cos) = (TAU * TONE_FREQ * index as f64 / SAMPLE_RATE).sin_cos();
+                    (sum_sin + value * sin, sum_cos + value * cos)
+                });
+        2.0 * sum_sin.hypot(sum_cos) / channel.len() as f64
+    }
+
+    #[test]
+    fn separates_left_from_right() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let (state_sender, state_receiver) = mpsc::channel();
+        let state_sender = Mutex::new(state_sender);
+        let decoder = FmStereoDecode::<f32>::new(Arc::new(move |state| {
+            let _ = state_sender.lock().unwrap().send(state);
+        }));
+
+        let (sender, sender_connector) = new_sender::<Signal<Complex<f32>>>();
+        decoder.feed_from(&sender_connector);
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<f32>>>();
+        receiver_connector.feed_from(&decoder);
+
+        // a tone in only the left channel, with the pilot at 10% (where the pilot isn't in phase with the PLL)
+        let total_samples = (SAMPLE_RATE * 4.0) as usize;
+        spawn(async move {
+            let mut buf_pool = ChunkBufPool::<Complex<f32>>::new();
+            for start in (0..total_samples).step_by(4096) {
+                let mut chunk = buf_pool.get_with_capacity(4096);
+                for index in start..(start + 4096).min(total_samples) {
+                    let time = index as f64 / SAMPLE_RATE;
+                    let left = 0.5 * (TAU * TONE_FREQ * time).sin();
+                    let pilot_phase = TAU * PILOT_FREQ * time + 0.3;
+                    let mpx = left / 2.0
+                        + left / 2.0 * (2.0 * pilot_phase).sin()
+                        + 0.1 * pilot_phase.sin();
+                    chunk.push(Complex::new(mpx as f32, 0.0));
+                }
+                let Ok(()) = sender
+                    .send(Signal::Samples {
+                        sample_rate: SAMPLE_RATE,
+                        chunk: chunk.finalize(),
+                    })
+                    .await
+                else {
+                    return;
+                };
+            }
+        });
+
+        let mut left = vec![];
+        let mut right = vec![];
+        while left.len() < total_samples {
+            let Ok(Signal::Samples { chunk, .. }) = rt.block_on(receiver.recv()) else {
+                continue;
+            };
+            for sample in chunk.iter() {
+                left.push(sample.re as f64);
+                right.push(sample.im as f64);
+            }
+        }
+
+        // the pilot is locked, so the audio is fully stereo by the end
+        let state = state_receiver.try_iter().last().unwrap();
+        assert!(state.is_stereo);
+        assert!(state.blend >= 0.9, "blend was {}", state.blend);
+
+        // only look at the last second, once the audio has moved to stereo
+        let last_second = total_samples - SAMPLE_RATE as usize;
+        let left_level = tone_amplitude(&left[last_second..]);
+        let right_level = tone_amplitude(&right[last_second..]);
+        assert!((left_level - 0.5).abs() < 0.05, "left was {}", left_level);
+        assert!(
+            right_level < left_level / 30.0,
+            "right was {} (left was {})",
+            right_level,
+            left_level
+        );
+    }
+}
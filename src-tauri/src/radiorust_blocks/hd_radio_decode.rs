@@ -23,6 +23,7 @@ use crate::{
     },
 };
 use base64::Engine;
+use log::{debug, error, info};
 use radiorust::{
     flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
     impl_block_trait,
@@ -31,9 +32,10 @@ use radiorust::{
     signal::Signal,
 };
 use serde::Serialize;
-use tauri::ipc::Channel;
 use tokio::{spawn, sync::watch};
 
+pub type HdRadioStateCallback = Arc<dyn Fn(HdRadioState) + Send + Sync>;
+
 pub struct HdRadioDecode<Flt> {
     receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
     sender_connector: SenderConnector<Signal<Complex<Flt>>>,
@@ -221,7 +223,7 @@ fn lot_to_base64_url(lot_file: LotFile) -> String {
 
 unsafe extern "C" fn nrsc5_custom_callback(event: *const nrsc5_event_t, opaque: *mut c_void) {
     if opaque.is_null() {
-        println!("The nrsc5 callback opaque value is empty!");
+        error!("The nrsc5 callback opaque value is empty!");
         return;
     }
     let callback_opaque = &mut *(opaque as *mut Nrsc5CallbackOpaque);
@@ -260,12 +262,12 @@ unsafe extern "C" fn nrsc5_custom_callback(event: *const nrsc5_event_t, opaque:
         let raw_ufid_owner = (*event).__bindgen_anon_1.id3.ufid.owner;
         if !raw_ufid_owner.is_null() {
             let ufid_owner = CStr::from_ptr(raw_ufid_owner).to_string_lossy();
-            println!("UFID Owner: {}", ufid_owner);
+            debug!("UFID Owner: {}", ufid_owner);
         }
         let raw_ufid_id = (*event).__bindgen_anon_1.id3.ufid.id;
         if !raw_ufid_id.is_null() {
             let ufid_id = CStr::from_ptr(raw_ufid_id).to_string_lossy();
-            println!("UFID ID: {}", ufid_id);
+            debug!("UFID ID: {}", ufid_id);
         }
 
         let lot_id = (*event).__bindgen_anon_1.id3.xhdr.lot;
@@ -299,7 +301,7 @@ unsafe extern "C" fn nrsc5_custom_callback(event: *const nrsc5_event_t, opaque:
             callback_opaque.extend_audio_samples(audio_data);
         }
     } else if (*event).event == NRSC5_EVENT_LOT {
-        println!(
+        debug!(
             "-----------------Name: {}",
             CStr::from_ptr((*event).__bindgen_anon_1.lot.name)
                 .to_str()
@@ -326,7 +328,7 @@ unsafe extern "C" fn nrsc5_custom_callback(event: *const nrsc5_event_t, opaque:
             binary_data,
         );
 
-        println!(
+        debug!(
             "  LOT: {}, MIME: {:#x}, Port: {}, Size: {}",
             (*event).__bindgen_anon_1.lot.lot,
             (*event).__bindgen_anon_1.lot.mime,
@@ -335,9 +337,9 @@ unsafe extern "C" fn nrsc5_custom_callback(event: *const nrsc5_event_t, opaque:
         );
         lots_updated = true;
     } else if (*event).event == NRSC5_EVENT_SYNC {
-        println!("Synced to Station");
+        info!("Synced to Station");
     } else if (*event).event == NRSC5_EVENT_LOST_SYNC {
-        println!("Lost Sync to Station");
+        info!("Lost Sync to Station");
     } else if (*event).event == NRSC5_EVENT_BER {
         callback_opaque.state.ber = (*event).__bindgen_anon_1.ber.cber;
         // println!(
@@ -351,13 +353,13 @@ unsafe extern "C" fn nrsc5_custom_callback(event: *const nrsc5_event_t, opaque:
         //     (*event).__bindgen_anon_1.mer.upper
         // );
     } else if (*event).event == NRSC5_EVENT_SIG {
-        println!("Station Channels:");
+        debug!("Station Channels:");
         let mut cur_sig = (*event).__bindgen_anon_1.sig.services;
         while !cur_sig.is_null() {
             let raw_name = (*cur_sig).name;
             if !raw_name.is_null() {
                 let name = CStr::from_ptr(raw_name).to_string_lossy();
-                println!(
+                debug!(
                     "  {}. {} ({})",
                     (*cur_sig).number,
                     name,
@@ -371,13 +373,13 @@ unsafe extern "C" fn nrsc5_custom_callback(event: *const nrsc5_event_t, opaque:
                 let mut cur_component = (*cur_sig).components;
                 while !cur_component.is_null() {
                     if (*cur_component).type_ == NRSC5_SIG_COMPONENT_AUDIO as u8 {
-                        println!(
+                        debug!(
                             "      Audio Component (MIME {:#x} - PORT {})",
                             (*cur_component).__bindgen_anon_1.audio.mime,
                             (*cur_component).__bindgen_anon_1.audio.port
                         );
                     } else {
-                        println!(
+                        debug!(
                             "      Data Component (MIME {:#x} - PORT {})",
                             (*cur_component).__bindgen_anon_1.data.mime,
                             (*cur_component).__bindgen_anon_1.data.port
@@ -606,7 +608,7 @@ where
         program: u32,
         pass_along: bool,
         hdradio_callback: impl Fn(HdRadioState) + Send + Sync + 'static,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
         let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
 
@@ -615,17 +617,18 @@ where
 
         let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
 
-        spawn(async move {
-            let mut nrsc5_opaque = Box::new(Nrsc5CallbackOpaque {
-                state: HdRadioState::new(program),
-                callback: Arc::new(hdradio_callback),
-                audio_samples: Arc::new(Mutex::new(vec![])),
-            });
-            let mut nrsc5_decoder = Nrsc5::new(
-                Some(nrsc5_custom_callback),
-                &mut *nrsc5_opaque as *mut _ as *mut c_void,
-            );
+        // open the decoder before spawning, so a failure is returned instead of panicking in the task
+        let mut nrsc5_opaque = Box::new(Nrsc5CallbackOpaque {
+            state: HdRadioState::new(program),
+            callback: Arc::new(hdradio_callback),
+            audio_samples: Arc::new(Mutex::new(vec![])),
+        });
+        let mut nrsc5_decoder = Nrsc5::new(
+            Some(nrsc5_custom_callback),
+            &mut *nrsc5_opaque as *mut _ as *mut c_void,
+        )?;
 
+        spawn(async move {
             loop {
                 let Ok(signal) = receiver.recv().await else {
                     return;
@@ -662,7 +665,7 @@ where
                             continue;
                         }
 
-                        println!("{} Audio samples are being sent", new_audio_samples.len());
+                        debug!("{} Audio samples are being sent", new_audio_samples.len());
 
                         let mut output_chunk = buf_pool.get();
 
@@ -680,7 +683,7 @@ where
                             })
                             .await
                         else {
-                            println!("Receiver is no longer available");
+                            debug!("Receiver is no longer available");
                             return;
                         };
                         // if pass_along {
@@ -705,12 +708,12 @@ where
                 }
             }
         });
-        Self {
+        Ok(Self {
             receiver_connector,
             sender_connector,
             program: program_send,
             should_reset: should_reset_send,
-        }
+        })
     }
 
     fn convert_complex_to_iq_samples<'a>(iter: impl Iterator<Item = Complex<Flt>>) -> Vec<i16> {
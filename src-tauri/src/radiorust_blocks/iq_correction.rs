@@ -0,0 +1,143 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use tokio::spawn;
+
+// how quickly the DC offset is tracked (in seconds)
+const DC_TIME_CONSTANT: f64 = 0.01;
+// the imbalance barely changes, so it is averaged over a long time (in seconds)
+const IMBALANCE_TIME_CONSTANT: f64 = 1.0;
+
+/// A custom radiorust block that removes the DC offset of an IQ stream, and corrects the gain and phase imbalance
+/// between I and Q. Cheap SDRs have both, causing a spike at the center frequency and mirror images of signals.
+pub struct IqCorrection<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    is_enabled: Arc<AtomicBool>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for IqCorrection<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for IqCorrection<Flt> }
+
+impl<Flt> IqCorrection<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    /// Samples are passed through unchanged while the correction is disabled
+    pub fn new(is_enabled: bool) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let is_enabled = Arc::new(AtomicBool::new(is_enabled));
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        let is_enabled_clone = is_enabled.clone();
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut dc_alpha: f64 = 0.0;
+            let mut imbalance_alpha: f64 = 0.0;
+
+            let mut dc_offset: Complex<f64> = Complex::new(0.0, 0.0);
+            // running averages of I², Q², and I*Q
+            let mut i_power: f64 = 1.0;
+            let mut q_power: f64 = 1.0;
+            let mut iq_correlation: f64 = 0.0;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        if !is_enabled_clone.load(Ordering::SeqCst) {
+                            let Ok(()) = sender
+                                .send(Signal::Samples {
+                                    sample_rate,
+                                    chunk: input_chunk,
+                                })
+                                .await
+                            else {
+                                return;
+                            };
+                            continue;
+                        }
+
+                        // the averages depend on the sample rate
+                        if sample_rate != current_sample_rate {
+                            current_sample_rate = sample_rate;
+                            dc_alpha = 1.0 - (-1.0 / (DC_TIME_CONSTANT * sample_rate)).exp();
+                            imbalance_alpha =
+                                1.0 - (-1.0 / (IMBALANCE_TIME_CONSTANT * sample_rate)).exp();
+                        }
+
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+
+                        for sample in input_chunk.iter() {
+                            let sample: Complex<f64> =
+                                Complex::new(sample.re.into(), sample.im.into());
+
+                            // remove the DC offset
+                            dc_offset += (sample - dc_offset) * dc_alpha;
+                            let sample = sample - dc_offset;
+
+                            i_power += (sample.re * sample.re - i_power) * imbalance_alpha;
+                            q_power += (sample.im * sample.im - q_power) * imbalance_alpha;
+                            iq_correlation +=
+                                (sample.re * sample.im - iq_correlation) * imbalance_alpha;
+
+                            // make Q uncorrelated with I (which fixes the phase), then give it the same power as I
+                            let phase_correction = iq_correlation / i_power.max(f64::EPSILON);
+                            let orthogonal_q = sample.im - phase_correction * sample.re;
+                            let orthogonal_q_power =
+                                (q_power - iq_correlation * phase_correction).max(f64::EPSILON);
+                            let gain_correction = (i_power / orthogonal_q_power).sqrt();
+
+                            output_chunk.push(Complex::new(
+                                Flt::from_f64(sample.re).unwrap(),
+                                Flt::from_f64(orthogonal_q * gain_correction).unwrap(),
+                            ));
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+            is_enabled,
+        }
+    }
+
+    pub fn set_enabled(&self, is_enabled: bool) {
+        self.is_enabled.store(is_enabled, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    f64::consts::PI,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{error, info};
+use radiorust::{
+    flow::{new_sender, Sender, SenderConnector},
+    impl_block_trait,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+
+use crate::sdr::iq_file::{FilePacing, IqFileArgs};
+
+// number of samples sent in each chunk
+const CHUNK_SIZE: usize = 16384;
+
+/// A custom radiorust block that reads IQ samples from a recording, shifting them so the tuned frequency is centered at 0hz.
+pub struct IqFileSource {
+    sender_connector: SenderConnector<Signal<Complex<f32>>>,
+    sender: Mutex<Option<Sender<Signal<Complex<f32>>>>>,
+    args: IqFileArgs,
+    frequency: Arc<Mutex<f64>>,
+    stop_flag: Arc<AtomicBool>,
+    // set once a file that doesn't loop has been read to the end
+    reached_end: Arc<AtomicBool>,
+}
+
+impl_block_trait! { Producer<Signal<Complex<f32>>> for IqFileSource }
+
+impl IqFileSource {
+    pub fn new(args: IqFileArgs, frequency: Arc<Mutex<f64>>) -> Self {
+        let (sender, sender_connector) = new_sender::<Signal<Complex<f32>>>();
+
+        Self {
+            sender_connector,
+            sender: Mutex::new(Some(sender)),
+            args,
+            frequency,
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            reached_end: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Start reading samples from the file
+    pub fn activate(&self) -> Result<(), String> {
+        let Some(sender) = self.sender.lock().unwrap().take() else {
+            return Err(String::from("IQ file stream is already active"));
+        };
+
+        let file = File::open(&self.args.path)
+            .map_err(|err| format!("Could not open {}: {}", self.args.path, err))?;
+
+        let rt = tokio::runtime::Handle::current();
+        let args = self.args.clone();
+        let frequency = self.frequency.clone();
+        let stop_flag = self.stop_flag.clone();
+        let reached_end = self.reached_end.clone();
+
+        thread::spawn(move || {
+            let mut reader = BufReader::new(file);
+            let mut buf_pool = ChunkBufPool::<Complex<f32>>::new();
+            let mut raw_buf = vec![0u8; CHUNK_SIZE * args.format.bytes_per_sample()];
+
+            let mut phase: f64 = 0.0;
+            let mut samples_sent: u64 = 0;
+            let start = Instant::now();
+
+            while !stop_flag.load(Ordering::SeqCst) {
+                let bytes_read = match read_full(&mut reader, &mut raw_buf) {
+                    Ok(bytes_read) => bytes_read,
+                    Err(err) => {
+                        error!("Error reading IQ file {}: {}", args.path, err);
+                        return;
+                    }
+                };
+
+                // only use whole samples
+                let samples_read = bytes_read / args.format.bytes_per_sample();
+
+                if samples_read > 0 {
+                    // shift the tuned frequency to the center of the signal
+                    let freq_offset = *frequency.lock().unwrap() - args.center_freq;
+                    let phase_step = -2.0 * PI * freq_offset / args.sample_rate;
+
+                    let mut output_chunk = buf_pool.get_with_capacity(samples_read);
+                    for i in 0..samples_read {
+                        let sample = args
+                            .format
+                            .decode_sample(&raw_buf[i * args.format.bytes_per_sample()..]);
+                        let mixer = Complex::new(phase.cos() as f32, phase.sin() as f32);
+                        output_chunk.push(sample * mixer);
+
+                        phase = (phase + phase_step) % (2.0 * PI);
+                    }
+
+                    let Ok(()) = rt.block_on(sender.send(Signal::Samples {
+                        sample_rate: args.sample_rate,
+                        chunk: output_chunk.finalize(),
+                    })) else {
+                        return;
+                    };
+
+                    samples_sent += samples_read as u64;
+
+                    if args.pacing == FilePacing::RealTime {
+                        let expected_elapsed =
+                            Duration::from_secs_f64(samples_sent as f64 / args.sample_rate);
+                        let elapsed = start.elapsed();
+                        if expected_elapsed > elapsed {
+                            thread::sleep(expected_elapsed - elapsed);
+                        }
+                    }
+                }
+
+                // the end of the file was reached
+                if bytes_read < raw_buf.len() {
+                    if !args.looping {
+                        info!("Reached the end of IQ file {}", args.path);
+                        // set before the stream is closed, so it isn't mistaken for a failure
+                        reached_end.store(true, Ordering::SeqCst);
+                        return;
+                    }
+
+                    if let Err(err) = reader.seek(SeekFrom::Start(0)) {
+                        error!("Could not loop IQ file {}: {}", args.path, err);
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Whether the whole file was played, which ends the stream
+    pub fn has_reached_end(&self) -> bool {
+        self.reached_end.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for IqFileSource {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+    }
+}
+
+// read until the buffer is full or the end of the file is reached
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut total_read = 0;
+    while total_read < buf.len() {
+        let bytes_read = reader.read(&mut buf[total_read..])?;
+        if bytes_read == 0 {
+            break;
+        }
+        total_read += bytes_read;
+    }
+    Ok(total_read)
+}
@@ -1,9 +1,30 @@
 pub mod adsb_decode;
 pub mod am_demod;
+pub mod audio_mixer;
+pub mod audio_recorder;
 #[allow(dead_code)]
 pub mod better_cpal;
+pub mod biquad_filter;
+pub mod channelizer;
+pub mod fm_stereo_decode;
 pub mod hd_radio_decode;
-pub mod pauseable;
+pub mod iq_correction;
+pub mod iq_file_source;
+pub mod noise_blanker;
+pub mod noise_reduction;
+pub mod pcm_writer;
 pub mod rbds_decode;
+pub mod recording;
+pub mod rtl_tcp_sink;
+pub mod rtl_tcp_source;
+pub mod sample_capture;
+pub mod sigmf_writer;
+pub mod simulated_source;
+pub mod spectrum_analyzer;
+pub mod squelch;
+pub mod ssb_demod;
+pub mod stream_monitor;
+pub mod subaudible_tone;
+pub mod time_shift;
 #[allow(dead_code)]
 pub mod wav_writer;
@@ -0,0 +1,154 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use tokio::spawn;
+
+// the average level of the signal, which impulses are compared against (in seconds)
+const AVERAGE_TIME_CONSTANT: f64 = 0.02;
+// how much is blanked before and after an impulse, since its edges are below the threshold (in seconds)
+const GUARD_TIME: f64 = 0.000_1;
+// how far above the average an impulse needs to be at the lowest and highest levels
+const MAX_THRESHOLD: f64 = 20.0;
+const MIN_THRESHOLD: f64 = 2.0;
+
+/// A custom radiorust block that silences short impulses (such as from power lines and motors) in an IQ stream. It
+/// needs to be before any narrow filters, since they would spread an impulse out until it can't be told apart.
+///
+/// The level goes from 0 (off) to 1 (blanks anything a little above the average).
+pub struct NoiseBlanker<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    level: Arc<Mutex<f64>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for NoiseBlanker<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for NoiseBlanker<Flt> }
+
+impl<Flt> NoiseBlanker<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(level: f64) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let level = Arc::new(Mutex::new(level));
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        let level_clone = level.clone();
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut average_alpha: f64 = 0.0;
+            let mut guard_samples: usize = 0;
+
+            let mut average: f64 = 0.0;
+            // the samples are delayed by the guard time, so the start of an impulse can be blanked too
+            let mut delay: VecDeque<Complex<Flt>> = VecDeque::new();
+            let mut samples_to_blank: usize = 0;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        let level = level_clone.lock().unwrap().clamp(0.0, 1.0);
+                        if level == 0.0 {
+                            // start over when turned back on
+                            delay.clear();
+                            samples_to_blank = 0;
+
+                            let Ok(()) = sender
+                                .send(Signal::Samples {
+                                    sample_rate,
+                                    chunk: input_chunk,
+                                })
+                                .await
+                            else {
+                                return;
+                            };
+                            continue;
+                        }
+
+                        if sample_rate != current_sample_rate {
+                            current_sample_rate = sample_rate;
+                            average_alpha =
+                                1.0 - (-1.0 / (AVERAGE_TIME_CONSTANT * sample_rate)).exp();
+                            guard_samples = ((GUARD_TIME * sample_rate) as usize).max(1);
+                            delay.clear();
+                        }
+                        let threshold = MAX_THRESHOLD - level * (MAX_THRESHOLD - MIN_THRESHOLD);
+
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+
+                        for &sample in input_chunk.iter() {
+                            let magnitude: f64 =
+                                (sample.re.powi(2) + sample.im.powi(2)).sqrt().into();
+
+                            if average > 0.0 && magnitude > average * threshold {
+                                // blank the delayed samples before the impulse, the impulse, and the ones after it
+                                samples_to_blank = 2 * guard_samples + 1;
+                            } else {
+                                // impulses would raise the average, so it is only measured without them
+                                average += (magnitude - average) * average_alpha;
+                            }
+
+                            delay.push_back(sample);
+                            if delay.len() <= guard_samples {
+                                // the delay is still filling up
+                                output_chunk.push(Complex::new(Flt::zero(), Flt::zero()));
+                                continue;
+                            }
+                            let delayed_sample = delay.pop_front().unwrap();
+
+                            if samples_to_blank > 0 {
+                                samples_to_blank -= 1;
+                                output_chunk.push(Complex::new(Flt::zero(), Flt::zero()));
+                            } else {
+                                output_chunk.push(delayed_sample);
+                            }
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+            level,
+        }
+    }
+
+    pub fn set_level(&self, level: f64) {
+        *self.level.lock().unwrap() = level;
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    f64::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use rustfft::{num_complex::Complex as FftComplex, Fft, FftPlanner};
+use tokio::spawn;
+
+// the length of each FFT, which is rounded up to a power of two (in seconds)
+const FRAME_TIME: f64 = 0.02;
+// how long the power of each bin is averaged over (in seconds)
+const POWER_TIME_CONSTANT: f64 = 0.05;
+// the noise follows the power down right away, but rises slowly so voices aren't counted as noise (in seconds)
+const NOISE_RISE_TIME: f64 = 2.0;
+// how much of the noise is removed at the highest level (in dB), where removing all of it sounds watery
+const MAX_REDUCTION: f64 = 20.0;
+// how much more than the noise estimate is subtracted at the highest level
+const MAX_OVERSUBTRACTION: f64 = 2.0;
+// smooths the gain of each bin between frames, which stops random bins from chirping ("musical noise")
+const GAIN_SMOOTHING: f64 = 0.5;
+
+/// A custom radiorust block that removes steady background noise (such as hiss and static) from demodulated audio with
+/// spectral subtraction. The noise of each frequency is estimated from the quietest moments, and subtracted from the
+/// audio. Stereo audio uses the same gain for both channels, so the stereo image doesn't move.
+///
+/// The level goes from 0 (off) to 1 (removes as much noise as possible).
+pub struct NoiseReduction<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    level: Arc<Mutex<f64>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for NoiseReduction<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for NoiseReduction<Flt> }
+
+impl<Flt> NoiseReduction<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(level: f64) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let level = Arc::new(Mutex::new(level));
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        let level_clone = level.clone();
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut subtraction: Option<SpectralSubtraction> = None;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        let level = level_clone.lock().unwrap().clamp(0.0, 1.0);
+                        if level == 0.0 {
+                            // start over when turned back on, since the noise could have changed
+                            subtraction = None;
+
+                            let Ok(()) = sender
+                                .send(Signal::Samples {
+                                    sample_rate,
+                                    chunk: input_chunk,
+                                })
+                                .await
+                            else {
+                                return;
+                            };
+                            continue;
+                        }
+
+                        if sample_rate != current_sample_rate {
+                            current_sample_rate = sample_rate;
+                            subtraction = None;
+                        }
+                        let subtraction = subtraction
+                            .get_or_insert_with(|| SpectralSubtraction::new(sample_rate));
+
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+
+                        for sample in input_chunk.iter() {
+                            let sample = FftComplex::new(sample.re.into(), sample.im.into());
+                            if let Some(output) = subtraction.push(sample, level) {
+                                output_chunk.extend(output.iter().map(|sample| {
+                                    Complex::new(
+                                        Flt::from_f64(sample.re).unwrap(),
+                                        Flt::from_f64(sample.im).unwrap(),
+                                    )
+                                }));
+                            }
+                        }
+
+                        // the audio is processed a frame at a time, so nothing is ready yet
+                        if output_chunk.is_empty() {
+                            continue;
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+            level,
+        }
+    }
+
+    pub fn set_level(&self, level: f64) {
+        *self.level.lock().unwrap() = level;
+    }
+}
+
+// overlapping frames with a square-root hann window before and after the FFT, which add back up to the original audio
+struct SpectralSubtraction {
+    fft: Arc<dyn Fft<f64>>,
+    ifft: Arc<dyn Fft<f64>>,
+    window: Vec<f64>,
+    hop_size: usize,
+    power_alpha: f64,
+    noise_alpha: f64,
+
+    // the last frame of audio, where a new frame is processed once it is full
+    input: Vec<FftComplex<f64>>,
+    // the second half of the last frame, which is added to the first half of the next one
+    overlap: Vec<FftComplex<f64>>,
+    output: Vec<FftComplex<f64>>,
+    buffer: Vec<FftComplex<f64>>,
+
+    // the averages start at the power of the first frame, instead of rising from nothing
+    is_first_frame: bool,
+    // for each bin from 0 hz up to half the sample rate
+    power: Vec<f64>,
+    noise: Vec<f64>,
+    gain: Vec<f64>,
+}
+
+impl SpectralSubtraction {
+    fn new(sample_rate: f64) -> Self {
+        let frame_size = ((FRAME_TIME * sample_rate) as usize)
+            .next_power_of_two()
+            .max(64);
+        let hop_size = frame_size / 2;
+        let frames_per_second = sample_rate / hop_size as f64;
+
+        let mut planner = FftPlanner::<f64>::new();
+        let num_bins = frame_size / 2 + 1;
+
+        Self {
+            fft: planner.plan_fft_forward(frame_size),
+            ifft: planner.plan_fft_inverse(frame_size),
+            window: (0..frame_size)
+                .map(|i| (PI * i as f64 / frame_size as f64).sin())
+                .collect(),
+            hop_size,
+            power_alpha: 1.0 - (-1.0 / (POWER_TIME_CONSTANT * frames_per_second)).exp(),
+            noise_alpha: 1.0 - (-1.0 / (NOISE_RISE_TIME * frames_per_second)).exp(),
+            // start with half a frame of silence, so the first frame is ready after one hop
+            input: vec![FftComplex::new(0.0, 0.0); hop_size],
+            overlap: vec![FftComplex::new(0.0, 0.0); hop_size],
+            output: Vec::with_capacity(hop_size),
+            buffer: vec![FftComplex::new(0.0, 0.0); frame_size],
+            is_first_frame: true,
+            power: vec![0.0; num_bins],
+            noise: vec![0.0; num_bins],
+            gain: vec![1.0; num_bins],
+        }
+    }
+
+    // add a sample, returning the next hop of audio once a frame has been processed
+    fn push(&mut self, sample: FftComplex<f64>, level: f64) -> Option<&[FftComplex<f64>]> {
+        self.input.push(sample);
+        if self.input.len() < self.window.len() {
+            return None;
+        }
+
+        let frame_size = self.window.len();
+        for (i, (bin, sample)) in self.buffer.iter_mut().zip(self.input.iter()).enumerate() {
+            *bin = *sample * self.window[i];
+        }
+        self.fft.process(&mut self.buffer);
+
+        let oversubtraction = 1.0 + level * MAX_OVERSUBTRACTION;
+        let min_gain = 10.0_f64.powf(-level * MAX_REDUCTION / 20.0);
+
+        for k in 0..self.power.len() {
+            // the negative frequency of a bin is in the mirrored bin, which gets the same gain so real audio stays real
+            let mirrored_k = (frame_size - k) % frame_size;
+            let frame_power =
+                (self.buffer[k].norm_sqr() + self.buffer[mirrored_k].norm_sqr()) / 2.0;
+
+            if self.is_first_frame {
+                self.power[k] = frame_power;
+                self.noise[k] = frame_power;
+            }
+            self.power[k] += (frame_power - self.power[k]) * self.power_alpha;
+            if self.power[k] < self.noise[k] {
+                self.noise[k] = self.power[k];
+            } else {
+                self.noise[k] += (self.power[k] - self.noise[k]) * self.noise_alpha;
+            }
+
+            let gain = if frame_power > 0.0 {
+                (1.0 - oversubtraction * self.noise[k] / frame_power)
+                    .max(min_gain.powi(2))
+                    .sqrt()
+            } else {
+                min_gain
+            };
+            self.gain[k] += (gain - self.gain[k]) * GAIN_SMOOTHING;
+
+            self.buffer[k] *= self.gain[k];
+            if mirrored_k != k {
+                self.buffer[mirrored_k] *= self.gain[k];
+            }
+        }
+
+        self.is_first_frame = false;
+        self.ifft.process(&mut self.buffer);
+
+        // rustfft doesn't normalize the inverse FFT
+        let scale = 1.0 / frame_size as f64;
+        self.output.clear();
+        for i in 0..self.hop_size {
+            let first_half = self.buffer[i] * self.window[i] * scale;
+            let second_half =
+                self.buffer[i + self.hop_size] * self.window[i + self.hop_size] * scale;
+            self.output.push(self.overlap[i] + first_half);
+            self.overlap[i] = second_half;
+        }
+
+        self.input.drain(..self.hop_size);
+
+        Some(&self.output)
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::Write;
+
+use radiorust::{
+    flow::{new_receiver, ReceiverConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use tokio::spawn;
+
+/// A custom radiorust block that writes audio as interleaved 16-bit little-endian stereo PCM, such as to stdout so it
+/// can be piped into another program. Like [`AudioPlayer`](super::better_cpal::AudioPlayer), mono audio is in the real
+/// part of each sample unless `virtual_channels` is disabled, in which case the real and imaginary parts are left and right.
+pub struct PcmWriter<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for PcmWriter<Flt> }
+
+impl<Flt> PcmWriter<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new<W>(mut writer: W, virtual_channels: bool) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+
+        spawn(async move {
+            let mut bytes = vec![];
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                let Signal::Samples {
+                    chunk: input_chunk, ..
+                } = signal
+                else {
+                    continue;
+                };
+
+                bytes.clear();
+                for sample in input_chunk.iter() {
+                    let left: f64 = sample.re.into();
+                    let right: f64 = if virtual_channels {
+                        left
+                    } else {
+                        sample.im.into()
+                    };
+
+                    for value in [left, right] {
+                        let value = (value.clamp(-1.0, 1.0) * i16::MAX as f64) as i16;
+                        bytes.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+
+                // stop once the reader goes away (such as a closed pipe)
+                if writer
+                    .write_all(&bytes)
+                    .and_then(|_| writer.flush())
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        Self { receiver_connector }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::VecDeque, f64::consts::PI, ops::Range};
+use std::{collections::VecDeque, f64::consts::PI, ops::Range, sync::Arc};
 
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
@@ -6,21 +6,17 @@ use serde::{Deserialize, Serialize};
 #[cfg(debug_assertions)]
 use hound::{WavSpec, WavWriter};
 #[cfg(debug_assertions)]
-use std::{
-    fs,
-    io::BufWriter,
-    sync::{Arc, Mutex},
-};
+use std::{fs, io::BufWriter, sync::Mutex};
 
 use nalgebra::{SMatrix, SVector};
 use radiorust::{
-    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    blocks::{Buffer, Filter},
+    flow::{new_receiver, new_sender, Consumer, Producer, ReceiverConnector, SenderConnector},
     impl_block_trait,
     numbers::Float,
     prelude::{ChunkBuf, ChunkBufPool, Complex},
     signal::Signal,
 };
-use tauri::ipc::Channel;
 use tokio::spawn;
 pub struct DownMixer<Flt> {
     receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
@@ -91,6 +87,53 @@ where
     }
 }
 
+// the RBDS data of FM stations is in the upper sideband of a subcarrier at 57 kHz
+const RBDS_FREQ: f64 = 57_000.0;
+const RBDS_BANDWIDTH: f64 = 2_000.0;
+
+/// Build the blocks that decode RBDS from demodulated FM (the multiplex signal), which returns the buffer that the
+/// demodulator is fed to
+pub fn new_rbds_receiver<F>(
+    rbds_callback: RbdsStateCallback,
+    radiotext_callback: F,
+) -> Buffer<Complex<f32>>
+where
+    F: Fn(String) + Send + Sync + 'static,
+{
+    let rbds_buffer = Buffer::new(0.0, 0.0, 0.0, 5.0);
+
+    // Step 1. apply bandpass signal to 57Khz with bandwidth of 4KHz for RBDS decoding
+    let rbds_bandpass_filter = Filter::new(|_, freq| {
+        if freq.abs() >= (RBDS_FREQ + 1000.0 - (RBDS_BANDWIDTH / 2.0))
+            && freq.abs() <= (RBDS_FREQ + 1000.0 + (RBDS_BANDWIDTH / 2.0))
+        {
+            Complex::from(1.0)
+        } else {
+            Complex::from(0.0)
+        }
+    });
+    rbds_bandpass_filter.feed_from(&rbds_buffer);
+
+    // Step 2. downmix the signal
+    let rbds_downmixer = DownMixer::<f32>::new(RBDS_FREQ as f32);
+    rbds_downmixer.feed_from(&rbds_bandpass_filter);
+
+    // Step 3. remove high-frequency data and very-low frequency data
+    let rbds_lowpass_filter = Filter::new(|_, freq| {
+        if freq.abs() >= 10.0 && freq.abs() <= (RBDS_FREQ) {
+            Complex::from(1.0)
+        } else {
+            Complex::from(0.0)
+        }
+    });
+    rbds_lowpass_filter.feed_from(&rbds_downmixer);
+
+    let rbds_decoder = RbdsDecode::<f32>::new(rbds_callback, radiotext_callback);
+    rbds_decoder.feed_from(&rbds_lowpass_filter);
+
+    rbds_buffer
+}
+
 // constants for RDBS Decoding
 //const RBDS_CARRIER_FREQ: f64 = 57_000.0;
 //const RBDS_BANDWIDTH: f64 = 4_000.0;
@@ -204,6 +247,8 @@ impl RbdsDecodeState {
     }
 }
 
+pub type RbdsStateCallback = Arc<dyn Fn(RbdsState) + Send + Sync>;
+
 pub struct RbdsDecode<Flt> {
     receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
 }
@@ -214,7 +259,8 @@ impl<Flt> RbdsDecode<Flt>
 where
     Flt: Float + Into<f64> + Into<f32>,
 {
-    pub fn new<F>(rbds_channel: Channel<RbdsState>, radiotext_callback: F) -> Self
+    /// Decode RBDS data, passing each updated state to `rbds_callback`
+    pub fn new<F>(rbds_callback: RbdsStateCallback, radiotext_callback: F) -> Self
     where
         F: Fn(String) + Send + Sync + 'static,
     {
@@ -341,7 +387,7 @@ where
                                             &mut decoded_bits,
                                             &mut rbds_decode_state,
                                             &radiotext_callback,
-                                            rbds_channel.clone(),
+                                            rbds_callback.clone(),
                                             true,
                                         );
                                         decoded_bits.clear();
@@ -380,7 +426,7 @@ where
                             &mut decoded_bits,
                             &mut rbds_decode_state,
                             &radiotext_callback,
-                            rbds_channel.clone(),
+                            rbds_callback.clone(),
                             false,
                         );
 
@@ -637,7 +683,7 @@ fn process_rbds_group<F>(
     group_data: Vec<(u32, String)>,
     rbds_state: &mut RbdsState,
     radiotext_callback: &F,
-    rbds_channel: Channel<RbdsState>,
+    rbds_callback: RbdsStateCallback,
 ) where
     F: Fn(String),
 {
@@ -861,14 +907,14 @@ fn process_rbds_group<F>(
     rbds_state.tp = Some(tp);
 
     // update frontend
-    rbds_channel.send(rbds_state.clone()).unwrap();
+    rbds_callback(rbds_state.clone());
 }
 
 fn rbds_process_bits<F>(
     bit_stream: &mut Vec<u8>,
     rbds_decode_state: &mut RbdsDecodeState,
     radiotext_callback: &F,
-    rbds_channel: Channel<RbdsState>,
+    rbds_callback: RbdsStateCallback,
     bit_stream_ending: bool,
 ) where
     F: Fn(String),
@@ -938,7 +984,7 @@ fn rbds_process_bits<F>(
                             rbds_decode_state.current_block_group.clone(),
                             &mut rbds_decode_state.rbds_state,
                             radiotext_callback,
-                            rbds_channel.clone(),
+                            rbds_callback.clone(),
                         );
                         rbds_decode_state.current_block_group.clear();
                     }
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use log::error;
+use radiorust::numbers::Float;
+
+/// Called with the error when a recording had to be stopped, so the frontend can be told about it
+pub type RecordingErrorCallback = Arc<dyn Fn(String) + Send + Sync>;
+
+/// Stop a recording that failed (so the disk isn't hammered with failing writes), but still save what was recorded
+pub fn stop_failed_recording<R>(
+    recording: R,
+    err: String,
+    finish: impl FnOnce(R) -> Result<(), String>,
+    error_callback: &RecordingErrorCallback,
+) {
+    error!("{}", err);
+    if let Err(finish_err) = finish(recording) {
+        error!("{}", finish_err);
+    }
+    error_callback(err);
+}
+
+/// Convert a sample from -1 to 1 into 16-bit audio
+pub fn to_i16<Flt: Float + Into<f64>>(value: Flt) -> i16 {
+    let value: f64 = value.into();
+    (value.clamp(-1.0, 1.0) * i16::MAX as f64) as i16
+}
+
+/// Convert 16-bit audio back into a sample from -1 to 1
+pub fn from_i16<Flt: Float>(value: i16) -> Flt {
+    Flt::from_f64(value as f64 / i16::MAX as f64).unwrap()
+}
@@ -0,0 +1,100 @@
+use std::{
+    io::Write,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, TrySendError},
+        Arc,
+    },
+    thread,
+};
+
+use log::{debug, info};
+use radiorust::{
+    flow::{new_receiver, ReceiverConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use tokio::spawn;
+
+// how many chunks can be waiting to be sent before samples are dropped
+const MAX_QUEUED_CHUNKS: usize = 500;
+
+/// A custom radiorust block that sends the input stream to an rtl_tcp client as unsigned 8-bit IQ samples.
+pub struct RtlTcpSink<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    is_closed: Arc<AtomicBool>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for RtlTcpSink<Flt> }
+
+impl<Flt> RtlTcpSink<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(mut stream: TcpStream) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+
+        let is_closed = Arc::new(AtomicBool::new(false));
+
+        // write to the socket on a separate thread so a slow client doesn't block the SDR
+        let (chunk_sender, chunk_receiver) = sync_channel::<Vec<u8>>(MAX_QUEUED_CHUNKS);
+        let is_closed_clone = is_closed.clone();
+        thread::spawn(move || {
+            while let Ok(bytes) = chunk_receiver.recv() {
+                if let Err(err) = stream.write_all(&bytes) {
+                    info!("rtl_tcp client stopped receiving samples: {}", err);
+                    break;
+                }
+            }
+            is_closed_clone.store(true, Ordering::SeqCst);
+        });
+
+        spawn(async move {
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate: _,
+                        chunk: input_chunk,
+                    } => {
+                        let mut bytes = Vec::with_capacity(input_chunk.len() * 2);
+                        for sample in input_chunk.iter() {
+                            bytes.push(RtlTcpSink::to_u8(sample.re));
+                            bytes.push(RtlTcpSink::to_u8(sample.im));
+                        }
+
+                        match chunk_sender.try_send(bytes) {
+                            Ok(()) => {}
+                            Err(TrySendError::Full(_)) => {
+                                debug!("rtl_tcp client is too slow, dropping samples");
+                            }
+                            Err(TrySendError::Disconnected(_)) => {
+                                return;
+                            }
+                        }
+                    }
+                    Signal::Event(_event) => {}
+                }
+            }
+        });
+        Self {
+            receiver_connector,
+            is_closed,
+        }
+    }
+
+    /// Whether the client can no longer receive samples
+    pub fn is_closed(&self) -> bool {
+        self.is_closed.load(Ordering::SeqCst)
+    }
+
+    fn to_u8(value: Flt) -> u8 {
+        let value: f64 = value.into();
+        (value * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8
+    }
+}
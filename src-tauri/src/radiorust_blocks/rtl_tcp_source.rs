@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+
+use radiorust::{
+    flow::{new_sender, Sender, SenderConnector},
+    impl_block_trait,
+    prelude::Complex,
+    signal::Signal,
+};
+use tokio::runtime::Handle;
+
+/// Where the network reader of an rtl_tcp connection sends samples to. When it
+/// is empty, received samples are discarded so the server doesn't stall.
+pub type RtlTcpSampleSlot = Arc<Mutex<Option<(Arc<Sender<Signal<Complex<f32>>>>, Handle)>>>;
+
+/// A custom radiorust block that outputs the samples received from an rtl_tcp server.
+pub struct RtlTcpSource {
+    sender_connector: SenderConnector<Signal<Complex<f32>>>,
+    sender: Mutex<Option<Sender<Signal<Complex<f32>>>>>,
+    sample_slot: RtlTcpSampleSlot,
+}
+
+impl_block_trait! { Producer<Signal<Complex<f32>>> for RtlTcpSource }
+
+impl RtlTcpSource {
+    pub fn new(sample_slot: RtlTcpSampleSlot) -> Self {
+        let (sender, sender_connector) = new_sender::<Signal<Complex<f32>>>();
+
+        Self {
+            sender_connector,
+            sender: Mutex::new(Some(sender)),
+            sample_slot,
+        }
+    }
+
+    /// Start receiving samples from the connection
+    pub fn activate(&self) -> Result<(), String> {
+        let mut sample_slot = self.sample_slot.lock().unwrap();
+        if sample_slot.is_some() {
+            return Err(String::from("rtl_tcp connection is already streaming"));
+        }
+
+        let Some(sender) = self.sender.lock().unwrap().take() else {
+            return Err(String::from("rtl_tcp stream is already active"));
+        };
+        *sample_slot = Some((Arc::new(sender), Handle::current()));
+
+        Ok(())
+    }
+}
+
+impl Drop for RtlTcpSource {
+    fn drop(&mut self) {
+        // only release the connection if this block is the one using it
+        if self.sender.lock().unwrap().is_none() {
+            *self.sample_slot.lock().unwrap() = None;
+        }
+    }
+}
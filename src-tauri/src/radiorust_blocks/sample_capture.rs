@@ -0,0 +1,76 @@
+use std::sync::Mutex;
+
+use radiorust::{
+    flow::{new_receiver, ReceiverConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use tokio::{spawn, sync::oneshot};
+
+/// A custom radiorust block that collects a fixed number of samples from the input stream, so they can be analyzed all at once.
+pub struct SampleCapture<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    result_receiver: Mutex<Option<oneshot::Receiver<(Vec<Complex<Flt>>, f64)>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for SampleCapture<Flt> }
+
+impl<Flt> SampleCapture<Flt>
+where
+    Flt: Float,
+{
+    pub fn new(num_samples: usize) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        spawn(async move {
+            let mut samples = Vec::with_capacity(num_samples);
+            let mut current_sample_rate = 0.0;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        // restart if the sample rate changes so all samples are at the same rate
+                        if sample_rate != current_sample_rate {
+                            samples.clear();
+                            current_sample_rate = sample_rate;
+                        }
+
+                        let remaining = num_samples - samples.len();
+                        samples.extend(input_chunk.iter().take(remaining));
+
+                        if samples.len() == num_samples {
+                            let _ = result_sender.send((samples, current_sample_rate));
+                            return;
+                        }
+                    }
+                    Signal::Event(_) => {}
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            result_receiver: Mutex::new(Some(result_receiver)),
+        }
+    }
+
+    /// Wait until all of the samples have been collected, and get them along with their sample rate
+    pub async fn samples(&self) -> Result<(Vec<Complex<Flt>>, f64), String> {
+        let Some(result_receiver) = self.result_receiver.lock().unwrap().take() else {
+            return Err(String::from("Samples have already been taken"));
+        };
+
+        result_receiver
+            .await
+            .map_err(|_| String::from("Stream ended before all samples were captured"))
+    }
+}
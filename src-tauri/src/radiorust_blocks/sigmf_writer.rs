@@ -0,0 +1,346 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{SecondsFormat, Utc};
+use log::info;
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use serde_json::{json, Value};
+use tokio::spawn;
+
+use super::recording::{stop_failed_recording, RecordingErrorCallback};
+use crate::sdr::{enumeration::AvailableSDRArgs, iq_file::IqSampleFormat};
+
+struct SigMfRecording {
+    base_path: PathBuf,
+    data_writer: BufWriter<File>,
+    format: IqSampleFormat,
+    sample_rate: f64,
+    samples_written: u64,
+    captures: Vec<Value>,
+    annotations: Vec<Value>,
+}
+
+struct SigMfDeviceInfo {
+    sdr_args: AvailableSDRArgs,
+    sample_rate: f64,
+    frequency: f64,
+    gain: f64,
+}
+
+/// A handle to start and stop the recording of a [`SigMfWriterBlock`] while the pipeline is running.
+#[derive(Clone)]
+pub struct SigMfRecorder {
+    recording: Arc<Mutex<Option<SigMfRecording>>>,
+    device_info: Arc<Mutex<SigMfDeviceInfo>>,
+}
+
+impl SigMfRecorder {
+    /// Start recording to `<base_path>.sigmf-data` and `<base_path>.sigmf-meta`
+    pub fn start(&self, base_path: PathBuf, format: IqSampleFormat) -> Result<(), String> {
+        // only one lock is held at a time, so this can't deadlock with the notifications from the pipeline
+        let (sample_rate, frequency, gain) = {
+            let device_info = self.device_info.lock().unwrap();
+            (
+                device_info.sample_rate,
+                device_info.frequency,
+                device_info.gain,
+            )
+        };
+
+        let mut recording = self.recording.lock().unwrap();
+        if recording.is_some() {
+            return Err(String::from("Already recording"));
+        }
+
+        let data_path = base_path.with_extension("sigmf-data");
+        let data_file = File::create(&data_path)
+            .map_err(|err| format!("Could not create {}: {}", data_path.display(), err))?;
+
+        info!("Started IQ recording to {}", data_path.display());
+
+        *recording = Some(SigMfRecording {
+            base_path,
+            data_writer: BufWriter::new(data_file),
+            format,
+            // so the metadata is valid even if the recording is stopped before any samples arrive
+            sample_rate,
+            samples_written: 0,
+            captures: vec![SigMfRecorder::capture_segment(0, frequency)],
+            annotations: vec![SigMfRecorder::gain_annotation(0, gain)],
+        });
+
+        Ok(())
+    }
+
+    /// Stop recording and write the metadata file
+    pub fn stop(&self) -> Result<(), String> {
+        let Some(recording) = self.recording.lock().unwrap().take() else {
+            return Err(String::from("Not recording"));
+        };
+
+        self.finish(recording)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.lock().unwrap().is_some()
+    }
+
+    // flush the samples and write the metadata file of a recording that was taken out of the recorder
+    fn finish(&self, mut recording: SigMfRecording) -> Result<(), String> {
+        recording
+            .data_writer
+            .flush()
+            .map_err(|err| format!("Could not save IQ recording: {}", err))?;
+
+        let device_info = self.device_info.lock().unwrap();
+        let sdr_args = &device_info.sdr_args;
+
+        let meta = json!({
+            "global": {
+                "core:datatype": recording.format.sigmf_datatype(),
+                "core:sample_rate": recording.sample_rate,
+                "core:version": "1.0.0",
+                "core:recorder": format!("RTL-SDR Radio {}", env!("CARGO_PKG_VERSION")),
+                "core:hw": format!("{} ({}, serial {})", sdr_args.label, sdr_args.driver, sdr_args.serial),
+                "core:extensions": [{
+                    "name": "rtlsdr_radio",
+                    "version": "1.0.0",
+                    "optional": true,
+                }],
+                "rtlsdr_radio:sdr_args": sdr_args,
+            },
+            "captures": recording.captures,
+            "annotations": recording.annotations,
+        });
+
+        let meta_path = recording.base_path.with_extension("sigmf-meta");
+        fs::write(&meta_path, serde_json::to_string_pretty(&meta).unwrap())
+            .map_err(|err| format!("Could not create {}: {}", meta_path.display(), err))?;
+
+        info!(
+            "Saved IQ recording with {} samples to {}",
+            recording.samples_written,
+            meta_path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Notify the recorder that the SDR was retuned, which starts a new capture segment
+    pub fn set_frequency(&self, frequency: f64) {
+        {
+            let mut device_info = self.device_info.lock().unwrap();
+            if device_info.frequency == frequency {
+                return;
+            }
+            device_info.frequency = frequency;
+        }
+
+        if let Some(recording) = self.recording.lock().unwrap().as_mut() {
+            let sample_start = recording.samples_written;
+            // only keep one capture per sample index
+            recording
+                .captures
+                .retain(|capture| capture["core:sample_start"].as_u64() != Some(sample_start));
+            recording
+                .captures
+                .push(SigMfRecorder::capture_segment(sample_start, frequency));
+        }
+    }
+
+    /// Notify the recorder that the gain was changed, which is saved as an annotation
+    pub fn set_gain(&self, gain: f64) {
+        {
+            let mut device_info = self.device_info.lock().unwrap();
+            if device_info.gain == gain {
+                return;
+            }
+            device_info.gain = gain;
+        }
+
+        if let Some(recording) = self.recording.lock().unwrap().as_mut() {
+            recording.annotations.push(SigMfRecorder::gain_annotation(
+                recording.samples_written,
+                gain,
+            ));
+        }
+    }
+
+    fn capture_segment(sample_start: u64, frequency: f64) -> Value {
+        json!({
+            "core:sample_start": sample_start,
+            "core:frequency": frequency,
+            "core:datetime": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        })
+    }
+
+    fn gain_annotation(sample_start: u64, gain: f64) -> Value {
+        json!({
+            "core:sample_start": sample_start,
+            "core:label": "gain",
+            "core:comment": format!(
+                "Gain set to {} dB at {}",
+                gain,
+                Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+            ),
+        })
+    }
+}
+
+/// A custom radiorust block that passes along the IQ stream and records it as SigMF while a recording is started through its [`SigMfRecorder`].
+pub struct SigMfWriterBlock<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    recorder: SigMfRecorder,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for SigMfWriterBlock<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for SigMfWriterBlock<Flt> }
+
+impl<Flt> SigMfWriterBlock<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(
+        sdr_args: AvailableSDRArgs,
+        sample_rate: f64,
+        frequency: f64,
+        gain: f64,
+        error_callback: RecordingErrorCallback,
+    ) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let recorder = SigMfRecorder {
+            recording: Arc::new(Mutex::new(None)),
+            device_info: Arc::new(Mutex::new(SigMfDeviceInfo {
+                sdr_args,
+                sample_rate,
+                frequency,
+                gain,
+            })),
+        };
+        let task_recorder = recorder.clone();
+
+        spawn(async move {
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        let failed_recording = {
+                            let mut recording_locked = task_recorder.recording.lock().unwrap();
+                            if let Some(recording) = recording_locked.as_mut() {
+                                // the rate of the samples is what the SDR actually delivers
+                                if recording.samples_written == 0 {
+                                    recording.sample_rate = sample_rate;
+                                }
+
+                                let mut write_result = Ok(());
+                                for sample in input_chunk.iter() {
+                                    write_result = SigMfWriterBlock::write_sample(
+                                        &mut recording.data_writer,
+                                        sample,
+                                        recording.format,
+                                    );
+                                    if write_result.is_err() {
+                                        break;
+                                    }
+                                }
+
+                                match write_result {
+                                    Ok(()) => {
+                                        recording.samples_written += input_chunk.len() as u64;
+                                        None
+                                    }
+                                    Err(err) => recording_locked.take().map(|recording| {
+                                        (
+                                            recording,
+                                            format!("Failed to write IQ recording: {}", err),
+                                        )
+                                    }),
+                                }
+                            } else {
+                                None
+                            }
+                        };
+                        if let Some((recording, err)) = failed_recording {
+                            stop_failed_recording(
+                                recording,
+                                err,
+                                |recording| task_recorder.finish(recording),
+                                &error_callback,
+                            );
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: input_chunk,
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+        Self {
+            receiver_connector,
+            sender_connector,
+            recorder,
+        }
+    }
+
+    pub fn recorder(&self) -> SigMfRecorder {
+        self.recorder.clone()
+    }
+
+    fn write_sample(
+        writer: &mut BufWriter<File>,
+        sample: &Complex<Flt>,
+        format: IqSampleFormat,
+    ) -> std::io::Result<()> {
+        let re: f64 = sample.re.into();
+        let im: f64 = sample.im.into();
+
+        match format {
+            IqSampleFormat::Cu8 => writer.write_all(&[
+                (re * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8,
+                (im * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8,
+            ]),
+            IqSampleFormat::Cs16 => {
+                writer.write_all(
+                    &((re * 32768.0).round().clamp(-32768.0, 32767.0) as i16).to_le_bytes(),
+                )?;
+                writer.write_all(
+                    &((im * 32768.0).round().clamp(-32768.0, 32767.0) as i16).to_le_bytes(),
+                )
+            }
+            IqSampleFormat::Cf32 => {
+                writer.write_all(&(re as f32).to_le_bytes())?;
+                writer.write_all(&(im as f32).to_le_bytes())
+            }
+        }
+    }
+}
@@ -0,0 +1,640 @@
+use std::{
+    f64::consts::PI,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use radiorust::{
+    flow::{new_sender, Sender, SenderConnector},
+    impl_block_trait,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+
+use crate::{
+    modes::MODES_TEST_MESSAGES,
+    sdr::{
+        iq_file::FilePacing,
+        simulated::{SimulatedArgs, SimulatedRdsArgs, SimulatedSignal},
+    },
+};
+
+// number of samples sent in each chunk
+const CHUNK_SIZE: usize = 16384;
+
+const FM_DEVIATION: f64 = 75_000.0;
+const FM_PILOT_FREQ: f64 = 19_000.0;
+// the RDS subcarrier is locked to the third harmonic of the pilot
+const RDS_CARRIER_FREQ: f64 = FM_PILOT_FREQ * 3.0;
+const RDS_BIT_RATE: f64 = RDS_CARRIER_FREQ / 48.0;
+// As defined by RDS spec: x^10 + x^8 + x^7 + x^5 + x^4 + x^3 + 1
+const RDS_CRC_POLYNOMIAL: u32 = 0b10110111001;
+// offset words added to the checkword of blocks A, B, C, and D
+const RDS_OFFSET_WORDS: [u16; 4] = [0b0011111100, 0b0110011000, 0b0101101000, 0b0110110100];
+
+// time between each Mode S message (in seconds)
+const MODES_MESSAGE_INTERVAL: f64 = 0.01;
+// the data of a Mode S message starts after the 8 microsecond preamble
+const MODES_PREAMBLE_US: f64 = 8.0;
+// start and end of each preamble pulse (in microseconds)
+const MODES_PREAMBLE_PULSES: [(f64, f64); 4] = [(0.0, 0.5), (1.0, 1.5), (3.5, 4.0), (4.5, 5.0)];
+
+/// A custom radiorust block that generates the test signals of a simulated SDR, relative to the tuned frequency.
+pub struct SimulatedSource {
+    sender_connector: SenderConnector<Signal<Complex<f32>>>,
+    sender: Mutex<Option<Sender<Signal<Complex<f32>>>>>,
+    args: SimulatedArgs,
+    frequency: Arc<Mutex<f64>>,
+    sample_rate: Arc<Mutex<f64>>,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl_block_trait! { Producer<Signal<Complex<f32>>> for SimulatedSource }
+
+impl SimulatedSource {
+    pub fn new(
+        args: SimulatedArgs,
+        frequency: Arc<Mutex<f64>>,
+        sample_rate: Arc<Mutex<f64>>,
+    ) -> Self {
+        let (sender, sender_connector) = new_sender::<Signal<Complex<f32>>>();
+
+        Self {
+            sender_connector,
+            sender: Mutex::new(Some(sender)),
+            args,
+            frequency,
+            sample_rate,
+            stop_flag: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Start generating samples
+    pub fn activate(&self) -> Result<(), String> {
+        let mut generators = self
+            .args
+            .signals
+            .iter()
+            .map(|signal| SignalGenerator::new(signal.clone()))
+            .collect::<Result<Vec<SignalGenerator>, String>>()?;
+
+        let Some(sender) = self.sender.lock().unwrap().take() else {
+            return Err(String::from("Simulated stream is already active"));
+        };
+
+        let rt = tokio::runtime::Handle::current();
+        let noise_level = self.args.noise_level;
+        let pacing = self.args.pacing;
+        let frequency = self.frequency.clone();
+        let sample_rate = self.sample_rate.clone();
+        let stop_flag = self.stop_flag.clone();
+
+        thread::spawn(move || {
+            let mut buf_pool = ChunkBufPool::<Complex<f32>>::new();
+            // the noise is always the same, so the output is deterministic
+            let mut noise = Xorshift(0x2545f4914f6cdd1d);
+
+            // seconds of samples generated
+            let mut time: f64 = 0.0;
+            let start = Instant::now();
+
+            while !stop_flag.load(Ordering::SeqCst) {
+                let sample_rate = *sample_rate.lock().unwrap();
+                let tuned_freq = *frequency.lock().unwrap();
+
+                let mut output_chunk = buf_pool.get_with_capacity(CHUNK_SIZE);
+                for _ in 0..CHUNK_SIZE {
+                    let mut sample = Complex::new(0.0, 0.0);
+                    for generator in generators.iter_mut() {
+                        sample += generator.next_sample(time, tuned_freq, sample_rate);
+                    }
+
+                    output_chunk.push(Complex::new(
+                        sample.re as f32 + noise.next() * noise_level,
+                        sample.im as f32 + noise.next() * noise_level,
+                    ));
+
+                    time += 1.0 / sample_rate;
+                }
+
+                let Ok(()) = rt.block_on(sender.send(Signal::Samples {
+                    sample_rate,
+                    chunk: output_chunk.finalize(),
+                })) else {
+                    return;
+                };
+
+                if pacing == FilePacing::RealTime {
+                    let expected_elapsed = Duration::from_secs_f64(time);
+                    let elapsed = start.elapsed();
+                    if expected_elapsed > elapsed {
+                        thread::sleep(expected_elapsed - elapsed);
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+impl Drop for SimulatedSource {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+    }
+}
+
+struct SignalGenerator {
+    signal: SimulatedSignal,
+    // phase of the carrier relative to the tuned frequency
+    carrier_phase: f64,
+    // phase added by frequency modulation
+    fm_phase: f64,
+    rds_bits: Vec<u8>,
+    rds_bit_index: Option<u64>,
+    // the differentially encoded RDS bit that is currently being sent
+    rds_level: u8,
+    modes_messages: Vec<Vec<u8>>,
+}
+
+impl SignalGenerator {
+    fn new(signal: SimulatedSignal) -> Result<Self, String> {
+        let mut rds_bits = vec![];
+        let mut modes_messages = vec![];
+
+        match &signal {
+            SimulatedSignal::WbFm {
+                rds: Some(rds_args),
+                ..
+            } => {
+                rds_bits = build_rds_bits(rds_args);
+            }
+            SimulatedSignal::ModeS { messages, .. } => {
+                let messages = messages.clone().unwrap_or_else(|| {
+                    MODES_TEST_MESSAGES
+                        .iter()
+                        .map(|message| message.to_string())
+                        .collect()
+                });
+                for message in messages {
+                    modes_messages.push(parse_modes_message(&message)?);
+                }
+                if modes_messages.is_empty() {
+                    return Err(String::from("A Mode S signal needs at least one message"));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(Self {
+            signal,
+            carrier_phase: 0.0,
+            fm_phase: 0.0,
+            rds_bits,
+            rds_bit_index: None,
+            rds_level: 0,
+            modes_messages,
+        })
+    }
+
+    fn next_sample(&mut self, time: f64, tuned_freq: f64, sample_rate: f64) -> Complex<f64> {
+        let signal_freq = match &self.signal {
+            SimulatedSignal::WbFm { frequency, .. }
+            | SimulatedSignal::Am { frequency, .. }
+            | SimulatedSignal::ModeS { frequency, .. } => *frequency,
+        };
+        let freq_offset = signal_freq - tuned_freq;
+
+        // don't generate signals that can't be received
+        if freq_offset.abs() > sample_rate / 2.0 {
+            return Complex::new(0.0, 0.0);
+        }
+
+        let sample = match &self.signal {
+            SimulatedSignal::WbFm { tone_freq, .. } => {
+                let mut baseband = 0.8 * (2.0 * PI * tone_freq * time).sin()
+                    + 0.1 * (2.0 * PI * FM_PILOT_FREQ * time).sin();
+                if !self.rds_bits.is_empty() {
+                    baseband +=
+                        0.05 * self.rds_symbol(time) * (2.0 * PI * RDS_CARRIER_FREQ * time).cos();
+                }
+
+                self.fm_phase =
+                    (self.fm_phase + 2.0 * PI * FM_DEVIATION * baseband / sample_rate) % (2.0 * PI);
+                Complex::from_polar(0.5, self.carrier_phase + self.fm_phase)
+            }
+            SimulatedSignal::Am {
+                tone_freq,
+                modulation_depth,
+                ..
+            } => {
+                let envelope = 1.0 + modulation_depth * (2.0 * PI * tone_freq * time).sin();
+                Complex::from_polar(0.25 * envelope, self.carrier_phase)
+            }
+            SimulatedSignal::ModeS { .. } => {
+                // sample in the middle of the sample period so pulse edges line up with samples
+                let amplitude = if self.is_modes_pulse(time + 0.5 / sample_rate) {
+                    0.5
+                } else {
+                    0.0
+                };
+                Complex::from_polar(amplitude, self.carrier_phase)
+            }
+        };
+
+        self.carrier_phase =
+            (self.carrier_phase + 2.0 * PI * freq_offset / sample_rate) % (2.0 * PI);
+
+        sample
+    }
+
+    // get the biphase symbol (-1 or 1) of the RDS data at a point in time
+    fn rds_symbol(&mut self, time: f64) -> f64 {
+        let bit_position = time * RDS_BIT_RATE;
+        let bit_index = bit_position.floor() as u64;
+
+        // differentially encode each new bit
+        if self.rds_bit_index != Some(bit_index) {
+            self.rds_level ^= self.rds_bits[(bit_index % self.rds_bits.len() as u64) as usize];
+            self.rds_bit_index = Some(bit_index);
+        }
+
+        let is_first_half = bit_position - (bit_index as f64) < 0.5;
+        if (self.rds_level == 1) == is_first_half {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    fn is_modes_pulse(&self, time: f64) -> bool {
+        let message_index = (time / MODES_MESSAGE_INTERVAL).floor();
+        let message = &self.modes_messages[(message_index as usize) % self.modes_messages.len()];
+        let message_us = (time - message_index * MODES_MESSAGE_INTERVAL) * 1e6;
+
+        if message_us < MODES_PREAMBLE_US {
+            return MODES_PREAMBLE_PULSES
+                .iter()
+                .any(|(start, end)| message_us >= *start && message_us < *end);
+        }
+
+        // each bit is 1 microsecond, with a pulse in the first half for a 1 and the second half for a 0
+        let bit_position = message_us - MODES_PREAMBLE_US;
+        let Some(bit) = message.get(bit_position.floor() as usize) else {
+            return false;
+        };
+        let is_first_half = bit_position.fract() < 0.5;
+
+        (*bit == 1) == is_first_half
+    }
+}
+
+// converts a Mode S message written as bits or hex to a list of bits
+fn parse_modes_message(message: &str) -> Result<Vec<u8>, String> {
+    let bits: Vec<u8> = if message.chars().all(|c| c == '0' || c == '1') {
+        message
+            .chars()
+            .map(|c| if c == '1' { 1 } else { 0 })
+            .collect()
+    } else {
+        let mut bits = vec![];
+        for c in message.chars() {
+            let nibble = c
+                .to_digit(16)
+                .ok_or(format!("Invalid Mode S message {}", message))?;
+            for i in (0..4).rev() {
+                bits.push(((nibble >> i) & 1) as u8);
+            }
+        }
+        bits
+    };
+
+    if bits.len() != 56 && bits.len() != 112 {
+        return Err(format!("Mode S message {} must be 56 or 112 bits", message));
+    }
+
+    Ok(bits)
+}
+
+// builds the bits of the groups needed to send the whole program service name and radio text at least once
+fn build_rds_bits(rds_args: &SimulatedRdsArgs) -> Vec<u8> {
+    let ps: Vec<u8> = format!("{:<8}", rds_args.ps).bytes().take(8).collect();
+
+    // radio text shorter than 64 characters is ended with a carriage return
+    let mut rt: Vec<u8> = rds_args.rt.bytes().take(64).collect();
+    if rt.len() < 64 {
+        rt.push(b'\r');
+    }
+    while rt.len() % 4 != 0 {
+        rt.push(b' ');
+    }
+
+    let pty = ((rds_args.pty & 0b11111) as u16) << 5;
+
+    // every segment of the program service name is sent, even if the radio text has fewer segments
+    let rt_segments = rt.len() / 4;
+    let group_pairs = rt_segments.max(4);
+
+    let mut bits = vec![];
+    for group_pair in 0..group_pairs {
+        // group 0A (program service name), sent between each radio text segment
+        let ps_segment = group_pair % 4;
+        let rt_segment = group_pair % rt_segments;
+        // music/speech flag is set to music and the only decoder info flag set is stereo (segment 3)
+        let decoder_info = if ps_segment == 3 { 1 } else { 0 };
+        push_rds_group(
+            &mut bits,
+            [
+                rds_args.pi,
+                // group type 0 is all zeros
+                pty | (1 << 3) | (decoder_info << 2) | ps_segment as u16,
+                // no alternative frequencies
+                0xE0CD,
+                u16::from_be_bytes([ps[ps_segment * 2], ps[ps_segment * 2 + 1]]),
+            ],
+        );
+
+        // group 2A (radio text)
+        push_rds_group(
+            &mut bits,
+            [
+                rds_args.pi,
+                (0b0010 << 12) | pty | rt_segment as u16,
+                u16::from_be_bytes([rt[rt_segment * 4], rt[rt_segment * 4 + 1]]),
+                u16::from_be_bytes([rt[rt_segment * 4 + 2], rt[rt_segment * 4 + 3]]),
+            ],
+        );
+    }
+
+    bits
+}
+
+fn push_rds_group(bits: &mut Vec<u8>, blocks: [u16; 4]) {
+    for (block, offset_word) in blocks.iter().zip(RDS_OFFSET_WORDS) {
+        let checkword = rds_checkword(*block) ^ offset_word;
+        let block_bits = ((*block as u32) << 10) | checkword as u32;
+
+        for i in (0..26).rev() {
+            bits.push(((block_bits >> i) & 1) as u8);
+        }
+    }
+}
+
+// remainder of the data divided by the RDS generator polynomial
+fn rds_checkword(data: u16) -> u16 {
+    let mut remainder = (data as u32) << 10;
+    for i in (10..26).rev() {
+        if (remainder >> i) & 1 == 1 {
+            remainder ^= RDS_CRC_POLYNOMIAL << (i - 10);
+        }
+    }
+    (remainder & 0x3ff) as u16
+}
+
+// small random number generator for noise (https://en.wikipedia.org/wiki/Xorshift)
+struct Xorshift(u64);
+
+impl Xorshift {
+    // returns a value between -1 and 1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 as f64 / u64::MAX as f64 * 2.0 - 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use radiorust::{flow::new_receiver, prelude::*};
+
+    use super::*;
+    use crate::{
+        modes::types::ModeSState,
+        radiorust_blocks::{
+            adsb_decode::AdsbDecode,
+            am_demod::{AmDemod, AmDemodSettings},
+            rbds_decode::{new_rbds_receiver, RbdsState},
+        },
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn new_source(signal: SimulatedSignal, frequency: f64, sample_rate: f64) -> SimulatedSource {
+        SimulatedSource::new(
+            SimulatedArgs {
+                name: String::from("Test"),
+                signals: vec![signal],
+                noise_level: 0.01,
+                pacing: FilePacing::RealTime,
+            },
+            Arc::new(Mutex::new(frequency)),
+            Arc::new(Mutex::new(sample_rate)),
+        )
+    }
+
+    // a callback that passes along everything it is called with
+    fn channel<T: Send + 'static>() -> (Arc<dyn Fn(T) + Send + Sync>, mpsc::Receiver<T>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        (
+            Arc::new(move |value| {
+                let _ = sender.lock().unwrap().send(value);
+            }),
+            receiver,
+        )
+    }
+
+    // wait until the callback is called with a value that matches
+    fn wait_for<T>(receiver: &mpsc::Receiver<T>, is_match: impl Fn(&T) -> bool) -> bool {
+        let deadline = Instant::now() + TIMEOUT;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match receiver.recv_timeout(remaining) {
+                Ok(value) if is_match(&value) => return true,
+                Ok(_) => {}
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+
+    // decode the RDS of a simulated FM station, returning whether the whole station name and radio text were found
+    fn decode_rds(radio_text: &str) -> bool {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let source = new_source(
+            SimulatedSignal::WbFm {
+                frequency: 100e6,
+                tone_freq: 1_000.0,
+                rds: Some(SimulatedRdsArgs {
+                    pi: 0x1234,
+                    ps: String::from("SIM FM"),
+                    rt: radio_text.to_string(),
+                    pty: 1,
+                }),
+            },
+            100e6,
+            336_000.0,
+        );
+        let demodulator = blocks::modulation::FmDemod::<f32>::new(150_000.0);
+        demodulator.feed_from(&source);
+
+        let (rbds_callback, rbds_receiver) = channel::<RbdsState>();
+        let rbds_decoder = new_rbds_receiver(rbds_callback, |_| {});
+        rbds_decoder.feed_from(&demodulator);
+        source.activate().unwrap();
+
+        wait_for(&rbds_receiver, |state| {
+            state.pi == 0x1234
+                && state.service_name.trim() == "SIM FM"
+                && state.radio_text.trim() == radio_text
+        })
+    }
+
+    #[test]
+    fn sends_every_ps_segment() {
+        let bits = build_rds_bits(&SimulatedRdsArgs {
+            pi: 0x1234,
+            ps: String::from("SIM FM"),
+            rt: String::new(),
+            pty: 1,
+        });
+
+        // each group is 4 blocks of 26 bits, where the first 16 bits of block B have the group type and segment
+        let ps_segments: Vec<u16> = bits
+            .chunks(104)
+            .map(|group| {
+                group[26..42]
+                    .iter()
+                    .fold(0, |block_b, bit| (block_b << 1) | *bit as u16)
+            })
+            .filter(|block_b| block_b >> 11 == 0)
+            .map(|block_b| block_b & 0b11)
+            .collect();
+        assert_eq!(ps_segments, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn decodes_rds() {
+        assert!(decode_rds("Simulated FM station from the virtual SDR"));
+    }
+
+    #[test]
+    fn decodes_rds_with_short_radio_text() {
+        // there are fewer radio text segments than program service name segments
+        assert!(decode_rds("Hi"));
+        assert!(decode_rds(""));
+    }
+
+    #[test]
+    fn demodulates_am_tone() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let sample_rate = 48_000.0;
+        let source = new_source(
+            SimulatedSignal::Am {
+                frequency: 1_000e3,
+                tone_freq: 1_000.0,
+                modulation_depth: 0.5,
+            },
+            1_000e3,
+            sample_rate,
+        );
+        let demodulator = AmDemod::<f32>::new();
+        demodulator.feed_from(&source);
+
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<f32>>>();
+        receiver_connector.feed_from(&demodulator);
+        source.activate().unwrap();
+
+        // skip the start, where the filter is still settling
+        let mut audio: Vec<f32> = vec![];
+        while audio.len() < (sample_rate * 1.5) as usize {
+            let Ok(Signal::Samples { chunk, .. }) = rt.block_on(receiver.recv()) else {
+                continue;
+            };
+            audio.extend(chunk.iter().map(|sample| sample.re));
+        }
+        let audio = &audio[(sample_rate * 0.5) as usize..];
+
+        // the tone crosses its average twice every period
+        let average = audio.iter().sum::<f32>() / audio.len() as f32;
+        let crossings = audio
+            .windows(2)
+            .filter(|pair| (pair[0] < average) != (pair[1] < average))
+            .count();
+        let tone_freq = crossings as f64 / 2.0 / (audio.len() as f64 / sample_rate);
+        assert!(
+            (tone_freq - 1_000.0).abs() < 20.0,
+            "tone was {} hz",
+            tone_freq
+        );
+    }
+
+    #[test]
+    fn opens_am_squelch_for_constant_carrier() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        // the carrier is on from the start, like ATIS
+        let source = new_source(
+            SimulatedSignal::Am {
+                frequency: 118.1e6,
+                tone_freq: 1_000.0,
+                modulation_depth: 0.5,
+            },
+            118.1e6,
+            48_000.0,
+        );
+        let (squelch_callback, squelch_receiver) = channel::<bool>();
+        let demodulator = AmDemod::<f32>::with_settings(
+            AmDemodSettings {
+                agc: true,
+                squelch: Some(10.0),
+            },
+            Some(squelch_callback),
+        );
+        demodulator.feed_from(&source);
+        source.activate().unwrap();
+
+        assert!(wait_for(&squelch_receiver, |is_open| *is_open));
+    }
+
+    #[test]
+    fn decodes_modes_icao() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let source = new_source(
+            SimulatedSignal::ModeS {
+                frequency: 1090e6,
+                messages: Some(vec![MODES_TEST_MESSAGES[0].to_string()]),
+            },
+            1090e6,
+            2e6,
+        );
+        // the decoder looks for messages inside of each chunk, so they can't be split up too often
+        let rechunker = blocks::Rechunker::new(200_000);
+        rechunker.feed_from(&source);
+
+        let (modes_callback, modes_receiver) = channel();
+        let adsb_decode = AdsbDecode::<f32>::new(modes_callback, false);
+        adsb_decode.feed_from(&rechunker);
+        source.activate().unwrap();
+
+        assert!(wait_for(&modes_receiver, |state: &ModeSState| {
+            state
+                .aircraft
+                .iter()
+                .any(|aircraft| aircraft.icao_address == 0xAC42DF)
+        }));
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    f64::consts::TAU,
+    sync::{Arc, Mutex},
+};
+
+use radiorust::{
+    flow::{new_receiver, ReceiverConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use rustfft::{num_complex::Complex as FftComplex, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use tokio::spawn;
+
+const MIN_FFT_SIZE: usize = 64;
+const MAX_FFT_SIZE: usize = 65536;
+// more FFTs per frame barely reduce the noise, so extra samples are skipped to save CPU
+const MAX_AVERAGES: usize = 16;
+
+pub type SpectrumCallback = Arc<dyn Fn(SpectrumFrame) + Send + Sync>;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SpectrumSettings {
+    // number of frequency bins in each frame
+    pub fft_size: usize,
+    // how many frames are sent per second
+    pub frame_rate: f64,
+}
+
+impl Default for SpectrumSettings {
+    fn default() -> Self {
+        Self {
+            fft_size: 2048,
+            frame_rate: 20.0,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrumFrame {
+    pub center_frequency: f64,
+    pub sample_rate: f64,
+    // power of each bin in dBFS, from the lowest to the highest frequency
+    pub bins: Vec<f32>,
+}
+
+struct SpectrumConfig {
+    settings: SpectrumSettings,
+    center_frequency: f64,
+}
+
+/// A custom radiorust block that computes averaged power spectrums of an IQ stream, for displaying a spectrum and waterfall.
+pub struct SpectrumAnalyzer<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    config: Arc<Mutex<SpectrumConfig>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for SpectrumAnalyzer<Flt> }
+
+impl<Flt> SpectrumAnalyzer<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(
+        settings: SpectrumSettings,
+        center_frequency: f64,
+        spectrum_callback: SpectrumCallback,
+    ) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+
+        let config = Arc::new(Mutex::new(SpectrumConfig {
+            settings,
+            center_frequency,
+        }));
+
+        let config_clone = config.clone();
+        spawn(async move {
+            let mut current_settings: Option<SpectrumSettings> = None;
+            let mut current_sample_rate = 0.0;
+            let mut fft: Option<Arc<dyn Fft<f64>>> = None;
+            let mut window: Vec<f64> = vec![];
+            let mut window_gain = 1.0;
+
+            let mut fft_buffer: Vec<FftComplex<f64>> = vec![];
+            let mut power_sums: Vec<f64> = vec![];
+            let mut num_averages = 0;
+            let mut samples_since_frame = 0;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                let Signal::Samples {
+                    sample_rate,
+                    chunk: input_chunk,
+                } = signal
+                else {
+                    continue;
+                };
+
+                let (settings, center_frequency) = {
+                    let config = config_clone.lock().unwrap();
+                    (config.settings, config.center_frequency)
+                };
+
+                // restart the frame if the resolution or sample rate changes
+                if current_settings.map(|current| current.fft_size) != Some(settings.fft_size)
+                    || sample_rate != current_sample_rate
+                {
+                    let fft_size = settings.fft_size.clamp(MIN_FFT_SIZE, MAX_FFT_SIZE);
+                    fft = Some(FftPlanner::<f64>::new().plan_fft_forward(fft_size));
+
+                    // hann window
+                    window = (0..fft_size)
+                        .map(|i| 0.5 - 0.5 * (TAU * i as f64 / fft_size as f64).cos())
+                        .collect();
+                    window_gain = window.iter().sum::<f64>().powi(2);
+
+                    fft_buffer = Vec::with_capacity(fft_size);
+                    power_sums = vec![0.0; fft_size];
+                    num_averages = 0;
+                    samples_since_frame = 0;
+                }
+                current_settings = Some(settings);
+                current_sample_rate = sample_rate;
+
+                let Some(fft) = fft.as_ref() else {
+                    continue;
+                };
+                let fft_size = power_sums.len();
+                let samples_per_frame =
+                    ((sample_rate / settings.frame_rate.max(0.1)) as usize).max(fft_size);
+
+                for sample in input_chunk.iter() {
+                    samples_since_frame += 1;
+
+                    if num_averages < MAX_AVERAGES {
+                        let index = fft_buffer.len();
+                        fft_buffer.push(FftComplex::new(
+                            Into::<f64>::into(sample.re) * window[index],
+                            Into::<f64>::into(sample.im) * window[index],
+                        ));
+
+                        if fft_buffer.len() == fft_size {
+                            fft.process(&mut fft_buffer);
+                            for (power_sum, bin) in power_sums.iter_mut().zip(fft_buffer.iter()) {
+                                *power_sum += bin.norm_sqr();
+                            }
+                            num_averages += 1;
+                            fft_buffer.clear();
+                        }
+                    }
+
+                    if samples_since_frame >= samples_per_frame && num_averages > 0 {
+                        // move 0hz to the middle, and normalize so a full scale tone is 0 dBFS
+                        let scale = 1.0 / (num_averages as f64 * window_gain);
+                        let bins = (0..fft_size)
+                            .map(|i| {
+                                let power = power_sums[(i + fft_size / 2) % fft_size] * scale;
+                                (10.0 * power.max(1e-20).log10()) as f32
+                            })
+                            .collect();
+
+                        spectrum_callback(SpectrumFrame {
+                            center_frequency,
+                            sample_rate,
+                            bins,
+                        });
+
+                        power_sums.iter_mut().for_each(|power_sum| *power_sum = 0.0);
+                        num_averages = 0;
+                        samples_since_frame = 0;
+                        fft_buffer.clear();
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            config,
+        }
+    }
+
+    pub fn set_settings(&self, settings: SpectrumSettings) {
+        self.config.lock().unwrap().settings = settings;
+    }
+
+    pub fn set_center_frequency(&self, center_frequency: f64) {
+        self.config.lock().unwrap().center_frequency = center_frequency;
+    }
+}
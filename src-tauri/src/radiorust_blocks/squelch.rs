@@ -0,0 +1,259 @@
+use std::sync::{Arc, Mutex};
+
+use biquad::{Biquad, Coefficients, DirectForm1, ToHertz, Type, Q_BUTTERWORTH_F64};
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use tokio::spawn;
+
+// the noise of the FM discriminator is measured above the voice (in hz)
+const NOISE_HIGHPASS: f64 = 4_000.0;
+// how long the noise is averaged over (in seconds)
+const NOISE_TIME_CONSTANT: f64 = 0.02;
+// the noise (in dB) with no carrier, and how far below it the squelch can be set
+const NO_CARRIER_NOISE: f64 = -3.0;
+const SQUELCH_RANGE: f64 = 30.0;
+// the noise needs to rise this much above the threshold to close the squelch (in dB)
+const HYSTERESIS: f64 = 3.0;
+// how long the squelch stays open after the carrier goes away, so short fades aren't cut off (in seconds)
+const HANG_TIME: f64 = 0.25;
+// fade the audio in and out instead of clicking (in seconds)
+const FADE_TIME: f64 = 0.005;
+
+pub type SquelchCallback = Arc<dyn Fn(bool) + Send + Sync>;
+
+/// A custom radiorust block that silences demodulated FM audio when there is no carrier. The carrier is found by
+/// measuring the noise of the discriminator above the voice, which drops as the carrier gets stronger.
+///
+/// The level goes from 0 (always open) to 1 (only open for very strong carriers).
+pub struct Squelch<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    level: Arc<Mutex<f64>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for Squelch<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for Squelch<Flt> }
+
+impl<Flt> Squelch<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    /// `squelch_callback` is called whenever the squelch opens or closes
+    pub fn new(level: f64, squelch_callback: SquelchCallback) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let level = Arc::new(Mutex::new(level));
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        let level_clone = level.clone();
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut noise_filter: Option<DirectForm1<f64>> = None;
+            let mut noise_alpha: f64 = 0.0;
+            let mut fade_alpha: f64 = 0.0;
+            let mut hang_samples: usize = 0;
+
+            let mut noise_power: f64 = 1.0;
+            let mut is_open = false;
+            let mut samples_since_carrier: usize = 0;
+            let mut gain: f64 = 0.0;
+            let mut last_reported: Option<bool> = None;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        if sample_rate != current_sample_rate {
+                            current_sample_rate = sample_rate;
+
+                            noise_filter = if sample_rate > 2.0 * NOISE_HIGHPASS {
+                                Coefficients::<f64>::from_params(
+                                    Type::HighPass,
+                                    ToHertz::hz(sample_rate),
+                                    ToHertz::hz(NOISE_HIGHPASS),
+                                    Q_BUTTERWORTH_F64,
+                                )
+                                .ok()
+                                .map(DirectForm1::<f64>::new)
+                            } else {
+                                None
+                            };
+                            noise_alpha = 1.0 - (-1.0 / (NOISE_TIME_CONSTANT * sample_rate)).exp();
+                            fade_alpha = 1.0 - (-1.0 / (FADE_TIME * sample_rate)).exp();
+                            hang_samples = (HANG_TIME * sample_rate) as usize;
+                        }
+
+                        let level = level_clone.lock().unwrap().clamp(0.0, 1.0);
+                        let threshold = NO_CARRIER_NOISE - level * SQUELCH_RANGE;
+
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+
+                        for sample in input_chunk.iter() {
+                            let audio: f64 = sample.re.into();
+
+                            // without a high enough sample rate, the noise can't be measured, so the squelch stays open
+                            if let Some(filter) = noise_filter.as_mut() {
+                                let noise = filter.run(audio);
+                                noise_power += (noise * noise - noise_power) * noise_alpha;
+                            } else {
+                                noise_power = 0.0;
+                            }
+                            let noise_db = 10.0 * noise_power.max(f64::EPSILON).log10();
+
+                            if level == 0.0 || noise_db < threshold {
+                                is_open = true;
+                                samples_since_carrier = 0;
+                            } else if noise_db > threshold + HYSTERESIS {
+                                samples_since_carrier += 1;
+                                if samples_since_carrier > hang_samples {
+                                    is_open = false;
+                                }
+                            }
+
+                            gain += ((is_open as u8 as f64) - gain) * fade_alpha;
+
+                            output_chunk.push(Complex::new(
+                                Flt::from_f64(audio * gain).unwrap(),
+                                Flt::zero(),
+                            ));
+                        }
+
+                        if last_reported != Some(is_open) {
+                            squelch_callback(is_open);
+                            last_reported = Some(is_open);
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+            level,
+        }
+    }
+
+    pub fn set_level(&self, level: f64) {
+        *self.level.lock().unwrap() = level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f64::consts::TAU, sync::mpsc};
+
+    use radiorust::prelude::*;
+
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48_000.0;
+
+    // run a second of audio through a squelch at half its level, returning each time it opened or closed and
+    // the audio that came out
+    fn run_squelch(audio: impl Fn(usize) -> f64 + Send + 'static) -> (Vec<bool>, Vec<f32>) {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let (state_sender, state_receiver) = mpsc::channel();
+        let state_sender = Mutex::new(state_sender);
+        let squelch = Squelch::<f32>::new(
+            0.5,
+            Arc::new(move |is_open| {
+                let _ = state_sender.lock().unwrap().send(is_open);
+            }),
+        );
+
+        let (sender, sender_connector) = new_sender::<Signal<Complex<f32>>>();
+        squelch.feed_from(&sender_connector);
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<f32>>>();
+        receiver_connector.feed_from(&squelch);
+
+        let total_samples = SAMPLE_RATE as usize;
+        spawn(async move {
+            let mut buf_pool = ChunkBufPool::<Complex<f32>>::new();
+            for start in (0..total_samples).step_by(4_800) {
+                let mut chunk = buf_pool.get_with_capacity(4_800);
+                for index in start..start + 4_800 {
+                    chunk.push(Complex::new(audio(index) as f32, 0.0));
+                }
+                let Ok(()) = sender
+                    .send(Signal::Samples {
+                        sample_rate: SAMPLE_RATE,
+                        chunk: chunk.finalize(),
+                    })
+                    .await
+                else {
+                    return;
+                };
+            }
+        });
+
+        let mut output = vec![];
+        while output.len() < total_samples {
+            let Ok(Signal::Samples { chunk, .. }) = rt.block_on(receiver.recv()) else {
+                continue;
+            };
+            output.extend(chunk.iter().map(|sample| sample.re));
+        }
+
+        (state_receiver.try_iter().collect(), output)
+    }
+
+    // the same noise every time, from a xorshift generator, between -1 and 1
+    fn noise(index: usize) -> f64 {
+        let mut state = (index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as f64 / u64::MAX as f64 * 2.0 - 1.0
+    }
+
+    #[test]
+    fn stays_closed_on_noise() {
+        // with no carrier, the discriminator only puts out noise
+        let (states, output) = run_squelch(noise);
+
+        assert_eq!(states, vec![false]);
+        assert!(output.iter().all(|sample| sample.abs() < 0.01));
+    }
+
+    #[test]
+    fn opens_on_carrier() {
+        // a carrier quiets the noise, leaving the voice (a 1 kHz tone)
+        let (states, output) = run_squelch(|index| {
+            0.5 * (TAU * 1_000.0 * index as f64 / SAMPLE_RATE).sin() + 0.01 * noise(index)
+        });
+
+        assert_eq!(states.last(), Some(&true));
+        let last_half = &output[output.len() / 2..];
+        assert!(last_half.iter().any(|sample| sample.abs() > 0.4));
+    }
+}
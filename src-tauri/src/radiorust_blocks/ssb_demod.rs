@@ -0,0 +1,213 @@
+use std::{
+    f64::consts::TAU,
+    sync::{Arc, Mutex},
+};
+
+use biquad::{Biquad, Coefficients, DirectForm1, ToHertz, Type};
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use tokio::spawn;
+
+use super::biquad_filter::BUTTERWORTH_4_Q;
+
+// how quickly the AGC reacts to a louder signal, and how slowly it recovers once it is quieter (in seconds)
+const AGC_ATTACK_TIME: f64 = 0.002;
+const AGC_DECAY_TIME: f64 = 0.5;
+// the level the AGC keeps the audio at
+const AGC_TARGET: f64 = 0.3;
+// stops the AGC from endlessly amplifying silence
+const AGC_MIN_LEVEL: f64 = 1e-5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sideband {
+    Upper,
+    Lower,
+}
+
+/// How a [`SsbDemod`] demodulates the signal, which can be changed while running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SsbDemodSettings {
+    pub sideband: Sideband,
+    // moves the signal up in the audio (in hz), which sets the pitch of CW and fine tunes SSB
+    pub bfo_offset: f64,
+    // the lowest and highest audio frequencies that are kept (in hz)
+    pub low_cut: f64,
+    pub high_cut: f64,
+}
+
+// the filter and oscillators for the current settings and sample rate
+struct SsbFilter {
+    // turns the center of the passband into 0 hz, then back into audio
+    shift_step: f64,
+    center_step: f64,
+    re_lowpass: Vec<DirectForm1<f64>>,
+    im_lowpass: Vec<DirectForm1<f64>>,
+}
+
+impl SsbFilter {
+    fn new(settings: &SsbDemodSettings, sample_rate: f64) -> Self {
+        let center = (settings.low_cut + settings.high_cut) / 2.0;
+        let cutoff = ((settings.high_cut - settings.low_cut) / 2.0).max(1.0);
+
+        let lowpass = || -> Vec<DirectForm1<f64>> {
+            BUTTERWORTH_4_Q
+                .iter()
+                .filter_map(|&q| {
+                    Coefficients::<f64>::from_params(
+                        Type::LowPass,
+                        ToHertz::hz(sample_rate),
+                        ToHertz::hz(cutoff),
+                        q,
+                    )
+                    .ok()
+                    .map(DirectForm1::<f64>::new)
+                })
+                .collect()
+        };
+
+        Self {
+            shift_step: TAU * (settings.bfo_offset - center) / sample_rate,
+            center_step: TAU * center / sample_rate,
+            re_lowpass: lowpass(),
+            im_lowpass: lowpass(),
+        }
+    }
+}
+
+/// A custom radiorust block that demodulates single-sideband (SSB) and CW signals, whose carrier is at 0 hz. Only the
+/// chosen sideband between `low_cut` and `high_cut` is kept, and an AGC keeps the audio at the same volume.
+pub struct SsbDemod<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    settings: Arc<Mutex<SsbDemodSettings>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for SsbDemod<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for SsbDemod<Flt> }
+
+impl<Flt> SsbDemod<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(settings: SsbDemodSettings) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let settings = Arc::new(Mutex::new(settings));
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        let settings_clone = settings.clone();
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut current_settings: Option<SsbDemodSettings> = None;
+            let mut filter: Option<SsbFilter> = None;
+
+            let mut shift_phase: f64 = 0.0;
+            let mut center_phase: f64 = 0.0;
+
+            let mut agc_attack_alpha: f64 = 0.0;
+            let mut agc_decay_alpha: f64 = 0.0;
+            let mut agc_level: f64 = AGC_MIN_LEVEL;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        // the filter depends on both the settings and the sample rate
+                        let settings = *settings_clone.lock().unwrap();
+                        if sample_rate != current_sample_rate || current_settings != Some(settings)
+                        {
+                            current_sample_rate = sample_rate;
+                            current_settings = Some(settings);
+                            filter = Some(SsbFilter::new(&settings, sample_rate));
+
+                            agc_attack_alpha = 1.0 - (-1.0 / (AGC_ATTACK_TIME * sample_rate)).exp();
+                            agc_decay_alpha = 1.0 - (-1.0 / (AGC_DECAY_TIME * sample_rate)).exp();
+                        }
+                        let filter = filter.as_mut().unwrap();
+
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+
+                        for sample in input_chunk.iter() {
+                            let mut sample: Complex<f64> =
+                                Complex::new(sample.re.into(), sample.im.into());
+
+                            // mirror the lower sideband, so it is above the carrier like the upper sideband
+                            if settings.sideband == Sideband::Lower {
+                                sample = sample.conj();
+                            }
+
+                            // move the center of the passband to 0 hz, so a lowpass filter keeps only the passband
+                            let (shift_sin, shift_cos) = shift_phase.sin_cos();
+                            let shifted = sample * Complex::new(shift_cos, shift_sin);
+                            shift_phase = (shift_phase + filter.shift_step) % TAU;
+
+                            let mut filtered = shifted;
+                            for lowpass in filter.re_lowpass.iter_mut() {
+                                filtered.re = lowpass.run(filtered.re);
+                            }
+                            for lowpass in filter.im_lowpass.iter_mut() {
+                                filtered.im = lowpass.run(filtered.im);
+                            }
+
+                            // move the passband back to where it is heard, where the real part is the audio
+                            let (center_sin, center_cos) = center_phase.sin_cos();
+                            let audio = (filtered * Complex::new(center_cos, center_sin)).re;
+                            center_phase = (center_phase + filter.center_step) % TAU;
+
+                            let magnitude = audio.abs();
+                            let agc_alpha = if magnitude > agc_level {
+                                agc_attack_alpha
+                            } else {
+                                agc_decay_alpha
+                            };
+                            agc_level = (agc_level + (magnitude - agc_level) * agc_alpha)
+                                .max(AGC_MIN_LEVEL);
+
+                            output_chunk.push(Complex::new(
+                                Flt::from_f64(audio * AGC_TARGET / agc_level).unwrap(),
+                                Flt::zero(),
+                            ));
+                        }
+
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: output_chunk.finalize(),
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+            settings,
+        }
+    }
+
+    pub fn set_settings(&self, settings: SsbDemodSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+}
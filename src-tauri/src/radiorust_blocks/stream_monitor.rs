@@ -0,0 +1,85 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use tokio::spawn;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamHealth {
+    Healthy,
+    // no samples have been received recently
+    Stalled,
+    // the stream was closed, which happens when the SDR fails to read samples
+    Ended,
+}
+
+/// A custom radiorust block that passes the stream through unchanged, while keeping track of whether samples are still being received.
+pub struct StreamMonitor<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+    last_samples_time: Arc<Mutex<Instant>>,
+    has_ended: Arc<AtomicBool>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for StreamMonitor<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for StreamMonitor<Flt> }
+
+impl<Flt> StreamMonitor<Flt>
+where
+    Flt: Float,
+{
+    pub fn new() -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let last_samples_time = Arc::new(Mutex::new(Instant::now()));
+        let has_ended = Arc::new(AtomicBool::new(false));
+
+        let last_samples_time_clone = last_samples_time.clone();
+        let has_ended_clone = has_ended.clone();
+        spawn(async move {
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    has_ended_clone.store(true, Ordering::SeqCst);
+                    return;
+                };
+                if let Signal::Samples { .. } = signal {
+                    *last_samples_time_clone.lock().unwrap() = Instant::now();
+                }
+
+                let Ok(()) = sender.send(signal).await else {
+                    return;
+                };
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+            last_samples_time,
+            has_ended,
+        }
+    }
+
+    /// Check the stream, which is stalled if no samples were received within `timeout`
+    pub fn health(&self, timeout: Duration) -> StreamHealth {
+        if self.has_ended.load(Ordering::SeqCst) {
+            StreamHealth::Ended
+        } else if self.last_samples_time.lock().unwrap().elapsed() > timeout {
+            StreamHealth::Stalled
+        } else {
+            StreamHealth::Healthy
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::{f64::consts::TAU, sync::Arc};
+
+use biquad::{Biquad, Coefficients, DirectForm1, ToHertz, Type};
+use radiorust::{
+    flow::{new_receiver, ReceiverConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::Complex,
+    signal::Signal,
+};
+use serde::Serialize;
+use tokio::spawn;
+
+use super::biquad_filter::BUTTERWORTH_4_Q;
+
+// the standard CTCSS tones (in hz)
+const CTCSS_TONES: [f64; 51] = [
+    67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5, 94.8, 97.4, 100.0, 103.5, 107.2,
+    110.9, 114.8, 118.8, 123.0, 127.3, 131.8, 136.5, 141.3, 146.2, 150.0, 151.4, 156.7, 159.8,
+    162.2, 165.5, 167.9, 171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6, 199.5,
+    203.5, 206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3, 254.1,
+];
+// CTCSS and DCS are below the voice, so everything above this is removed (in hz)
+const SUBAUDIBLE_BANDWIDTH: f64 = 300.0;
+// the audio is downsampled to about this rate, since only the subaudible band is needed (in hz)
+const TONE_SAMPLE_RATE: f64 = 2_000.0;
+// long enough to tell apart the closest tones, which are 1.4 hz apart (in seconds)
+const CTCSS_WINDOW: f64 = 1.0;
+// how much of the subaudible band needs to be the tone, and how much stronger it needs to be than any other tone
+const CTCSS_MIN_RATIO: f64 = 0.3;
+const CTCSS_MIN_MARGIN: f64 = 2.0;
+const DCS_BIT_RATE: f64 = 134.4;
+// how quickly the bit clock is moved to the transitions of the signal
+const DCS_CLOCK_GAIN: f64 = 0.3;
+// the DC offset of the signal is averaged over this (in seconds)
+const DCS_DC_TIME_CONSTANT: f64 = 0.5;
+// the code is forgotten if it isn't seen again within this many bits
+const DCS_TIMEOUT_BITS: usize = 3 * DCS_WORD_BITS;
+// DCS codes are sent as a 23 bit Golay codeword: 9 bits of code, the fixed bits 100, then 11 parity bits
+const DCS_WORD_BITS: usize = 23;
+const DCS_WORD_MASK: u32 = (1 << DCS_WORD_BITS) - 1;
+const DCS_FIXED_BITS: u32 = 0b100;
+// x^11 + x^10 + x^6 + x^5 + x^4 + x^2 + 1
+const GOLAY_POLY: u32 = 0xC75;
+
+/// A subaudible tone that opens the squelch of radios listening to a repeater or channel
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum SubaudibleTone {
+    // in hz
+    Ctcss(f32),
+    // the code is shown in octal, and inverted codes are sent with the opposite polarity
+    Dcs { code: u16, inverted: bool },
+}
+
+pub type SubaudibleToneCallback = Arc<dyn Fn(Option<SubaudibleTone>) + Send + Sync>;
+
+/// A custom radiorust block that finds the CTCSS tone or DCS code in demodulated narrowband FM audio
+pub struct SubaudibleToneDetect<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for SubaudibleToneDetect<Flt> }
+
+impl<Flt> SubaudibleToneDetect<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    /// `tone_callback` is called whenever the tone or code changes, or is lost
+    pub fn new(tone_callback: SubaudibleToneCallback) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+
+        spawn(async move {
+            let mut current_sample_rate = 0.0;
+            let mut lowpass: Vec<DirectForm1<f64>> = vec![];
+            let mut decimation: usize = 1;
+            let mut samples_until_decimated: usize = 0;
+
+            let mut ctcss = CtcssDetector::new(TONE_SAMPLE_RATE);
+            let mut dcs = DcsDetector::new(TONE_SAMPLE_RATE);
+            let mut last_tone: Option<SubaudibleTone> = None;
+
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                let Signal::Samples {
+                    sample_rate,
+                    chunk: input_chunk,
+                } = signal
+                else {
+                    continue;
+                };
+
+                if sample_rate != current_sample_rate {
+                    current_sample_rate = sample_rate;
+
+                    lowpass = BUTTERWORTH_4_Q
+                        .iter()
+                        .filter_map(|&q| {
+                            Coefficients::<f64>::from_params(
+                                Type::LowPass,
+                                ToHertz::hz(sample_rate),
+                                ToHertz::hz(SUBAUDIBLE_BANDWIDTH),
+                                q,
+                            )
+                            .ok()
+                            .map(DirectForm1::<f64>::new)
+                        })
+                        .collect();
+                    decimation = ((sample_rate / TONE_SAMPLE_RATE) as usize).max(1);
+                    samples_until_decimated = 0;
+
+                    let decimated_rate = sample_rate / decimation as f64;
+                    ctcss = CtcssDetector::new(decimated_rate);
+                    dcs = DcsDetector::new(decimated_rate);
+                }
+
+                for sample in input_chunk.iter() {
+                    let mut value: f64 = sample.re.into();
+                    for filter in lowpass.iter_mut() {
+                        value = filter.run(value);
+                    }
+
+                    if samples_until_decimated > 0 {
+                        samples_until_decimated -= 1;
+                        continue;
+                    }
+                    samples_until_decimated = decimation - 1;
+
+                    dcs.process(value);
+                    let Some(ctcss_tone) = ctcss.process(value) else {
+                        continue;
+                    };
+
+                    // a DCS code is a data signal, so it wouldn't look like a tone as well
+                    let tone = match dcs.code() {
+                        Some((code, inverted)) => Some(SubaudibleTone::Dcs { code, inverted }),
+                        None => ctcss_tone.map(|freq| SubaudibleTone::Ctcss(freq as f32)),
+                    };
+                    if tone != last_tone {
+                        tone_callback(tone.clone());
+                        last_tone = tone;
+                    }
+                }
+            }
+        });
+
+        Self { receiver_connector }
+    }
+}
+
+// finds the strongest CTCSS tone with the Goertzel algorithm over each window
+struct CtcssDetector {
+    coefficients: Vec<f64>,
+    states: Vec<(f64, f64)>,
+    window_size: usize,
+    num_samples: usize,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl CtcssDetector {
+    fn new(sample_rate: f64) -> Self {
+        Self {
+            coefficients: CTCSS_TONES
+                .iter()
+                .map(|freq| 2.0 * (TAU * freq / sample_rate).cos())
+                .collect(),
+            states: vec![(0.0, 0.0); CTCSS_TONES.len()],
+            window_size: (CTCSS_WINDOW * sample_rate) as usize,
+            num_samples: 0,
+            sum: 0.0,
+            sum_squares: 0.0,
+        }
+    }
+
+    // returns the tone that was found at the end of each window
+    fn process(&mut self, value: f64) -> Option<Option<f64>> {
+        for (coefficient, (s1, s2)) in self.coefficients.iter().zip(self.states.iter_mut()) {
+            let s = value + coefficient * *s1 - *s2;
+            *s2 = *s1;
+            *s1 = s;
+        }
+        self.sum += value;
+        self.sum_squares += value * value;
+        self.num_samples += 1;
+
+        if self.num_samples < self.window_size {
+            return None;
+        }
+
+        // the DC offset from a mistuned station isn't part of the subaudible band
+        let n = self.num_samples as f64;
+        let energy = self.sum_squares - self.sum * self.sum / n;
+
+        // a tone that is the whole signal has a power of n/2 times the energy
+        let mut powers: Vec<(f64, f64)> = self
+            .coefficients
+            .iter()
+            .zip(self.states.iter())
+            .zip(CTCSS_TONES.iter())
+            .map(|((coefficient, (s1, s2)), freq)| {
+                let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+                (*freq, power / (n / 2.0 * energy.max(f64::EPSILON)))
+            })
+            .collect();
+        powers.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        self.states.iter_mut().for_each(|state| *state = (0.0, 0.0));
+        self.num_samples = 0;
+        self.sum = 0.0;
+        self.sum_squares = 0.0;
+
+        let (freq, ratio) = powers[0];
+        let runner_up = powers[1].1;
+        if ratio >= CTCSS_MIN_RATIO && ratio >= runner_up * CTCSS_MIN_MARGIN {
+            Some(Some(freq))
+        } else {
+            Some(None)
+        }
+    }
+}
+
+// recovers the bits of a DCS signal, and looks for a valid codeword in the last 23 bits
+struct DcsDetector {
+    bit_step: f64,
+    bit_phase: f64,
+    dc_alpha: f64,
+    dc_offset: f64,
+    was_high: bool,
+    word: u32,
+    num_bits: usize,
+    // the code that was last seen, and when it was seen
+    candidate: Option<(u16, bool, usize)>,
+    // the code that was seen twice in a row, and when it was last seen
+    confirmed: Option<(u16, bool, usize)>,
+}
+
+impl DcsDetector {
+    fn new(sample_rate: f64) -> Self {
+        Self {
+            bit_step: DCS_BIT_RATE / sample_rate,
+            bit_phase: 0.0,
+            dc_alpha: 1.0 - (-1.0 / (DCS_DC_TIME_CONSTANT * sample_rate)).exp(),
+            dc_offset: 0.0,
+            was_high: false,
+            word: 0,
+            num_bits: 0,
+            candidate: None,
+            confirmed: None,
+        }
+    }
+
+    fn process(&mut self, value: f64) {
+        self.dc_offset += (value - self.dc_offset) * self.dc_alpha;
+        let is_high = value > self.dc_offset;
+
+        let previous_phase = self.bit_phase;
+        self.bit_phase += self.bit_step;
+
+        // transitions happen between bits, so move the clock toward them
+        if is_high != self.was_high {
+            let error = self.bit_phase - self.bit_phase.round();
+            self.bit_phase -= error * DCS_CLOCK_GAIN;
+        }
+        self.was_high = is_high;
+
+        // sample in the middle of each bit, where the first bit ends up at the bottom of the word
+        if previous_phase < 0.5 && self.bit_phase >= 0.5 {
+            self.word = (self.word >> 1) | ((is_high as u32) << (DCS_WORD_BITS - 1));
+            self.num_bits += 1;
+            self.check_word();
+        }
+        if self.bit_phase >= 1.0 {
+            self.bit_phase -= 1.0;
+        }
+    }
+
+    fn check_word(&mut self) {
+        let found = decode_dcs_word(self.word)
+            .map(|code| (code, false))
+            .or_else(|| decode_dcs_word(!self.word & DCS_WORD_MASK).map(|code| (code, true)));
+
+        if let Some((code, inverted)) = found {
+            // the codeword is sent over and over, so it should be seen again exactly one word later
+            if let Some((candidate_code, candidate_inverted, seen_at)) = self.candidate {
+                if candidate_code == code
+                    && candidate_inverted == inverted
+                    && self.num_bits - seen_at == DCS_WORD_BITS
+                {
+                    self.confirmed = Some((code, inverted, self.num_bits));
+                }
+            }
+            self.candidate = Some((code, inverted, self.num_bits));
+        }
+
+        if let Some((_, _, seen_at)) = self.confirmed {
+            if self.num_bits - seen_at > DCS_TIMEOUT_BITS {
+                self.confirmed = None;
+            }
+        }
+    }
+
+    fn code(&self) -> Option<(u16, bool)> {
+        self.confirmed.map(|(code, inverted, _)| (code, inverted))
+    }
+}
+
+// returns the code if the word is a valid DCS codeword
+fn decode_dcs_word(word: u32) -> Option<u16> {
+    let data = word & 0xFFF;
+    if data >> 9 != DCS_FIXED_BITS || golay_parity(data) != word >> 12 {
+        return None;
+    }
+    Some((data & 0x1FF) as u16)
+}
+
+// the 11 parity bits of the (23, 12) Golay code
+fn golay_parity(data: u32) -> u32 {
+    let mut remainder = data << 11;
+    for bit in (11..DCS_WORD_BITS).rev() {
+        if remainder & (1 << bit) != 0 {
+            remainder ^= GOLAY_POLY << (bit - 11);
+        }
+    }
+    remainder & 0x7FF
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{mpsc, Mutex},
+        time::Duration,
+    };
+
+    use radiorust::{flow::new_sender, prelude::*};
+
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48_000.0;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    // one window of a tone at the rate the detector runs at
+    fn detect_ctcss(freq: f64) -> Option<Option<f64>> {
+        let mut detector = CtcssDetector::new(TONE_SAMPLE_RATE);
+        (0..(CTCSS_WINDOW * TONE_SAMPLE_RATE) as usize)
+            .map(|index| (TAU * freq * index as f64 / TONE_SAMPLE_RATE).sin())
+            .filter_map(|value| detector.process(value))
+            .last()
+    }
+
+    #[test]
+    fn tells_apart_neighbouring_tones() {
+        assert_eq!(detect_ctcss(100.0), Some(Some(100.0)));
+        assert_eq!(detect_ctcss(97.4), Some(Some(97.4)));
+        assert_eq!(detect_ctcss(103.5), Some(Some(103.5)));
+    }
+
+    #[test]
+    fn detects_ctcss_under_voice() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let (tone_sender, tone_receiver) = mpsc::channel();
+        let tone_sender = Mutex::new(tone_sender);
+        let detector = SubaudibleToneDetect::<f32>::new(Arc::new(move |tone| {
+            let _ = tone_sender.lock().unwrap().send(tone);
+        }));
+        let (sender, sender_connector) = new_sender::<Signal<Complex<f32>>>();
+        detector.feed_from(&sender_connector);
+
+        // a 1 kHz tone stands in for the voice, which is much louder than the 100.0 hz tone
+        spawn(async move {
+            let mut buf_pool = ChunkBufPool::<Complex<f32>>::new();
+            for start in (0..(SAMPLE_RATE * 4.0) as usize).step_by(4_800) {
+                let mut chunk = buf_pool.get_with_capacity(4_800);
+                for index in start..start + 4_800 {
+                    let time = index as f64 / SAMPLE_RATE;
+                    let audio =
+                        0.5 * (TAU * 1_000.0 * time).sin() + 0.1 * (TAU * 100.0 * time).sin();
+                    chunk.push(Complex::new(audio as f32, 0.0));
+                }
+                let Ok(()) = sender
+                    .send(Signal::Samples {
+                        sample_rate: SAMPLE_RATE,
+                        chunk: chunk.finalize(),
+                    })
+                    .await
+                else {
+                    return;
+                };
+            }
+        });
+
+        // the first window is taken up by the filter settling, but every tone after that should be the same
+        let tone = tone_receiver.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(tone, Some(SubaudibleTone::Ctcss(100.0)));
+        assert!(tone_receiver.recv_timeout(Duration::from_secs(1)).is_err());
+    }
+}
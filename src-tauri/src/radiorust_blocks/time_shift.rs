@@ -0,0 +1,410 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use radiorust::{
+    flow::{new_receiver, new_sender, ReceiverConnector, SenderConnector},
+    impl_block_trait,
+    numbers::Float,
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use serde::Serialize;
+use tokio::spawn;
+
+use super::{
+    audio_recorder::RecordingChannels,
+    recording::{from_i16, to_i16},
+};
+
+// how often the position is reported while the audio plays (in seconds)
+const REPORT_INTERVAL: f64 = 0.5;
+
+pub type TimeShiftCallback = Arc<dyn Fn(TimeShiftPosition) + Send + Sync>;
+
+/// Where playback is in the time-shift buffer
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeShiftPosition {
+    // how far behind live the audio is (in seconds)
+    pub offset: f64,
+    // how much audio can be rewound to (in seconds)
+    pub buffered: f64,
+    pub is_paused: bool,
+}
+
+struct TimeShiftState {
+    // how much audio is kept (in seconds)
+    length: f64,
+    sample_rate: f64,
+    // the latest audio, where the newest sample is at the back (16-bit, and only the channels that are used, so long
+    // buffers don't use too much memory)
+    history: VecDeque<i16>,
+    // how many samples behind live playback is
+    delay: usize,
+    is_paused: bool,
+    samples_since_report: usize,
+}
+
+/// A handle to pause, rewind, and catch up to live audio through a [`TimeShiftBlock`]. It can be kept between blocks,
+/// so the buffered audio isn't lost when the SDR is reconnected.
+#[derive(Clone)]
+pub struct TimeShift {
+    state: Arc<Mutex<TimeShiftState>>,
+    // interleaved audio has two samples for each moment, which need to stay in order
+    frame_size: usize,
+    // how many values of the history make up each sample (only stereo uses the imaginary part)
+    sample_channels: usize,
+    callback: TimeShiftCallback,
+}
+
+impl TimeShift {
+    pub fn new(length: f64, channels: RecordingChannels, callback: TimeShiftCallback) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TimeShiftState {
+                length,
+                sample_rate: 0.0,
+                history: VecDeque::new(),
+                delay: 0,
+                is_paused: false,
+                samples_since_report: 0,
+            })),
+            frame_size: match channels {
+                RecordingChannels::Interleaved => 2,
+                RecordingChannels::Mono | RecordingChannels::Stereo => 1,
+            },
+            sample_channels: match channels {
+                RecordingChannels::Stereo => 2,
+                RecordingChannels::Mono | RecordingChannels::Interleaved => 1,
+            },
+            callback,
+        }
+    }
+
+    /// Change how much audio is kept, where the oldest audio is dropped if it is shortened
+    pub fn set_length(&self, length: f64) {
+        self.state.lock().unwrap().length = length;
+    }
+
+    /// Keep buffering the audio without playing it, so it continues from the same place once it is resumed
+    pub fn set_paused(&self, is_paused: bool) {
+        self.state.lock().unwrap().is_paused = is_paused;
+        self.report();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().is_paused
+    }
+
+    /// Play the audio from `offset` seconds behind live, where 0 catches up to live
+    pub fn seek(&self, offset: f64) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let delay = (offset.max(0.0) * state.sample_rate) as usize * self.frame_size;
+            state.delay = self.clamp_delay(&state, delay);
+        }
+        self.report();
+    }
+
+    /// Move the audio forward (towards live) or backward by a number of seconds
+    pub fn skip(&self, seconds: f64) {
+        let offset = self.position().offset;
+        self.seek(offset - seconds);
+    }
+
+    pub fn position(&self) -> TimeShiftPosition {
+        self.position_of(&self.state.lock().unwrap())
+    }
+
+    fn report(&self) {
+        (self.callback)(self.position());
+    }
+
+    fn position_of(&self, state: &TimeShiftState) -> TimeShiftPosition {
+        let samples_per_second = state.sample_rate * self.frame_size as f64;
+        let seconds = |samples: usize| {
+            if samples_per_second > 0.0 {
+                samples as f64 / samples_per_second
+            } else {
+                0.0
+            }
+        };
+        TimeShiftPosition {
+            offset: seconds(state.delay),
+            buffered: seconds(self.buffered_samples(state)),
+            is_paused: state.is_paused,
+        }
+    }
+
+    fn buffered_samples(&self, state: &TimeShiftState) -> usize {
+        state.history.len() / self.sample_channels
+    }
+
+    // playback can't be further behind than the audio that is buffered
+    fn clamp_delay(&self, state: &TimeShiftState, delay: usize) -> usize {
+        let delay = delay.min(self.buffered_samples(state));
+        delay - delay % self.frame_size
+    }
+
+    // keep the buffered audio when the sample rate changes, by picking the closest moments at the new rate
+    fn resample(&self, state: &mut TimeShiftState, sample_rate: f64) {
+        let moment_size = self.frame_size * self.sample_channels;
+        let ratio = state.sample_rate / sample_rate;
+        let moments = state.history.len() / moment_size;
+        let new_moments = (moments as f64 / ratio) as usize;
+
+        let history = std::mem::take(&mut state.history);
+        state.history = (0..new_moments)
+            .flat_map(|moment| {
+                let start = ((moment as f64 * ratio) as usize).min(moments - 1) * moment_size;
+                history.range(start..start + moment_size).copied()
+            })
+            .collect();
+
+        let delay = (state.delay as f64 / ratio) as usize;
+        state.delay = self.clamp_delay(state, delay);
+    }
+}
+
+/// A custom radiorust block that plays audio from a time-shift buffer, which is controlled by its [`TimeShift`]. The
+/// latest audio is always kept, so pausing outputs silence while the broadcast is buffered, instead of losing it.
+pub struct TimeShiftBlock<Flt> {
+    receiver_connector: ReceiverConnector<Signal<Complex<Flt>>>,
+    sender_connector: SenderConnector<Signal<Complex<Flt>>>,
+}
+
+impl_block_trait! { <Flt> Consumer<Signal<Complex<Flt>>> for TimeShiftBlock<Flt> }
+impl_block_trait! { <Flt> Producer<Signal<Complex<Flt>>> for TimeShiftBlock<Flt> }
+
+impl<Flt> TimeShiftBlock<Flt>
+where
+    Flt: Float + Into<f64>,
+{
+    pub fn new(time_shift: TimeShift) -> Self {
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<Flt>>>();
+        let (sender, sender_connector) = new_sender::<Signal<Complex<Flt>>>();
+
+        let mut buf_pool = ChunkBufPool::<Complex<Flt>>::new();
+
+        spawn(async move {
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    return;
+                };
+                match signal {
+                    Signal::Samples {
+                        sample_rate,
+                        chunk: input_chunk,
+                    } => {
+                        let mut output_chunk = buf_pool.get_with_capacity(input_chunk.len());
+                        let mut position = None;
+                        let mut is_live = false;
+
+                        {
+                            let mut state = time_shift.state.lock().unwrap();
+                            if sample_rate != state.sample_rate {
+                                if state.sample_rate > 0.0 {
+                                    time_shift.resample(&mut state, sample_rate);
+                                }
+                                state.sample_rate = sample_rate;
+                            }
+
+                            // add the new audio
+                            let max_samples = (state.length.max(0.0) * sample_rate) as usize
+                                * time_shift.frame_size;
+                            for sample in input_chunk.iter() {
+                                state.history.push_back(to_i16(sample.re));
+                                if time_shift.sample_channels == 2 {
+                                    state.history.push_back(to_i16(sample.im));
+                                }
+                            }
+
+                            // playback falls further behind live while paused, but never further than the buffer
+                            let delay = if state.is_paused {
+                                state.delay + input_chunk.len()
+                            } else {
+                                state.delay
+                            };
+                            state.delay = time_shift.clamp_delay(&state, delay.min(max_samples));
+
+                            if state.is_paused {
+                                output_chunk.extend(
+                                    (0..input_chunk.len()).map(|_| Complex::from(Flt::zero())),
+                                );
+                            } else if state.delay == 0 {
+                                is_live = true;
+                            } else {
+                                // any audio from before the buffer started is silent
+                                let start = time_shift.buffered_samples(&state) as isize
+                                    - state.delay as isize
+                                    - input_chunk.len() as isize;
+                                output_chunk.extend((0..input_chunk.len()).map(|i| {
+                                    let index = start + i as isize;
+                                    if index < 0 {
+                                        return Complex::from(Flt::zero());
+                                    }
+                                    let index = index as usize * time_shift.sample_channels;
+                                    Complex::new(
+                                        from_i16(state.history[index]),
+                                        if time_shift.sample_channels == 2 {
+                                            from_i16(state.history[index + 1])
+                                        } else {
+                                            Flt::zero()
+                                        },
+                                    )
+                                }));
+                            }
+
+                            // drop anything older than the length of the buffer, once the audio that was
+                            // rewound to has been played
+                            let extra_samples = time_shift
+                                .buffered_samples(&state)
+                                .saturating_sub(max_samples);
+                            state
+                                .history
+                                .drain(..extra_samples * time_shift.sample_channels);
+
+                            state.samples_since_report += input_chunk.len();
+                            if state.samples_since_report as f64
+                                >= REPORT_INTERVAL * sample_rate * time_shift.frame_size as f64
+                            {
+                                state.samples_since_report = 0;
+                                position = Some(time_shift.position_of(&state));
+                            }
+                        }
+
+                        if let Some(position) = position {
+                            (time_shift.callback)(position);
+                        }
+
+                        // live audio is passed along as it is
+                        let Ok(()) = sender
+                            .send(Signal::Samples {
+                                sample_rate,
+                                chunk: if is_live {
+                                    input_chunk
+                                } else {
+                                    output_chunk.finalize()
+                                },
+                            })
+                            .await
+                        else {
+                            return;
+                        };
+                    }
+                    Signal::Event(event) => {
+                        let Ok(()) = sender.send(Signal::Event(event)).await else {
+                            return;
+                        };
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver_connector,
+            sender_connector,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use radiorust::{
+        flow::{Receiver, Sender},
+        prelude::*,
+    };
+
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 1_000.0;
+    const CHUNK_SIZE: usize = 100;
+
+    // every sample is different, so it can be told where the audio came from
+    fn ramp(index: usize) -> f32 {
+        index as f32 / 4_000.0
+    }
+
+    // what a sample sounds like after being kept in the buffer
+    fn buffered(index: usize) -> f32 {
+        from_i16(to_i16(ramp(index)))
+    }
+
+    // play a chunk of the ramp through the block, returning the audio that comes out
+    fn play(
+        rt: &tokio::runtime::Runtime,
+        sender: &Sender<Signal<Complex<f32>>>,
+        receiver: &mut Receiver<Signal<Complex<f32>>>,
+        start: usize,
+    ) -> Vec<f32> {
+        let mut buf_pool = ChunkBufPool::<Complex<f32>>::new();
+        let mut chunk = buf_pool.get_with_capacity(CHUNK_SIZE);
+        chunk.extend((start..start + CHUNK_SIZE).map(|index| Complex::new(ramp(index), 0.0)));
+
+        rt.block_on(async {
+            let sent = sender
+                .send(Signal::Samples {
+                    sample_rate: SAMPLE_RATE,
+                    chunk: chunk.finalize(),
+                })
+                .await;
+            assert!(sent.is_ok());
+            loop {
+                let Ok(signal) = receiver.recv().await else {
+                    panic!("the time-shift block stopped");
+                };
+                if let Signal::Samples { chunk, .. } = signal {
+                    return chunk.iter().map(|sample| sample.re).collect();
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn rewind_is_clamped_to_buffer() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let time_shift = TimeShift::new(
+            1.0,
+            RecordingChannels::Mono,
+            Arc::new(|_: TimeShiftPosition| {}),
+        );
+        let block = TimeShiftBlock::<f32>::new(time_shift.clone());
+        let (sender, sender_connector) = new_sender::<Signal<Complex<f32>>>();
+        block.feed_from(&sender_connector);
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<f32>>>();
+        receiver_connector.feed_from(&block);
+
+        // three times as much audio as the buffer keeps, which is played live
+        for start in (0..3_000).step_by(CHUNK_SIZE) {
+            let audio = play(&rt, &sender, &mut receiver, start);
+            assert_eq!(
+                audio,
+                (start..start + CHUNK_SIZE).map(ramp).collect::<Vec<_>>()
+            );
+        }
+
+        // only the last second can be rewound to
+        time_shift.seek(5.0);
+        let position = time_shift.position();
+        assert_eq!(position.offset, 1.0);
+        assert_eq!(position.buffered, 1.0);
+
+        // the audio from a second ago is played, and continues from there
+        for start in (3_000..3_300).step_by(CHUNK_SIZE) {
+            let audio = play(&rt, &sender, &mut receiver, start);
+            let expected: Vec<_> = (start - 1_000..start - 1_000 + CHUNK_SIZE)
+                .map(buffered)
+                .collect();
+            assert_eq!(audio, expected);
+        }
+        assert_eq!(time_shift.position().offset, 1.0);
+
+        // catching up plays live audio again
+        time_shift.seek(0.0);
+        let audio = play(&rt, &sender, &mut receiver, 3_300);
+        assert_eq!(audio, (3_300..3_400).map(ramp).collect::<Vec<_>>());
+    }
+}
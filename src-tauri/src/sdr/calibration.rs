@@ -0,0 +1,268 @@
+use std::f64::consts::TAU;
+
+use log::info;
+use radiorust::flow::Consumer;
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+use soapysdr::Direction;
+
+use crate::{
+    error::{RadioError, RadioResult},
+    radiorust_blocks::sample_capture::SampleCapture,
+};
+
+use super::{
+    device::SDRDevice,
+    enumeration::AvailableSDRArgs,
+    frequency_correction::{
+        apply_frequency_correction, save_frequency_correction, set_device_frequency_correction,
+    },
+    get_sdr_dev, release_sdr_dev, SDRRegistry,
+};
+
+const CALIBRATION_SAMPLE_RATE: f64 = 1.024e6;
+// samples at the start of the stream are discarded while the tuner settles (in seconds)
+const CALIBRATION_SETTLE_TIME: f64 = 0.5;
+// longer measurements are more precise (in seconds)
+const CALIBRATION_DURATION: f64 = 4.0;
+// the largest error expected from a cheap crystal (in PPM)
+const MAX_PPM_ERROR: f64 = 100.0;
+
+const FM_PILOT_FREQ: f64 = 19_000.0;
+// carriers are tuned off-center to avoid the DC spike of many SDRs (in hz)
+const CARRIER_OFFSET: f64 = 250e3;
+const MAX_CARRIER_SEARCH_RANGE: f64 = 200e3;
+
+const COARSE_FFT_SIZE: usize = 1 << 16;
+const FINE_BLOCK_SIZE: usize = 1024;
+// how much stronger than the noise floor the calibration signal must be
+const MIN_PEAK_TO_NOISE: f64 = 10.0;
+
+/// A signal with a precisely known frequency (in hz) to calibrate against
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum CalibrationSource {
+    // a stereo FM station, whose 19 kHz pilot tone is locked to an accurate reference. This
+    // measures the error of the sample clock, which shares a crystal with the tuner on RTL-SDRs.
+    FmPilot { frequency: f64 },
+    // an unmodulated carrier, such as a time signal or beacon
+    Carrier { frequency: f64 },
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CalibrationResult {
+    pub ppm: f64,
+    // how far the SDR was tuned from the calibration frequency without correction (in hz)
+    pub frequency_offset: f64,
+}
+
+impl CalibrationResult {
+    fn new(ppm: f64, frequency: f64) -> Self {
+        Self {
+            ppm,
+            frequency_offset: frequency * ppm / 1e6,
+        }
+    }
+}
+
+/// Measure the frequency error of an SDR and save it as the SDR's frequency correction
+pub async fn calibrate_sdr(
+    sdr_registry: SDRRegistry,
+    args: AvailableSDRArgs,
+    source: CalibrationSource,
+) -> RadioResult<CalibrationResult> {
+    let (sdr_dev, sdr_args) = get_sdr_dev(&sdr_registry, args)?;
+
+    info!("Calibrating {} with {:?}", sdr_args.label, source);
+
+    let result = measure_frequency_error(&sdr_dev, source)
+        .await
+        .map_err(RadioError::Device)
+        .and_then(|calibration| {
+            save_frequency_correction(sdr_registry.data_dir(), &sdr_args, calibration.ppm)
+                .map(|_| calibration)
+                .map_err(RadioError::Storage)
+        });
+
+    // use the new correction, or restore the previous one if calibration failed
+    match &result {
+        Ok(calibration) => set_device_frequency_correction(&sdr_dev, &sdr_args, calibration.ppm),
+        Err(_) => apply_frequency_correction(sdr_registry.data_dir(), &sdr_dev, &sdr_args),
+    }
+    release_sdr_dev(&sdr_registry, sdr_dev, sdr_args)?;
+
+    result
+}
+
+async fn measure_frequency_error(
+    sdr_dev: &SDRDevice,
+    source: CalibrationSource,
+) -> Result<CalibrationResult, String> {
+    let (frequency, tuned_frequency) = match source {
+        CalibrationSource::FmPilot { frequency } => (frequency, frequency),
+        CalibrationSource::Carrier { frequency } => (frequency, frequency + CARRIER_OFFSET),
+    };
+
+    let capabilities = sdr_dev.capabilities(Direction::Rx, 0)?;
+    if !capabilities.supports_frequency(tuned_frequency) {
+        return Err(format!(
+            "The SDR can't tune to {} MHz",
+            tuned_frequency / 1e6
+        ));
+    }
+
+    // measure the uncorrected error (not all SDRs support correction, which is fine)
+    let _ = sdr_dev.set_frequency_correction(Direction::Rx, 0, 0.0);
+
+    if capabilities.has_automatic_gain {
+        sdr_dev.set_gain_mode(Direction::Rx, 0, true)?;
+    } else if capabilities.has_manual_gain() {
+        sdr_dev.set_gain(Direction::Rx, 0, capabilities.gain_range.maximum)?;
+    }
+
+    let requested_sample_rate = capabilities.closest_sample_rate(CALIBRATION_SAMPLE_RATE);
+    sdr_dev.set_sample_rate(Direction::Rx, 0, requested_sample_rate)?;
+    sdr_dev.set_frequency(Direction::Rx, 0, tuned_frequency, "")?;
+
+    let sdr_rx = sdr_dev.rx_stream(requested_sample_rate)?;
+    let sample_capture = SampleCapture::<f32>::new(
+        ((CALIBRATION_SETTLE_TIME + CALIBRATION_DURATION) * requested_sample_rate) as usize,
+    );
+    sample_capture.feed_from(&sdr_rx);
+
+    sdr_rx.activate().await?;
+    let (raw_samples, sample_rate) = sample_capture.samples().await?;
+    drop(sdr_rx);
+
+    // the analysis takes a while, so keep it off of the async runtime
+    tokio::task::spawn_blocking(move || {
+        let settle_samples =
+            ((CALIBRATION_SETTLE_TIME * sample_rate) as usize).min(raw_samples.len());
+        let samples: Vec<Complex<f64>> = raw_samples[settle_samples..]
+            .iter()
+            .map(|sample| Complex::new(sample.re as f64, sample.im as f64))
+            .collect();
+
+        // a crystal that is fast by some PPM makes every signal appear low by the same PPM
+        match source {
+            CalibrationSource::FmPilot { frequency } => {
+                let pilot_freq = measure_tone_frequency(
+                    &fm_demodulate(&samples),
+                    sample_rate,
+                    FM_PILOT_FREQ,
+                    FM_PILOT_FREQ * MAX_PPM_ERROR / 1e6,
+                )?;
+
+                Ok(CalibrationResult::new(
+                    -(pilot_freq - FM_PILOT_FREQ) / FM_PILOT_FREQ * 1e6,
+                    frequency,
+                ))
+            }
+            CalibrationSource::Carrier { frequency } => {
+                let carrier_freq = measure_tone_frequency(
+                    &samples,
+                    sample_rate,
+                    -CARRIER_OFFSET,
+                    (frequency * MAX_PPM_ERROR / 1e6).min(MAX_CARRIER_SEARCH_RANGE),
+                )?;
+
+                Ok(CalibrationResult::new(
+                    -(carrier_freq + CARRIER_OFFSET) / frequency * 1e6,
+                    frequency,
+                ))
+            }
+        }
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+// get the instantaneous frequency of each sample (in radians per sample)
+fn fm_demodulate(samples: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    samples
+        .windows(2)
+        .map(|pair| Complex::new((pair[1] * pair[0].conj()).arg(), 0.0))
+        .collect()
+}
+
+/// Find the precise frequency of a tone within `search_range` of `expected_freq` (in hz).
+/// The strongest FFT bin gives a coarse estimate, which is refined by how quickly the
+/// phase of the tone drifts once it is mixed down to 0 hz.
+fn measure_tone_frequency(
+    samples: &[Complex<f64>],
+    sample_rate: f64,
+    expected_freq: f64,
+    search_range: f64,
+) -> Result<f64, String> {
+    if samples.len() < COARSE_FFT_SIZE {
+        return Err(String::from(
+            "Not enough samples were captured to calibrate",
+        ));
+    }
+
+    // average the spectrum of each segment to reduce the noise
+    let mut planner = FftPlanner::<f64>::new();
+    let fft = planner.plan_fft_forward(COARSE_FFT_SIZE);
+    let window: Vec<f64> = (0..COARSE_FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (TAU * i as f64 / COARSE_FFT_SIZE as f64).cos())
+        .collect();
+
+    let mut power = vec![0.0; COARSE_FFT_SIZE];
+    let mut buffer = vec![Complex::default(); COARSE_FFT_SIZE];
+    for segment in samples.chunks_exact(COARSE_FFT_SIZE) {
+        for (i, sample) in segment.iter().enumerate() {
+            buffer[i] = *sample * window[i];
+        }
+        fft.process(&mut buffer);
+        for (bin_power, bin) in power.iter_mut().zip(buffer.iter()) {
+            *bin_power += bin.norm_sqr();
+        }
+    }
+
+    let bin_width = sample_rate / COARSE_FFT_SIZE as f64;
+    let bin_freq = |bin: usize| {
+        if bin < COARSE_FFT_SIZE / 2 {
+            bin as f64 * bin_width
+        } else {
+            (bin as f64 - COARSE_FFT_SIZE as f64) * bin_width
+        }
+    };
+
+    let (peak_bin, peak_power) = (0..COARSE_FFT_SIZE)
+        .filter(|&bin| (bin_freq(bin) - expected_freq).abs() <= search_range + bin_width)
+        .map(|bin| (bin, power[bin]))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .ok_or(String::from(
+            "The calibration signal is outside of the received bandwidth",
+        ))?;
+
+    let mut sorted_power = power.clone();
+    sorted_power.sort_by(f64::total_cmp);
+    let noise_floor = sorted_power[COARSE_FFT_SIZE / 2];
+    if peak_power < noise_floor * MIN_PEAK_TO_NOISE {
+        return Err(String::from("Could not find the calibration signal"));
+    }
+
+    let coarse_freq = bin_freq(peak_bin);
+
+    // sum blocks of samples after mixing, so each block's phase shows the remaining offset
+    let phase_step = -TAU * coarse_freq / sample_rate;
+    let blocks: Vec<Complex<f64>> = samples
+        .chunks_exact(FINE_BLOCK_SIZE)
+        .enumerate()
+        .map(|(block_i, block)| {
+            block
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| {
+                    let n = (block_i * FINE_BLOCK_SIZE + i) as f64;
+                    *sample * Complex::from_polar(1.0, phase_step * n)
+                })
+                .sum()
+        })
+        .collect();
+
+    let phase_drift: Complex<f64> = blocks.windows(2).map(|pair| pair[1] * pair[0].conj()).sum();
+    let fine_offset = phase_drift.arg() / TAU * sample_rate / FINE_BLOCK_SIZE as f64;
+
+    Ok(coarse_freq + fine_offset)
+}
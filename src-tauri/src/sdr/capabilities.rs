@@ -0,0 +1,157 @@
+use serde::Serialize;
+use soapysdr::{Device, Direction, Range};
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SDRRange {
+    pub minimum: f64,
+    pub maximum: f64,
+    // 0 if any value in the range can be used
+    pub step: f64,
+}
+
+impl SDRRange {
+    pub fn new(minimum: f64, maximum: f64, step: f64) -> Self {
+        Self {
+            minimum,
+            maximum,
+            step,
+        }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        value >= self.minimum && value <= self.maximum
+    }
+
+    /// Get the closest value to `value` that is in the range
+    pub fn clamp(&self, value: f64) -> f64 {
+        let clamped_value = value.clamp(self.minimum, self.maximum);
+
+        if self.step > 0.0 {
+            let steps = ((clamped_value - self.minimum) / self.step).round();
+            (self.minimum + steps * self.step).min(self.maximum)
+        } else {
+            clamped_value
+        }
+    }
+}
+
+impl From<Range> for SDRRange {
+    fn from(range: Range) -> Self {
+        SDRRange::new(range.minimum, range.maximum, range.step)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SDRGainElement {
+    pub name: String,
+    pub range: SDRRange,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SDRSetting {
+    pub key: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub default_value: String,
+}
+
+impl SDRSetting {
+    pub fn new(key: &str, name: &str, default_value: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            name: Some(name.to_string()),
+            description: None,
+            default_value: default_value.to_string(),
+        }
+    }
+}
+
+/// What an SDR supports, so the pipelines can pick valid values for any device.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SDRCapabilities {
+    pub has_automatic_gain: bool,
+    // range of the overall gain (in dB)
+    pub gain_range: SDRRange,
+    // the individual amplifiers that make up the overall gain
+    pub gain_elements: Vec<SDRGainElement>,
+    pub sample_rate_ranges: Vec<SDRRange>,
+    pub frequency_ranges: Vec<SDRRange>,
+    pub antennas: Vec<String>,
+    pub settings: Vec<SDRSetting>,
+}
+
+impl SDRCapabilities {
+    pub fn from_soapysdr(dev: &Device, dir: Direction, channel: usize) -> Result<Self, String> {
+        let gain_elements = dev
+            .list_gains(dir, channel)
+            .map_err(|err| err.to_string())?
+            .into_iter()
+            .filter_map(|name| {
+                let range = dev.gain_element_range(dir, channel, name.as_str()).ok()?;
+                Some(SDRGainElement {
+                    name,
+                    range: range.into(),
+                })
+            })
+            .collect();
+
+        let settings = dev
+            .setting_info()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|setting| SDRSetting {
+                key: setting.key,
+                name: setting.name,
+                description: setting.description,
+                default_value: setting.value,
+            })
+            .collect();
+
+        Ok(Self {
+            has_automatic_gain: dev.has_gain_mode(dir, channel).unwrap_or(false),
+            gain_range: dev
+                .gain_range(dir, channel)
+                .map_err(|err| err.to_string())?
+                .into(),
+            gain_elements,
+            sample_rate_ranges: dev
+                .get_sample_rate_range(dir, channel)
+                .map_err(|err| err.to_string())?
+                .into_iter()
+                .map(SDRRange::from)
+                .collect(),
+            frequency_ranges: dev
+                .frequency_range(dir, channel)
+                .map_err(|err| err.to_string())?
+                .into_iter()
+                .map(SDRRange::from)
+                .collect(),
+            antennas: dev.antennas(dir, channel).unwrap_or_default(),
+            settings,
+        })
+    }
+
+    /// Get the supported sample rate that is closest to `sample_rate`
+    pub fn closest_sample_rate(&self, sample_rate: f64) -> f64 {
+        self.sample_rate_ranges
+            .iter()
+            .map(|range| range.clamp(sample_rate))
+            .min_by(|a, b| (a - sample_rate).abs().total_cmp(&(b - sample_rate).abs()))
+            .unwrap_or(sample_rate)
+    }
+
+    pub fn supports_frequency(&self, frequency: f64) -> bool {
+        self.frequency_ranges
+            .iter()
+            .any(|range| range.contains(frequency))
+    }
+
+    pub fn has_setting(&self, key: &str) -> bool {
+        self.settings.iter().any(|setting| setting.key == key)
+    }
+
+    /// Whether the gain can be set manually, otherwise automatic gain should be used
+    pub fn has_manual_gain(&self) -> bool {
+        self.gain_range.maximum > self.gain_range.minimum
+    }
+}
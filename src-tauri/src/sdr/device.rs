@@ -0,0 +1,261 @@
+use radiorust::{
+    blocks::io::rf,
+    flow::{Producer, SenderConnector},
+    prelude::Complex,
+    signal::Signal,
+};
+use soapysdr::{Device, Direction};
+
+use crate::radiorust_blocks::{
+    iq_file_source::IqFileSource, rtl_tcp_source::RtlTcpSource, simulated_source::SimulatedSource,
+};
+
+use super::{
+    capabilities::SDRCapabilities,
+    enumeration::{AvailableSDRArgs, SDRSourceArgs},
+    iq_file::IqFileDevice,
+    rtl_tcp::RtlTcpDevice,
+    simulated::SimulatedDevice,
+};
+
+/// A handle to any device that can provide IQ samples to the radio pipelines.
+///
+/// The methods mirror the subset of [`soapysdr::Device`] that the pipelines use,
+/// so each type of device can be controlled the same way.
+#[derive(Clone)]
+pub enum SDRDevice {
+    SoapySDR(Device),
+    IqFile(IqFileDevice),
+    RtlTcp(RtlTcpDevice),
+    Simulated(SimulatedDevice),
+}
+
+impl SDRDevice {
+    pub fn new(args: AvailableSDRArgs) -> Result<Self, String> {
+        match args.source.clone() {
+            None => Device::new(args)
+                .map(SDRDevice::SoapySDR)
+                .map_err(|err| err.to_string()),
+            Some(SDRSourceArgs::IqFile(file_args)) => {
+                IqFileDevice::new(file_args).map(SDRDevice::IqFile)
+            }
+            Some(SDRSourceArgs::RtlTcp(rtl_tcp_args)) => {
+                RtlTcpDevice::new(rtl_tcp_args).map(SDRDevice::RtlTcp)
+            }
+            Some(SDRSourceArgs::Simulated(simulated_args)) => {
+                Ok(SDRDevice::Simulated(SimulatedDevice::new(simulated_args)))
+            }
+        }
+    }
+
+    pub fn set_sample_rate(&self, dir: Direction, channel: usize, rate: f64) -> Result<(), String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev
+                .set_sample_rate(dir, channel, rate)
+                .map_err(|err| err.to_string()),
+            // the sample rate of a recording is fixed
+            SDRDevice::IqFile(_) => Ok(()),
+            SDRDevice::RtlTcp(dev) => dev.set_sample_rate(rate),
+            SDRDevice::Simulated(dev) => {
+                dev.set_sample_rate(rate);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn sample_rate(&self, dir: Direction, channel: usize) -> Result<f64, String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => {
+                dev.sample_rate(dir, channel).map_err(|err| err.to_string())
+            }
+            SDRDevice::IqFile(dev) => Ok(dev.sample_rate()),
+            SDRDevice::RtlTcp(dev) => Ok(dev.sample_rate()),
+            SDRDevice::Simulated(dev) => Ok(dev.sample_rate()),
+        }
+    }
+
+    pub fn set_frequency(
+        &self,
+        dir: Direction,
+        channel: usize,
+        frequency: f64,
+        args: &str,
+    ) -> Result<(), String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev
+                .set_frequency(dir, channel, frequency, args)
+                .map_err(|err| err.to_string()),
+            SDRDevice::IqFile(dev) => {
+                dev.set_frequency(frequency);
+                Ok(())
+            }
+            SDRDevice::RtlTcp(dev) => dev.set_frequency(frequency),
+            SDRDevice::Simulated(dev) => {
+                dev.set_frequency(frequency);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn frequency(&self, dir: Direction, channel: usize) -> Result<f64, String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev.frequency(dir, channel).map_err(|err| err.to_string()),
+            SDRDevice::IqFile(dev) => Ok(dev.frequency()),
+            SDRDevice::RtlTcp(dev) => Ok(dev.frequency()),
+            SDRDevice::Simulated(dev) => Ok(dev.frequency()),
+        }
+    }
+
+    pub fn set_bandwidth(&self, dir: Direction, channel: usize, bw: f64) -> Result<(), String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev
+                .set_bandwidth(dir, channel, bw)
+                .map_err(|err| err.to_string()),
+            // rtl_tcp has no command for the tuner bandwidth
+            SDRDevice::IqFile(_) | SDRDevice::RtlTcp(_) | SDRDevice::Simulated(_) => Ok(()),
+        }
+    }
+
+    pub fn set_gain_mode(
+        &self,
+        dir: Direction,
+        channel: usize,
+        automatic: bool,
+    ) -> Result<(), String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev
+                .set_gain_mode(dir, channel, automatic)
+                .map_err(|err| err.to_string()),
+            SDRDevice::IqFile(_) => Ok(()),
+            SDRDevice::RtlTcp(dev) => dev.set_gain_mode(automatic),
+            SDRDevice::Simulated(_) => Ok(()),
+        }
+    }
+
+    pub fn set_gain(&self, dir: Direction, channel: usize, gain: f64) -> Result<(), String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev
+                .set_gain(dir, channel, gain)
+                .map_err(|err| err.to_string()),
+            SDRDevice::IqFile(dev) => {
+                dev.set_gain(gain);
+                Ok(())
+            }
+            SDRDevice::RtlTcp(dev) => dev.set_gain(gain),
+            SDRDevice::Simulated(dev) => {
+                dev.set_gain(gain);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn gain(&self, dir: Direction, channel: usize) -> Result<f64, String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev.gain(dir, channel).map_err(|err| err.to_string()),
+            SDRDevice::IqFile(dev) => Ok(dev.gain()),
+            SDRDevice::RtlTcp(dev) => Ok(dev.gain()),
+            SDRDevice::Simulated(dev) => Ok(dev.gain()),
+        }
+    }
+
+    /// Correct the frequency error of the device's oscillator (in parts per million)
+    pub fn set_frequency_correction(
+        &self,
+        dir: Direction,
+        channel: usize,
+        ppm: f64,
+    ) -> Result<(), String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => dev
+                .set_component_frequency(dir, channel, "CORR", ppm, "")
+                .map_err(|err| err.to_string()),
+            SDRDevice::IqFile(_) | SDRDevice::Simulated(_) => Ok(()),
+            SDRDevice::RtlTcp(dev) => dev.set_frequency_correction(ppm.round() as i32),
+        }
+    }
+
+    pub fn write_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => {
+                dev.write_setting(key, value).map_err(|err| err.to_string())
+            }
+            SDRDevice::IqFile(_) | SDRDevice::Simulated(_) => Ok(()),
+            SDRDevice::RtlTcp(dev) => dev.write_setting(key, value),
+        }
+    }
+
+    pub fn capabilities(&self, dir: Direction, channel: usize) -> Result<SDRCapabilities, String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => SDRCapabilities::from_soapysdr(dev, dir, channel),
+            SDRDevice::IqFile(dev) => Ok(dev.capabilities()),
+            SDRDevice::RtlTcp(dev) => Ok(dev.capabilities()),
+            SDRDevice::Simulated(dev) => Ok(dev.capabilities()),
+        }
+    }
+
+    /// Create a receive stream on the first channel. Devices with a fixed sample
+    /// rate ignore the requested `sample_rate`, so downstream blocks should use
+    /// the sample rate of the received signal.
+    pub fn rx_stream(&self, sample_rate: f64) -> Result<SDRRx, String> {
+        match self {
+            SDRDevice::SoapySDR(dev) => {
+                let rx_stream = dev
+                    .rx_stream::<Complex<f32>>(&[0])
+                    .map_err(|err| err.to_string())?;
+                Ok(SDRRx::SoapySDR(rf::soapysdr::SoapySdrRx::new(
+                    rx_stream,
+                    sample_rate,
+                )))
+            }
+            SDRDevice::IqFile(dev) => Ok(SDRRx::IqFile(dev.rx_stream())),
+            SDRDevice::RtlTcp(dev) => Ok(SDRRx::RtlTcp(dev.rx_stream())),
+            SDRDevice::Simulated(dev) => Ok(SDRRx::Simulated(dev.rx_stream())),
+        }
+    }
+}
+
+/// The radiorust block that produces samples for an [`SDRDevice`]
+pub enum SDRRx {
+    SoapySDR(rf::soapysdr::SoapySdrRx),
+    IqFile(IqFileSource),
+    RtlTcp(RtlTcpSource),
+    Simulated(SimulatedSource),
+}
+
+impl SDRRx {
+    pub async fn activate(&self) -> Result<(), String> {
+        match self {
+            SDRRx::SoapySDR(rx) => rx.activate().await.map_err(|err| err.to_string()),
+            SDRRx::IqFile(rx) => rx.activate(),
+            SDRRx::RtlTcp(rx) => rx.activate(),
+            SDRRx::Simulated(rx) => rx.activate(),
+        }
+    }
+
+    /// Stop reading from the device. The other sources stop when they are dropped.
+    pub async fn deactivate(&self) -> Result<(), String> {
+        match self {
+            SDRRx::SoapySDR(rx) => rx.deactivate().await.map_err(|err| err.to_string()),
+            SDRRx::IqFile(_) | SDRRx::RtlTcp(_) | SDRRx::Simulated(_) => Ok(()),
+        }
+    }
+
+    /// Whether the stream ended because a recording was played to the end, instead of failing
+    pub fn has_reached_end(&self) -> bool {
+        match self {
+            SDRRx::IqFile(rx) => rx.has_reached_end(),
+            SDRRx::SoapySDR(_) | SDRRx::RtlTcp(_) | SDRRx::Simulated(_) => false,
+        }
+    }
+}
+
+impl Producer<Signal<Complex<f32>>> for SDRRx {
+    fn sender_connector(&self) -> &SenderConnector<Signal<Complex<f32>>> {
+        match self {
+            SDRRx::SoapySDR(rx) => rx.sender_connector(),
+            SDRRx::IqFile(rx) => rx.sender_connector(),
+            SDRRx::RtlTcp(rx) => rx.sender_connector(),
+            SDRRx::Simulated(rx) => rx.sender_connector(),
+        }
+    }
+}
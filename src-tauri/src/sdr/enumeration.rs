@@ -1,10 +1,24 @@
-use std::{panic, thread::sleep, time::Duration};
+use std::{
+    collections::HashMap,
+    panic,
+    sync::{
+        mpsc::{channel, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    thread::{self, sleep},
+    time::Duration,
+};
 
-use rusb::{Context, DeviceList, UsbContext};
+use log::{error, info, warn};
+use rusb::{Context, Device, Hotplug, HotplugBuilder, UsbContext};
 use serde::{Deserialize, Serialize};
 use soapysdr::{enumerate, Args};
 use struct_iterable::Iterable;
-use tokio::task;
+
+use super::{iq_file::IqFileArgs, rtl_tcp::RtlTcpArgs, simulated::SimulatedArgs};
+
+// how long to wait after a USB device is plugged in before enumerating
+const HOTPLUG_SETTLE_TIME: Duration = Duration::from_millis(250);
 
 pub fn get_available_sdr_args() -> Result<Vec<AvailableSDRArgs>, ()> {
     let args = panic::catch_unwind(|| enumerate(""));
@@ -16,36 +30,193 @@ pub fn get_available_sdr_args() -> Result<Vec<AvailableSDRArgs>, ()> {
     Ok(args_to_available_sdr_args(args.unwrap().unwrap()))
 }
 
-pub fn register_available_sdrs_callback<F>(polling_rate: f32, callback: F)
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EnumerationRequest {
+    // a USB device was plugged in or removed
+    UsbChanged,
+    // enumerate again even if no USB devices changed (needed to find network SDRs)
+    Refresh,
+}
+
+/// Lets SDRs be enumerated on demand, since SDRs that aren't on USB (such as
+/// SoapyRemote or network SDRs) can't be detected when they appear.
+pub struct AvailableSDRsRefresher(Mutex<Sender<EnumerationRequest>>);
+
+impl AvailableSDRsRefresher {
+    pub fn refresh(&self) {
+        let _ = self.0.lock().unwrap().send(EnumerationRequest::Refresh);
+    }
+}
+
+/// Identifies a USB device by where it is plugged in and its serial, so identical SDRs can be told apart
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct UsbDeviceId {
+    bus_number: u8,
+    port_numbers: Vec<u8>,
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+}
+
+struct UsbDeviceList {
+    context: Context,
+    // reading a serial requires opening the device, so it is only done once per connection
+    serials: HashMap<(u8, u8), Option<String>>,
+}
+
+impl UsbDeviceList {
+    fn new(context: Context) -> Self {
+        Self {
+            context,
+            serials: HashMap::new(),
+        }
+    }
+
+    fn device_ids(&mut self) -> Vec<UsbDeviceId> {
+        let Ok(devices) = self.context.devices() else {
+            return vec![];
+        };
+
+        let mut connected_devices = vec![];
+        let mut device_ids: Vec<UsbDeviceId> = devices
+            .iter()
+            .filter_map(|device| {
+                let descriptor = device.device_descriptor().ok()?;
+                let location = (device.bus_number(), device.address());
+                connected_devices.push(location);
+
+                let serial = self
+                    .serials
+                    .entry(location)
+                    .or_insert_with(|| {
+                        descriptor.serial_number_string_index()?;
+                        device
+                            .open()
+                            .ok()?
+                            .read_serial_number_string_ascii(&descriptor)
+                            .ok()
+                    })
+                    .clone();
+
+                Some(UsbDeviceId {
+                    bus_number: device.bus_number(),
+                    port_numbers: device.port_numbers().unwrap_or_default(),
+                    vendor_id: descriptor.vendor_id(),
+                    product_id: descriptor.product_id(),
+                    serial,
+                })
+            })
+            .collect();
+
+        self.serials
+            .retain(|location, _| connected_devices.contains(location));
+
+        device_ids.sort();
+        device_ids
+    }
+}
+
+struct HotplugHandler(Sender<EnumerationRequest>);
+
+impl<T: UsbContext> Hotplug<T> for HotplugHandler {
+    fn device_arrived(&mut self, _device: Device<T>) {
+        let _ = self.0.send(EnumerationRequest::UsbChanged);
+    }
+
+    fn device_left(&mut self, _device: Device<T>) {
+        let _ = self.0.send(EnumerationRequest::UsbChanged);
+    }
+}
+
+// returns false if hotplug events are not supported on this platform
+fn register_hotplug_events(context: Context, request_sender: Sender<EnumerationRequest>) -> bool {
+    if !rusb::has_hotplug() {
+        return false;
+    }
+
+    let hotplug_handler: Box<dyn Hotplug<Context>> = Box::new(HotplugHandler(request_sender));
+    let registration = HotplugBuilder::new()
+        .enumerate(false)
+        .register(&context, hotplug_handler);
+
+    let Ok(registration) = registration else {
+        warn!("Could not register USB hotplug callback, falling back to polling");
+        return false;
+    };
+
+    // libusb only runs the callbacks while handling events
+    thread::spawn(move || {
+        let _registration = registration;
+        loop {
+            if let Err(err) = context.handle_events(None) {
+                error!("Error handling USB events: {}", err);
+                return;
+            }
+        }
+    });
+
+    true
+}
+
+/// Calls `callback` with the available SDRs whenever they change. USB hotplug events are
+/// used when supported, otherwise USB devices are polled `polling_rate` times per second.
+pub fn register_available_sdrs_callback<F>(polling_rate: f32, callback: F) -> AvailableSDRsRefresher
 where
     F: Fn(Vec<AvailableSDRArgs>) + Send + 'static,
 {
-    task::spawn({
-        let polling_rate = polling_rate;
-        async move {
-            let libusb_context = Context::new().unwrap();
-
-            let mut prev_dev_list: DeviceList<Context> = libusb_context.devices().unwrap();
-            let mut is_first_call = true;
-
-            // run until the application closes
-            loop {
-                let dev_list = libusb_context.devices().unwrap();
-
-                if !are_device_lists_equal(&dev_list, &prev_dev_list) || is_first_call {
-                    let args = get_available_sdr_args();
-
-                    if args.is_ok() {
-                        callback(args.unwrap());
-                    }
-                    prev_dev_list = dev_list;
-                    is_first_call = false;
+    let (request_sender, request_receiver) = channel::<EnumerationRequest>();
+
+    let libusb_context = Context::new().unwrap();
+    let has_hotplug = register_hotplug_events(libusb_context.clone(), request_sender.clone());
+    if has_hotplug {
+        info!("Using USB hotplug events to detect SDRs");
+    }
+
+    // enumerating is slow, so don't block the async runtime
+    thread::spawn(move || {
+        let mut usb_devices = UsbDeviceList::new(libusb_context);
+        let mut prev_device_ids: Option<Vec<UsbDeviceId>> = None;
+
+        // run until the application closes
+        loop {
+            let request = if prev_device_ids.is_none() {
+                // always enumerate when starting
+                Some(EnumerationRequest::Refresh)
+            } else if has_hotplug {
+                match request_receiver.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return,
+                }
+            } else {
+                match request_receiver.recv_timeout(Duration::from_secs_f32(1.0 / polling_rate)) {
+                    Ok(request) => Some(request),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
                 }
+            };
+
+            // give the OS time to set up the device, and handle bursts of events at once
+            let mut is_refresh = request == Some(EnumerationRequest::Refresh);
+            if request == Some(EnumerationRequest::UsbChanged) {
+                sleep(HOTPLUG_SETTLE_TIME);
+            }
+            while let Ok(request) = request_receiver.try_recv() {
+                is_refresh |= request == EnumerationRequest::Refresh;
+            }
+
+            let device_ids = usb_devices.device_ids();
+            if !is_refresh && prev_device_ids.as_ref() == Some(&device_ids) {
+                continue;
+            }
+            prev_device_ids = Some(device_ids);
 
-                sleep(Duration::from_secs_f32(1.0 / polling_rate));
+            if let Ok(args) = get_available_sdr_args() {
+                callback(args);
             }
         }
     });
+
+    AvailableSDRsRefresher(Mutex::new(request_sender))
 }
 
 #[derive(Serialize, Clone, Debug, PartialEq, Deserialize, Iterable)]
@@ -56,6 +227,17 @@ pub struct AvailableSDRArgs {
     pub product: String,
     pub serial: String,
     pub tuner: String,
+    // only set for SDRs that are not enumerated by SoapySDR
+    #[serde(default)]
+    pub source: Option<SDRSourceArgs>,
+}
+
+/// Describes how to open an SDR that SoapySDR does not know about.
+#[derive(Serialize, Clone, Debug, PartialEq, Deserialize)]
+pub enum SDRSourceArgs {
+    IqFile(IqFileArgs),
+    RtlTcp(RtlTcpArgs),
+    Simulated(SimulatedArgs),
 }
 
 impl Into<Args> for AvailableSDRArgs {
@@ -79,23 +261,7 @@ pub fn args_to_available_sdr_args(args: Vec<Args>) -> Vec<AvailableSDRArgs> {
             product: args.get("product").unwrap_or("Unknown").to_string(),
             serial: args.get("serial").unwrap_or("Unknown").to_string(),
             tuner: args.get("tuner").unwrap_or("Unknown").to_string(),
+            source: None,
         })
         .collect::<Vec<AvailableSDRArgs>>()
 }
-
-pub fn are_device_lists_equal(list1: &DeviceList<Context>, list2: &DeviceList<Context>) -> bool {
-    if list1.len() != list2.len() {
-        return false;
-    }
-
-    for (a, b) in list1.iter().zip(list2.iter()) {
-        let a_desc = a.device_descriptor().unwrap();
-        let b_desc = b.device_descriptor().unwrap();
-
-        if a_desc.product_id() != b_desc.product_id() || a_desc.vendor_id() != b_desc.vendor_id() {
-            return false;
-        }
-    }
-
-    true
-}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use log::{info, warn};
+use soapysdr::Direction;
+
+use super::{device::SDRDevice, enumeration::AvailableSDRArgs};
+use crate::store::Store;
+
+/// Get the saved frequency correction (in PPM) of an SDR, which is 0 if it hasn't been set
+pub fn get_frequency_correction(data_dir: Option<&Path>, args: &AvailableSDRArgs) -> f64 {
+    Store::new(data_dir)
+        .get_device_preferences(args)
+        .ppm
+        .unwrap_or(0.0)
+}
+
+pub fn save_frequency_correction(
+    data_dir: Option<&Path>,
+    args: &AvailableSDRArgs,
+    ppm: f64,
+) -> Result<(), String> {
+    Store::new(data_dir)
+        .update_device_preferences(args, |preferences| {
+            preferences.ppm = if ppm == 0.0 { None } else { Some(ppm) };
+        })
+        .map_err(|err| format!("Could not save frequency correction: {}", err))?;
+
+    info!(
+        "Saved frequency correction of {} PPM for {}",
+        ppm, args.label
+    );
+
+    Ok(())
+}
+
+/// Apply the saved frequency correction of an SDR to its device
+pub fn apply_frequency_correction(
+    data_dir: Option<&Path>,
+    dev: &SDRDevice,
+    args: &AvailableSDRArgs,
+) {
+    // nothing is saved for SDRs without a serial, so they keep the correction set while connected
+    if !Store::saves_device_preferences(args) {
+        return;
+    }
+
+    // also set when 0 so a removed correction doesn't stay applied
+    set_device_frequency_correction(dev, args, get_frequency_correction(data_dir, args));
+}
+
+/// Apply a frequency correction to the device of an SDR
+pub fn set_device_frequency_correction(dev: &SDRDevice, args: &AvailableSDRArgs, ppm: f64) {
+    let result = dev.set_frequency_correction(Direction::Rx, 0, ppm);
+
+    // not every SDR supports frequency correction, which only matters if one is set
+    if let Err(err) = result {
+        if ppm != 0.0 {
+            warn!(
+                "Could not apply frequency correction of {} PPM to {}: {}",
+                ppm, args.label, err
+            );
+        }
+    }
+}
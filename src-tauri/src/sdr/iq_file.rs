@@ -0,0 +1,279 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use log::{info, warn};
+use radiorust::prelude::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::radiorust_blocks::iq_file_source::IqFileSource;
+
+use super::{
+    capabilities::{SDRCapabilities, SDRRange},
+    enumeration::{AvailableSDRArgs, SDRSourceArgs},
+};
+
+pub const IQ_FILE_DRIVER: &str = "iq_file";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum IqSampleFormat {
+    // interleaved unsigned 8-bit samples (the native RTL-SDR format)
+    Cu8,
+    // interleaved signed 16-bit little-endian samples
+    Cs16,
+    // interleaved 32-bit little-endian floats
+    Cf32,
+}
+
+impl IqSampleFormat {
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            IqSampleFormat::Cu8 => 2,
+            IqSampleFormat::Cs16 => 4,
+            IqSampleFormat::Cf32 => 8,
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "cu8" | "u8" => Some(IqSampleFormat::Cu8),
+            "cs16" | "s16" => Some(IqSampleFormat::Cs16),
+            "cf32" | "fc32" | "cfile" => Some(IqSampleFormat::Cf32),
+            _ => None,
+        }
+    }
+
+    pub fn from_sigmf_datatype(datatype: &str) -> Option<Self> {
+        match datatype {
+            "cu8" => Some(IqSampleFormat::Cu8),
+            "ci16_le" => Some(IqSampleFormat::Cs16),
+            "cf32_le" => Some(IqSampleFormat::Cf32),
+            _ => None,
+        }
+    }
+
+    /// Convert the bytes of one sample into a complex value between -1 and 1
+    pub fn decode_sample(&self, bytes: &[u8]) -> Complex<f32> {
+        match self {
+            IqSampleFormat::Cu8 => Complex::new(
+                (bytes[0] as f32 - 127.5) / 127.5,
+                (bytes[1] as f32 - 127.5) / 127.5,
+            ),
+            IqSampleFormat::Cs16 => Complex::new(
+                i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+                i16::from_le_bytes([bytes[2], bytes[3]]) as f32 / 32768.0,
+            ),
+            IqSampleFormat::Cf32 => Complex::new(
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            ),
+        }
+    }
+
+    pub fn sigmf_datatype(&self) -> &'static str {
+        match self {
+            IqSampleFormat::Cu8 => "cu8",
+            IqSampleFormat::Cs16 => "ci16_le",
+            IqSampleFormat::Cf32 => "cf32_le",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FilePacing {
+    // play the file back at the rate it was recorded
+    RealTime,
+    // read samples as quickly as the pipeline can take them
+    AsFastAsPossible,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IqFileArgs {
+    // path to the raw sample data (the .sigmf-data file for SigMF recordings)
+    pub path: String,
+    pub format: IqSampleFormat,
+    pub sample_rate: f64,
+    pub center_freq: f64,
+    pub looping: bool,
+    pub pacing: FilePacing,
+}
+
+/// Builds the SDR arguments for an IQ recording. SigMF recordings (either the
+/// `.sigmf-meta` or `.sigmf-data` file) provide their own format, sample rate,
+/// and center frequency, while raw recordings need them to be specified.
+pub fn get_iq_file_sdr_args(
+    path: String,
+    format: Option<IqSampleFormat>,
+    sample_rate: Option<f64>,
+    center_freq: Option<f64>,
+    looping: bool,
+    pacing: FilePacing,
+) -> Result<AvailableSDRArgs, String> {
+    let file_path = Path::new(&path);
+    let extension = file_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let file_args = if extension == "sigmf-meta" || extension == "sigmf-data" {
+        read_sigmf_args(file_path, looping, pacing)?
+    } else {
+        let format = format
+            .or(IqSampleFormat::from_extension(&extension))
+            .ok_or(String::from("Could not determine the IQ sample format"))?;
+
+        IqFileArgs {
+            path: path.clone(),
+            format,
+            sample_rate: sample_rate.ok_or(String::from("A sample rate is required"))?,
+            center_freq: center_freq.ok_or(String::from("A center frequency is required"))?,
+            looping,
+            pacing,
+        }
+    };
+
+    if !Path::new(&file_args.path).is_file() {
+        return Err(format!("Could not find IQ file {}", file_args.path));
+    }
+
+    let file_name = Path::new(&file_args.path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("Unknown")
+        .to_string();
+
+    Ok(AvailableSDRArgs {
+        driver: IQ_FILE_DRIVER.to_string(),
+        label: format!("IQ File: {}", file_name),
+        manufacturer: String::from("Unknown"),
+        product: format!("{:?}", file_args.format),
+        // the path is used as the serial since it is unique
+        serial: file_args.path.clone(),
+        tuner: String::from("Unknown"),
+        source: Some(SDRSourceArgs::IqFile(file_args)),
+    })
+}
+
+fn read_sigmf_args(path: &Path, looping: bool, pacing: FilePacing) -> Result<IqFileArgs, String> {
+    let meta_path = path.with_extension("sigmf-meta");
+    let data_path = path.with_extension("sigmf-data");
+
+    let meta_string = fs::read_to_string(&meta_path)
+        .map_err(|err| format!("Could not read {}: {}", meta_path.display(), err))?;
+    let meta: serde_json::Value = serde_json::from_str(&meta_string)
+        .map_err(|err| format!("Invalid SigMF metadata: {}", err))?;
+
+    let datatype = meta["global"]["core:datatype"]
+        .as_str()
+        .ok_or(String::from("SigMF metadata is missing core:datatype"))?;
+    let format = IqSampleFormat::from_sigmf_datatype(datatype)
+        .ok_or(format!("Unsupported SigMF datatype {}", datatype))?;
+    let sample_rate = meta["global"]["core:sample_rate"]
+        .as_f64()
+        .ok_or(String::from("SigMF metadata is missing core:sample_rate"))?;
+
+    // use the frequency of the first capture segment
+    let center_freq = meta["captures"][0]["core:frequency"]
+        .as_f64()
+        .ok_or(String::from("SigMF metadata is missing core:frequency"))?;
+    if meta["captures"]
+        .as_array()
+        .map_or(0, |captures| captures.len())
+        > 1
+    {
+        warn!("SigMF recording has multiple captures, only the first frequency will be used");
+    }
+
+    Ok(IqFileArgs {
+        path: data_path
+            .to_str()
+            .ok_or(format!("{} is not a valid path", data_path.display()))?
+            .to_string(),
+        format,
+        sample_rate,
+        center_freq,
+        looping,
+        pacing,
+    })
+}
+
+/// A virtual device that plays back an IQ recording. Tuning is emulated by
+/// shifting the recording digitally, so only frequencies inside the recorded
+/// bandwidth can be received.
+#[derive(Clone)]
+pub struct IqFileDevice {
+    args: IqFileArgs,
+    frequency: Arc<Mutex<f64>>,
+    gain: Arc<Mutex<f64>>,
+}
+
+impl IqFileDevice {
+    pub fn new(args: IqFileArgs) -> Result<Self, String> {
+        if !Path::new(&args.path).is_file() {
+            return Err(format!("Could not find IQ file {}", args.path));
+        }
+
+        info!("Opened IQ file {} ({:?})", args.path, args.format);
+
+        Ok(Self {
+            frequency: Arc::new(Mutex::new(args.center_freq)),
+            gain: Arc::new(Mutex::new(0.0)),
+            args,
+        })
+    }
+
+    pub fn set_frequency(&self, freq: f64) {
+        if (freq - self.args.center_freq).abs() > self.args.sample_rate / 2.0 {
+            warn!(
+                "{}hz is outside of the recorded bandwidth of {}",
+                freq, self.args.path
+            );
+        }
+        *self.frequency.lock().unwrap() = freq;
+    }
+
+    pub fn frequency(&self) -> f64 {
+        *self.frequency.lock().unwrap()
+    }
+
+    // gain can't be changed on a recording, but it is remembered so the pipelines behave the same
+    pub fn set_gain(&self, gain: f64) {
+        *self.gain.lock().unwrap() = gain;
+    }
+
+    pub fn gain(&self) -> f64 {
+        *self.gain.lock().unwrap()
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.args.sample_rate
+    }
+
+    pub fn capabilities(&self) -> SDRCapabilities {
+        SDRCapabilities {
+            has_automatic_gain: false,
+            gain_range: SDRRange::new(0.0, 0.0, 0.0),
+            gain_elements: vec![],
+            sample_rate_ranges: vec![SDRRange::new(
+                self.args.sample_rate,
+                self.args.sample_rate,
+                0.0,
+            )],
+            // only the recorded bandwidth can be tuned to
+            frequency_ranges: vec![SDRRange::new(
+                self.args.center_freq - self.args.sample_rate / 2.0,
+                self.args.center_freq + self.args.sample_rate / 2.0,
+                0.0,
+            )],
+            antennas: vec![],
+            settings: vec![],
+        }
+    }
+
+    pub fn rx_stream(&self) -> IqFileSource {
+        IqFileSource::new(self.args.clone(), self.frequency.clone())
+    }
+}
@@ -1,12 +1,31 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use capabilities::SDRCapabilities;
+use device::SDRDevice;
 use enumeration::AvailableSDRArgs;
+use frequency_correction::{
+    apply_frequency_correction, save_frequency_correction, set_device_frequency_correction,
+};
 use log::{error, info};
 use serde::{Serialize, Serializer};
-use soapysdr::Device;
-use tauri::{AppHandle, Emitter, Manager, State};
+use soapysdr::Direction;
 
-use crate::AppState;
+use crate::{
+    error::{RadioError, RadioResult},
+    events::SharedEventSink,
+};
 
+pub mod calibration;
+pub mod capabilities;
+pub mod device;
 pub mod enumeration;
+pub mod frequency_correction;
+pub mod iq_file;
+pub mod rtl_tcp;
+pub mod simulated;
 
 fn serialize_device<S>(dev: &SDRDeviceState, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -28,7 +47,7 @@ where
 #[derive(Clone)]
 pub enum SDRDeviceState {
     Available,
-    Connected { dev: Device },
+    Connected { dev: SDRDevice },
     InUse,
 }
 
@@ -37,112 +56,145 @@ pub struct SDRState {
     pub args: AvailableSDRArgs,
     #[serde(serialize_with = "serialize_device")]
     pub dev: SDRDeviceState,
+    // saved when connecting, so they are available while the SDR is in use
+    #[serde(skip)]
+    pub capabilities: Option<SDRCapabilities>,
 }
 
-fn connect_to_sdr_with_mut(sdr_state: &mut SDRState) -> Result<(), ()> {
-    let dev_result = Device::new(sdr_state.args.clone());
+/// The SDRs that are plugged in (or added), and whether they are being used. Changes are published as `sdr_states`.
+#[derive(Clone)]
+pub struct SDRRegistry {
+    sdrs: Arc<Mutex<Vec<SDRState>>>,
+    events: SharedEventSink,
+    // where settings of each SDR (such as the frequency correction) are saved
+    data_dir: Option<PathBuf>,
+}
 
-    if dev_result.is_err() {
-        error!("Could not connect to {}.", sdr_state.args.label);
-        return Err(());
+impl SDRRegistry {
+    pub fn new(events: SharedEventSink, data_dir: Option<PathBuf>) -> Self {
+        Self {
+            sdrs: Arc::new(Mutex::new(vec![])),
+            events,
+            data_dir,
+        }
     }
 
-    let dev = dev_result.unwrap();
+    pub fn lock(&self) -> MutexGuard<'_, Vec<SDRState>> {
+        self.sdrs.lock().unwrap()
+    }
 
-    info!("Connected to {}!", sdr_state.args.label);
+    pub fn data_dir(&self) -> Option<&Path> {
+        self.data_dir.as_deref()
+    }
 
-    sdr_state.dev = SDRDeviceState::Connected { dev };
+    pub fn emit_states(&self, sdrs: &[SDRState]) {
+        self.events.emit("sdr_states", sdrs);
+    }
+}
 
-    Ok(())
+// open the SDR, so the error says why it could not be connected to
+fn open_sdr_dev(args: &AvailableSDRArgs) -> RadioResult<SDRDevice> {
+    SDRDevice::new(args.clone()).map_err(|err| {
+        error!("Could not connect to {}: {}", args.label, err);
+        RadioError::Device(format!("Could not connect to {}: {}", args.label, err))
+    })
 }
 
-pub fn connect_to_sdr(args: AvailableSDRArgs, app: AppHandle) -> Result<(), ()> {
-    let state = app.state::<AppState>();
+fn connect_to_sdr_with_mut(sdr_state: &mut SDRState) -> RadioResult<()> {
+    let dev = open_sdr_dev(&sdr_state.args)?;
 
-    let dev_result = Device::new(args.clone());
+    info!("Connected to {}!", sdr_state.args.label);
 
-    if dev_result.is_err() {
-        error!("Could not connect to {}.", args.label);
-        return Err(());
-    }
+    sdr_state.capabilities = dev.capabilities(Direction::Rx, 0).ok();
+    sdr_state.dev = SDRDeviceState::Connected { dev };
 
-    let dev = dev_result.unwrap();
+    Ok(())
+}
+
+pub fn connect_to_sdr(sdr_registry: &SDRRegistry, args: AvailableSDRArgs) -> RadioResult<()> {
+    let dev = open_sdr_dev(&args)?;
 
     info!("Connected to {}!", args.label);
 
+    let capabilities = dev.capabilities(Direction::Rx, 0).ok();
+
     // find sdr and add dev to it
-    let mut sdrs = state.sdrs.lock().unwrap();
+    let mut sdrs = sdr_registry.lock();
     let find_sdr_result = sdrs.iter_mut().find(|sdr| sdr.args == args);
     if find_sdr_result.is_some() {
-        find_sdr_result.unwrap().dev = SDRDeviceState::Connected { dev };
+        let sdr = find_sdr_result.unwrap();
+        sdr.dev = SDRDeviceState::Connected { dev };
+        sdr.capabilities = capabilities;
     } else {
         // create new sdr state if not found
         sdrs.push(SDRState {
             args,
             dev: SDRDeviceState::Connected { dev },
+            capabilities,
         });
     }
 
-    app.emit("sdr_states", sdrs.clone()).unwrap();
+    sdr_registry.emit_states(&sdrs);
 
     Ok(())
 }
 
-pub fn disconnect_sdr(
-    args: AvailableSDRArgs,
-    app: AppHandle,
-    state: State<'_, AppState>,
-) -> Result<(), &str> {
+pub fn disconnect_sdr(sdr_registry: &SDRRegistry, args: AvailableSDRArgs) -> RadioResult<()> {
     // find sdr and add dev to it
-    let mut sdrs = state.sdrs.lock().unwrap();
+    let mut sdrs = sdr_registry.lock();
     let find_sdr_result = sdrs.iter_mut().find(|sdr| sdr.args == args);
     if find_sdr_result.is_none() {
-        return Err("Could not find SDR to disconnect");
+        return Err(RadioError::NotFound(String::from(
+            "Could not find SDR to disconnect",
+        )));
     }
 
     let sdr = find_sdr_result.unwrap();
 
     match sdr.dev.clone() {
         SDRDeviceState::Available => {
-            return Err("SDR already disconnected");
+            return Err(RadioError::Device(String::from("SDR already disconnected")));
         }
         SDRDeviceState::Connected { dev: _ } => {
             sdr.dev = SDRDeviceState::Available;
 
-            app.emit("sdr_states", sdrs.clone()).unwrap();
+            sdr_registry.emit_states(&sdrs);
             return Ok(());
         }
         SDRDeviceState::InUse => {
-            return Err("Can't disconnect SDR because it is in use");
+            return Err(RadioError::Device(String::from(
+                "Can't disconnect SDR because it is in use",
+            )));
         }
     }
 }
 
 pub fn get_sdr_dev(
-    app: AppHandle,
+    sdr_registry: &SDRRegistry,
     args: AvailableSDRArgs,
-) -> Result<(Device, AvailableSDRArgs), String> {
-    let state = app.state::<AppState>();
-    let mut sdrs = state.sdrs.lock().unwrap();
+) -> RadioResult<(SDRDevice, AvailableSDRArgs)> {
+    let mut sdrs = sdr_registry.lock();
 
-    let dev_clone: Device;
+    let dev_clone: SDRDevice;
     let args_clone: AvailableSDRArgs;
 
     {
         let sdr_result = sdrs.iter_mut().find(|sdr_state| sdr_state.args == args);
 
         if sdr_result.is_none() {
-            return Err(String::from("Could not find SDR with specified arguments"));
+            return Err(RadioError::NotFound(String::from(
+                "Could not find SDR with specified arguments",
+            )));
         }
 
         let sdr = sdr_result.unwrap();
 
         if let SDRDeviceState::InUse = sdr.dev {
-            return Err(String::from("SDR already in use"));
+            return Err(RadioError::Device(String::from("SDR already in use")));
         }
 
         if let SDRDeviceState::Available = sdr.dev {
-            connect_to_sdr_with_mut(sdr).unwrap();
+            connect_to_sdr_with_mut(sdr)?;
         }
 
         if let SDRDeviceState::Connected { dev } = sdr.dev.clone() {
@@ -151,29 +203,166 @@ pub fn get_sdr_dev(
             dev_clone = dev.clone();
             args_clone = sdr.args.clone();
         } else {
-            return Err(String::from("Could not get SDR device"));
+            return Err(RadioError::Device(String::from("Could not get SDR device")));
         }
     }
 
-    app.emit("sdr_states", (*sdrs).clone()).unwrap();
+    sdr_registry.emit_states(&sdrs);
+
+    apply_frequency_correction(sdr_registry.data_dir(), &dev_clone, &args_clone);
 
     return Ok((dev_clone, args_clone));
 }
 
-pub fn release_sdr_dev(app: AppHandle, dev: Device, args: AvailableSDRArgs) -> Result<(), String> {
-    let state = app.state::<AppState>();
-    let mut sdrs = state.sdrs.lock().unwrap();
+pub fn release_sdr_dev(
+    sdr_registry: &SDRRegistry,
+    dev: SDRDevice,
+    args: AvailableSDRArgs,
+) -> RadioResult<()> {
+    let mut sdrs = sdr_registry.lock();
     let find_sdr_result = sdrs.iter_mut().find(|sdr| sdr.args == args);
 
     if find_sdr_result.is_none() {
-        return Err(String::from("Could not find SDR to release dev to"));
+        return Err(RadioError::NotFound(String::from(
+            "Could not find SDR to release dev to",
+        )));
     }
 
     let sdr = find_sdr_result.unwrap();
 
     sdr.dev = SDRDeviceState::Connected { dev: dev };
 
-    app.emit("sdr_states", sdrs.clone()).unwrap();
+    sdr_registry.emit_states(&sdrs);
 
     return Ok(());
 }
+
+/// Release an SDR whose device stopped working, so it is reconnected the next time it is used
+pub fn reset_sdr_dev(sdr_registry: &SDRRegistry, args: AvailableSDRArgs) {
+    let mut sdrs = sdr_registry.lock();
+
+    // the SDR might have already been removed if it was unplugged
+    if let Some(sdr) = sdrs.iter_mut().find(|sdr| sdr.args == args) {
+        sdr.dev = SDRDeviceState::Available;
+    }
+
+    sdr_registry.emit_states(&sdrs);
+}
+
+/// Whether an SDR is plugged in (or added) and not being used by anything else
+pub fn is_sdr_available(sdr_registry: &SDRRegistry, args: &AvailableSDRArgs) -> bool {
+    let sdrs = sdr_registry.lock();
+
+    sdrs.iter()
+        .any(|sdr| sdr.args == *args && !matches!(sdr.dev, SDRDeviceState::InUse))
+}
+
+/// Adds an SDR that is not enumerated by SoapySDR (such as an IQ file or rtl_tcp server) to the list of SDRs
+pub fn add_virtual_sdr(sdr_registry: &SDRRegistry, args: AvailableSDRArgs) -> RadioResult<()> {
+    if args.source.is_none() {
+        return Err(RadioError::Device(String::from(
+            "Only virtual SDRs can be added manually",
+        )));
+    }
+
+    let mut sdrs = sdr_registry.lock();
+
+    if sdrs.iter().any(|sdr| sdr.args.serial == args.serial) {
+        return Err(RadioError::Device(String::from(
+            "SDR has already been added",
+        )));
+    }
+
+    info!("Added virtual SDR {}", args.label);
+
+    sdrs.push(SDRState {
+        args,
+        dev: SDRDeviceState::Available,
+        capabilities: None,
+    });
+
+    sdr_registry.emit_states(&sdrs);
+
+    Ok(())
+}
+
+pub fn remove_virtual_sdr(sdr_registry: &SDRRegistry, args: AvailableSDRArgs) -> RadioResult<()> {
+    let mut sdrs = sdr_registry.lock();
+
+    let find_sdr_result = sdrs
+        .iter()
+        .find(|sdr| sdr.args == args && sdr.args.source.is_some());
+    if find_sdr_result.is_none() {
+        return Err(RadioError::NotFound(String::from(
+            "Could not find virtual SDR to remove",
+        )));
+    }
+
+    if let SDRDeviceState::InUse = find_sdr_result.unwrap().dev {
+        return Err(RadioError::Device(String::from(
+            "Can't remove SDR because it is in use",
+        )));
+    }
+
+    sdrs.retain(|sdr| sdr.args != args);
+
+    sdr_registry.emit_states(&sdrs);
+
+    Ok(())
+}
+
+/// Get what an SDR supports, connecting to it if needed
+pub fn get_sdr_capabilities(
+    sdr_registry: &SDRRegistry,
+    args: AvailableSDRArgs,
+) -> RadioResult<SDRCapabilities> {
+    let mut sdrs = sdr_registry.lock();
+
+    let find_sdr_result = sdrs.iter_mut().find(|sdr| sdr.args == args);
+    if find_sdr_result.is_none() {
+        return Err(RadioError::NotFound(String::from(
+            "Could not find SDR with specified arguments",
+        )));
+    }
+
+    let sdr = find_sdr_result.unwrap();
+
+    if let SDRDeviceState::Available = sdr.dev {
+        connect_to_sdr_with_mut(sdr)?;
+        let capabilities = sdr.capabilities.clone();
+
+        sdr_registry.emit_states(&sdrs);
+
+        return capabilities.ok_or(RadioError::Device(String::from(
+            "Could not get SDR capabilities",
+        )));
+    }
+
+    sdr.capabilities
+        .clone()
+        .ok_or(RadioError::Device(String::from(
+            "Could not get SDR capabilities",
+        )))
+}
+
+/// Save the frequency correction (in PPM) of an SDR, applying it now if the SDR is connected
+pub fn set_sdr_frequency_correction(
+    sdr_registry: &SDRRegistry,
+    args: AvailableSDRArgs,
+    ppm: f64,
+) -> RadioResult<()> {
+    save_frequency_correction(sdr_registry.data_dir(), &args, ppm).map_err(RadioError::Storage)?;
+
+    let sdrs = sdr_registry.lock();
+
+    // SDRs that are in use get the correction the next time they are used
+    if let Some(SDRState {
+        dev: SDRDeviceState::Connected { dev },
+        ..
+    }) = sdrs.iter().find(|sdr| sdr.args == args)
+    {
+        set_device_frequency_correction(dev, &args, ppm);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,474 @@
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use log::{error, info};
+use radiorust::{
+    prelude::{ChunkBufPool, Complex},
+    signal::Signal,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::radiorust_blocks::rtl_tcp_source::{RtlTcpSampleSlot, RtlTcpSource};
+
+use super::{
+    capabilities::{SDRCapabilities, SDRGainElement, SDRRange, SDRSetting},
+    enumeration::{AvailableSDRArgs, SDRSourceArgs},
+    iq_file::IqSampleFormat,
+};
+
+pub const RTL_TCP_DRIVER: &str = "rtl_tcp";
+
+// commands of the rtl_tcp protocol (each is sent as a 1 byte id followed by a big-endian u32)
+pub const RTL_TCP_SET_FREQ: u8 = 0x01;
+pub const RTL_TCP_SET_SAMPLE_RATE: u8 = 0x02;
+pub const RTL_TCP_SET_GAIN_MODE: u8 = 0x03;
+pub const RTL_TCP_SET_GAIN: u8 = 0x04;
+pub const RTL_TCP_SET_FREQ_CORRECTION: u8 = 0x05;
+pub const RTL_TCP_SET_IF_GAIN: u8 = 0x06;
+pub const RTL_TCP_SET_TEST_MODE: u8 = 0x07;
+pub const RTL_TCP_SET_AGC_MODE: u8 = 0x08;
+pub const RTL_TCP_SET_DIRECT_SAMPLING: u8 = 0x09;
+pub const RTL_TCP_SET_OFFSET_TUNING: u8 = 0x0a;
+pub const RTL_TCP_SET_RTL_XTAL: u8 = 0x0b;
+pub const RTL_TCP_SET_TUNER_XTAL: u8 = 0x0c;
+pub const RTL_TCP_SET_GAIN_BY_INDEX: u8 = 0x0d;
+pub const RTL_TCP_SET_BIAS_TEE: u8 = 0x0e;
+
+// the tuner types reported in the rtl_tcp header (in order of their id)
+pub const RTL_TCP_TUNER_NAMES: [&str; 7] = [
+    "Unknown", "E4000", "FC0012", "FC0013", "FC2580", "R820T", "R828D",
+];
+
+// the default port and sample rate of rtl_tcp servers
+pub const RTL_TCP_DEFAULT_PORT: u16 = 1234;
+pub const RTL_TCP_DEFAULT_SAMPLE_RATE: f64 = 2.048e6;
+// number of bytes read from the server at a time (2 bytes per sample)
+const READ_SIZE: usize = 32768;
+
+/// The gain values (in tenths of a dB) that librtlsdr reports for each tuner type,
+/// which clients use for the gain by index command.
+pub fn get_rtl_tcp_tuner_gains(tuner_type: usize) -> &'static [i32] {
+    match RTL_TCP_TUNER_NAMES.get(tuner_type) {
+        Some(&"E4000") => &[
+            -10, 15, 40, 65, 90, 115, 140, 165, 190, 215, 240, 290, 340, 420,
+        ],
+        Some(&"FC0012") => &[-99, -40, 71, 179, 192],
+        Some(&"FC0013") => &[
+            -99, -73, -65, -63, -60, -58, -54, 58, 61, 63, 65, 67, 68, 70, 71, 179, 181, 182, 184,
+            186, 188, 191, 197,
+        ],
+        Some(&"R820T") | Some(&"R828D") => &[
+            0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207, 229, 254, 280, 297, 328, 338,
+            364, 372, 386, 402, 421, 434, 439, 445, 480, 496,
+        ],
+        _ => &[0],
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RtlTcpArgs {
+    pub host: String,
+    pub port: u16,
+}
+
+pub fn get_rtl_tcp_sdr_args(host: String, port: u16) -> AvailableSDRArgs {
+    let address = format!("{}:{}", host, port);
+
+    AvailableSDRArgs {
+        driver: RTL_TCP_DRIVER.to_string(),
+        label: format!("rtl_tcp: {}", address),
+        manufacturer: String::from("Unknown"),
+        product: String::from("rtl_tcp"),
+        // the address is used as the serial since it is unique
+        serial: address,
+        tuner: String::from("Unknown"),
+        source: Some(SDRSourceArgs::RtlTcp(RtlTcpArgs { host, port })),
+    }
+}
+
+struct RtlTcpConnection {
+    command_stream: Mutex<TcpStream>,
+    frequency: Mutex<f64>,
+    gain: Mutex<f64>,
+}
+
+impl Drop for RtlTcpConnection {
+    fn drop(&mut self) {
+        // this also stops the reader thread
+        let _ = self.command_stream.lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
+
+/// A remote RTL-SDR that is controlled and streamed over the rtl_tcp protocol.
+#[derive(Clone)]
+pub struct RtlTcpDevice {
+    connection: Arc<RtlTcpConnection>,
+    // index into RTL_TCP_TUNER_NAMES
+    tuner_type: usize,
+    sample_rate: Arc<Mutex<f64>>,
+    sample_slot: RtlTcpSampleSlot,
+}
+
+impl RtlTcpDevice {
+    pub fn new(args: RtlTcpArgs) -> Result<Self, String> {
+        let address = format!("{}:{}", args.host, args.port);
+
+        let socket_addr = address
+            .to_socket_addrs()
+            .map_err(|err| format!("Could not resolve {}: {}", address, err))?
+            .next()
+            .ok_or(format!("Could not resolve {}", address))?;
+
+        let mut stream = TcpStream::connect_timeout(&socket_addr, Duration::from_secs(5))
+            .map_err(|err| format!("Could not connect to {}: {}", address, err))?;
+        let _ = stream.set_nodelay(true);
+
+        // the server starts by sending "RTL0", the tuner type, and the number of gain values
+        let mut header = [0u8; 12];
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        stream
+            .read_exact(&mut header)
+            .map_err(|err| format!("Did not receive rtl_tcp header from {}: {}", address, err))?;
+        let _ = stream.set_read_timeout(None);
+
+        if &header[0..4] != b"RTL0" {
+            return Err(format!("{} is not an rtl_tcp server", address));
+        }
+
+        let tuner_type = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let gain_count = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+        let tuner = RTL_TCP_TUNER_NAMES
+            .get(tuner_type as usize)
+            .unwrap_or(&"Unknown")
+            .to_string();
+
+        info!(
+            "Connected to rtl_tcp server {} (tuner: {}, gain values: {})",
+            address, tuner, gain_count
+        );
+
+        let reader_stream = stream
+            .try_clone()
+            .map_err(|err| format!("Could not read from {}: {}", address, err))?;
+
+        let device = Self {
+            tuner_type: tuner_type as usize,
+            connection: Arc::new(RtlTcpConnection {
+                command_stream: Mutex::new(stream),
+                frequency: Mutex::new(0.0),
+                gain: Mutex::new(0.0),
+            }),
+            sample_rate: Arc::new(Mutex::new(RTL_TCP_DEFAULT_SAMPLE_RATE)),
+            sample_slot: Arc::new(Mutex::new(None)),
+        };
+
+        RtlTcpDevice::spawn_reader(
+            address,
+            reader_stream,
+            device.sample_rate.clone(),
+            device.sample_slot.clone(),
+        );
+
+        Ok(device)
+    }
+
+    // continuously read samples so the server never has to drop any
+    fn spawn_reader(
+        address: String,
+        mut stream: TcpStream,
+        sample_rate: Arc<Mutex<f64>>,
+        sample_slot: RtlTcpSampleSlot,
+    ) {
+        thread::spawn(move || {
+            let mut buf_pool = ChunkBufPool::<Complex<f32>>::new();
+            let mut raw_buf = vec![0u8; READ_SIZE];
+
+            loop {
+                if let Err(err) = stream.read_exact(&mut raw_buf) {
+                    info!("rtl_tcp connection to {} closed: {}", address, err);
+                    *sample_slot.lock().unwrap() = None;
+                    return;
+                }
+
+                // don't hold the lock while sending, so the source can still be dropped or replaced while the
+                // pipeline is too busy to take the samples
+                let Some((sender, rt)) = sample_slot.lock().unwrap().clone() else {
+                    continue;
+                };
+
+                let mut output_chunk = buf_pool.get_with_capacity(READ_SIZE / 2);
+                for sample_bytes in raw_buf.chunks_exact(2) {
+                    output_chunk.push(IqSampleFormat::Cu8.decode_sample(sample_bytes));
+                }
+
+                let send_result = rt.block_on(sender.send(Signal::Samples {
+                    sample_rate: *sample_rate.lock().unwrap(),
+                    chunk: output_chunk.finalize(),
+                }));
+                if send_result.is_err() {
+                    // the pipeline was stopped, so discard samples until a new one starts (unless one already has)
+                    let mut sample_slot = sample_slot.lock().unwrap();
+                    if sample_slot
+                        .as_ref()
+                        .is_some_and(|(slot_sender, _)| Arc::ptr_eq(slot_sender, &sender))
+                    {
+                        *sample_slot = None;
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn send_command(&self, command: u8, param: u32) -> Result<(), String> {
+        let mut message = [0u8; 5];
+        message[0] = command;
+        message[1..].copy_from_slice(&param.to_be_bytes());
+
+        self.connection
+            .command_stream
+            .lock()
+            .unwrap()
+            .write_all(&message)
+            .map_err(|err| {
+                error!("Failed to send rtl_tcp command {:#04x}: {}", command, err);
+                format!("Failed to send command to rtl_tcp server: {}", err)
+            })
+    }
+
+    pub fn set_frequency(&self, frequency: f64) -> Result<(), String> {
+        self.send_command(RTL_TCP_SET_FREQ, frequency.round() as u32)?;
+        *self.connection.frequency.lock().unwrap() = frequency;
+        Ok(())
+    }
+
+    pub fn frequency(&self) -> f64 {
+        *self.connection.frequency.lock().unwrap()
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f64) -> Result<(), String> {
+        self.send_command(RTL_TCP_SET_SAMPLE_RATE, sample_rate.round() as u32)?;
+        *self.sample_rate.lock().unwrap() = sample_rate;
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        *self.sample_rate.lock().unwrap()
+    }
+
+    pub fn set_gain_mode(&self, automatic: bool) -> Result<(), String> {
+        // 0 -> automatic, 1 -> manual
+        self.send_command(RTL_TCP_SET_GAIN_MODE, if automatic { 0 } else { 1 })
+    }
+
+    pub fn set_gain(&self, gain: f64) -> Result<(), String> {
+        // the gain is sent in tenths of a dB
+        self.send_command(RTL_TCP_SET_GAIN, ((gain * 10.0).round() as i32) as u32)?;
+        *self.connection.gain.lock().unwrap() = gain;
+        Ok(())
+    }
+
+    pub fn gain(&self) -> f64 {
+        *self.connection.gain.lock().unwrap()
+    }
+
+    pub fn set_frequency_correction(&self, ppm: i32) -> Result<(), String> {
+        self.send_command(RTL_TCP_SET_FREQ_CORRECTION, ppm as u32)
+    }
+
+    /// Handles the same settings as the SoapySDR RTL-SDR driver
+    pub fn write_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        let is_enabled = value == "true" || value == "1";
+        match key {
+            "direct_samp" => self.send_command(
+                RTL_TCP_SET_DIRECT_SAMPLING,
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid direct sampling mode {}", value))?,
+            ),
+            "digital_agc" => self.send_command(RTL_TCP_SET_AGC_MODE, is_enabled as u32),
+            "offset_tune" => self.send_command(RTL_TCP_SET_OFFSET_TUNING, is_enabled as u32),
+            "testmode" => self.send_command(RTL_TCP_SET_TEST_MODE, is_enabled as u32),
+            "biastee" => self.send_command(RTL_TCP_SET_BIAS_TEE, is_enabled as u32),
+            _ => Err(format!("rtl_tcp does not support the {} setting", key)),
+        }
+    }
+
+    /// The same capabilities that the SoapySDR RTL-SDR driver reports for the tuner
+    pub fn capabilities(&self) -> SDRCapabilities {
+        let tuner_gains = get_rtl_tcp_tuner_gains(self.tuner_type);
+        let gain_range = SDRRange::new(
+            *tuner_gains.iter().min().unwrap() as f64 / 10.0,
+            *tuner_gains.iter().max().unwrap() as f64 / 10.0,
+            0.0,
+        );
+
+        SDRCapabilities {
+            has_automatic_gain: true,
+            gain_range: gain_range.clone(),
+            gain_elements: vec![SDRGainElement {
+                name: String::from("TUNER"),
+                range: gain_range,
+            }],
+            // librtlsdr rejects the sample rates between these ranges
+            sample_rate_ranges: vec![
+                SDRRange::new(225_001.0, 300_000.0, 0.0),
+                SDRRange::new(900_001.0, 3_200_000.0, 0.0),
+            ],
+            frequency_ranges: vec![
+                // direct sampling
+                SDRRange::new(0.0, 28.8e6, 0.0),
+                SDRRange::new(24e6, 1766e6, 0.0),
+            ],
+            antennas: vec![String::from("RX")],
+            settings: vec![
+                SDRSetting::new("direct_samp", "Direct Sampling", "0"),
+                SDRSetting::new("offset_tune", "Offset Tune", "false"),
+                SDRSetting::new("digital_agc", "Digital AGC", "false"),
+                SDRSetting::new("testmode", "Test Mode", "false"),
+                SDRSetting::new("biastee", "Bias Tee", "false"),
+            ],
+        }
+    }
+
+    pub fn rx_stream(&self) -> RtlTcpSource {
+        RtlTcpSource::new(self.sample_slot.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, sync::mpsc};
+
+    use radiorust::{flow::new_receiver, prelude::*};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // a local stand-in for an rtl_tcp server with an R820T, which streams full scale samples and passes along every
+    // command it receives
+    fn spawn_server() -> (RtlTcpArgs, mpsc::Receiver<[u8; 5]>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (command_sender, command_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut header = [0u8; 12];
+            header[0..4].copy_from_slice(b"RTL0");
+            header[4..8].copy_from_slice(&5u32.to_be_bytes());
+            header[8..12].copy_from_slice(&29u32.to_be_bytes());
+            stream.write_all(&header).unwrap();
+
+            let mut sample_stream = stream.try_clone().unwrap();
+            thread::spawn(move || {
+                let samples = [255u8, 0].repeat(READ_SIZE / 2);
+                while sample_stream.write_all(&samples).is_ok() {
+                    thread::sleep(Duration::from_millis(10));
+                }
+            });
+
+            let mut command = [0u8; 5];
+            while stream.read_exact(&mut command).is_ok() {
+                let _ = command_sender.send(command);
+            }
+        });
+
+        (
+            RtlTcpArgs {
+                host: String::from("127.0.0.1"),
+                port,
+            },
+            command_receiver,
+        )
+    }
+
+    fn command(command: u8, param: u32) -> [u8; 5] {
+        let mut message = [command, 0, 0, 0, 0];
+        message[1..].copy_from_slice(&param.to_be_bytes());
+        message
+    }
+
+    #[test]
+    fn sends_commands() {
+        let (args, command_receiver) = spawn_server();
+        let device = RtlTcpDevice::new(args).unwrap();
+
+        // the gains of the tuner in the header are used
+        assert_eq!(device.capabilities().gain_range.maximum, 49.6);
+
+        device.set_frequency(100e6).unwrap();
+        device.set_gain(19.7).unwrap();
+        device.write_setting("biastee", "true").unwrap();
+
+        assert_eq!(
+            command_receiver.recv_timeout(TIMEOUT).unwrap(),
+            command(RTL_TCP_SET_FREQ, 100_000_000)
+        );
+        assert_eq!(
+            command_receiver.recv_timeout(TIMEOUT).unwrap(),
+            command(RTL_TCP_SET_GAIN, 197)
+        );
+        assert_eq!(
+            command_receiver.recv_timeout(TIMEOUT).unwrap(),
+            command(RTL_TCP_SET_BIAS_TEE, 1)
+        );
+        assert_eq!(device.frequency(), 100e6);
+        assert_eq!(device.gain(), 19.7);
+    }
+
+    #[test]
+    fn streams_samples() {
+        let (args, _command_receiver) = spawn_server();
+        let device = RtlTcpDevice::new(args).unwrap();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let source = device.rx_stream();
+        let (mut receiver, receiver_connector) = new_receiver::<Signal<Complex<f32>>>();
+        receiver_connector.feed_from(&source);
+        source.activate().unwrap();
+
+        let Ok(Signal::Samples { sample_rate, chunk }) = rt.block_on(receiver.recv()) else {
+            panic!("Did not receive samples");
+        };
+        assert_eq!(sample_rate, RTL_TCP_DEFAULT_SAMPLE_RATE);
+        assert_eq!(chunk.len(), READ_SIZE / 2);
+        assert!(chunk
+            .iter()
+            .all(|sample| *sample == Complex::new(1.0, -1.0)));
+    }
+
+    #[test]
+    fn drops_source_while_pipeline_is_full() {
+        let (args, _command_receiver) = spawn_server();
+        let device = RtlTcpDevice::new(args).unwrap();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        // the receiver is never read from, so the reader ends up waiting to send
+        let source = device.rx_stream();
+        let (_receiver, receiver_connector) = new_receiver::<Signal<Complex<f32>>>();
+        receiver_connector.feed_from(&source);
+        source.activate().unwrap();
+        thread::sleep(Duration::from_millis(500));
+
+        let (dropped_sender, dropped_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            drop(source);
+            let _ = dropped_sender.send(());
+        });
+        dropped_receiver.recv_timeout(TIMEOUT).unwrap();
+
+        // a new stream can take over the connection
+        device.rx_stream().activate().unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::radiorust_blocks::simulated_source::SimulatedSource;
+
+use super::{
+    capabilities::{SDRCapabilities, SDRRange},
+    enumeration::{AvailableSDRArgs, SDRSourceArgs},
+    iq_file::FilePacing,
+};
+
+pub const SIMULATED_DRIVER: &str = "simulated";
+
+// the sample rate before any is requested (the same as most RTL-SDRs)
+const SIMULATED_DEFAULT_SAMPLE_RATE: f64 = 2.048e6;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SimulatedRdsArgs {
+    // program identification code
+    pub pi: u16,
+    // program service name (up to 8 characters)
+    pub ps: String,
+    // radio text (up to 64 characters)
+    pub rt: String,
+    // program type
+    pub pty: u8,
+}
+
+/// A test signal transmitted at a fixed frequency (in hz)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SimulatedSignal {
+    // wideband FM station with a mono tone, 19 kHz stereo pilot, and optional RDS data
+    WbFm {
+        frequency: f64,
+        tone_freq: f64,
+        rds: Option<SimulatedRdsArgs>,
+    },
+    // AM carrier modulated by a tone
+    Am {
+        frequency: f64,
+        tone_freq: f64,
+        modulation_depth: f64,
+    },
+    // Mode S transponder repeating messages (as bits or hex), which defaults to the test messages
+    ModeS {
+        frequency: f64,
+        messages: Option<Vec<String>>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SimulatedArgs {
+    pub name: String,
+    pub signals: Vec<SimulatedSignal>,
+    // amplitude of the white noise added to the signals
+    pub noise_level: f32,
+    pub pacing: FilePacing,
+}
+
+impl Default for SimulatedArgs {
+    fn default() -> Self {
+        Self {
+            name: String::from("Test Signals"),
+            signals: vec![
+                SimulatedSignal::WbFm {
+                    frequency: 101.1e6,
+                    tone_freq: 1_000.0,
+                    rds: Some(SimulatedRdsArgs {
+                        pi: 0x1234,
+                        ps: String::from("SIM FM"),
+                        rt: String::from("Simulated FM station from the virtual SDR"),
+                        pty: 1,
+                    }),
+                },
+                SimulatedSignal::Am {
+                    frequency: 1_000e3,
+                    tone_freq: 1_000.0,
+                    modulation_depth: 0.5,
+                },
+                SimulatedSignal::ModeS {
+                    frequency: 1090e6,
+                    messages: None,
+                },
+            ],
+            noise_level: 0.01,
+            pacing: FilePacing::RealTime,
+        }
+    }
+}
+
+pub fn get_simulated_sdr_args(args: SimulatedArgs) -> AvailableSDRArgs {
+    AvailableSDRArgs {
+        driver: SIMULATED_DRIVER.to_string(),
+        label: format!("Simulated: {}", args.name),
+        manufacturer: String::from("Unknown"),
+        product: String::from("Simulated SDR"),
+        // the name is used as the serial, so each simulated SDR needs a unique name
+        serial: format!("simulated:{}", args.name),
+        tuner: String::from("Unknown"),
+        source: Some(SDRSourceArgs::Simulated(args)),
+    }
+}
+
+/// A virtual device that generates test signals, so the pipelines can be run
+/// without any hardware. Signals outside of the tuned bandwidth are not generated.
+#[derive(Clone)]
+pub struct SimulatedDevice {
+    args: SimulatedArgs,
+    frequency: Arc<Mutex<f64>>,
+    sample_rate: Arc<Mutex<f64>>,
+    gain: Arc<Mutex<f64>>,
+}
+
+impl SimulatedDevice {
+    pub fn new(args: SimulatedArgs) -> Self {
+        info!(
+            "Created simulated SDR {} with {} signals",
+            args.name,
+            args.signals.len()
+        );
+
+        Self {
+            args,
+            frequency: Arc::new(Mutex::new(0.0)),
+            sample_rate: Arc::new(Mutex::new(SIMULATED_DEFAULT_SAMPLE_RATE)),
+            gain: Arc::new(Mutex::new(0.0)),
+        }
+    }
+
+    pub fn set_frequency(&self, freq: f64) {
+        *self.frequency.lock().unwrap() = freq;
+    }
+
+    pub fn frequency(&self) -> f64 {
+        *self.frequency.lock().unwrap()
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f64) {
+        *self.sample_rate.lock().unwrap() = sample_rate;
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        *self.sample_rate.lock().unwrap()
+    }
+
+    // the signals have a fixed level, but gain is remembered so the pipelines behave the same
+    pub fn set_gain(&self, gain: f64) {
+        *self.gain.lock().unwrap() = gain;
+    }
+
+    pub fn gain(&self) -> f64 {
+        *self.gain.lock().unwrap()
+    }
+
+    pub fn capabilities(&self) -> SDRCapabilities {
+        SDRCapabilities {
+            has_automatic_gain: true,
+            gain_range: SDRRange::new(0.0, 50.0, 0.0),
+            gain_elements: vec![],
+            sample_rate_ranges: vec![SDRRange::new(1e3, 20e6, 0.0)],
+            frequency_ranges: vec![SDRRange::new(0.0, 6e9, 0.0)],
+            antennas: vec![],
+            settings: vec![],
+        }
+    }
+
+    pub fn rx_stream(&self) -> SimulatedSource {
+        SimulatedSource::new(
+            self.args.clone(),
+            self.frequency.clone(),
+            self.sample_rate.clone(),
+        )
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{radio_services::soapysdr_radio::StreamType, sdr::enumeration::AvailableSDRArgs};
+
+const STORE_FILE: &str = "store.json";
+
+// the store is read and written as a whole, so changes from different threads can't overlap
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum StationType {
+    HDRadio = 0,
+    FMRadio = 1,
+    AMRadio = 2,
+    // SSB and CW stations on shortwave
+    HFRadio = 3,
+    // narrowband FM channels, such as weather radio and repeaters
+    NBFMRadio = 4,
+    // AM channels in the VHF airband, such as tower and approach frequencies
+    AirbandRadio = 5,
+}
+
+impl TryFrom<u8> for StationType {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(StationType::HDRadio),
+            1 => Ok(StationType::FMRadio),
+            2 => Ok(StationType::AMRadio),
+            3 => Ok(StationType::HFRadio),
+            4 => Ok(StationType::NBFMRadio),
+            5 => Ok(StationType::AirbandRadio),
+            _ => Err(format!("Unknown station type: {}", value)),
+        }
+    }
+}
+
+impl From<StationType> for u8 {
+    fn from(station_type: StationType) -> Self {
+        station_type as u8
+    }
+}
+
+/// Identifies a station, without the details that can be changed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Station {
+    #[serde(rename = "type")]
+    pub station_type: StationType,
+    pub frequency: f64,
+    pub channel: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StationDetails {
+    #[serde(rename = "type")]
+    pub station_type: StationType,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u32>,
+    pub frequency: f64,
+    pub is_favorite: bool,
+}
+
+impl StationDetails {
+    // the channel is only compared if the station being looked for has one
+    fn is_station(&self, station: &Station) -> bool {
+        self.station_type == station.station_type
+            && self.frequency == station.frequency
+            && (station.channel.is_none() || self.channel == station.channel)
+    }
+
+    fn station(&self) -> Station {
+        Station {
+            station_type: self.station_type,
+            frequency: self.frequency,
+            channel: self.channel,
+        }
+    }
+}
+
+/// The settings that were last used with an SDR, so they can be used again
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DevicePreferences {
+    pub gain: Option<f64>,
+    // frequency correction
+    pub ppm: Option<f64>,
+    pub sample_rate: Option<f64>,
+    pub preferred_mode: Option<StreamType>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct StoreData {
+    stations: Vec<StationDetails>,
+    seconds_listened: f64,
+    // keyed by the driver and serial of each SDR
+    device_preferences: HashMap<String, DevicePreferences>,
+}
+
+/// Saved stations, listening stats and the preferences of each SDR, which are saved as JSON in the app data
+/// directory. It doesn't need the app, so the headless receiver can use the same data.
+#[derive(Clone)]
+pub struct Store {
+    data_dir: Option<PathBuf>,
+}
+
+impl Store {
+    pub fn new(data_dir: Option<&Path>) -> Self {
+        Self {
+            data_dir: data_dir.map(Path::to_path_buf),
+        }
+    }
+
+    fn data_dir(&self) -> Result<&Path, String> {
+        self.data_dir
+            .as_deref()
+            .ok_or(String::from("Could not find app data directory"))
+    }
+
+    fn read(&self) -> StoreData {
+        self.try_read().unwrap_or_else(|err| {
+            warn!("{}", err);
+            StoreData::default()
+        })
+    }
+
+    // only fails if the store exists, but couldn't be parsed
+    fn try_read(&self) -> Result<StoreData, String> {
+        let Ok(data_dir) = self.data_dir() else {
+            return Ok(StoreData::default());
+        };
+        let path = data_dir.join(STORE_FILE);
+
+        // nothing has been saved yet
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(StoreData::default());
+        };
+
+        serde_json::from_str(&contents)
+            .map_err(|err| format!("Could not parse {}: {}", path.display(), err))
+    }
+
+    fn write(&self, data: &StoreData) -> Result<(), String> {
+        let data_dir = self.data_dir()?;
+        fs::create_dir_all(data_dir)
+            .map_err(|err| format!("Could not create {}: {}", data_dir.display(), err))?;
+
+        // write to another file first, so the store isn't lost if writing fails part of the way through
+        let path = data_dir.join(STORE_FILE);
+        let temp_path = path.with_extension("json.tmp");
+        let contents = serde_json::to_string_pretty(data).unwrap();
+        fs::write(&temp_path, contents).map_err(|err| format!("Could not save store: {}", err))?;
+        fs::rename(&temp_path, &path).map_err(|err| format!("Could not save store: {}", err))
+    }
+
+    fn update<T>(&self, change: impl FnOnce(&mut StoreData) -> T) -> Result<T, String> {
+        let _lock = STORE_LOCK.lock().unwrap();
+
+        let mut data = match self.try_read() {
+            Ok(data) => data,
+            Err(err) => {
+                // keep the store as a backup, instead of overwriting the saved stations in it
+                let path = self.data_dir()?.join(STORE_FILE);
+                let backup_path = path.with_extension("json.bak");
+                fs::rename(&path, &backup_path).map_err(|rename_err| {
+                    format!("{}, and it could not be backed up: {}", err, rename_err)
+                })?;
+                warn!("{}, so it was moved to {}", err, backup_path.display());
+                StoreData::default()
+            }
+        };
+        let result = change(&mut data);
+        self.write(&data)?;
+
+        Ok(result)
+    }
+
+    pub fn get_stations(&self, station_type: Option<StationType>) -> Vec<StationDetails> {
+        let stations = self.read().stations;
+
+        match station_type {
+            Some(station_type) => stations
+                .into_iter()
+                .filter(|station| station.station_type == station_type)
+                .collect(),
+            None => stations,
+        }
+    }
+
+    pub fn is_station_saved(&self, station: &Station) -> bool {
+        self.read()
+            .stations
+            .iter()
+            .any(|saved_station| saved_station.is_station(station))
+    }
+
+    pub fn save_station(&self, station: StationDetails) -> Result<(), String> {
+        self.update(|data| {
+            let station_key = station.station();
+            if !data
+                .stations
+                .iter()
+                .any(|saved_station| saved_station.is_station(&station_key))
+            {
+                data.stations.push(station);
+            }
+        })
+    }
+
+    /// Replace the details of a station, saving it if it wasn't already
+    pub fn update_station(
+        &self,
+        old_station: &Station,
+        new_station: StationDetails,
+    ) -> Result<(), String> {
+        self.update(|data| {
+            match data
+                .stations
+                .iter_mut()
+                .find(|saved_station| saved_station.is_station(old_station))
+            {
+                Some(saved_station) => *saved_station = new_station,
+                None => data.stations.push(new_station),
+            }
+        })
+    }
+
+    pub fn remove_station(&self, station: &Station) -> Result<(), String> {
+        self.update(|data| {
+            data.stations
+                .retain(|saved_station| !saved_station.is_station(station));
+        })
+    }
+
+    pub fn get_seconds_listened(&self) -> f64 {
+        self.read().seconds_listened
+    }
+
+    /// Add to the total time listened to the radio, returning the new total
+    pub fn increase_listening_duration(&self, seconds: f64) -> Result<f64, String> {
+        self.update(|data| {
+            data.seconds_listened += seconds;
+            data.seconds_listened
+        })
+    }
+
+    /// Whether the preferences of an SDR can be saved, which needs a serial to tell it apart from others
+    pub fn saves_device_preferences(args: &AvailableSDRArgs) -> bool {
+        device_key(args).is_some()
+    }
+
+    pub fn get_device_preferences(&self, args: &AvailableSDRArgs) -> DevicePreferences {
+        let Some(key) = device_key(args) else {
+            return DevicePreferences::default();
+        };
+
+        self.read()
+            .device_preferences
+            .remove(&key)
+            .unwrap_or_default()
+    }
+
+    /// Change the saved preferences of an SDR, which does nothing if they can't be saved
+    pub fn update_device_preferences(
+        &self,
+        args: &AvailableSDRArgs,
+        change: impl FnOnce(&mut DevicePreferences),
+    ) -> Result<(), String> {
+        let Some(key) = device_key(args) else {
+            debug!(
+                "Not saving the preferences of {}, since it has no serial",
+                args.label
+            );
+            return Ok(());
+        };
+
+        self.update(|data| change(data.device_preferences.entry(key).or_default()))
+    }
+}
+
+// SDRs without a serial would all share the same preferences, so they don't get any
+fn device_key(args: &AvailableSDRArgs) -> Option<String> {
+    if args.serial.is_empty() || args.serial == "Unknown" {
+        return None;
+    }
+
+    Some(format!("{}:{}", args.driver, args.serial))
+}
@@ -2,7 +2,7 @@ use libc::{dlopen, RTLD_NOW};
 use libloading::Library;
 use log::{debug, info};
 use std::{env, ffi::CString};
-use tauri::{App, Emitter, Manager};
+use tauri::{App, Manager};
 
 use crate::{
     sdr::{enumeration::register_available_sdrs_callback, SDRDeviceState, SDRState},
@@ -42,15 +42,19 @@ pub fn setup_dependencies(app: &mut App) {
 }
 
 pub fn setup_callbacks(app: &mut App) {
-    let app_handle = app.app_handle().clone();
-    register_available_sdrs_callback(5.0, move |args| {
+    let sdr_registry = app.state::<AppState>().sdr_registry.clone();
+    let available_sdrs_refresher = register_available_sdrs_callback(5.0, move |args| {
         info!("Available SDR Details: {:?}", args);
 
-        let state = app_handle.state::<AppState>();
-        let mut sdrs = state.sdrs.lock().unwrap();
+        let mut sdrs = sdr_registry.lock();
 
         // remove existing devices that no longer are available
         for existing_dev in sdrs.clone().iter() {
+            // virtual SDRs are never enumerated, so they are only removed manually
+            if existing_dev.args.source.is_some() {
+                continue;
+            }
+
             let matching_args = args
                 .iter()
                 .find(|available_args| existing_dev.args == **available_args);
@@ -69,10 +73,12 @@ pub fn setup_callbacks(app: &mut App) {
                 sdrs.push(SDRState {
                     args: available_arg.to_owned(),
                     dev: SDRDeviceState::Available,
+                    capabilities: None,
                 });
             }
         }
 
-        let _ = app_handle.emit("sdr_states", sdrs.clone());
+        sdr_registry.emit_states(&sdrs);
     });
+    app.manage(available_sdrs_refresher);
 }
@@ -46,6 +46,16 @@ const views: ViewData[] = [
         name: "AM Radio",
         view: (props) => <RadioView type={StationType.AMRadio} {...props} />,
       },
+      {
+        id: "hf-radio",
+        name: "Shortwave (SSB/CW)",
+        view: (props) => <RadioView type={StationType.HFRadio} {...props} />,
+      },
+      {
+        id: "nbfm-radio",
+        name: "Narrowband FM",
+        view: (props) => <RadioView type={StationType.NBFMRadio} {...props} />,
+      },
     ],
   },
   {
@@ -57,6 +67,13 @@ const views: ViewData[] = [
         name: "ADS-B",
         view: (props) => <AdsbDecoderView {...props} />,
       },
+      {
+        id: "airband",
+        name: "Airband",
+        view: (props) => (
+          <RadioView type={StationType.AirbandRadio} {...props} />
+        ),
+      },
     ],
   },
 ];
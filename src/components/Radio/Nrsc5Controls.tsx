@@ -70,19 +70,15 @@ export default function Nrsc5Controls({
   const [nrsc5Status, setNrsc5Status] = useState(Nrsc5Status.Stopped);
   const [streamDetails, setStreamDetails] = useState<StreamDetails>({});
 
-  const [isSaved, setIsSaved] = useState(
-    isStationSaved({
-      type: StationType.HDRadio,
-      frequency: streamDetails.frequency!,
-      channel: streamDetails.channel,
-    })
-  );
+  const [isSaved, setIsSaved] = useState(false);
 
   useEffect(() => {
     if (currentStation) {
-      setIsSaved(isStationSaved(currentStation));
+      (async () => {
+        setIsSaved(await isStationSaved(currentStation));
+      })();
     }
-  });
+  }, [currentStation]);
 
   useEffect(() => {
     (async () => {
@@ -25,6 +25,8 @@ export default function RadioView({
   const [currentStation, setCurrentStation] = useState<undefined | Station>(
     undefined
   );
+  // shortwave stations can be listened to with any of these modes
+  const [hfStreamType, setHfStreamType] = useState(StreamType.USB);
 
   return (
     <div className="flex h-full w-full gap-4 p-4">
@@ -72,6 +74,38 @@ export default function RadioView({
             globalState={globalState}
             setGlobalState={setGlobalState}
           />
+        ) : type == StationType.HFRadio ? (
+          <RtlSdrControls
+            currentStation={currentStation}
+            setCurrentStation={setCurrentStation}
+            requestedStation={requestedStation}
+            setRequestedStation={setRequestedStation}
+            streamType={hfStreamType}
+            streamTypeOptions={[StreamType.USB, StreamType.LSB, StreamType.CW]}
+            setStreamType={setHfStreamType}
+            globalState={globalState}
+            setGlobalState={setGlobalState}
+          />
+        ) : type == StationType.NBFMRadio ? (
+          <RtlSdrControls
+            currentStation={currentStation}
+            setCurrentStation={setCurrentStation}
+            requestedStation={requestedStation}
+            setRequestedStation={setRequestedStation}
+            streamType={StreamType.NBFM}
+            globalState={globalState}
+            setGlobalState={setGlobalState}
+          />
+        ) : type == StationType.AirbandRadio ? (
+          <RtlSdrControls
+            currentStation={currentStation}
+            setCurrentStation={setCurrentStation}
+            requestedStation={requestedStation}
+            setRequestedStation={setRequestedStation}
+            streamType={StreamType.AIR}
+            globalState={globalState}
+            setGlobalState={setGlobalState}
+          />
         ) : (
           <RtlSdrControls
             currentStation={currentStation}
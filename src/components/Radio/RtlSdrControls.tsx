@@ -22,7 +22,19 @@ import {
   volumeStorageName,
   AvailableSdrArgs,
   HdRadioState,
+  SdrCapabilities,
+  StreamRecoveryEvent,
+  RadioError,
+  RadioErrorEvent,
+  FmStereoState,
+  Deemphasis,
+  deemphasisStorageName,
+  amBandwidthStorageName,
+  SubaudibleTone,
+  AirbandChannelState,
+  TimeShiftPosition,
 } from "@/lib/types";
+import { getDefaultDeemphasis, getRadioErrorMessage } from "@/lib/utils";
 import { Channel, invoke } from "@tauri-apps/api/core";
 import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
 import { Globe, Loader2, MusicIcon } from "lucide-react";
@@ -49,7 +61,13 @@ import {
   HoverCardContent,
   HoverCardTrigger,
 } from "../ui/hover-card";
-import { Select, SelectContent, SelectItem, SelectTrigger } from "../ui/select";
+import {
+  Select,
+  SelectContent,
+  SelectItem,
+  SelectTrigger,
+  SelectValue,
+} from "../ui/select";
 import { Badge } from "../ui/badge";
 const appWindow = getCurrentWebviewWindow();
 
@@ -66,6 +84,8 @@ export default function RtlSdrControls({
   requestedStation,
   setRequestedStation,
   streamType,
+  streamTypeOptions,
+  setStreamType,
   globalState,
   setGlobalState,
 }: {
@@ -74,48 +94,117 @@ export default function RtlSdrControls({
   requestedStation: Station | undefined | null;
   setRequestedStation: Dispatch<SetStateAction<Station | undefined | null>>;
   streamType: StreamType;
+  // the modes that can be picked while stopped (such as USB, LSB, and CW)
+  streamTypeOptions?: StreamType[];
+  setStreamType?: Dispatch<SetStateAction<StreamType>>;
   globalState: GlobalState;
   setGlobalState: React.Dispatch<React.SetStateAction<GlobalState>>;
 }) {
+  const isHfStream =
+    streamType == StreamType.USB ||
+    streamType == StreamType.LSB ||
+    streamType == StreamType.CW;
+  // narrowband FM and airband are received as channels with a squelch
+  const isChannelStream =
+    streamType == StreamType.NBFM || streamType == StreamType.AIR;
   const currentStationType =
     streamType == StreamType.FM
       ? StationType.FMRadio
-      : StreamType.HD
+      : streamType == StreamType.HD
       ? StationType.HDRadio
-      : StationType.AMRadio;
+      : streamType == StreamType.AM
+      ? StationType.AMRadio
+      : streamType == StreamType.NBFM
+      ? StationType.NBFMRadio
+      : streamType == StreamType.AIR
+      ? StationType.AirbandRadio
+      : StationType.HFRadio;
 
   const [status, setStatus] = useState(RtlSdrStatus.Stopped);
   const [streamSettings, setStreamSettings] = useState<RadioStreamSettings>({
     hd_radio_program: streamType == StreamType.HD ? 0 : undefined,
     freq: parseFloat(
       localStorage.getItem(streamType.toString() + freqStorageName) ||
-        (streamType == StreamType.AM ? "850" : "101.5")
+        (streamType == StreamType.AM
+          ? "850"
+          : isHfStream
+          ? "14200"
+          : streamType == StreamType.NBFM
+          ? "162.55"
+          : streamType == StreamType.AIR
+          ? "121.5"
+          : "101.5")
     ),
     volume: parseFloat(
       localStorage.getItem(streamType.toString() + volumeStorageName) || "0.5"
     ),
-    gain: streamType == StreamType.AM ? 0.0 : 12.0,
+    gain: streamType == StreamType.AM || isHfStream ? 0.0 : 12.0,
     sample_rate: parseFloat(
       localStorage.getItem(streamType.toString() + srStorageName) || "48000.0"
     ),
     stream_type: streamType,
+    deemphasis:
+      streamType == StreamType.FM
+        ? (localStorage.getItem(deemphasisStorageName) as Deemphasis | null) ||
+          getDefaultDeemphasis()
+        : undefined,
+    am_bandwidth:
+      streamType == StreamType.AM
+        ? parseFloat(localStorage.getItem(amBandwidthStorageName) || "10000")
+        : undefined,
   });
   const [isProcessingRequest, setIsProcessingRequest] = useState(false);
   const [error, setError] = useState("");
   //const [rbdsData, setRbdsData] = useState<RbdsData>({} as RbdsData);
   const [has10SecondsElapsed, set10SecondsElapsed] = useState(false);
+  const [fmStereoState, setFmStereoState] = useState<
+    undefined | FmStereoState
+  >(undefined);
+  const [isSquelchOpen, setIsSquelchOpen] = useState<undefined | boolean>(
+    undefined
+  );
+  const [subaudibleTone, setSubaudibleTone] = useState<
+    undefined | null | SubaudibleTone
+  >(undefined);
+  // the text of the monitored airband channels, which is parsed as it is typed
+  const [monitoredChannelsText, setMonitoredChannelsText] = useState("");
+  const [airbandChannelStates, setAirbandChannelStates] = useState<
+    AirbandChannelState[]
+  >([]);
+  const [timeShiftPosition, setTimeShiftPosition] = useState<
+    undefined | TimeShiftPosition
+  >(undefined);
   const [totalSecondsListened, setTotalSecondsListened] = useState(0);
   const [currentSdrArgs, setCurrentSdrArgs] = useState<
     undefined | AvailableSdrArgs
   >(undefined);
+  const [sdrCapabilities, setSdrCapabilities] = useState<
+    undefined | SdrCapabilities
+  >(undefined);
   let counterId: NodeJS.Timeout | undefined;
 
-  const [isSaved, setIsSaved] = useState(
-    isStationSaved({
-      type: currentStationType,
-      frequency: streamSettings.freq,
-    })
-  );
+  const [isSaved, setIsSaved] = useState(false);
+
+  // the BFO offset and filter width have different defaults for each mode
+  useEffect(() => {
+    setStreamSettings((old) => ({
+      ...old,
+      stream_type: streamType,
+      bfo_offset: undefined,
+      filter_width: undefined,
+    }));
+  }, [streamType]);
+
+  useEffect(() => {
+    (async () => {
+      setIsSaved(
+        await isStationSaved({
+          type: currentStationType,
+          frequency: streamSettings.freq,
+        })
+      );
+    })();
+  }, []);
 
   useEffect(() => {
     (async () => {
@@ -123,6 +212,27 @@ export default function RtlSdrControls({
     })();
   }, []);
 
+  // get the gain range of the selected SDR
+  useEffect(() => {
+    const sdrArgs = currentSdrArgs || globalState.defaultSdrArgs;
+    if (!sdrArgs) {
+      setSdrCapabilities(undefined);
+      return;
+    }
+
+    (async () => {
+      try {
+        setSdrCapabilities(
+          await invoke<SdrCapabilities>("get_sdr_capabilities", {
+            args: sdrArgs,
+          })
+        );
+      } catch (e) {
+        setSdrCapabilities(undefined);
+      }
+    })();
+  }, [currentSdrArgs, globalState.defaultSdrArgs]);
+
   const [listeningIntervalId, setListeningIntervalId] = useState<
     NodeJS.Timeout | undefined
   >(undefined);
@@ -179,11 +289,22 @@ export default function RtlSdrControls({
       streamType.toString() + srStorageName,
       streamSettings.sample_rate.toString()
     );
+    if (streamSettings.deemphasis) {
+      localStorage.setItem(deemphasisStorageName, streamSettings.deemphasis);
+    }
+    if (streamSettings.am_bandwidth) {
+      localStorage.setItem(
+        amBandwidthStorageName,
+        streamSettings.am_bandwidth.toString()
+      );
+    }
   }, [streamSettings, streamType]);
 
   useEffect(() => {
     if (currentStation) {
-      setIsSaved(isStationSaved(currentStation));
+      (async () => {
+        setIsSaved(await isStationSaved(currentStation));
+      })();
     }
   }, [currentStation]);
 
@@ -286,12 +407,21 @@ export default function RtlSdrControls({
       channel: undefined,
     });
     setError("");
-    await invoke<string>("start_stream", {
-      streamSettings,
-      sdrArgs: globalState.defaultSdrArgs,
-      rbdsChannel,
-      hdRadioChannel,
-    });
+    try {
+      await invoke("start_stream", {
+        streamSettings,
+        sdrArgs: globalState.defaultSdrArgs,
+        rbdsChannel,
+        hdRadioChannel,
+      });
+    } catch (e) {
+      // the stream couldn't be started, such as when the SDR is missing or in use
+      setStatus(RtlSdrStatus.Stopped);
+      setError(getRadioErrorMessage(e as RadioError));
+      await setCurrentStation(undefined);
+      await setRequestedStation(null);
+      return;
+    }
     setCurrentSdrArgs(globalState.defaultSdrArgs);
     updateSdrGlobalState(globalState.defaultSdrArgs, {
       functionName: streamType.toString().toUpperCase() + " Radio",
@@ -308,6 +438,11 @@ export default function RtlSdrControls({
       counterId = undefined;
     }
     set10SecondsElapsed(false);
+    setFmStereoState(undefined);
+    setIsSquelchOpen(undefined);
+    setSubaudibleTone(undefined);
+    setAirbandChannelStates([]);
+    setTimeShiftPosition(undefined);
     await invoke<string>("stop_stream", {});
     updateSdrGlobalState(currentSdrArgs!, {
       functionName: undefined,
@@ -337,12 +472,83 @@ export default function RtlSdrControls({
     }
   });
 
-  appWindow.listen("rtlsdr_err", async (event: { payload: string }) => {
-    setError(event.payload);
-    await setCurrentStation(undefined);
-    await setRequestedStation(null);
+  appWindow.listen(
+    "stream_recovery_status",
+    (event: { payload: StreamRecoveryEvent }) => {
+      if (event.payload.service != "radio" || !currentSdrArgs) {
+        return;
+      }
+      const status = event.payload.status;
+      let statusText;
+      if (status == "WaitingForDevice") {
+        statusText = "Waiting for SDR to reconnect...";
+      } else if (status == "Recovered") {
+        statusText = "Reconnected";
+      } else if ("Reconnecting" in status) {
+        statusText = `Reconnecting (attempt ${status.Reconnecting.attempt})...`;
+      } else {
+        statusText = `Stream failed: ${status.StreamFailed.error}`;
+      }
+      updateSdrGlobalState(currentSdrArgs, { statusText });
+    }
+  );
+
+  appWindow.listen("fm_stereo_state", (event: { payload: FmStereoState }) => {
+    setFmStereoState(event.payload);
+  });
+
+  appWindow.listen("squelch_state", (event: { payload: boolean }) => {
+    setIsSquelchOpen(event.payload);
   });
 
+  appWindow.listen(
+    "subaudible_tone",
+    (event: { payload: SubaudibleTone | null }) => {
+      setSubaudibleTone(event.payload);
+    }
+  );
+
+  appWindow.listen(
+    "airband_channel_state",
+    (event: { payload: AirbandChannelState }) => {
+      setAirbandChannelStates((old) => {
+        const newStates = old.filter(
+          (state) => state.frequency != event.payload.frequency
+        );
+        newStates.push(event.payload);
+        return newStates.sort((a, b) => a.frequency - b.frequency);
+      });
+    }
+  );
+
+  appWindow.listen(
+    "time_shift_position",
+    (event: { payload: TimeShiftPosition }) => {
+      setTimeShiftPosition(event.payload);
+    }
+  );
+
+  const seekTimeShift = async (offset: number) => {
+    if (!timeShiftPosition) {
+      return;
+    }
+    await invoke("seek_time_shift", {
+      offset: Math.min(Math.max(offset, 0), timeShiftPosition.buffered),
+    });
+  };
+
+  appWindow.listen(
+    "radio_error",
+    async (event: { payload: RadioErrorEvent }) => {
+      if (event.payload.service != "radio") {
+        return;
+      }
+      setError(getRadioErrorMessage(event.payload.error));
+      await setCurrentStation(undefined);
+      await setRequestedStation(null);
+    }
+  );
+
   return (
     <div className="flex xl:flex-row flex-col gap-4 xl:max-w-[48rem] max-w-[24rem] grow">
       <form
@@ -357,21 +563,88 @@ export default function RtlSdrControls({
           }
         }}
       >
-        {streamType == StreamType.AM && (
+        {(streamType == StreamType.AM || isHfStream) && (
           <span className="text-center text-amber-300">
-            RTL-SDRs often struggle with AM radio signals below 24 MHz (without
-            an upconvertor), resulting in significant static. Reception quality
-            will likely be poor.
+            RTL-SDRs often struggle with{" "}
+            {isHfStream ? "shortwave" : "AM radio"} signals below 24 MHz
+            (without an upconvertor), resulting in significant static.
+            Reception quality will likely be poor.
           </span>
         )}
+        {streamTypeOptions && setStreamType && (
+          <div className="grid w-full gap-1.5">
+            <Label htmlFor="stream_type">Mode</Label>
+            <Select
+              value={streamType}
+              disabled={status != RtlSdrStatus.Stopped}
+              onValueChange={(value) => setStreamType(value as StreamType)}
+            >
+              <SelectTrigger id="stream_type">
+                <SelectValue />
+              </SelectTrigger>
+              <SelectContent>
+                {streamTypeOptions.map((option) => (
+                  <SelectItem key={option} value={option}>
+                    {option == StreamType.USB
+                      ? "Upper Sideband (USB)"
+                      : option == StreamType.LSB
+                      ? "Lower Sideband (LSB)"
+                      : option == StreamType.CW
+                      ? "Morse Code (CW)"
+                      : option.valueOf()}
+                  </SelectItem>
+                ))}
+              </SelectContent>
+            </Select>
+          </div>
+        )}
         <div className="grid w-full gap-1.5">
-          <Label htmlFor="freq_slider">{streamType.valueOf()} Station</Label>
+          <Label htmlFor="freq_slider">
+            {isHfStream ? "Frequency (KHz)" : `${streamType.valueOf()} Station`}
+          </Label>
           <Input
             type="number"
-            step={streamType == StreamType.AM ? 10 : 0.2}
-            min={streamType == StreamType.AM ? 540 : 88.1}
-            max={streamType == StreamType.AM ? 1700 : 107.9}
+            step={
+              streamType == StreamType.AM
+                ? 10
+                : isHfStream
+                ? 0.1
+                : streamType == StreamType.NBFM
+                ? 0.0025
+                : streamType == StreamType.AIR
+                ? streamSettings.channel_spacing == 8333
+                  ? 0.005
+                  : 0.025
+                : 0.2
+            }
+            min={
+              streamType == StreamType.AM
+                ? 540
+                : isHfStream
+                ? 100
+                : streamType == StreamType.NBFM
+                ? 24
+                : streamType == StreamType.AIR
+                ? 118
+                : 88.1
+            }
+            max={
+              streamType == StreamType.AM
+                ? 1700
+                : isHfStream
+                ? 30000
+                : streamType == StreamType.NBFM
+                ? 1766
+                : streamType == StreamType.AIR
+                ? 137
+                : 107.9
+            }
             placeholder="#"
+            disabled={
+              streamType == StreamType.AIR &&
+              status != RtlSdrStatus.Stopped &&
+              (streamSettings.monitored_channels?.length || 0) > 0
+            }
             value={streamSettings.freq}
             onChange={(e) =>
               setStreamSettings((old) => ({
@@ -399,6 +672,293 @@ export default function RtlSdrControls({
             }
           />
         </div>
+        {streamType == StreamType.FM && (
+          <div className="grid w-full gap-1.5">
+            <Label htmlFor="deemphasis">Region (De-emphasis)</Label>
+            <Select
+              value={streamSettings.deemphasis}
+              onValueChange={(deemphasis) =>
+                setStreamSettings((old) => ({
+                  ...old,
+                  deemphasis: deemphasis as Deemphasis,
+                }))
+              }
+            >
+              <SelectTrigger id="deemphasis">
+                <SelectValue />
+              </SelectTrigger>
+              <SelectContent>
+                <SelectItem value={Deemphasis.Americas}>
+                  Americas and South Korea (75 µs)
+                </SelectItem>
+                <SelectItem value={Deemphasis.International}>
+                  Rest of the World (50 µs)
+                </SelectItem>
+                <SelectItem value={Deemphasis.Disabled}>Off</SelectItem>
+              </SelectContent>
+            </Select>
+          </div>
+        )}
+        {streamType == StreamType.AM && (
+          <div className="grid w-full gap-1.5">
+            <Label htmlFor="am_bandwidth">IF Bandwidth</Label>
+            <Select
+              value={(streamSettings.am_bandwidth || 10000).toString()}
+              onValueChange={(bandwidth) =>
+                setStreamSettings((old) => ({
+                  ...old,
+                  am_bandwidth: parseFloat(bandwidth),
+                }))
+              }
+            >
+              <SelectTrigger id="am_bandwidth">
+                <SelectValue />
+              </SelectTrigger>
+              <SelectContent>
+                <SelectItem value="2500">2.5 kHz (Narrow)</SelectItem>
+                <SelectItem value="5000">5 kHz</SelectItem>
+                <SelectItem value="10000">10 kHz (Wide)</SelectItem>
+              </SelectContent>
+            </Select>
+          </div>
+        )}
+        {isChannelStream && (
+          <>
+            <div className="grid w-full gap-1.5">
+              <Label htmlFor="channel_spacing">Channel Spacing</Label>
+              <Select
+                value={(
+                  streamSettings.channel_spacing ||
+                  (streamType == StreamType.AIR ? 25000 : 12500)
+                ).toString()}
+                disabled={status != RtlSdrStatus.Stopped}
+                onValueChange={(spacing) =>
+                  setStreamSettings((old) => ({
+                    ...old,
+                    channel_spacing: parseFloat(spacing),
+                  }))
+                }
+              >
+                <SelectTrigger id="channel_spacing">
+                  <SelectValue />
+                </SelectTrigger>
+                <SelectContent>
+                  {streamType == StreamType.AIR ? (
+                    <>
+                      <SelectItem value="8333">8.33 kHz</SelectItem>
+                      <SelectItem value="25000">25 kHz</SelectItem>
+                    </>
+                  ) : (
+                    <>
+                      <SelectItem value="12500">
+                        12.5 kHz (2.5 kHz deviation)
+                      </SelectItem>
+                      <SelectItem value="25000">
+                        25 kHz (5 kHz deviation)
+                      </SelectItem>
+                    </>
+                  )}
+                </SelectContent>
+              </Select>
+            </div>
+            <div className="grid w-full gap-1.5">
+              <Label htmlFor="squelch_slider">
+                Squelch -{" "}
+                {streamSettings.squelch
+                  ? `${Math.round(streamSettings.squelch * 100)}%`
+                  : "Off"}
+              </Label>
+              <Slider
+                min={0.0}
+                max={1.0}
+                step={0.01}
+                value={[streamSettings.squelch || 0.0]}
+                id="squelch_slider"
+                className="py-[2px]"
+                onValueChange={(values) => {
+                  setStreamSettings((old) => ({ ...old, squelch: values[0] }));
+                }}
+              />
+            </div>
+            {streamType == StreamType.AIR && (
+              <div className="grid w-full gap-1.5">
+                <Label htmlFor="monitored_channels">
+                  Also Monitor (MHz, comma-separated)
+                </Label>
+                <Input
+                  type="text"
+                  id="monitored_channels"
+                  placeholder="e.g. 118.3, 119.1"
+                  disabled={status != RtlSdrStatus.Stopped}
+                  value={monitoredChannelsText}
+                  onChange={(e) => {
+                    setMonitoredChannelsText(e.target.value);
+                    setStreamSettings((old) => ({
+                      ...old,
+                      monitored_channels: e.target.value
+                        .split(",")
+                        .map((channel) => parseFloat(channel))
+                        .filter((channel) => !isNaN(channel)),
+                    }));
+                  }}
+                />
+              </div>
+            )}
+            {streamType == StreamType.AIR &&
+              status == RtlSdrStatus.Running && (
+                <div className="flex flex-wrap gap-1">
+                  {airbandChannelStates.map((channel) => (
+                    <Badge
+                      key={channel.frequency}
+                      variant="outline"
+                      className={`before:content-[''] before:inline-block before:w-2 before:h-2 ${
+                        channel.isOpen
+                          ? "before:bg-green-500"
+                          : "before:bg-stone-500"
+                      } before:rounded-full before:mr-1 w-max`}
+                    >
+                      {channel.frequency.toFixed(3)}
+                    </Badge>
+                  ))}
+                </div>
+              )}
+            {streamType == StreamType.NBFM &&
+              status == RtlSdrStatus.Running && (
+                <div className="flex gap-1">
+                  {isSquelchOpen !== undefined && (
+                    <Badge
+                      variant="outline"
+                      className={`before:content-[''] before:inline-block before:w-2 before:h-2 ${
+                        isSquelchOpen
+                          ? "before:bg-green-500"
+                          : "before:bg-stone-500"
+                      } before:rounded-full before:mr-1 w-max`}
+                    >
+                      {isSquelchOpen ? "Squelch Open" : "Squelch Closed"}
+                    </Badge>
+                  )}
+                  {subaudibleTone !== undefined && (
+                    <Badge variant="outline" className="w-max">
+                      {formatSubaudibleTone(subaudibleTone)}
+                    </Badge>
+                  )}
+                </div>
+              )}
+          </>
+        )}
+        {isHfStream && (
+          <>
+            <div className="grid w-full gap-1.5">
+              <Label htmlFor="bfo_offset">
+                {streamType == StreamType.CW ? "CW Pitch" : "BFO Offset"} -{" "}
+                {streamSettings.bfo_offset ??
+                  (streamType == StreamType.CW ? 700 : 0)}{" "}
+                Hz
+              </Label>
+              <Slider
+                min={streamType == StreamType.CW ? 300 : -1000}
+                max={streamType == StreamType.CW ? 1200 : 1000}
+                step={10}
+                value={[
+                  streamSettings.bfo_offset ??
+                    (streamType == StreamType.CW ? 700 : 0),
+                ]}
+                id="bfo_offset"
+                className="py-[2px]"
+                onValueChange={(values) => {
+                  setStreamSettings((old) => ({
+                    ...old,
+                    bfo_offset: values[0],
+                  }));
+                }}
+              />
+            </div>
+            <div className="grid w-full gap-1.5">
+              <Label htmlFor="filter_width">Filter Width</Label>
+              <Select
+                value={(
+                  streamSettings.filter_width ||
+                  (streamType == StreamType.CW ? 500 : 2400)
+                ).toString()}
+                onValueChange={(width) =>
+                  setStreamSettings((old) => ({
+                    ...old,
+                    filter_width: parseFloat(width),
+                  }))
+                }
+              >
+                <SelectTrigger id="filter_width">
+                  <SelectValue />
+                </SelectTrigger>
+                <SelectContent>
+                  {streamType == StreamType.CW ? (
+                    <>
+                      <SelectItem value="250">250 Hz (Narrow)</SelectItem>
+                      <SelectItem value="500">500 Hz</SelectItem>
+                      <SelectItem value="1000">1 kHz (Wide)</SelectItem>
+                    </>
+                  ) : (
+                    <>
+                      <SelectItem value="1800">1.8 kHz (Narrow)</SelectItem>
+                      <SelectItem value="2400">2.4 kHz</SelectItem>
+                      <SelectItem value="3000">3 kHz (Wide)</SelectItem>
+                    </>
+                  )}
+                </SelectContent>
+              </Select>
+            </div>
+          </>
+        )}
+        {(streamType == StreamType.AM ||
+          isHfStream ||
+          streamType == StreamType.AIR) && (
+          <div className="grid w-full gap-1.5">
+            <Label htmlFor="noise_blanker_slider">
+              Noise Blanker -{" "}
+              {streamSettings.noise_blanker
+                ? `${Math.round(streamSettings.noise_blanker * 100)}%`
+                : "Off"}
+            </Label>
+            <Slider
+              min={0.0}
+              max={1.0}
+              step={0.01}
+              value={[streamSettings.noise_blanker || 0.0]}
+              id="noise_blanker_slider"
+              className="py-[2px]"
+              onValueChange={(values) => {
+                setStreamSettings((old) => ({
+                  ...old,
+                  noise_blanker: values[0],
+                }));
+              }}
+            />
+          </div>
+        )}
+        {streamType != StreamType.HD && (
+          <div className="grid w-full gap-1.5">
+            <Label htmlFor="noise_reduction_slider">
+              Noise Reduction -{" "}
+              {streamSettings.noise_reduction
+                ? `${Math.round(streamSettings.noise_reduction * 100)}%`
+                : "Off"}
+            </Label>
+            <Slider
+              min={0.0}
+              max={1.0}
+              step={0.01}
+              value={[streamSettings.noise_reduction || 0.0]}
+              id="noise_reduction_slider"
+              className="py-[2px]"
+              onValueChange={(values) => {
+                setStreamSettings((old) => ({
+                  ...old,
+                  noise_reduction: values[0],
+                }));
+              }}
+            />
+          </div>
+        )}
         <div className="grid w-full gap-1.5">
           <Label htmlFor="volume_slider">
             Volume - {Math.round(streamSettings.volume * 100)}%
@@ -418,19 +978,9 @@ export default function RtlSdrControls({
         <div className="grid w-full gap-1.5">
           <Label htmlFor="gain_slider">Gain - {streamSettings.gain} dB</Label>
           <Slider
-            min={0.0}
-            max={
-              (currentSdrArgs || globalState.defaultSdrArgs)?.driver ==
-              "sdrplay"
-                ? 27.0
-                : 49.6
-            }
-            step={
-              (currentSdrArgs || globalState.defaultSdrArgs)?.driver ==
-              "sdrplay"
-                ? 1.0
-                : 0.1
-            }
+            min={sdrCapabilities?.gain_range.minimum || 0.0}
+            max={sdrCapabilities?.gain_range.maximum || 49.6}
+            step={sdrCapabilities?.gain_range.step || 0.1}
             value={[streamSettings.gain]}
             id="gain_slider"
             className="py-[2px]"
@@ -439,6 +989,81 @@ export default function RtlSdrControls({
             }}
           />
         </div>
+        <div className="grid w-full gap-1.5">
+          <Label htmlFor="time_shift_length">Time Shift Buffer</Label>
+          <Select
+            value={(streamSettings.time_shift_length ?? 300).toString()}
+            onValueChange={(length) =>
+              setStreamSettings((old) => ({
+                ...old,
+                time_shift_length: parseFloat(length),
+              }))
+            }
+          >
+            <SelectTrigger id="time_shift_length">
+              <SelectValue />
+            </SelectTrigger>
+            <SelectContent>
+              <SelectItem value="0">Off</SelectItem>
+              <SelectItem value="60">1 Minute</SelectItem>
+              <SelectItem value="300">5 Minutes</SelectItem>
+              <SelectItem value="900">15 Minutes</SelectItem>
+            </SelectContent>
+          </Select>
+        </div>
+        {status == RtlSdrStatus.Running && timeShiftPosition && (
+          <div className="grid w-full gap-1.5">
+            <Label htmlFor="time_shift_slider">
+              {timeShiftPosition.isPaused ? "Paused" : "Playing"} -{" "}
+              {formatTimeShiftOffset(timeShiftPosition.offset)}
+            </Label>
+            <Slider
+              min={-Math.max(Math.floor(timeShiftPosition.buffered), 1)}
+              max={0}
+              step={1}
+              value={[-timeShiftPosition.offset]}
+              id="time_shift_slider"
+              className="py-[2px]"
+              onValueChange={(values) => seekTimeShift(-values[0])}
+            />
+            <div className="grid grid-cols-4 gap-1">
+              <Button
+                type="button"
+                variant="outline"
+                onClick={() => seekTimeShift(timeShiftPosition.offset + 10)}
+              >
+                -10s
+              </Button>
+              <Button
+                type="button"
+                variant="outline"
+                onClick={() =>
+                  invoke("set_time_shift_paused", {
+                    paused: !timeShiftPosition.isPaused,
+                  })
+                }
+              >
+                {timeShiftPosition.isPaused ? "Resume" : "Pause"}
+              </Button>
+              <Button
+                type="button"
+                variant="outline"
+                disabled={timeShiftPosition.offset == 0}
+                onClick={() => seekTimeShift(timeShiftPosition.offset - 10)}
+              >
+                +10s
+              </Button>
+              <Button
+                type="button"
+                variant="outline"
+                disabled={timeShiftPosition.offset == 0}
+                onClick={() => seekTimeShift(0)}
+              >
+                Live
+              </Button>
+            </div>
+          </div>
+        )}
         <Button
           onClick={() => {
             if (status == RtlSdrStatus.Running) {
@@ -506,7 +1131,7 @@ export default function RtlSdrControls({
           <HoursListenedToRadioView listenedForSeconds={totalSecondsListened} />
         )}
       </form>
-      {streamType != StreamType.AM && (
+      {(streamType == StreamType.FM || streamType == StreamType.HD) && (
         <div className="max-w-[24rem] w-full xl-grow">
           <Tabs
             defaultValue="radioInfo"
@@ -534,6 +1159,7 @@ export default function RtlSdrControls({
                 {streamType == StreamType.FM ? (
                   <RbdsDataView
                     globalState={globalState}
+                    fmStereoState={fmStereoState}
                     has10SecondsElapsed={has10SecondsElapsed}
                   />
                 ) : (
@@ -564,6 +1190,28 @@ export default function RtlSdrControls({
   );
 }
 
+// such as "Live" or "-1:05"
+function formatTimeShiftOffset(offset: number) {
+  const seconds = Math.round(offset);
+  if (seconds == 0) {
+    return "Live";
+  }
+  const minutes = Math.floor(seconds / 60);
+  return `-${minutes}:${(seconds % 60).toString().padStart(2, "0")}`;
+}
+
+function formatSubaudibleTone(tone: SubaudibleTone | null) {
+  if (!tone) {
+    return "No Tone";
+  } else if ("Ctcss" in tone) {
+    return `CTCSS ${tone.Ctcss.toFixed(1)} Hz`;
+  } else {
+    return `DCS ${tone.Dcs.code.toString(8).padStart(3, "0")}${
+      tone.Dcs.inverted ? "I" : "N"
+    }`;
+  }
+}
+
 function HdRadioStateView({
   globalState,
   streamSettings,
@@ -728,9 +1376,11 @@ function HdRadioStateView({
 
 function RbdsDataView({
   globalState,
+  fmStereoState,
   has10SecondsElapsed,
 }: {
   globalState: GlobalState;
+  fmStereoState: FmStereoState | undefined;
   has10SecondsElapsed: boolean;
 }) {
   return (
@@ -738,6 +1388,24 @@ function RbdsDataView({
       <TabsContent value="radioInfo">
         <Card>
           <CardHeader>
+            {fmStereoState && (
+              <Badge
+                variant="outline"
+                className={`before:content-[''] before:inline-block before:w-2 before:h-2 before:${
+                  !fmStereoState.isStereo
+                    ? "bg-stone-500"
+                    : fmStereoState.blend >= 1
+                    ? "bg-green-500"
+                    : "bg-yellow-500"
+                } before:rounded-full before:mr-1 w-max`}
+              >
+                {fmStereoState.isStereo
+                  ? fmStereoState.blend >= 1
+                    ? "Stereo"
+                    : `Stereo (${Math.round(fmStereoState.blend * 100)}%)`
+                  : "Mono"}
+              </Badge>
+            )}
             {globalState.rbdsData.radioText ? (
               <CardTitle
                 className="whitespace-pre-wrap"
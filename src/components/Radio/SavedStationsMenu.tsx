@@ -217,6 +217,12 @@ function SavedStationCard({
               ? "before:bg-blue-400"
               : station.type == StationType.AMRadio
               ? "before:bg-orange-300"
+              : station.type == StationType.HFRadio
+              ? "before:bg-green-400"
+              : station.type == StationType.NBFMRadio
+              ? "before:bg-teal-400"
+              : station.type == StationType.AirbandRadio
+              ? "before:bg-sky-400"
               : "before:bg-gray-400"
           } before:rounded-full before:mr-2`}
         >
@@ -226,6 +232,12 @@ function SavedStationCard({
             ? "FM "
             : station.type == StationType.AMRadio
             ? "AM "
+            : station.type == StationType.HFRadio
+            ? "HF "
+            : station.type == StationType.NBFMRadio
+            ? "NBFM "
+            : station.type == StationType.AirbandRadio
+            ? "AIR "
             : ""}
           {station.frequency}
         </Badge>
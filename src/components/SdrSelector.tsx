@@ -12,6 +12,7 @@ import {
   SelectValue,
 } from "./ui/select";
 import { GlobalState } from "./AppView";
+import { RefreshCw } from "lucide-react";
 
 const appWindow = getCurrentWebviewWindow();
 
@@ -98,6 +99,11 @@ export default function SdrSelector({
     await invoke("disconnect_sdr", { args: sdrArgs });
   };
 
+  // network SDRs can't be detected automatically, so they are found on demand
+  const refreshSdrs = async () => {
+    await invoke("refresh_sdrs", {});
+  };
+
   return (
     <div className="flex gap-2 w-screen items-center align-middle justify-center">
       <div className="flex-1" />
@@ -127,7 +133,15 @@ export default function SdrSelector({
         const selectedSdr = getSdrFromSerial(selectedSdrSerial);
 
         return (
-          <div className="flex flex-1">
+          <div className="flex flex-1 gap-2">
+            <Button
+              onClick={refreshSdrs}
+              variant="ghost"
+              size="sm"
+              title="Refresh SDRs"
+            >
+              <RefreshCw className="h-4 w-4" />
+            </Button>
             {selectedSdr && (
               <Button
                 onClick={() =>
@@ -1,110 +1,100 @@
+import { invoke } from "@tauri-apps/api/core";
 import {
+  ScannedStation,
   Station,
   StationDetails,
   StationSortOption,
   StationType,
+  StreamType,
 } from "./types";
 
-const stationsStorageName = "savedStations";
-
-export async function saveStation(station: StationDetails) {
-  if (isStationSaved(station)) return;
-
-  let oldStations = localStorage.getItem(stationsStorageName);
-
-  if (!oldStations) {
-    oldStations = "[]";
+// stations used to be saved in localStorage, so they are moved to the backend the first time they are used
+const legacyStationsStorageName = "savedStations";
+let legacyStationsMigration: Promise<void> | undefined;
+
+function migrateLegacyStations() {
+  if (!legacyStationsMigration) {
+    legacyStationsMigration = (async () => {
+      const legacyStations = localStorage.getItem(legacyStationsStorageName);
+      if (!legacyStations) return;
+
+      const parsedStations: StationDetails[] = JSON.parse(legacyStations);
+      for (const station of parsedStations) {
+        await invoke("save_station", { station });
+      }
+
+      localStorage.removeItem(legacyStationsStorageName);
+    })().catch((err) => {
+      // try again the next time, instead of failing every call
+      legacyStationsMigration = undefined;
+      throw err;
+    });
   }
 
-  const parsedStations: [StationDetails] = JSON.parse(oldStations);
+  return legacyStationsMigration;
+}
 
-  parsedStations.push(station);
+export async function saveStation(station: StationDetails) {
+  await migrateLegacyStations();
 
-  await localStorage.setItem(
-    stationsStorageName,
-    JSON.stringify(parsedStations)
-  );
+  await invoke("save_station", { station });
   dispatchEvent(new Event("saved_stations"));
 }
 
-export function isStationSaved(station: Station) {
-  const stations = localStorage.getItem(stationsStorageName);
+export async function isStationSaved(station: Station): Promise<boolean> {
+  await migrateLegacyStations();
 
-  if (!stations) return false;
-
-  const parsedStations: [StationDetails] = JSON.parse(stations);
-
-  const filteredStations = parsedStations.filter((e_station) => {
-    return (
-      e_station.type == station.type &&
-      e_station.frequency == station.frequency &&
-      (station.channel ? e_station.channel == station.channel : true)
-    );
-  });
-
-  return filteredStations.length > 0 ? true : false;
+  return await invoke<boolean>("is_station_saved", { station });
 }
 
 export async function removeStation(station: StationDetails) {
-  if (!isStationSaved(station)) return;
+  await migrateLegacyStations();
 
-  const stations: [StationDetails] = JSON.parse(
-    localStorage.getItem(stationsStorageName)!
-  );
-
-  const updatedStations = stations.filter((e_station) => {
-    return !(
-      e_station.type == station.type &&
-      e_station.frequency == station.frequency &&
-      (station.channel ? e_station.channel == station.channel : true)
-    );
-  });
-
-  await localStorage.setItem(
-    stationsStorageName,
-    JSON.stringify(updatedStations)
-  );
+  await invoke("remove_station", { station });
   dispatchEvent(new Event("saved_stations"));
 }
 
 export async function getSavedStations(
   stationType?: StationType
 ): Promise<StationDetails[]> {
-  const stations = localStorage.getItem(stationsStorageName);
+  await migrateLegacyStations();
 
-  if (!stations) return [];
-
-  const parsedStations: [StationDetails] = JSON.parse(stations);
-
-  if (stationType !== undefined) {
-    return parsedStations.filter((station) => station.type == stationType);
-  }
-
-  return parsedStations;
+  return await invoke<StationDetails[]>("get_saved_stations", { stationType });
 }
 
 export async function updateStation(
   oldStation: StationDetails,
   newStation: StationDetails
 ) {
-  if (!isStationSaved(oldStation)) await saveStation(newStation);
-
-  const stations: [StationDetails] = JSON.parse(
-    localStorage.getItem(stationsStorageName)!
-  );
+  await migrateLegacyStations();
 
-  const filteredStations = stations.filter((station) => {
-    return (
-      station.type == oldStation.type &&
-      station.frequency == oldStation.frequency &&
-      (oldStation.channel ? station.channel == oldStation.channel : true)
-    );
-  });
+  await invoke("update_station", { oldStation, newStation });
+  dispatchEvent(new Event("saved_stations"));
+}
 
-  stations[stations.indexOf(filteredStations[0])] = newStation;
+// use the station's RBDS name when it is known
+export function scannedStationToDetails(
+  station: ScannedStation,
+  streamType: StreamType
+): StationDetails {
+  const unit = streamType == StreamType.AM ? "KHz" : "MHz";
+
+  return {
+    type:
+      streamType == StreamType.AM ? StationType.AMRadio : StationType.FMRadio,
+    title: station.serviceName || `${station.frequency} ${unit}`,
+    frequency: station.frequency,
+    isFavorite: false,
+  };
+}
 
-  await localStorage.setItem(stationsStorageName, JSON.stringify(stations));
-  dispatchEvent(new Event("saved_stations"));
+export async function saveScannedStations(
+  stations: ScannedStation[],
+  streamType: StreamType
+) {
+  for (const station of stations) {
+    await saveStation(scannedStationToDetails(station, streamType));
+  }
 }
 
 export function areStationsEqual(
@@ -1,26 +1,44 @@
-const STATS_STORAGE_NAME = "stats";
+import { invoke } from "@tauri-apps/api/core";
+
+// the stats used to be saved in localStorage, so they are moved to the backend the first time they are used
+const legacyStatsStorageName = "stats";
+let legacyStatsMigration: Promise<void> | undefined;
+
+function migrateLegacyStats() {
+  if (!legacyStatsMigration) {
+    legacyStatsMigration = (async () => {
+      const legacySeconds = Number(
+        localStorage.getItem(legacyStatsStorageName)
+      );
+      if (!isNaN(legacySeconds) && legacySeconds > 0) {
+        await invoke("increase_listening_duration", {
+          seconds: legacySeconds,
+        });
+      }
+
+      localStorage.removeItem(legacyStatsStorageName);
+    })().catch((err) => {
+      // try again the next time, instead of failing every call
+      legacyStatsMigration = undefined;
+      throw err;
+    });
+  }
+
+  return legacyStatsMigration;
+}
 
 export async function increaseListeningDuration(
   seconds_listened: number
 ): Promise<number> {
-  const prev_seconds = await getSecondsListenedTo();
-
-  const new_seconds = prev_seconds + seconds_listened;
+  await migrateLegacyStats();
 
-  await localStorage.setItem(STATS_STORAGE_NAME, new_seconds.toString());
-
-  return new_seconds;
+  return await invoke<number>("increase_listening_duration", {
+    seconds: seconds_listened,
+  });
 }
 
 export async function getSecondsListenedTo(): Promise<number> {
-  const current_seconds_raw = localStorage.getItem(STATS_STORAGE_NAME);
-
-  if (current_seconds_raw === null) {
-    localStorage.setItem(STATS_STORAGE_NAME, "0");
-    return 0;
-  }
-
-  const current_seconds = Number(current_seconds_raw);
+  await migrateLegacyStats();
 
-  return isNaN(current_seconds) ? 0 : current_seconds;
+  return await invoke<number>("get_seconds_listened");
 }
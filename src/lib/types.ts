@@ -1,6 +1,8 @@
 export const srStorageName = "radio_sample_rate";
 export const volumeStorageName = "radio_volume";
 export const freqStorageName = "radio_freq";
+export const deemphasisStorageName = "radio_deemphasis";
+export const amBandwidthStorageName = "radio_am_bandwidth";
 
 export enum StationSavingState {
   Idle,
@@ -12,12 +14,27 @@ export enum StationType {
   HDRadio = 0,
   FMRadio,
   AMRadio,
+  HFRadio,
+  NBFMRadio,
+  AirbandRadio,
 }
 
 export enum StreamType {
   FM = "FM",
   AM = "AM",
   HD = "HD",
+  USB = "USB",
+  LSB = "LSB",
+  CW = "CW",
+  NBFM = "NBFM",
+  AIR = "AIR",
+}
+
+// FM de-emphasis, which depends on the region the station is in
+export enum Deemphasis {
+  Americas = "Americas",
+  International = "International",
+  Disabled = "Disabled",
 }
 
 export enum StationSortOption {
@@ -65,6 +82,37 @@ export interface RadioStreamSettings {
   sample_rate: number;
   stream_type: StreamType;
   hd_radio_program?: number | undefined;
+  spectrum?: SpectrumSettings | undefined;
+  iq_correction?: boolean | undefined;
+  deemphasis?: Deemphasis | undefined;
+  // in hz
+  audio_bandwidth?: number | undefined;
+  am_bandwidth?: number | undefined;
+  // fine tuning of SSB, or the pitch of CW (in hz)
+  bfo_offset?: number | undefined;
+  filter_width?: number | undefined;
+  // 12.5 or 25 kHz for narrowband FM, and 8.33 or 25 kHz for airband (in hz)
+  channel_spacing?: number | undefined;
+  // from 0 (always open) to 1
+  squelch?: number | undefined;
+  // other airband channels that are played at the same time (in MHz)
+  monitored_channels?: number[] | undefined;
+  // from 0 (off) to 1
+  noise_blanker?: number | undefined;
+  noise_reduction?: number | undefined;
+  // how much audio is kept to pause and rewind (in seconds)
+  time_shift_length?: number | undefined;
+}
+
+export interface SpectrumSettings {
+  fft_size: number;
+  frame_rate: number;
+}
+
+export interface SpectrumFrame {
+  centerFrequency: number;
+  sampleRate: number;
+  bins: number[];
 }
 
 export interface RbdsData {
@@ -86,6 +134,69 @@ export interface RbdsData {
   } | null;
 }
 
+export interface MultiFmSettings {
+  stations: number[];
+  center_freq?: number | undefined;
+  audio_station: number;
+  volume: number;
+  gain: number;
+  sample_rate: number;
+  iq_correction?: boolean | undefined;
+}
+
+export interface FmStereoState {
+  isStereo: boolean;
+  // from 0 (mono) to 1 (full stereo)
+  blend: number;
+}
+
+// the code of DCS is shown in octal
+export type SubaudibleTone =
+  | { Ctcss: number }
+  | { Dcs: { code: number; inverted: boolean } };
+
+// whether the squelch of an airband channel is open
+export interface AirbandChannelState {
+  // in MHz
+  frequency: number;
+  isOpen: boolean;
+}
+
+// where playback is in the time-shift buffer (in seconds)
+export interface TimeShiftPosition {
+  // how far behind live the audio is
+  offset: number;
+  buffered: number;
+  isPaused: boolean;
+}
+
+export interface StationRbdsData {
+  frequency: number;
+  rbdsState: RbdsData;
+}
+
+export interface ScanSettings {
+  stream_type: StreamType;
+  gain: number;
+  threshold?: number | undefined;
+  dwell_time?: number | undefined;
+}
+
+export interface ScannedStation {
+  frequency: number;
+  signalStrength: number;
+  isStereo?: boolean | null;
+  pi?: number | null;
+  serviceName?: string | null;
+  programType?: string | null;
+}
+
+export type ScanUpdate =
+  | { Sweeping: { progress: number } }
+  | { Identifying: { frequency: number } }
+  | { StationFound: ScannedStation }
+  | { Finished: ScannedStation[] };
+
 export interface HdRadioState {
   program: number;
   title: string;
@@ -347,6 +458,126 @@ export interface AvailableSdrArgs {
   product: string;
   serial: string;
   tuner: string;
+  source?: SdrSourceArgs | null;
+}
+
+export type IqSampleFormat = "Cu8" | "Cs16" | "Cf32";
+
+export type FilePacing = "RealTime" | "AsFastAsPossible";
+
+export interface IqFileArgs {
+  path: string;
+  format: IqSampleFormat;
+  sample_rate: number;
+  center_freq: number;
+  looping: boolean;
+  pacing: FilePacing;
+}
+
+export interface RtlTcpArgs {
+  host: string;
+  port: number;
+}
+
+export interface SimulatedRdsArgs {
+  pi: number;
+  ps: string;
+  rt: string;
+  pty: number;
+}
+
+export type SimulatedSignal =
+  | {
+      WbFm: {
+        frequency: number;
+        tone_freq: number;
+        rds?: SimulatedRdsArgs | null;
+      };
+    }
+  | {
+      Am: {
+        frequency: number;
+        tone_freq: number;
+        modulation_depth: number;
+      };
+    }
+  | { ModeS: { frequency: number; messages?: string[] | null } };
+
+export interface SimulatedArgs {
+  name: string;
+  signals: SimulatedSignal[];
+  noise_level: number;
+  pacing: FilePacing;
+}
+
+export type SdrSourceArgs =
+  | { IqFile: IqFileArgs }
+  | { RtlTcp: RtlTcpArgs }
+  | { Simulated: SimulatedArgs };
+
+export interface SdrRange {
+  minimum: number;
+  maximum: number;
+  step: number;
+}
+
+export interface SdrCapabilities {
+  has_automatic_gain: boolean;
+  gain_range: SdrRange;
+  gain_elements: { name: string; range: SdrRange }[];
+  sample_rate_ranges: SdrRange[];
+  frequency_ranges: SdrRange[];
+  antennas: string[];
+  settings: {
+    key: string;
+    name?: string | null;
+    description?: string | null;
+    default_value: string;
+  }[];
+}
+
+export type CalibrationSource =
+  | { FmPilot: { frequency: number } }
+  | { Carrier: { frequency: number } };
+
+// the settings that were last used with an SDR
+export interface DevicePreferences {
+  gain?: number;
+  ppm?: number;
+  sampleRate?: number;
+  preferredMode?: StreamType;
+}
+
+export interface CalibrationResult {
+  ppm: number;
+  frequency_offset: number;
+}
+
+export type StreamRecoveryStatus =
+  | { StreamFailed: { error: string } }
+  | "WaitingForDevice"
+  | { Reconnecting: { attempt: number } }
+  | "Recovered";
+
+export interface StreamRecoveryEvent {
+  service: "radio" | "adsb" | "multi_fm";
+  status: StreamRecoveryStatus;
+}
+
+export type RadioError =
+  | { Device: string }
+  | { Stream: string }
+  | { Audio: string }
+  | { Decoder: string }
+  | { NotFound: string }
+  | { Settings: string }
+  | { State: string }
+  | { Recording: string }
+  | { Storage: string };
+
+export interface RadioErrorEvent {
+  service: "radio" | "adsb" | "multi_fm" | "scanner";
+  error: RadioError;
 }
 
 export interface SDRState {
@@ -1,5 +1,6 @@
 import { type ClassValue, clsx } from "clsx";
 import { twMerge } from "tailwind-merge";
+import { Deemphasis, RadioError } from "@/lib/types";
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs));
@@ -24,3 +25,44 @@ export function formatZipCode(zip_code: string) {
   }
   return zip_code;
 }
+
+// every kind of error only has a message
+export function getRadioErrorMessage(error: RadioError): string {
+  return Object.values(error)[0];
+}
+
+// countries that use 75 µs de-emphasis for FM (the Americas and South Korea)
+const americasDeemphasisRegions = [
+  "US",
+  "CA",
+  "MX",
+  "BR",
+  "AR",
+  "CL",
+  "CO",
+  "PE",
+  "VE",
+  "EC",
+  "BO",
+  "PY",
+  "UY",
+  "GT",
+  "HN",
+  "SV",
+  "NI",
+  "CR",
+  "PA",
+  "CU",
+  "DO",
+  "PR",
+  "JM",
+  "KR",
+];
+
+// guess the de-emphasis from the region of the user's language
+export function getDefaultDeemphasis(): Deemphasis {
+  const region = navigator.language.split("-")[1]?.toUpperCase();
+  return region && americasDeemphasisRegions.includes(region)
+    ? Deemphasis.Americas
+    : Deemphasis.International;
+}